@@ -0,0 +1,183 @@
+use rusqlite::Error;
+
+use crate::{
+    db::{library_not_opened, search::MEME_TABLE, MemeDatabaseState},
+    error::AppResult,
+    meme::{fetch_listed_content, meme_from_row, MemeQueried},
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Album {
+    id: i64,
+    name: String,
+    description: Option<String>,
+    count: i64,
+}
+
+#[tauri::command]
+pub async fn create_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    name: String,
+    description: Option<String>,
+) -> AppResult<i64> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "INSERT INTO album(name, description) VALUES (?1, ?2)",
+            (name, description),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(state.conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub async fn update_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    name: String,
+    description: Option<String>,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "UPDATE album SET name = ?1, description = ?2 WHERE id = ?3",
+            (name, description, id),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Delete album, memes in album are kept
+#[tauri::command]
+pub async fn delete_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM album_meme WHERE album_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM album WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_albums(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<Vec<Album>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare(
+            "SELECT id, name, description, (SELECT COUNT(*) FROM album_meme WHERE album_id = album.id) AS count
+            FROM album ORDER BY create_time",
+        )
+        .map_err(|e| e.to_string())?;
+    let albums = query
+        .query_map([], |row| {
+            Ok(Album {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                description: row.get("description")?,
+                count: row.get("count")?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Album>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(albums)
+}
+
+/// Query memes in album ordered by their position
+#[tauri::command]
+pub async fn get_album_memes(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<Vec<MemeQueried>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare(&format!(
+            "SELECT * FROM album_meme LEFT JOIN {} ON album_meme.meme_id = meme.id
+            WHERE album_id = ?1 ORDER BY position",
+            MEME_TABLE
+        ))
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([id], |row| meme_from_row(row, &state.path))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<MemeQueried>, Error>>()
+        .map_err(|e| e.to_string())?;
+    fetch_listed_content(state, &memes);
+    Ok(memes)
+}
+
+/// Append memes to the end of album, memes already in album are ignored
+#[tauri::command]
+pub async fn add_memes_to_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    meme_ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    for meme_id in meme_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO album_meme(album_id, meme_id, position)
+            VALUES (?1, ?2, (SELECT IFNULL(MAX(position) + 1, 0) FROM album_meme WHERE album_id = ?1))",
+            (id, meme_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_memes_from_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    meme_ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    for meme_id in meme_ids {
+        conn.execute(
+            "DELETE FROM album_meme WHERE album_id = ?1 AND meme_id = ?2",
+            (id, meme_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reorder album, `meme_ids` is the new order of memes in album
+#[tauri::command]
+pub async fn reorder_album(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    meme_ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    for (position, meme_id) in meme_ids.iter().enumerate() {
+        conn.execute(
+            "UPDATE album_meme SET position = ?1 WHERE album_id = ?2 AND meme_id = ?3",
+            (position as i64, id, meme_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
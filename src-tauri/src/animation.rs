@@ -0,0 +1,143 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        webp::WebPDecoder,
+    },
+    imageops::{self, FilterType},
+    AnimationDecoder, Frame,
+};
+use rusqlite::Connection;
+
+use crate::{file::compute_path, thumbnail::thumbnail_path};
+
+/// Animated preview is skipped for long animations to keep it small
+const PREVIEW_MAX_FRAMES: usize = 200;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct AnimationInfo {
+    pub frame_count: u32,
+    /// Total duration in milliseconds
+    pub duration: u32,
+}
+
+/// Decode all frames of animated GIF or WebP
+/// return None if the file is not an animation
+pub fn decode_frames(path: &Path) -> Option<Vec<Frame>> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    let reader = BufReader::new(File::open(path).ok()?);
+    let frames = match ext.as_str() {
+        "gif" => GifDecoder::new(reader).ok()?.into_frames().collect_frames(),
+        "webp" => {
+            let decoder = WebPDecoder::new(reader).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            decoder.into_frames().collect_frames()
+        }
+        _ => return None,
+    }
+    .ok()?;
+    if frames.len() > 1 {
+        Some(frames)
+    } else {
+        None
+    }
+}
+
+fn animation_info(frames: &[Frame]) -> AnimationInfo {
+    let duration = frames
+        .iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            numer / denom.max(1)
+        })
+        .sum();
+    AnimationInfo {
+        frame_count: frames.len() as u32,
+        duration,
+    }
+}
+
+/// Animated previews are stored next to the static thumbnail as gif
+pub fn preview_path<P: AsRef<Path>>(base: P, hash: &str) -> PathBuf {
+    let mut path = thumbnail_path(base, hash);
+    path.set_extension("gif");
+    path
+}
+
+/// Return path of animated preview if it exists
+pub fn query_preview<P: AsRef<Path>>(base: P, hash: &str) -> Option<String> {
+    let path = preview_path(base, hash);
+    if path.exists() {
+        path.to_str().map(|x| x.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Encode a gif from frames down-scaled to fit in `size`
+fn generate_preview(frames: Vec<Frame>, path: &Path, size: u32) -> Result<(), String> {
+    let path_parent = path.parent().unwrap();
+    if !path_parent.exists() {
+        fs::create_dir_all(path_parent).map_err(|e| e.to_string())?;
+    }
+    encode_scaled_gif(frames, File::create(path).map_err(|e| e.to_string())?, size)
+}
+
+/// Encode frames down-scaled to fit in `size` as a looping gif into `writer`
+pub fn encode_scaled_gif<W: Write>(frames: Vec<Frame>, writer: W, size: u32) -> Result<(), String> {
+    let frames = frames.into_iter().map(|frame| {
+        let (width, height) = frame.buffer().dimensions();
+        let scale = f64::min(1.0, size as f64 / u32::max(width, height) as f64);
+        let (width, height) = (
+            ((width as f64 * scale) as u32).max(1),
+            ((height as f64 * scale) as u32).max(1),
+        );
+        let delay = frame.delay();
+        Frame::from_parts(
+            imageops::resize(frame.buffer(), width, height, FilterType::Triangle),
+            0,
+            0,
+            delay,
+        )
+    });
+
+    let mut encoder = GifEncoder::new(writer);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|e| e.to_string())?;
+    encoder.encode_frames(frames).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Detect animation of meme content, save its frame count and duration
+/// and generate an animated preview of `preview_size` if the animation is not too long
+pub fn update_meme_animation<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    content: &str,
+    preview_size: u32,
+) -> Result<Option<AnimationInfo>, String> {
+    let frames = match decode_frames(&compute_path(&base, content)) {
+        Some(frames) => frames,
+        None => return Ok(None),
+    };
+    let info = animation_info(&frames);
+    conn.execute(
+        "UPDATE meme SET frame_count = ?1, duration = ?2 WHERE id = ?3",
+        (info.frame_count, info.duration, meme_id),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if frames.len() <= PREVIEW_MAX_FRAMES {
+        generate_preview(frames, &preview_path(&base, content), preview_size)?;
+    }
+    Ok(Some(info))
+}
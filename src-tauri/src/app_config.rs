@@ -0,0 +1,193 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    db::MemeDatabaseState,
+    error::{AppError, AppResult},
+    watch::WatchFolder,
+    AppDir,
+};
+
+/// App wide config is stored in app storage, libraries have their own data
+const CONFIG_FILE: &str = "config.json";
+/// Libraries added without a path are created in this directory of app storage by default
+const DEFAULT_LIBRARY_ROOT: &str = "libraries";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NamedLibrary {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct AppConfig {
+    /// Directory new libraries are created in
+    #[serde(default)]
+    pub library_root: Option<PathBuf>,
+    #[serde(default)]
+    pub libraries: Vec<NamedLibrary>,
+    /// Name of library opened last time, it is opened again on start
+    #[serde(default)]
+    pub current: Option<String>,
+    /// Local port of read-only REST API, served only if built with `rest-api` feature.
+    /// `None` disables it, changes take effect after restart
+    #[serde(default)]
+    pub api_port: Option<u16>,
+    /// Token required by REST API to import memes, importing is disabled if it is `None`
+    #[serde(default)]
+    pub api_token: Option<String>,
+    /// Global shortcut opening quick picker, `None` uses `picker::DEFAULT_SHORTCUT`
+    #[serde(default)]
+    pub picker_shortcut: Option<String>,
+    /// Serve JSON-RPC on a local socket for launchers and scripts, see `rpc`.
+    /// Changes take effect after restart
+    #[serde(default)]
+    pub rpc_enabled: bool,
+    /// Folders whose new files are imported into opened library automatically
+    #[serde(default)]
+    pub watch_folders: Vec<WatchFolder>,
+}
+
+impl AppConfig {
+    pub fn load(storage_dir: &Path) -> Result<Self, String> {
+        let path = storage_dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, storage_dir: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(storage_dir.join(CONFIG_FILE), content).map_err(|e| e.to_string())
+    }
+
+    fn find(&self, name: &str) -> AppResult<&NamedLibrary> {
+        self.libraries
+            .iter()
+            .find(|library| library.name == name)
+            .ok_or_else(|| AppError::NotFound(format!("library {}", name)))
+    }
+
+    pub fn current_library(&self) -> Option<&NamedLibrary> {
+        self.current.as_deref().and_then(|name| self.find(name).ok())
+    }
+
+    fn library_root(&self, storage_dir: &Path) -> PathBuf {
+        match &self.library_root {
+            Some(root) => root.clone(),
+            None => storage_dir.join(DEFAULT_LIBRARY_ROOT),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_app_config(app_dir: tauri::State<'_, AppDir>) -> AppResult<AppConfig> {
+    Ok(AppConfig::load(&app_dir.storage_dir)?)
+}
+
+#[tauri::command]
+pub async fn set_api_port(app_dir: tauri::State<'_, AppDir>, port: Option<u16>) -> AppResult<()> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.api_port = port;
+    Ok(config.save(&app_dir.storage_dir)?)
+}
+
+#[tauri::command]
+pub async fn set_api_token(
+    app_dir: tauri::State<'_, AppDir>,
+    token: Option<String>,
+) -> AppResult<()> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.api_token = token.filter(|token| !token.is_empty());
+    Ok(config.save(&app_dir.storage_dir)?)
+}
+
+#[tauri::command]
+pub async fn set_rpc_enabled(app_dir: tauri::State<'_, AppDir>, enabled: bool) -> AppResult<()> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.rpc_enabled = enabled;
+    Ok(config.save(&app_dir.storage_dir)?)
+}
+
+/// Change directory new libraries are created in, existing libraries are not moved
+/// `None` restores the default one
+#[tauri::command]
+pub async fn set_library_root(
+    app_dir: tauri::State<'_, AppDir>,
+    path: Option<String>,
+) -> AppResult<()> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.library_root = path.map(PathBuf::from);
+    Ok(config.save(&app_dir.storage_dir)?)
+}
+
+/// Register a library by name, it is created in library root if `path` is not given
+#[tauri::command]
+pub async fn add_library(
+    app_dir: tauri::State<'_, AppDir>,
+    name: String,
+    path: Option<String>,
+) -> AppResult<NamedLibrary> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    if config.find(&name).is_ok() {
+        return Err(AppError::Conflict(format!("library {} already exists", name)));
+    }
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => config.library_root(&app_dir.storage_dir).join(&name),
+    };
+    if !path.exists() {
+        fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    }
+
+    let library = NamedLibrary { name, path };
+    config.libraries.push(library.clone());
+    config.save(&app_dir.storage_dir)?;
+    Ok(library)
+}
+
+/// Forget a library, its files are kept on disk
+#[tauri::command]
+pub async fn remove_library(
+    app_dir: tauri::State<'_, AppDir>,
+    name: String,
+) -> AppResult<()> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    if config.current.as_deref() == Some(name.as_str()) {
+        return Err(AppError::Conflict(format!("library {} is in use", name)));
+    }
+    config.find(&name)?;
+    config.libraries.retain(|library| library.name != name);
+    Ok(config.save(&app_dir.storage_dir)?)
+}
+
+/// Close current library and open library `name`,
+/// it will be opened again on next start. `passphrase` is required if it is encrypted
+#[tauri::command]
+pub async fn switch_library(
+    app_dir: tauri::State<'_, AppDir>,
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    name: String,
+    passphrase: Option<String>,
+) -> AppResult<NamedLibrary> {
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    let library = config.find(&name)?.clone();
+    if !library.path.exists() {
+        return Err(AppError::NotFound(format!(
+            "library {} at {}",
+            name,
+            library.path.to_string_lossy()
+        )));
+    }
+
+    db_state
+        .open_with_passphrase(library.path.clone(), passphrase.as_deref())
+        .await?;
+    config.current = Some(name);
+    config.save(&app_dir.storage_dir)?;
+    Ok(library)
+}
@@ -0,0 +1,197 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    backup::BACKUP_DIR,
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    error::{AppError, AppResult},
+    storage::{fetch_content, CONFIG_FILE},
+};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const DATABASE_NAME: &str = "meme.db";
+const STORAGE_PREFIX: &str = "storage/";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveManifest {
+    /// Version of database schema
+    version: u32,
+    app_version: String,
+    create_time: String,
+    files: usize,
+}
+
+/// Collect all files in storage except the database itself and its backups
+fn list_storage_files(base: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut result = Vec::new();
+    let mut dirs = vec![base.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path != base.join(BACKUP_DIR) {
+                    dirs.push(path);
+                }
+            } else if !path.file_name().map_or(false, |name| {
+                // storage config may contain credentials of remote storage
+                name.to_string_lossy().starts_with(DATABASE_NAME) || name == CONFIG_FILE
+            }) {
+                result.push(path);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Bundle database and all content files into a zip archive.
+/// Content only in remote storage is downloaded first, since the archive leaves out
+/// storage config and could not fetch it afterwards
+#[tauri::command]
+pub async fn export_library(
+    state: tauri::State<'_, MemeDatabaseState>,
+    path: String,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let hashes = {
+        let mut query = state.conn.prepare("SELECT DISTINCT hash FROM meme_file")?;
+        let hashes = query
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        hashes
+    };
+    for hash in &hashes {
+        fetch_content(state.storage.as_ref(), &state.path, hash)
+            .map_err(AppError::StorageUnavailable)?;
+    }
+
+    // snapshot database so the archive is consistent
+    let snapshot = std::env::temp_dir().join(format!(
+        "mmm-export-{}.db",
+        OffsetDateTime::now_utc().unix_timestamp()
+    ));
+    state
+        .conn
+        .execute("VACUUM INTO ?1", [snapshot.to_str().unwrap()])
+        .map_err(|e| e.to_string())?;
+
+    let files = list_storage_files(&state.path).map_err(|e| e.to_string())?;
+    let manifest = ArchiveManifest {
+        version: MemeDatabaseConnection::CURRENT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_owned(),
+        create_time: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|e| e.to_string())?,
+        files: files.len(),
+    };
+
+    let mut zip = ZipWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    // most content are already compressed images
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(MANIFEST_NAME, options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(
+        serde_json::to_string_pretty(&manifest)
+            .map_err(|e| e.to_string())?
+            .as_bytes(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    zip.start_file(DATABASE_NAME, options)
+        .map_err(|e| e.to_string())?;
+    io::copy(
+        &mut File::open(&snapshot).map_err(|e| e.to_string())?,
+        &mut zip,
+    )
+    .map_err(|e| e.to_string())?;
+    fs::remove_file(&snapshot).map_err(|e| e.to_string())?;
+
+    for file in files {
+        let relative = file.strip_prefix(&state.path).unwrap();
+        let name = format!(
+            "{}{}",
+            STORAGE_PREFIX,
+            relative.to_string_lossy().replace('\\', "/")
+        );
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        io::copy(&mut File::open(&file).map_err(|e| e.to_string())?, &mut zip)
+            .map_err(|e| e.to_string())?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Extract an archive created by `export_library` into `target` and open it as storage
+#[tauri::command]
+pub async fn import_library(
+    state: tauri::State<'_, MemeDatabaseState>,
+    path: String,
+    target: String,
+) -> AppResult<()> {
+    let target = PathBuf::from(target);
+    if target.exists()
+        && fs::read_dir(&target)
+            .map_err(|e| e.to_string())?
+            .next()
+            .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "{} is not empty",
+            target.to_string_lossy()
+        )));
+    }
+
+    let mut archive =
+        ZipArchive::new(File::open(path).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+
+    let manifest: ArchiveManifest = serde_json::from_reader(
+        archive
+            .by_name(MANIFEST_NAME)
+            .map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())?;
+    if manifest.version > MemeDatabaseConnection::CURRENT_VERSION {
+        return Err(AppError::InvalidArgument(format!(
+            "archive is created by a newer version ({})",
+            manifest.app_version
+        )));
+    }
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        let name = match file.enclosed_name() {
+            Some(name) => name.to_string_lossy().replace('\\', "/"),
+            None => continue,
+        };
+        let out = if name == DATABASE_NAME {
+            target.join(DATABASE_NAME)
+        } else if let Some(relative) = name.strip_prefix(STORAGE_PREFIX) {
+            target.join(relative)
+        } else {
+            continue;
+        };
+        if file.is_dir() {
+            continue;
+        }
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        io::copy(
+            &mut file,
+            &mut File::create(&out).map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    state.open(target).await?;
+    Ok(())
+}
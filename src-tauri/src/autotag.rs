@@ -0,0 +1,157 @@
+use std::{collections::HashMap, fs, path::Path, process::Command};
+
+use rusqlite::{Connection, Error};
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    jobs::{enqueue_job, Job, JobQueue},
+    meme::{insert_meme_tag, make_tag, Tag},
+    storage::send_request,
+};
+
+/// Tags written by auto-tagger are kept in this namespace,
+/// so they can be told apart from tags given by user and replaced on the next run
+pub const AUTO_NAMESPACE: &str = "auto";
+
+/// Classifier guessing tags of an image, e.g. DeepDanbooru or WD tagger
+pub trait AutoTagger {
+    /// Confidence of each tag from 0 to 1, keyed by tag name
+    fn classify(&self, path: &Path) -> Result<HashMap<String, f32>, String>;
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AutoTaggerConfig {
+    /// Run `program` with `args` followed by path of image,
+    /// it prints a JSON object of tag names to confidences
+    Command { program: String, args: Vec<String> },
+    /// POST image to `url` as request body,
+    /// it responds with a JSON object of tag names to confidences
+    Http { url: String },
+}
+
+impl AutoTaggerConfig {
+    pub fn open(self) -> Box<dyn AutoTagger> {
+        match self {
+            AutoTaggerConfig::Command { program, args } => {
+                Box::new(CommandTagger { program, args })
+            }
+            AutoTaggerConfig::Http { url } => Box::new(HttpTagger { url }),
+        }
+    }
+}
+
+pub struct CommandTagger {
+    program: String,
+    args: Vec<String>,
+}
+
+impl AutoTagger for CommandTagger {
+    fn classify(&self, path: &Path) -> Result<HashMap<String, f32>, String> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(path)
+            .output()
+            .map_err(|e| format!("failed to run {}: {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).to_string());
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("invalid output of auto-tagger: {}", e))
+    }
+}
+
+pub struct HttpTagger {
+    url: String,
+}
+
+impl AutoTagger for HttpTagger {
+    fn classify(&self, path: &Path) -> Result<HashMap<String, f32>, String> {
+        let body = fs::read(path).map_err(|e| e.to_string())?;
+        let url = self.url.clone();
+        let (status, data) = send_request(move |client| client.post(url).body(body))?;
+        if !status.is_success() {
+            return Err(format!("auto-tagger responded {}", status));
+        }
+        serde_json::from_slice(&data)
+            .map_err(|e| format!("invalid response of auto-tagger: {}", e))
+    }
+}
+
+/// Names of tags whose confidence is not lower than `threshold`, most confident first
+pub fn pick_auto_tags(scores: HashMap<String, f32>, threshold: f32) -> Vec<String> {
+    let mut scores = scores
+        .into_iter()
+        .filter(|(name, confidence)| *confidence >= threshold && !name.trim().is_empty())
+        .collect::<Vec<(String, f32)>>();
+    scores.sort_by(|(_, l), (_, r)| r.total_cmp(l));
+    scores
+        .into_iter()
+        .map(|(name, _)| name.trim().to_owned())
+        .collect()
+}
+
+/// Replace `auto:` tags of meme `id` with tags `names`, return the tags written
+pub fn save_auto_tags(conn: &Connection, id: i64, names: &[String]) -> Result<Vec<Tag>, String> {
+    conn.execute(
+        "DELETE FROM meme_tag WHERE meme_id = ?1
+          AND tag_id IN (SELECT id FROM tag WHERE key = ?2)",
+        (id, AUTO_NAMESPACE),
+    )
+    .map_err(|e| e.to_string())?;
+    let mut tags = Vec::new();
+    for name in names {
+        let tag_id = make_tag(conn, AUTO_NAMESPACE, name)?;
+        insert_meme_tag(conn, id, tag_id)?;
+        tags.push(Tag {
+            key: AUTO_NAMESPACE.to_owned(),
+            value: name.clone(),
+        });
+    }
+    Ok(tags)
+}
+
+/// Image memes not in trash which have no `auto:` tag
+fn query_not_auto_tagged(conn: &Connection) -> Result<Vec<i64>, Error> {
+    let mut query = conn.prepare(
+        "SELECT id FROM meme WHERE ty = 'image' AND trash = false
+          AND NOT EXISTS (SELECT 1 FROM meme_tag JOIN tag ON tag.id = meme_tag.tag_id
+            WHERE meme_tag.meme_id = meme.id AND key = ?1)",
+    )?;
+    let ids = query
+        .query_map([AUTO_NAMESPACE], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, Error>>()?;
+    Ok(ids)
+}
+
+/// Queue auto-tagging jobs for image memes `ids`,
+/// or for every image meme without `auto:` tags if `ids` is None.
+/// Return the number of jobs queued
+#[tauri::command]
+pub async fn queue_auto_tagging(
+    state: tauri::State<'_, MemeDatabaseState>,
+    queue: tauri::State<'_, JobQueue>,
+    ids: Option<Vec<i64>>,
+) -> AppResult<usize> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    if load_settings(&state.conn)?.auto_tagger.is_none() {
+        return Err(AppError::InvalidArgument(
+            "auto-tagger is not configured".to_owned(),
+        ));
+    }
+
+    let ids = match ids {
+        Some(ids) => ids,
+        None => query_not_auto_tagged(&state.conn)?,
+    };
+    let conn = state.conn.transaction()?;
+    for meme_id in &ids {
+        enqueue_job(&conn, &Job::AutoTag { meme_id: *meme_id })?;
+    }
+    conn.commit()?;
+    queue.wake();
+    Ok(ids.len())
+}
@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{backup::Progress, Connection, DatabaseName};
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{
+    db::{
+        library_not_opened, migration::check_compatible, MemeDatabaseConnection, MemeDatabaseState,
+    },
+    encryption::{apply_passphrase, check_unlocked, export_rekeyed, replace_database},
+    error::{AppError, AppResult},
+};
+
+/// Backups are stored in `backups/` under storage directory
+pub const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "meme-";
+const BACKUP_EXT: &str = "db";
+/// Oldest backups are removed once there are more than this
+const MAX_BACKUPS: usize = 10;
+
+#[derive(Debug, serde::Serialize)]
+pub struct BackupFile {
+    path: String,
+    size: u64,
+}
+
+fn backup_dir(base: &Path) -> PathBuf {
+    base.join(BACKUP_DIR)
+}
+
+/// Backup files sorted from oldest to newest,
+/// file names contain timestamp so they sort by time
+fn list_backup_files(base: &Path) -> Result<Vec<PathBuf>, String> {
+    let dir = backup_dir(base);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut result = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|path| {
+            path.file_name()
+                .map_or(false, |x| x.to_string_lossy().starts_with(BACKUP_PREFIX))
+                && path.extension().map_or(false, |x| x == BACKUP_EXT)
+        })
+        .collect::<Vec<PathBuf>>();
+    result.sort();
+    Ok(result)
+}
+
+/// Path of a new timestamped backup, backup directory is created if missing.
+/// Backup API overwrites an existing file, so the path is never one of an existing backup
+pub fn new_backup_path(base: &Path) -> Result<PathBuf, String> {
+    let dir = backup_dir(base);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+    let timestamp = OffsetDateTime::now_utc()
+        .format(format_description!(
+            "[year][month][day]-[hour][minute][second]-[subsecond digits:6]"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut path = dir.join(format!("{}{}.{}", BACKUP_PREFIX, timestamp, BACKUP_EXT));
+    let mut count = 1;
+    while path.exists() {
+        path = dir.join(format!(
+            "{}{}-{}.{}",
+            BACKUP_PREFIX, timestamp, count, BACKUP_EXT
+        ));
+        count += 1;
+    }
+    Ok(path)
+}
+
+/// Snapshot database into a new timestamped backup by sqlite online backup API,
+/// then remove the oldest backups exceeding `MAX_BACKUPS`.
+/// Encrypted database is exported keyed by the same `passphrase` instead,
+/// since SQLCipher can not back it up by backup API
+pub fn create_backup(
+    conn: &Connection,
+    base: &Path,
+    passphrase: Option<&str>,
+) -> Result<PathBuf, String> {
+    let path = new_backup_path(base)?;
+    match passphrase {
+        Some(passphrase) => {
+            export_rekeyed(conn, &path, passphrase).map_err(|e| e.to_string())?;
+        }
+        None => conn
+            .backup(DatabaseName::Main, &path, None::<fn(Progress)>)
+            .map_err(|e| e.to_string())?,
+    }
+
+    let backups = list_backup_files(base)?;
+    if backups.len() > MAX_BACKUPS {
+        for old in &backups[..backups.len() - MAX_BACKUPS] {
+            fs::remove_file(old).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(path)
+}
+
+/// Remove all backups, returns the number of removed files
+pub fn remove_backups(base: &Path) -> Result<usize, String> {
+    let backups = list_backup_files(base)?;
+    for path in &backups {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    Ok(backups.len())
+}
+
+/// Backup database when it is opened, before any migration runs
+/// Newly created database with no table is skipped
+pub fn backup_on_open(
+    conn: &Connection,
+    base: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), String> {
+    let tables: i64 = conn
+        .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if tables > 0 {
+        create_backup(conn, base, passphrase)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_backup_now(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<String> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let path = create_backup(&state.conn, &state.path, state.passphrase.as_deref())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn list_backups(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<BackupFile>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let backups = list_backup_files(&state.path)?
+        .into_iter()
+        .rev()
+        .map(|path| BackupFile {
+            size: fs::metadata(&path).map_or(0, |x| x.len()),
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect();
+    Ok(backups)
+}
+
+/// Replace current database with backup at `path`
+/// Current database is backed up first, so a restore can be undone.
+/// The connection is reopened to upgrade backup made by older version.
+/// Backups of encrypted library are keyed by its passphrase
+#[tauri::command]
+pub async fn restore_backup(
+    state: tauri::State<'_, MemeDatabaseState>,
+    path: String,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let current = guard.as_mut().ok_or_else(library_not_opened)?;
+    let base = current.path.clone();
+    let passphrase = current.passphrase.clone();
+
+    let backup = Connection::open(&path)?;
+    if let Some(passphrase) = &passphrase {
+        apply_passphrase(&backup, passphrase)?;
+    }
+    check_unlocked(&backup)?;
+    let result = backup.query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))?;
+    if result != "ok" {
+        return Err(AppError::InvalidArgument(format!(
+            "backup is corrupted: {}",
+            result
+        )));
+    }
+    // current database would be replaced by one this build can not open
+    check_compatible(&backup)?;
+    drop(backup);
+
+    create_backup(&current.conn, &base, passphrase.as_deref())?;
+    match &passphrase {
+        None => current
+            .conn
+            .restore(DatabaseName::Main, &path, None::<fn(Progress)>)
+            .map_err(|e| e.to_string())?,
+        // SQLCipher can not restore by backup API either, database file is replaced
+        Some(_) => {
+            *guard = None;
+            replace_database(&base, Path::new(&path))?;
+        }
+    }
+
+    *guard = Some(MemeDatabaseConnection::open_with_passphrase(
+        base,
+        passphrase.as_deref(),
+    )?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rusqlite::Connection;
+    use tauri::Manager;
+    use tokio::sync::Mutex;
+
+    use crate::{
+        backup::{create_backup, list_backup_files, restore_backup},
+        db::{fixtures::seed_meme, MemeDatabaseConnection, MemeDatabaseState},
+    };
+
+    fn count_memes(path: &std::path::Path) -> i64 {
+        Connection::open(path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM meme", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_restore_can_be_undone() {
+        let dir = std::env::temp_dir().join(format!("mmm-backup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state = MemeDatabaseConnection::open(dir.clone()).unwrap();
+        let empty = create_backup(&state.conn, &dir, None).unwrap();
+        seed_meme(&state.conn, "cat", &[]);
+
+        let app = tauri::test::mock_app();
+        app.manage(MemeDatabaseState {
+            state: Mutex::new(Some(state)),
+        });
+        let path = empty.to_string_lossy().to_string();
+        tauri::async_runtime::block_on(restore_backup(app.state(), path)).unwrap();
+
+        // backups taken before restoring and on reopening are both kept
+        let backups = list_backup_files(&dir).unwrap();
+        assert_eq!(backups.len(), 3);
+        assert_eq!(count_memes(&backups[0]), 0);
+        assert_eq!(count_memes(&backups[1]), 1);
+        assert_eq!(count_memes(&backups[2]), 0);
+
+        drop(app);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
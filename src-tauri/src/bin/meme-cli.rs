@@ -0,0 +1,229 @@
+//! Command line companion of the app for scripting against a library.
+//! It works on the library opened last in the app unless `--library` is given.
+//! Passphrase of encrypted library is read from `MEME_PASSPHRASE` environment variable
+
+use std::{collections::HashSet, env, fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use mmm_lib::{
+    app_config::AppConfig,
+    app_storage_dir,
+    db::MemeDatabaseConnection,
+    error::{AppError, AppResult},
+    export::{export_meme, ExportOptions},
+    file::media_type,
+    meme::{
+        create_meme_with_tags, link_tags, parse_tag, search_memes, unlink_tags, AddMemeResult,
+        NewMeme,
+    },
+};
+
+const PASSPHRASE_VAR: &str = "MEME_PASSPHRASE";
+
+#[derive(Parser)]
+#[command(name = "meme-cli", about = "Manage a meme library from the shell")]
+struct Cli {
+    /// Directory of library, defaults to the library opened last in the app
+    #[arg(short, long, global = true)]
+    library: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Import image and video files, print id of each meme
+    Add {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Tag written as `namespace:value`, can be repeated
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Name of memes, defaults to file name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Remove EXIF and other metadata of images before storing
+        #[arg(long)]
+        strip_exif: bool,
+    },
+    /// Print id and name of memes matching search statement, separated by tab
+    Search {
+        query: String,
+        /// Next page of a previous search, printed after its result
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Defaults to page size in settings of library
+        #[arg(long)]
+        page_size: Option<i64>,
+        /// Only favorite memes
+        #[arg(long)]
+        fav: bool,
+        /// Print the whole page as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Link tags to memes, or unlink them with `--remove`
+    Tag {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        /// Tag written as `namespace:value`, can be repeated
+        #[arg(short, long = "tag", required = true)]
+        tags: Vec<String>,
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Copy memes into a directory with file names after their names, print path of each file
+    Export {
+        #[arg(short, long)]
+        dir: PathBuf,
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+}
+
+fn library_path(library: Option<PathBuf>) -> AppResult<PathBuf> {
+    let path = match library {
+        Some(path) => path,
+        None => AppConfig::load(&app_storage_dir())?
+            .current_library()
+            .map(|library| library.path.clone())
+            .ok_or_else(|| {
+                AppError::InvalidArgument(
+                    "no library is opened in app, choose one by --library".to_owned(),
+                )
+            })?,
+    };
+    // opening a directory without database would create an empty library
+    if !path.join("meme.db").exists() {
+        return Err(AppError::NotFound(format!("library at {}", path.display())));
+    }
+    Ok(path)
+}
+
+fn run(cli: Cli) -> AppResult<()> {
+    let passphrase = env::var(PASSPHRASE_VAR).ok();
+    let mut state = MemeDatabaseConnection::open_with_passphrase(
+        library_path(cli.library)?,
+        passphrase.as_deref(),
+    )?;
+    match cli.command {
+        Command::Add {
+            files,
+            tags,
+            name,
+            strip_exif,
+        } => {
+            let tags = tags.iter().map(|tag| parse_tag(tag)).collect::<Vec<_>>();
+            for file in files {
+                let ty = media_type(&file).ok_or_else(|| {
+                    AppError::InvalidArgument(format!(
+                        "{} is neither an image nor a video",
+                        file.display()
+                    ))
+                })?;
+                let name = name.clone().unwrap_or_else(|| {
+                    file.file_stem()
+                        .map(|x| x.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                });
+                let meme = NewMeme {
+                    name,
+                    description: None,
+                    ty: ty.to_owned(),
+                    content: file.to_string_lossy().to_string(),
+                    fav: false,
+                    tags: tags.clone(),
+                    pkg_id: 0,
+                    strip_exif,
+                    source_url: None,
+                    author: None,
+                    origin: None,
+                    derived_from: None,
+                };
+                let result = create_meme_with_tags(
+                    &mut state.conn,
+                    state.storage.as_ref(),
+                    &state.path,
+                    meme,
+                )?;
+                match result {
+                    AddMemeResult::Added(id) => println!("{}", id),
+                    AddMemeResult::Duplicate(id) => {
+                        eprintln!("{} is already in library", file.display());
+                        println!("{}", id);
+                    }
+                }
+            }
+        }
+        Command::Search {
+            query,
+            cursor,
+            page_size,
+            fav,
+            json,
+        } => {
+            let page = search_memes(
+                &state,
+                &query,
+                cursor.as_deref(),
+                page_size,
+                fav,
+                false,
+                None,
+            )?;
+            if json {
+                let text = serde_json::to_string_pretty(&page).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                for meme in page.items {
+                    println!("{}\t{}", meme.id, meme.name);
+                }
+                // stderr keeps stdout easy to pipe
+                if let Some(cursor) = page.next_cursor {
+                    eprintln!("more results: --cursor {}", cursor);
+                }
+            }
+        }
+        Command::Tag { ids, tags, remove } => {
+            let tags = tags.iter().map(|tag| parse_tag(tag)).collect::<Vec<_>>();
+            if remove {
+                unlink_tags(&mut state.conn, &ids, &tags)?;
+            } else {
+                link_tags(&mut state.conn, &ids, &tags)?;
+            }
+        }
+        Command::Export { dir, ids } => {
+            fs::create_dir_all(&dir)?;
+            let options = ExportOptions { sidecar: None };
+            let mut used = HashSet::new();
+            for id in ids {
+                let path = export_meme(
+                    &state.conn,
+                    state.storage.as_ref(),
+                    &state.path,
+                    id,
+                    &dir,
+                    &options,
+                    &mut used,
+                )?;
+                println!("{}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    // warnings of opening library, e.g. a failed backup, go along with other messages
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(tracing::Level::WARN)
+        .init();
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
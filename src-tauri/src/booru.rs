@@ -0,0 +1,71 @@
+use crate::meme::{parse_tag, Tag, DEFAULT_NAMESPACE};
+
+/// Parse booru tag string, tags are separated by whitespace and use `_` in place of space
+/// e.g. `blue_hair artist:someone`, all tags are put into `namespace` if it is given
+pub fn parse_booru_tags(text: &str, namespace: Option<&str>) -> Vec<Tag> {
+    text.split_whitespace()
+        .map(|tag| {
+            let tag = tag.replace('_', " ");
+            match namespace {
+                Some(namespace) => Tag {
+                    key: namespace.to_owned(),
+                    value: tag,
+                },
+                None => parse_tag(&tag),
+            }
+        })
+        .collect()
+}
+
+/// Parse Hydrus tag list, one `namespace:value` tag per line
+pub fn parse_hydrus_tags(text: &str) -> Vec<Tag> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(parse_tag)
+        .collect()
+}
+
+/// Tag strings in metadata exported from Danbooru-like sites,
+/// categorized strings are mapped to namespaces of the same name
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct BooruTagStrings {
+    #[serde(default)]
+    tag_string: Option<String>,
+    #[serde(default)]
+    tag_string_general: Option<String>,
+    #[serde(default)]
+    tag_string_artist: Option<String>,
+    #[serde(default)]
+    tag_string_character: Option<String>,
+    #[serde(default)]
+    tag_string_copyright: Option<String>,
+    #[serde(default)]
+    tag_string_meta: Option<String>,
+}
+
+impl BooruTagStrings {
+    pub fn into_tags(self) -> Vec<Tag> {
+        let categorized = [
+            (self.tag_string_general, DEFAULT_NAMESPACE),
+            (self.tag_string_artist, "artist"),
+            (self.tag_string_character, "character"),
+            (self.tag_string_copyright, "copyright"),
+            (self.tag_string_meta, "meta"),
+        ];
+        // categorized strings are more precise, the plain one is a fallback
+        if categorized.iter().any(|(text, _)| text.is_some()) {
+            categorized
+                .into_iter()
+                .filter_map(|(text, namespace)| {
+                    text.map(|text| parse_booru_tags(&text, Some(namespace)))
+                })
+                .flatten()
+                .collect()
+        } else {
+            self.tag_string
+                .map(|text| parse_booru_tags(&text, None))
+                .unwrap_or_default()
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+use image::{imageops::FilterType, DynamicImage};
+use ndarray::{Array, CowArray, IxDyn};
+use ort::{
+    tensor::IntoTensorElementDataType, Environment, GraphOptimizationLevel, Session,
+    SessionBuilder, Value,
+};
+use tokenizers::Tokenizer;
+
+/// Side of square image fed to the image encoder
+const IMAGE_SIZE: u32 = 224;
+/// Length of token sequence fed to the text encoder
+const CONTEXT_LENGTH: usize = 77;
+const PIXEL_MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const PIXEL_STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// Pad token ids to the context length, or truncate them keeping the end of text token,
+/// the text encoder pools its output at the position of that token
+fn fit_context(mut ids: Vec<u32>) -> Vec<u32> {
+    if ids.len() > CONTEXT_LENGTH {
+        let eot = ids[ids.len() - 1];
+        ids.truncate(CONTEXT_LENGTH - 1);
+        ids.push(eot);
+    }
+    ids.resize(CONTEXT_LENGTH, 0);
+    ids
+}
+
+/// Image and text encoders of CLIP run by ONNX Runtime,
+/// both produce normalized vectors in the same space
+pub struct ClipModel {
+    visual: Session,
+    textual: Session,
+    tokenizer: Tokenizer,
+}
+
+impl ClipModel {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let environment = Arc::new(
+            Environment::builder()
+                .with_name("clip")
+                .build()
+                .map_err(|e| e.to_string())?,
+        );
+        let session = |file: &str| -> Result<Session, String> {
+            SessionBuilder::new(&environment)
+                .and_then(|x| x.with_optimization_level(GraphOptimizationLevel::Level3))
+                .and_then(|x| x.with_model_from_file(dir.join(file)))
+                .map_err(|e| format!("failed to load {}: {}", file, e))
+        };
+        Ok(Self {
+            visual: session("visual.onnx")?,
+            textual: session("textual.onnx")?,
+            tokenizer: Tokenizer::from_file(dir.join("tokenizer.json"))
+                .map_err(|e| format!("failed to load tokenizer.json: {}", e))?,
+        })
+    }
+
+    /// Run encoder on a single input and take its output as the embedding
+    fn run<T>(session: &Session, input: Array<T, IxDyn>) -> Result<Vec<f32>, String>
+    where
+        T: IntoTensorElementDataType + Debug + Clone,
+    {
+        let input = CowArray::from(input);
+        let value = Value::from_array(session.allocator(), &input).map_err(|e| e.to_string())?;
+        let outputs = session.run(vec![value]).map_err(|e| e.to_string())?;
+        let output = outputs[0]
+            .try_extract::<f32>()
+            .map_err(|e| e.to_string())?;
+        let vector = output.view().iter().copied().collect();
+        Ok(normalize(vector))
+    }
+
+    /// Image is resized by its short side and cropped at the center,
+    /// then normalized the way CLIP is trained
+    pub fn embed_image(&self, img: &DynamicImage) -> Result<Vec<f32>, String> {
+        let img = img
+            .resize_to_fill(IMAGE_SIZE, IMAGE_SIZE, FilterType::CatmullRom)
+            .to_rgb8();
+        let size = IMAGE_SIZE as usize;
+        let input = Array::from_shape_fn((1, 3, size, size), |(_, c, y, x)| {
+            let value = img.get_pixel(x as u32, y as u32).0[c] as f32 / 255.0;
+            (value - PIXEL_MEAN[c]) / PIXEL_STD[c]
+        });
+        Self::run(&self.visual, input.into_dyn())
+    }
+
+    /// Text longer than the context of model is truncated
+    pub fn embed_text(&self, text: &str) -> Result<Vec<f32>, String> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| e.to_string())?;
+        let ids = fit_context(encoding.get_ids().to_vec());
+        let input = Array::from_shape_vec((1, CONTEXT_LENGTH), ids)
+            .map_err(|e| e.to_string())?
+            .mapv(|id| id as i64);
+        Self::run(&self.textual, input.into_dyn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clip::{fit_context, CONTEXT_LENGTH};
+
+    const BOS: u32 = 49406;
+    const EOT: u32 = 49407;
+
+    #[test]
+    fn test_fit_context() {
+        let ids = fit_context(vec![BOS, 1, 2, EOT]);
+        assert_eq!(ids.len(), CONTEXT_LENGTH);
+        assert_eq!(&ids[..5], &[BOS, 1, 2, EOT, 0]);
+
+        let mut long = vec![BOS];
+        long.extend(1..200);
+        long.push(EOT);
+        let ids = fit_context(long);
+        assert_eq!(ids.len(), CONTEXT_LENGTH);
+        assert_eq!(ids[0], BOS);
+        assert_eq!(ids[CONTEXT_LENGTH - 2], (CONTEXT_LENGTH - 2) as u32);
+        assert_eq!(ids[CONTEXT_LENGTH - 1], EOT);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{borrow::Cow, fs, io::Cursor};
+
+use arboard::{Clipboard, ImageData};
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    error::AppResult,
+    meme::{
+        insert_meme, insert_meme_tag, make_tag, process_image_content, query_meme_by_content_hash,
+        AddMemeResult, Tag,
+    },
+    staging::{publish_bytes, settle_staging},
+    storage::{fetch_content, store_content},
+    video::extract_poster_frame,
+};
+
+/// Put meme content into system clipboard
+/// Images are decoded into raw pixels since clipboard only accepts bitmap,
+/// so only the first frame of animated image is copied,
+/// and video is copied as its poster frame
+pub fn copy_to_clipboard(state: &MemeDatabaseConnection, id: i64) -> AppResult<()> {
+    let (ty, hash): (String, String) = state
+        .conn
+        .query_row("SELECT ty, hash FROM meme WHERE id = ?1", [id], |row| {
+            Ok((row.get("ty")?, row.get("hash")?))
+        })
+        .map_err(|e| e.to_string())?;
+    let path = fetch_content(state.storage.as_ref(), &state.path, &hash)?;
+
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    match ty.as_str() {
+        "text" => {
+            let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            clipboard.set_text(text).map_err(|e| e.to_string())?;
+        }
+        _ => {
+            let img = match ty.as_str() {
+                "video" => extract_poster_frame(path)?,
+                _ => image::open(path).map_err(|e| e.to_string())?,
+            }
+            .to_rgba8();
+            clipboard
+                .set_image(ImageData {
+                    width: img.width() as usize,
+                    height: img.height() as usize,
+                    bytes: Cow::Owned(img.into_raw()),
+                })
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn copy_meme_to_clipboard(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    copy_to_clipboard(state, id)
+}
+
+/// Import image in system clipboard as a new meme, it is stored as png
+pub async fn import_clipboard_image(
+    db_state: &MemeDatabaseState,
+    name: Option<String>,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+) -> AppResult<AddMemeResult> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    let data = clipboard.get_image().map_err(|e| e.to_string())?;
+    let img = RgbaImage::from_raw(
+        data.width as u32,
+        data.height as u32,
+        data.bytes.into_owned(),
+    )
+    .ok_or("invalid image in clipboard")?;
+    let mut content = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(img)
+        .write_to(&mut content, ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+    let content = content.into_inner();
+
+    let mut guard = db_state.state.lock().await;
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    if let Some(id) = query_meme_by_content_hash(&conn, &sha256::digest(content.as_slice()))? {
+        return Ok(AddMemeResult::Duplicate(id));
+    }
+
+    let tag_id = tags
+        .iter()
+        .map(|t| make_tag(&conn, &t.key, &t.value))
+        .collect::<Result<Vec<i64>, String>>()?;
+
+    let hash = publish_bytes(&state.path, &content, Some("png")).map_err(|e| e.to_string())?;
+    store_content(state.storage.as_ref(), &state.path, &hash)?;
+    let name = name.unwrap_or_else(|| {
+        format!(
+            "clipboard-{}",
+            OffsetDateTime::now_utc().unix_timestamp()
+        )
+    });
+    let meme_id = insert_meme(&conn, &name, None, "image", &hash, false, pkg_id)?;
+    process_image_content(&conn, &state.path, meme_id, &hash)?;
+    for tid in tag_id {
+        insert_meme_tag(&conn, meme_id, tid)?;
+    }
+
+    conn.commit().map_err(|e| e.to_string())?;
+    if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+        warn!(error = %e, "failed to settle staged clipboard image");
+    }
+    Ok(AddMemeResult::Added(meme_id))
+}
+
+#[tauri::command]
+pub async fn import_from_clipboard(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    name: Option<String>,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+) -> AppResult<AddMemeResult> {
+    import_clipboard_image(&db_state, name, tags, pkg_id).await
+}
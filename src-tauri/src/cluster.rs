@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    dedup::{cluster_by_phash, fill_missing_phash},
+    error::{AppError, AppResult},
+    meme::{query_tags_for_memes, Tag},
+};
+
+/// Tags suggested from this namespace if none is given
+const DEFAULT_NAMESPACE: &str = "character";
+
+#[derive(Debug, serde::Serialize)]
+pub struct SuggestedTag {
+    tag: Tag,
+    /// Number of memes in cluster already having the tag
+    support: usize,
+}
+
+/// Memes looking alike, some of which have tags of the namespace and some not
+#[derive(Debug, serde::Serialize)]
+pub struct ClusterSuggestion {
+    members: Vec<i64>,
+    /// Members without any tag of the namespace, the tags may be applied to them
+    untagged: Vec<i64>,
+    /// Tags of the namespace on other members, most common first
+    tags: Vec<SuggestedTag>,
+}
+
+/// Cluster visually similar memes by phash and suggest tags of `namespace`
+/// found on some members for members which have none of them.
+/// Memes without phash will be hashed before clustering.
+/// Clusters where every member or no member is tagged are left out, largest cluster first
+#[tauri::command]
+pub async fn suggest_cluster_tags(
+    state: tauri::State<'_, MemeDatabaseState>,
+    threshold: u32,
+    namespace: Option<String>,
+) -> AppResult<Vec<ClusterSuggestion>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+
+    fill_missing_phash(&state.conn, state.storage.as_ref(), &state.path)
+        .map_err(AppError::StorageUnavailable)?;
+    let mut result = Vec::new();
+    for members in cluster_by_phash(&state.conn, threshold)? {
+        let tags = query_tags_for_memes(&state.conn, &members)?;
+        let mut support = HashMap::<&Tag, usize>::new();
+        let mut untagged = Vec::new();
+        for id in &members {
+            let in_namespace = tags[id]
+                .iter()
+                .filter(|tag| tag.key == namespace)
+                .collect::<Vec<&Tag>>();
+            if in_namespace.is_empty() {
+                untagged.push(*id);
+            }
+            for tag in in_namespace {
+                *support.entry(tag).or_default() += 1;
+            }
+        }
+        if untagged.is_empty() || support.is_empty() {
+            continue;
+        }
+        let mut tags = support
+            .into_iter()
+            .map(|(tag, support)| SuggestedTag {
+                tag: tag.clone(),
+                support,
+            })
+            .collect::<Vec<SuggestedTag>>();
+        tags.sort_by(|l, r| r.support.cmp(&l.support).then(l.tag.value.cmp(&r.tag.value)));
+        result.push(ClusterSuggestion {
+            members,
+            untagged,
+            tags,
+        });
+    }
+    result.sort_by_key(|cluster| std::cmp::Reverse(cluster.members.len()));
+    Ok(result)
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use image::{imageops::FilterType, DynamicImage};
+use rusqlite::{Connection, Error};
+use tauri::Window;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::compute_path,
+    progress::{TaskProgress, TaskRegistry},
+    storage::fetch_content,
+};
+
+/// Colors searchable by `color:<name>`, every pixel falls into one of them
+pub const COLOR_NAMES: &[&str] = &[
+    "red", "orange", "yellow", "green", "cyan", "blue", "purple", "pink", "brown", "black",
+    "white", "gray",
+];
+/// Image is down-scaled before counting colors
+const SAMPLE_SIZE: u32 = 64;
+/// Colors covering less of image are left out of palette
+const MIN_PALETTE_RATIO: f64 = 0.05;
+/// Image has no dominant hue if no chromatic color covers this much of it
+const MIN_HUE_RATIO: f64 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteColor {
+    pub name: &'static str,
+    /// Average of pixels of this color, `#rrggbb`
+    pub color: String,
+    pub ratio: f64,
+    /// None for black, white and gray
+    pub hue: Option<f64>,
+}
+
+/// Hue in degrees, saturation and lightness in 0..1
+fn rgb_to_hsl(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let (r, g, b) = (r / 255.0, g / 255.0, b / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * (((g - b) / d).rem_euclid(6.0))
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    (h, s, l)
+}
+
+fn color_name(h: f64, s: f64, l: f64) -> &'static str {
+    if l < 0.12 {
+        return "black";
+    }
+    if l > 0.92 {
+        return "white";
+    }
+    if s < 0.15 {
+        return "gray";
+    }
+    match h {
+        h if (15.0..45.0).contains(&h) && l < 0.4 => "brown",
+        h if !(15.0..345.0).contains(&h) => "red",
+        h if h < 45.0 => "orange",
+        h if h < 70.0 => "yellow",
+        h if h < 170.0 => "green",
+        h if h < 200.0 => "cyan",
+        h if h < 260.0 => "blue",
+        h if h < 300.0 => "purple",
+        _ => "pink",
+    }
+}
+
+/// Colors of image by the part of image they cover, most common first.
+/// Transparent pixels are not counted
+pub fn compute_palette(img: &DynamicImage) -> Vec<PaletteColor> {
+    let img = img.resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle).to_rgba8();
+    // sum of r, g, b and count of pixels by index in `COLOR_NAMES`
+    let mut buckets = vec![(0.0, 0.0, 0.0, 0usize); COLOR_NAMES.len()];
+    let mut total = 0;
+    for pixel in img.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        let (r, g, b) = (r as f64, g as f64, b as f64);
+        let (h, s, l) = rgb_to_hsl(r, g, b);
+        let idx = COLOR_NAMES
+            .iter()
+            .position(|name| *name == color_name(h, s, l))
+            .unwrap();
+        let bucket = &mut buckets[idx];
+        *bucket = (bucket.0 + r, bucket.1 + g, bucket.2 + b, bucket.3 + 1);
+        total += 1;
+    }
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut palette = buckets
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, _, _, count))| *count > 0)
+        .map(|(idx, (r, g, b, count))| {
+            let (r, g, b) = (r / count as f64, g / count as f64, b / count as f64);
+            let name = COLOR_NAMES[idx];
+            let chromatic = !matches!(name, "black" | "white" | "gray");
+            PaletteColor {
+                name,
+                color: format!("#{:02x}{:02x}{:02x}", r as u8, g as u8, b as u8),
+                ratio: count as f64 / total as f64,
+                hue: Some(rgb_to_hsl(r, g, b).0).filter(|_| chromatic),
+            }
+        })
+        .filter(|color| color.ratio >= MIN_PALETTE_RATIO)
+        .collect::<Vec<PaletteColor>>();
+    palette.sort_by(|l, r| r.ratio.total_cmp(&l.ratio));
+    palette
+}
+
+/// Compute and save palette and dominant hue of image meme, content must be already in storage
+pub fn update_meme_colors<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    content: &str,
+) -> Result<(), String> {
+    let palette = match image::open(compute_path(base, content)) {
+        Ok(img) => compute_palette(&img),
+        Err(_) => Vec::new(),
+    };
+    conn.execute("DELETE FROM meme_color WHERE meme_id = ?1", [meme_id])
+        .map_err(|e| e.to_string())?;
+    for color in &palette {
+        conn.execute(
+            "INSERT INTO meme_color(meme_id, name, color, ratio) VALUES (?1, ?2, ?3, ?4)",
+            (meme_id, color.name, &color.color, color.ratio),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let hue = palette
+        .iter()
+        .find(|color| color.hue.is_some() && color.ratio >= MIN_HUE_RATIO)
+        .and_then(|color| color.hue);
+    conn.execute("UPDATE meme SET dominant_hue = ?1 WHERE id = ?2", (hue, meme_id))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Compute colors of all image memes which have no palette yet,
+/// progress of `task` is reported after each meme. Return the number of memes processed
+#[tauri::command]
+pub async fn index_colors(
+    window: Window,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+) -> AppResult<usize> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let mut query = state.conn.prepare(
+        "SELECT id, hash FROM meme WHERE ty = 'image'
+          AND NOT EXISTS (SELECT 1 FROM meme_color WHERE meme_id = meme.id)",
+    )?;
+    let memes = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, String)>, Error>>()?;
+    drop(query);
+
+    let progress = TaskProgress::start(window, &registry, task, memes.len())?;
+    let mut count = 0;
+    for (id, hash) in memes {
+        if progress.is_cancelled() {
+            break;
+        }
+        fetch_content(state.storage.as_ref(), &state.path, &hash)
+            .map_err(AppError::StorageUnavailable)?;
+        let conn = state.conn.transaction()?;
+        update_meme_colors(&conn, &state.path, id, &hash)?;
+        conn.commit()?;
+        count += 1;
+        progress.report(count, id)?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,124 @@
+use std::io::Cursor;
+
+use image::{GrayImage, ImageOutputFormat};
+use tracing::warn;
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::release_contents,
+    image_edit::load_meme_image,
+    pages::{query_image_pages, to_pages, write_pages, MemePage},
+    staging::{publish_bytes, settle_staging},
+    storage::store_content,
+    thumbnail::generate_thumbnail,
+};
+
+/// Rows of at least this many blank pixels separate panels, unless given otherwise
+const DEFAULT_MIN_GAP: u32 = 8;
+/// A row is blank if lightness of its pixels differs by no more than this,
+/// so gutters of any solid color and slight jpeg noise are both accepted
+const BLANK_TOLERANCE: u8 = 12;
+/// Panels lower than this are merged into their neighbour, they are usually
+/// a stray line of caption or the edge of a border rather than a panel
+const MIN_PANEL_HEIGHT: u32 = 32;
+
+fn is_blank_row(img: &GrayImage, y: u32) -> bool {
+    let (mut min, mut max) = (u8::MAX, u8::MIN);
+    for x in 0..img.width() {
+        let value = img.get_pixel(x, y)[0];
+        min = min.min(value);
+        max = max.max(value);
+    }
+    max.saturating_sub(min) <= BLANK_TOLERANCE
+}
+
+/// Vertical ranges `(top, bottom)` of panels in a stitched long image, `bottom` is exclusive.
+/// Panels are separated by at least `min_gap` blank rows, which are left out of every panel
+pub fn find_panels(img: &GrayImage, min_gap: u32) -> Vec<(u32, u32)> {
+    let min_gap = min_gap.max(1);
+    let mut panels = Vec::new();
+    let mut top = None;
+    let mut blank_rows = 0;
+    for y in 0..img.height() {
+        if !is_blank_row(img, y) {
+            top.get_or_insert(y);
+            blank_rows = 0;
+            continue;
+        }
+        blank_rows += 1;
+        if blank_rows == min_gap {
+            if let Some(top) = top.take() {
+                panels.push((top, y + 1 - min_gap));
+            }
+        }
+    }
+    if let Some(top) = top {
+        panels.push((top, img.height() - blank_rows));
+    }
+
+    let mut merged: Vec<(u32, u32)> = Vec::new();
+    for (top, bottom) in panels {
+        match merged.last_mut() {
+            Some(last) if bottom - top < MIN_PANEL_HEIGHT || last.1 - last.0 < MIN_PANEL_HEIGHT => {
+                last.1 = bottom;
+            }
+            _ => merged.push((top, bottom)),
+        }
+    }
+    merged
+}
+
+/// Split a tall stitched screenshot or comic of image meme `id` into one page per panel,
+/// cut at runs of at least `min_gap` blank rows. Panels are stored as png and replace
+/// the original image, whose content is removed unless another meme uses it.
+/// Only the first frame of animated image is split
+#[tauri::command]
+pub async fn split_long_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    min_gap: Option<u32>,
+) -> AppResult<Vec<MemePage>> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let old = query_image_pages(&state.conn, id)?;
+    if old.len() != 1 {
+        return Err(AppError::InvalidArgument(format!(
+            "meme {} already has {} pages",
+            id,
+            old.len()
+        )));
+    }
+    let img = load_meme_image(state, id)?;
+    let panels = find_panels(&img.to_luma8(), min_gap.unwrap_or(DEFAULT_MIN_GAP));
+    if panels.len() < 2 {
+        return Err(AppError::InvalidArgument(
+            "no panel boundary is found in image".to_owned(),
+        ));
+    }
+
+    let size = load_settings(&state.conn)?.thumbnail_size;
+    let mut hashes = Vec::with_capacity(panels.len());
+    for (top, bottom) in panels {
+        let panel = img.crop_imm(0, top, img.width(), bottom - top);
+        let mut data = Cursor::new(Vec::new());
+        panel
+            .write_to(&mut data, ImageOutputFormat::Png)
+            .map_err(|e| e.to_string())?;
+        let hash = publish_bytes(&state.path, data.get_ref(), Some("png"))?;
+        store_content(state.storage.as_ref(), &state.path, &hash)?;
+        generate_thumbnail(&state.path, &hash, size)?;
+        hashes.push(hash);
+    }
+
+    let conn = state.conn.transaction()?;
+    let released = write_pages(&conn, &state.path, id, &old, &hashes)?;
+    conn.commit()?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+        warn!(error = %e, "failed to settle staged panels");
+    }
+    Ok(to_pages(&state.path, hashes))
+}
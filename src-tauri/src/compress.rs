@@ -0,0 +1,234 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+};
+
+use image::{
+    codecs::{avif::AvifEncoder, png::PngDecoder},
+    ColorType, DynamicImage, ImageEncoder,
+};
+use rusqlite::{Connection, Error};
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::{compute_path, count_file_references},
+    jobs::{enqueue_job, Job, JobQueue},
+    media::update_media_info,
+    staging::publish_bytes,
+    storage::{fetch_content, store_content, Storage},
+    thumbnail::generate_thumbnail,
+};
+
+/// Speed of AVIF encoder from 1 to 10, faster gives larger files
+const AVIF_SPEED: u8 = 6;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressedFormat {
+    Webp,
+    Avif,
+}
+
+impl CompressedFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CompressedFormat::Webp => "webp",
+            CompressedFormat::Avif => "avif",
+        }
+    }
+}
+
+/// Lossy re-encoding of large PNG content by compression jobs
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CompressionConfig {
+    pub format: CompressedFormat,
+    /// From 0 to 100
+    pub quality: u8,
+    /// Only content larger than this many bytes is compressed
+    pub min_size: u64,
+}
+
+/// Space saved by compression jobs so far, in bytes
+#[derive(Debug, serde::Serialize)]
+pub struct CompressionSavings {
+    /// Number of compressed contents
+    count: i64,
+    original_size: i64,
+    size: i64,
+}
+
+fn encode(img: &DynamicImage, config: &CompressionConfig) -> Result<Vec<u8>, String> {
+    match config.format {
+        CompressedFormat::Webp => {
+            let encoder = webp::Encoder::from_image(img).map_err(|e| e.to_string())?;
+            Ok(encoder.encode(config.quality as f32).to_vec())
+        }
+        CompressedFormat::Avif => {
+            let img = img.to_rgba8();
+            let mut data = Vec::new();
+            AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, config.quality)
+                .write_image(img.as_raw(), img.width(), img.height(), ColorType::Rgba8)
+                .map_err(|e| e.to_string())?;
+            Ok(data)
+        }
+    }
+}
+
+/// Animated PNG would lose every frame but the first
+fn is_apng(path: &Path) -> Result<bool, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = PngDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    Ok(decoder.is_apng())
+}
+
+/// PNG content re-encoded by `encode_content`, already put into storage
+pub struct Compressed {
+    pub hash: String,
+    original_size: u64,
+    size: u64,
+}
+
+/// Re-encode PNG content `hash` as configured and put the result into storage with its
+/// thumbnail. Nothing is written to database, so it can run without holding it.
+/// Return None if content is animated, smaller than `min_size`, or would not shrink
+pub fn encode_content(
+    storage: &dyn Storage,
+    base: &Path,
+    hash: &str,
+    config: &CompressionConfig,
+    thumbnail_size: u32,
+) -> Result<Option<Compressed>, String> {
+    if !hash.ends_with(".png") {
+        return Ok(None);
+    }
+    let path = fetch_content(storage, base, hash)?;
+    let original_size = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+    if original_size < config.min_size || is_apng(&path)? {
+        return Ok(None);
+    }
+    let img = image::open(&path).map_err(|e| e.to_string())?;
+    let data = encode(&img, config)?;
+    if data.len() as u64 >= original_size {
+        return Ok(None);
+    }
+
+    let compressed =
+        publish_bytes(base, &data, Some(config.format.extension())).map_err(|e| e.to_string())?;
+    store_content(storage, base, &compressed)?;
+    generate_thumbnail(base, &compressed, thumbnail_size)?;
+    Ok(Some(Compressed {
+        hash: compressed,
+        original_size,
+        size: data.len() as u64,
+    }))
+}
+
+/// Point every meme page using content `original` to its compressed content.
+/// `original` is recorded in `content_alias`, so importing the same file again is still
+/// detected as a duplicate and sync keeps identifying the meme by it.
+/// `update_time` of memes is kept, since their metadata is unchanged.
+/// Return false without changing anything if no page uses `original` any more,
+/// otherwise it should be passed to `release_contents` once the transaction has committed
+pub fn replace_content(
+    conn: &Connection,
+    base: &Path,
+    original: &str,
+    compressed: &Compressed,
+) -> Result<bool, String> {
+    if count_file_references(conn, original).map_err(|e| e.to_string())? == 0 {
+        return Ok(false);
+    }
+    conn.execute(
+        "UPDATE meme_file SET hash = ?2 WHERE hash = ?1",
+        (original, &compressed.hash),
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("UPDATE meme SET hash = ?2 WHERE hash = ?1", (original, &compressed.hash))
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO content_alias(original, hash, original_size, size)
+          VALUES (?1, ?2, ?3, ?4)",
+        (
+            original,
+            &compressed.hash,
+            compressed.original_size as i64,
+            compressed.size as i64,
+        ),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let memes = {
+        let mut query = conn
+            .prepare("SELECT id FROM meme WHERE hash = ?1")
+            .map_err(|e| e.to_string())?;
+        let memes = query
+            .query_map([&compressed.hash], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<i64>, Error>>()
+            .map_err(|e| e.to_string())?;
+        memes
+    };
+    for id in memes {
+        update_media_info(conn, base, id, "image", &compressed.hash)?;
+    }
+    Ok(true)
+}
+
+/// Queue a compression job for every PNG content of image memes
+/// which is not known to be smaller than `min_size`, return the number of queued jobs.
+/// Jobs fail if compression is disabled in settings when they run
+#[tauri::command]
+pub async fn queue_compression(
+    state: tauri::State<'_, MemeDatabaseState>,
+    queue: tauri::State<'_, JobQueue>,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let config = load_settings(&state.conn)?
+        .compression
+        .ok_or_else(|| AppError::InvalidArgument("compression is disabled".to_owned()))?;
+
+    let mut query = state.conn.prepare(
+        "SELECT DISTINCT f.hash FROM meme_file f JOIN meme m ON m.id = f.meme_id
+          WHERE m.ty = 'image' AND f.hash LIKE '%.png'",
+    )?;
+    let hashes = query
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    let mut count = 0;
+    for hash in hashes {
+        // content only in remote storage is checked by the job once it is downloaded
+        let size = fs::metadata(compute_path(&state.path, &hash)).map(|x| x.len());
+        if matches!(size, Ok(size) if size < config.min_size) {
+            continue;
+        }
+        enqueue_job(&state.conn, &Job::Compress { hash })?;
+        count += 1;
+    }
+    queue.wake();
+    Ok(count)
+}
+
+#[tauri::command]
+pub async fn get_compression_savings(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<CompressionSavings> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(state.conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(original_size), 0), COALESCE(SUM(size), 0)
+          FROM content_alias",
+        [],
+        |row| {
+            Ok(CompressionSavings {
+                count: row.get(0)?,
+                original_size: row.get(1)?,
+                size: row.get(2)?,
+            })
+        },
+    )?)
+}
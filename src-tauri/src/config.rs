@@ -0,0 +1,125 @@
+use rusqlite::{Connection, Error};
+
+use crate::{
+    autotag::AutoTaggerConfig,
+    compress::CompressionConfig,
+    db::{library_not_opened, search::SortBy, MemeDatabaseState},
+    error::{AppError, AppResult},
+    normalize::CanonicalFormat,
+};
+
+/// Settings of a library, stored in `settings` table one field per row as JSON,
+/// so fields added later fall back to their default value
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Page size of search when it is not given by request
+    pub page_size: i64,
+    /// Longest side of thumbnails and animated previews in pixels
+    pub thumbnail_size: u32,
+    /// Memes stay in trash for this many days before they are purged
+    /// on opening library, 0 keeps them until purged manually
+    pub trash_retention_days: u32,
+    /// Order of search when it is not given by request,
+    /// `None` sorts by relevance of search statement
+    pub default_sort: Option<SortBy>,
+    /// Check sha256 of content file again when it is served,
+    /// mismatches are listed as corrupted in library report
+    pub verify_on_read: bool,
+    /// Classifier run by auto-tagging jobs, `None` disables auto-tagging
+    pub auto_tagger: Option<AutoTaggerConfig>,
+    /// Tags guessed with lower confidence are not written
+    pub auto_tag_threshold: f32,
+    /// Format SVG, BMP, TIFF, HEIC and AVIF images are converted to on import
+    pub import_format: CanonicalFormat,
+    /// Keep the original file of converted images in `originals` of library
+    pub keep_original_format: bool,
+    /// Lossy re-encoding of large PNG content, `None` disables compression jobs
+    pub compression: Option<CompressionConfig>,
+    /// Database is vacuumed and analyzed in background on opening library
+    /// if it has not been for this many days, 0 only maintains it on request
+    pub maintenance_interval_days: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            page_size: 30,
+            thumbnail_size: 256,
+            trash_retention_days: 0,
+            default_sort: None,
+            verify_on_read: false,
+            auto_tagger: None,
+            auto_tag_threshold: 0.35,
+            import_format: CanonicalFormat::Png,
+            keep_original_format: false,
+            compression: None,
+            maintenance_interval_days: 30,
+        }
+    }
+}
+
+pub fn load_settings(conn: &Connection) -> Result<Settings, String> {
+    let mut query = conn
+        .prepare("SELECT key, value FROM settings")
+        .map_err(|e| e.to_string())?;
+    let stored = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(String, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut settings = serde_json::to_value(Settings::default()).unwrap();
+    for (key, value) in stored {
+        // keys of removed fields are ignored by deserializer
+        let value = serde_json::from_str(&value).map_err(|e| e.to_string())?;
+        settings[key] = value;
+    }
+    serde_json::from_value(settings).map_err(|e| e.to_string())
+}
+
+pub fn save_settings(conn: &Connection, settings: &Settings) -> Result<(), String> {
+    let value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    for (key, value) in value.as_object().unwrap() {
+        conn.execute(
+            "INSERT OR REPLACE INTO settings(key, value) VALUES (?1, ?2)",
+            (key, value.to_string()),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_settings(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<Settings> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(load_settings(&state.conn)?)
+}
+
+#[tauri::command]
+pub async fn set_settings(
+    state: tauri::State<'_, MemeDatabaseState>,
+    settings: Settings,
+) -> AppResult<()> {
+    if settings.page_size <= 0 {
+        return Err(AppError::InvalidArgument(
+            "page size should be positive".to_owned(),
+        ));
+    }
+    if settings.thumbnail_size == 0 {
+        return Err(AppError::InvalidArgument(
+            "thumbnail size should be positive".to_owned(),
+        ));
+    }
+    if !(0.0..=1.0).contains(&settings.auto_tag_threshold) {
+        return Err(AppError::InvalidArgument(
+            "auto-tag threshold should be between 0 and 1".to_owned(),
+        ));
+    }
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    save_settings(&state.conn, &settings)?;
+    state.verify_on_read = settings.verify_on_read;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Libraries seeded with memes and tags for tests, they live in memory
+//! so tests need neither app data directory nor content files
+
+use rusqlite::Connection;
+
+use crate::{
+    db::MemeDatabaseConnection,
+    meme::{insert_meme, insert_meme_tag, make_tag},
+};
+
+/// Memes of `seeded_library` with their tags
+pub const SEED_MEMES: &[(&str, &[(&str, &str)])] = &[
+    ("happy cat", &[("character", "cat"), ("mood", "happy")]),
+    ("sad cat", &[("character", "cat"), ("mood", "sad")]),
+    ("happy dog", &[("character", "dog"), ("mood", "happy")]),
+    ("untagged", &[]),
+];
+
+/// Empty library migrated to current version, content files would go to temp dir
+pub fn empty_library() -> MemeDatabaseConnection {
+    MemeDatabaseConnection::open_in_memory(std::env::temp_dir().join("mmm-test"))
+        .expect("failed to open in-memory library")
+}
+
+/// Insert a text meme named `name` with `tags`, return its id.
+/// Text memes need no content file, their hash is made up from name
+pub fn seed_meme(conn: &Connection, name: &str, tags: &[(&str, &str)]) -> i64 {
+    let hash = format!("{}.txt", sha256::digest(name));
+    let id = insert_meme(conn, name, None, "text", &hash, false, 0).unwrap();
+    for (key, value) in tags {
+        let tag_id = make_tag(conn, key, value).unwrap();
+        insert_meme_tag(conn, id, tag_id).unwrap();
+    }
+    id
+}
+
+/// Library with `SEED_MEMES`, ids of memes are in the same order
+pub fn seeded_library() -> (MemeDatabaseConnection, Vec<i64>) {
+    let state = empty_library();
+    let ids = SEED_MEMES
+        .iter()
+        .map(|(name, tags)| seed_meme(&state.conn, name, tags))
+        .collect();
+    (state, ids)
+}
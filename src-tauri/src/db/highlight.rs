@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use rusqlite::{params_from_iter, Connection, Error};
+
+use crate::{
+    db::search::SearchTerm,
+    meme::{query_tags_for_memes, Tag},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchedField {
+    Name,
+    Description,
+    /// Value of a tag
+    Tag,
+    /// Text recognized by OCR
+    Text,
+}
+
+/// Part of a field of meme matching a search term,
+/// `start` and `end` are byte offsets into UTF-8 value of the field
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Highlight {
+    pub field: MatchedField,
+    /// Tag whose value is matched, only set for `MatchedField::Tag`
+    pub tag: Option<Tag>,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Compare characters the way FTS and LIKE do, ignoring case
+fn fold(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+/// Byte ranges where `needle` occurs in `haystack` ignoring case, ranges do not overlap
+fn find_ignore_case(haystack: &str, needle: &str) -> Vec<(usize, usize)> {
+    let needle = needle.chars().map(fold).collect::<Vec<char>>();
+    let mut ranges = Vec::new();
+    if needle.is_empty() {
+        return ranges;
+    }
+    let mut next = 0;
+    for (start, _) in haystack.char_indices() {
+        if start < next {
+            continue;
+        }
+        let mut chars = haystack[start..].char_indices();
+        let matched = needle.iter().all(|expected| {
+            chars
+                .next()
+                .map_or(false, |(_, ch)| fold(ch) == *expected)
+        });
+        if matched {
+            next = chars.next().map_or(haystack.len(), |(end, _)| start + end);
+            ranges.push((start, next));
+        }
+    }
+    ranges
+}
+
+fn highlight_field(highlights: &mut Vec<Highlight>, field: MatchedField, value: &str, kwd: &str) {
+    for (start, end) in find_ignore_case(value, kwd) {
+        highlights.push(Highlight {
+            field,
+            tag: None,
+            start,
+            end,
+        });
+    }
+}
+
+fn highlight_tags(highlights: &mut Vec<Highlight>, tags: &[Tag], term: &SearchTerm) {
+    for tag in tags {
+        if let SearchTerm::Tag(Some(namespace), _) = term {
+            if *namespace != tag.key {
+                continue;
+            }
+        }
+        let ranges = match term {
+            SearchTerm::Keyword(kwd) => find_ignore_case(&tag.value, kwd),
+            SearchTerm::Tag(_, None) => vec![(0, tag.value.len())],
+            // value is matched as a prefix
+            SearchTerm::Tag(_, Some(value)) => find_ignore_case(&tag.value, value)
+                .into_iter()
+                .take(1)
+                .filter(|(start, _)| *start == 0)
+                .collect(),
+        };
+        for (start, end) in ranges {
+            highlights.push(Highlight {
+                field: MatchedField::Tag,
+                tag: Some(tag.clone()),
+                start,
+                end,
+            });
+        }
+    }
+}
+
+/// Where `terms` of a search occur in memes `ids`, keyed by meme id.
+/// Memes matching no term, e.g. found only by a date or color filter, have no entry
+pub fn query_highlights(
+    conn: &Connection,
+    terms: &[SearchTerm],
+    ids: &[i64],
+) -> Result<HashMap<i64, Vec<Highlight>>, String> {
+    let mut result = HashMap::new();
+    if terms.is_empty() || ids.is_empty() {
+        return Ok(result);
+    }
+    let mut query = conn
+        .prepare(&format!(
+            "SELECT meme.id, name, description, text FROM meme
+              LEFT JOIN meme_text ON meme_text.meme_id = meme.id
+              WHERE meme.id IN ({})",
+            vec!["?"; ids.len()].join(", ")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map(params_from_iter(ids), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String, Option<String>, Option<String>)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    let tags = query_tags_for_memes(conn, ids)?;
+
+    for (id, name, description, text) in rows {
+        let mut highlights = Vec::new();
+        for term in terms {
+            if let SearchTerm::Keyword(kwd) = term {
+                let fields = [
+                    (MatchedField::Name, Some(&name)),
+                    (MatchedField::Description, description.as_ref()),
+                    (MatchedField::Text, text.as_ref()),
+                ];
+                for (field, value) in fields {
+                    if let Some(value) = value {
+                        highlight_field(&mut highlights, field, value, kwd);
+                    }
+                }
+            }
+            if let Some(tags) = tags.get(&id) {
+                highlight_tags(&mut highlights, tags, term);
+            }
+        }
+        if !highlights.is_empty() {
+            result.insert(id, highlights);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{
+        fixtures::seeded_library,
+        highlight::{find_ignore_case, query_highlights, MatchedField},
+        search::build_search_sql,
+    };
+
+    #[test]
+    fn test_find_ignore_case() {
+        assert_eq!(find_ignore_case("Cat cat", "cat"), vec![(0, 3), (4, 7)]);
+        assert_eq!(find_ignore_case("aaaa", "aa"), vec![(0, 2), (2, 4)]);
+        // offsets are in bytes
+        assert_eq!(find_ignore_case("猫猫 Cat", "cat"), vec![(7, 10)]);
+        assert!(find_ignore_case("cat", "").is_empty());
+    }
+
+    #[test]
+    fn test_highlights() {
+        let (state, ids) = seeded_library();
+        let search = build_search_sql("happy character:c -mood:sad").unwrap();
+        let highlights = query_highlights(&state.conn, &search.terms, &ids).unwrap();
+
+        let happy_cat = &highlights[&ids[0]];
+        assert!(happy_cat
+            .iter()
+            .any(|x| x.field == MatchedField::Name && (x.start, x.end) == (0, 5)));
+        assert!(happy_cat.iter().any(|x| {
+            x.field == MatchedField::Tag
+                && x.tag.as_ref().map(|tag| tag.key.as_str()) == Some("character")
+                && (x.start, x.end) == (0, 1)
+        }));
+        // negated terms are not highlighted
+        assert!(!highlights[&ids[1]]
+            .iter()
+            .any(|x| x.tag.as_ref().map_or(false, |tag| tag.value == "sad")));
+        assert!(!highlights.contains_key(&ids[3]));
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use tracing::{error, info};
+
+use crate::{
+    encryption::{apply_passphrase, check_unlocked},
+    error::{AppError, AppResult},
+};
+
+/// How a migration changes database
+pub enum MigrationStep {
+    /// Script run as a batch
+    Sql(&'static str),
+    /// Change which can not be written in SQL, `name` stands in for the script in checksum
+    Rust {
+        name: &'static str,
+        run: fn(&Connection) -> rusqlite::Result<()>,
+    },
+}
+
+/// Upgrade of database to `version` from the version before it
+pub struct Migration {
+    pub version: u32,
+    pub step: MigrationStep,
+}
+
+impl Migration {
+    const fn sql(version: u32, sql: &'static str) -> Self {
+        Self {
+            version,
+            step: MigrationStep::Sql(sql),
+        }
+    }
+
+    /// Recorded when migration is applied, script of an applied migration must not change
+    pub fn checksum(&self) -> String {
+        match &self.step {
+            MigrationStep::Sql(sql) => sha256::digest(*sql),
+            MigrationStep::Rust { name, .. } => sha256::digest(format!("rust:{}", name)),
+        }
+    }
+
+    fn apply(&self, conn: &Connection) -> AppResult<()> {
+        let result = match &self.step {
+            MigrationStep::Sql(sql) => conn.execute_batch(sql),
+            MigrationStep::Rust { run, .. } => run(conn),
+        };
+        result.map_err(|e| AppError::MigrationFailed {
+            version: self.version,
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Every migration in order of version. Version 1 creates database from scratch,
+/// a new migration is appended here with the next version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration::sql(1, include_str!("database_init.sql")),
+    Migration::sql(2, include_str!("migrations/v2.sql")),
+    Migration::sql(3, include_str!("migrations/v3.sql")),
+    Migration::sql(4, include_str!("migrations/v4.sql")),
+    Migration::sql(5, include_str!("migrations/v5.sql")),
+    Migration::sql(6, include_str!("migrations/v6.sql")),
+    Migration::sql(7, include_str!("migrations/v7.sql")),
+    Migration::sql(8, include_str!("migrations/v8.sql")),
+    Migration::sql(9, include_str!("migrations/v9.sql")),
+    Migration::sql(10, include_str!("migrations/v10.sql")),
+    Migration::sql(11, include_str!("migrations/v11.sql")),
+    Migration::sql(12, include_str!("migrations/v12.sql")),
+    Migration::sql(13, include_str!("migrations/v13.sql")),
+    Migration::sql(14, include_str!("migrations/v14.sql")),
+    Migration::sql(15, include_str!("migrations/v15.sql")),
+    Migration::sql(16, include_str!("migrations/v16.sql")),
+    Migration::sql(17, include_str!("migrations/v17.sql")),
+    Migration::sql(18, include_str!("migrations/v18.sql")),
+    Migration::sql(19, include_str!("migrations/v19.sql")),
+    Migration::sql(20, include_str!("migrations/v20.sql")),
+    Migration::sql(21, include_str!("migrations/v21.sql")),
+    Migration::sql(22, include_str!("migrations/v22.sql")),
+    Migration::sql(23, include_str!("migrations/v23.sql")),
+    Migration::sql(24, include_str!("migrations/v24.sql")),
+    Migration::sql(25, include_str!("migrations/v25.sql")),
+    Migration::sql(26, include_str!("migrations/v26.sql")),
+    Migration::sql(27, include_str!("migrations/v27.sql")),
+    Migration::sql(28, include_str!("migrations/v28.sql")),
+    Migration::sql(29, include_str!("migrations/v29.sql")),
+    Migration::sql(30, include_str!("migrations/v30.sql")),
+    Migration::sql(31, include_str!("migrations/v31.sql")),
+    Migration::sql(32, include_str!("migrations/v32.sql")),
+    Migration::sql(33, include_str!("migrations/v33.sql")),
+    Migration::sql(34, include_str!("migrations/v34.sql")),
+    Migration::sql(35, include_str!("migrations/v35.sql")),
+];
+
+/// Version of database once every migration is applied
+pub const CURRENT_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Version of database, `None` if it has never been migrated
+pub fn local_version(conn: &Connection) -> AppResult<Option<u32>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master
+          WHERE type = 'table' AND name = 'table_version')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(None);
+    }
+    Ok(conn
+        .query_row("SELECT version FROM table_version WHERE id = 0", [], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+/// Fail with `AppError::DatabaseTooNew` if database has been migrated by a newer build,
+/// whose schema this build does not know and must not write to
+pub fn check_compatible(conn: &Connection) -> AppResult<()> {
+    match local_version(conn)? {
+        Some(version) if version > CURRENT_VERSION => Err(AppError::DatabaseTooNew {
+            version,
+            supported: CURRENT_VERSION,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Migrations not applied to database at `version` yet
+pub fn pending_migrations(version: Option<u32>) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(move |migration| migration.version > version.unwrap_or(0))
+}
+
+fn ensure_version_tables(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS table_version (id INTEGER PRIMARY KEY, version INTEGER);
+        CREATE TABLE IF NOT EXISTS migration_history (
+          version INTEGER PRIMARY KEY,
+          checksum TEXT NOT NULL,
+          applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+    Ok(())
+}
+
+/// Databases migrated before checksums were recorded trust the scripts they were migrated by
+fn record_baseline(conn: &Connection, version: Option<u32>) -> AppResult<()> {
+    let applied = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version <= version.unwrap_or(0));
+    for migration in applied {
+        conn.execute(
+            "INSERT OR IGNORE INTO migration_history(version, checksum) VALUES (?1, ?2)",
+            (migration.version, migration.checksum()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Versions of applied migrations whose script differs from the one they were applied by
+fn changed_scripts(conn: &Connection) -> AppResult<Vec<u32>> {
+    let mut query = conn.prepare("SELECT version, checksum FROM migration_history")?;
+    let applied = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<u32, String>, rusqlite::Error>>()?;
+    let mut changed = MIGRATIONS
+        .iter()
+        .filter(|migration| {
+            applied
+                .get(&migration.version)
+                .map_or(false, |checksum| *checksum != migration.checksum())
+        })
+        .map(|migration| migration.version)
+        .collect::<Vec<u32>>();
+    changed.sort();
+    Ok(changed)
+}
+
+/// Apply pending migrations in order, each recorded with its checksum.
+/// Fail before anything is applied if an applied script has changed since,
+/// caller is expected to run it in a transaction
+pub fn run_migrations(conn: &Connection) -> AppResult<()> {
+    check_compatible(conn)?;
+    ensure_version_tables(conn)?;
+    let version = local_version(conn)?;
+    record_baseline(conn, version)?;
+    if let Some(version) = changed_scripts(conn)?.first() {
+        return Err(AppError::MigrationFailed {
+            version: *version,
+            message: "script of applied migration has been changed".to_owned(),
+        });
+    }
+
+    for migration in pending_migrations(version) {
+        if let Err(e) = migration.apply(conn) {
+            error!(version = migration.version, error = %e, "migration failed");
+            return Err(e);
+        }
+        info!(version = migration.version, "applied migration");
+        conn.execute(
+            "INSERT OR REPLACE INTO migration_history(version, checksum) VALUES (?1, ?2)",
+            (migration.version, migration.checksum()),
+        )?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO table_version(id, version) VALUES(0, ?1)",
+        [CURRENT_VERSION],
+    )?;
+    Ok(())
+}
+
+/// Whether opening database applies any migration
+pub fn needs_migration(conn: &Connection) -> AppResult<bool> {
+    Ok(pending_migrations(local_version(conn)?).next().is_some())
+}
+
+/// What opening a library would change in its database, nothing is written
+#[derive(Debug, serde::Serialize)]
+pub struct MigrationReport {
+    /// `None` if database has not been created yet
+    from_version: Option<u32>,
+    to_version: u32,
+    /// Versions of migrations which would be applied
+    pending: Vec<u32>,
+    /// Applied migrations whose script has changed, opening library fails if there is any
+    changed_scripts: Vec<u32>,
+    /// Schema objects as `type name`, e.g. `table meme_file`
+    created: Vec<String>,
+    dropped: Vec<String>,
+    altered: Vec<String>,
+}
+
+/// Definition of every table, index, view and trigger by `type name`
+fn query_schema(conn: &Connection) -> AppResult<HashMap<String, String>> {
+    let mut query = conn.prepare(
+        "SELECT type || ' ' || name, COALESCE(sql, '') FROM sqlite_master
+          WHERE name NOT LIKE 'sqlite_%'",
+    )?;
+    let schema = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<String, String>, rusqlite::Error>>()?;
+    Ok(schema)
+}
+
+/// Apply pending migrations in a transaction which is rolled back,
+/// report which migrations would run and how schema would change
+pub fn dry_run(conn: &mut Connection) -> AppResult<MigrationReport> {
+    check_compatible(conn)?;
+    let from_version = local_version(conn)?;
+    let tx = conn.transaction()?;
+    let before = query_schema(&tx)?;
+    ensure_version_tables(&tx)?;
+    record_baseline(&tx, from_version)?;
+    let changed_scripts = changed_scripts(&tx)?;
+    let pending = pending_migrations(from_version)
+        .map(|migration| migration.version)
+        .collect::<Vec<u32>>();
+    for migration in pending_migrations(from_version) {
+        migration.apply(&tx)?;
+    }
+    let after = query_schema(&tx)?;
+    // dropping transaction rolls it back
+    drop(tx);
+
+    let mut created = Vec::new();
+    let mut altered = Vec::new();
+    for (name, sql) in &after {
+        match before.get(name) {
+            None => created.push(name.clone()),
+            Some(old) if old != sql => altered.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    let mut dropped = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .cloned()
+        .collect::<Vec<String>>();
+    created.sort();
+    altered.sort();
+    dropped.sort();
+    Ok(MigrationReport {
+        from_version,
+        to_version: CURRENT_VERSION,
+        pending,
+        changed_scripts,
+        created,
+        dropped,
+        altered,
+    })
+}
+
+/// Report what opening library at `path` would migrate without opening it,
+/// `passphrase` is required if its database is encrypted
+#[tauri::command]
+pub async fn dry_run_migrations(
+    path: String,
+    passphrase: Option<String>,
+) -> AppResult<MigrationReport> {
+    let db_path = PathBuf::from(path).join("meme.db");
+    let mut conn = open_existing(&db_path)?;
+    if let Some(passphrase) = passphrase.as_deref() {
+        apply_passphrase(&conn, passphrase)?;
+    }
+    check_unlocked(&conn)?;
+    dry_run(&mut conn)
+}
+
+/// Database which does not exist yet is reported as if it were empty, without creating it
+fn open_existing(path: &Path) -> AppResult<Connection> {
+    if path.exists() {
+        Ok(Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?)
+    } else {
+        Ok(Connection::open_in_memory()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::{
+        db::migration::{
+            dry_run, local_version, needs_migration, run_migrations, CURRENT_VERSION, MIGRATIONS,
+        },
+        error::AppError,
+    };
+
+    #[test]
+    fn test_registry_order() {
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, idx as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_fresh_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(needs_migration(&conn).unwrap());
+        run_migrations(&conn).unwrap();
+        assert_eq!(local_version(&conn).unwrap(), Some(CURRENT_VERSION));
+        assert!(!needs_migration(&conn).unwrap());
+
+        let recorded: usize = conn
+            .query_row("SELECT COUNT(*) FROM migration_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(recorded, MIGRATIONS.len());
+        // running again changes nothing
+        run_migrations(&conn).unwrap();
+    }
+
+    #[test]
+    fn test_changed_script() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "UPDATE migration_history SET checksum = 'changed' WHERE version = 2",
+            [],
+        )
+        .unwrap();
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(AppError::MigrationFailed { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_too_new() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "UPDATE table_version SET version = ?1 WHERE id = 0",
+            [CURRENT_VERSION + 1],
+        )
+        .unwrap();
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(AppError::DatabaseTooNew { .. })
+        ));
+        // version of newer build is kept
+        assert_eq!(local_version(&conn).unwrap(), Some(CURRENT_VERSION + 1));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = dry_run(&mut conn).unwrap();
+        assert_eq!(report.from_version, None);
+        assert_eq!(report.pending.len(), MIGRATIONS.len());
+        assert!(report.created.contains(&"table meme".to_owned()));
+        assert!(report.dropped.is_empty());
+        // nothing is written
+        assert_eq!(local_version(&conn).unwrap(), None);
+
+        run_migrations(&conn).unwrap();
+        let report = dry_run(&mut conn).unwrap();
+        assert!(report.pending.is_empty());
+        assert!(report.created.is_empty() && report.altered.is_empty());
+    }
+}
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS media_info(
+  meme_id INTEGER PRIMARY KEY,
+  width INTEGER, /* 文字表情没有宽高 */
+  height INTEGER,
+  format TEXT NOT NULL, /* 文件格式，如 png、gif、mp4 */
+  size INTEGER NOT NULL, /* 文件大小，单位字节 */
+  CONSTRAINT media_info_meme_id_fk FOREIGN KEY(meme_id) REFERENCES meme(id)
+);
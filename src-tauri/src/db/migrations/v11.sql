@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS sync_device(
+  id INTEGER PRIMARY KEY, /* 只有 id = 0 一行 */
+  device_id TEXT NOT NULL /* 本机在同步目录中的标识 */
+);
+
+CREATE TABLE IF NOT EXISTS sync_snapshot(
+  hash TEXT PRIMARY KEY, /* 表情内容 hash，作为跨设备的标识 */
+  digest TEXT NOT NULL /* 上次同步时表情元数据的摘要 */
+);
+
+CREATE TABLE IF NOT EXISTS sync_peer(
+  device_id TEXT PRIMARY KEY,
+  applied INTEGER NOT NULL DEFAULT 0 /* 已应用的日志行数 */
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS settings(
+  key TEXT PRIMARY KEY,
+  value TEXT NOT NULL /* JSON 编码的值 */
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS tag_alias(
+  key TEXT NOT NULL, /* 别名，不是 tag 表中的标签 */
+  value TEXT NOT NULL,
+  tag_id INTEGER NOT NULL, /* 别名指向的标签 */
+  CONSTRAINT tag_alias_pk PRIMARY KEY(key, value),
+  CONSTRAINT tag_alias_tag_id_fk FOREIGN KEY(tag_id) REFERENCES tag(id)
+);
+
+CREATE TABLE IF NOT EXISTS tag_implication(
+  tag_id INTEGER NOT NULL,
+  implied_id INTEGER NOT NULL, /* 带有 tag_id 的表情会自动加上此标签 */
+  CONSTRAINT tag_implication_pk PRIMARY KEY(tag_id, implied_id),
+  CONSTRAINT tag_implication_tag_id_fk FOREIGN KEY(tag_id) REFERENCES tag(id),
+  CONSTRAINT tag_implication_implied_id_fk FOREIGN KEY(implied_id) REFERENCES tag(id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE tag ADD COLUMN last_used DATETIME; /* 最后一次被加到表情上的时间 */
+
+CREATE TRIGGER [UpdateTagLastUsed] AFTER INSERT ON meme_tag FOR EACH ROW
+BEGIN
+    UPDATE tag SET last_used = CURRENT_TIMESTAMP WHERE id = NEW.tag_id;
+END;
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS namespace(
+  key TEXT PRIMARY KEY NOT NULL, /* 对应 tag 表的 key */
+  color TEXT, /* 显示颜色，CSS 颜色值 */
+  priority INTEGER NOT NULL DEFAULT 0, /* 排序优先级，越大越靠前 */
+  description TEXT
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS journal(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  description TEXT NOT NULL, /* 操作说明，显示在撤销列表中 */
+  time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  undone INTEGER NOT NULL DEFAULT 0 /* 已撤销的操作可以重做 */
+);
+
+CREATE TABLE IF NOT EXISTS journal_entry(
+  journal_id INTEGER NOT NULL,
+  meme_id INTEGER NOT NULL,
+  before TEXT NOT NULL, /* 操作前表情的状态，JSON */
+  after TEXT NOT NULL, /* 操作后表情的状态，JSON */
+  CONSTRAINT journal_entry_pk PRIMARY KEY(journal_id, meme_id),
+  CONSTRAINT journal_entry_journal_id_fk FOREIGN KEY(journal_id) REFERENCES journal(id)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS meme_history(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  meme_id INTEGER NOT NULL,
+  field TEXT NOT NULL, /* 被修改的字段，如 name、tags */
+  old TEXT NOT NULL, /* 修改前的值，JSON */
+  new TEXT NOT NULL, /* 修改后的值，JSON */
+  time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT meme_history_meme_id_fk FOREIGN KEY(meme_id) REFERENCES meme(id)
+);
+
+CREATE INDEX IF NOT EXISTS meme_history_meme_id ON meme_history(meme_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS job(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  job TEXT NOT NULL, /* 任务内容，JSON */
+  status TEXT NOT NULL DEFAULT 'queued', /* queued、running、failed 或 done */
+  attempts INTEGER NOT NULL DEFAULT 0, /* 已执行次数，失败后重试 */
+  error TEXT, /* 最后一次失败的原因 */
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  update_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
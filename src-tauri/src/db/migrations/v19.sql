@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS content_check(
+  hash TEXT PRIMARY KEY, /* 内容文件名，与 meme.hash 相同 */
+  verified_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP, /* 最后一次校验 sha256 的时间 */
+  corrupted BOOLEAN NOT NULL DEFAULT false /* 文件内容与 sha256 不符 */
+);
@@ -0,0 +1 @@
+ALTER TABLE meme ADD COLUMN phash INTEGER; /* 感知 hash，用于查找相似表情 */
@@ -0,0 +1 @@
+ALTER TABLE meme ADD COLUMN source_url TEXT; /* 表情的来源网址，从网络导入时记录 */
@@ -0,0 +1,2 @@
+ALTER TABLE meme ADD COLUMN author TEXT; /* 表情的作者 */
+ALTER TABLE meme ADD COLUMN origin TEXT; /* 表情的出处，如网站、群聊或应用的名称 */
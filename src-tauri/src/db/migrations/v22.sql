@@ -0,0 +1 @@
+ALTER TABLE meme ADD COLUMN derived_from INTEGER REFERENCES meme(id) ON DELETE SET NULL; /* 由哪个表情编辑而来 */
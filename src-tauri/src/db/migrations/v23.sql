@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS meme_relation(
+  child_id INTEGER PRIMARY KEY REFERENCES meme(id) ON DELETE CASCADE, /* 变体表情，每个表情只有一个来源 */
+  parent_id INTEGER NOT NULL REFERENCES meme(id) ON DELETE CASCADE, /* 变体所基于的表情 */
+  kind TEXT NOT NULL CHECK (kind IN ('edit', 'translation', 'template_instance')), /* 变体的产生方式 */
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS meme_relation_parent ON meme_relation(parent_id);
+/* 已有的编辑结果 */
+INSERT OR IGNORE INTO meme_relation(child_id, parent_id, kind)
+  SELECT id, derived_from, 'edit' FROM meme WHERE derived_from IS NOT NULL;
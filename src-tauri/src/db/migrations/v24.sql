@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS template(
+  meme_id INTEGER PRIMARY KEY REFERENCES meme(id) ON DELETE CASCADE, /* 作为模板的图片表情 */
+  regions TEXT NOT NULL, /* 填写文字的区域，JSON 数组，坐标以像素为单位 */
+  font TEXT, /* 字体文件路径，为空时使用系统中的 Impact */
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,44 @@
+CREATE TABLE IF NOT EXISTS fav_group(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL UNIQUE, /* 收藏夹名称 */
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS fav_group_meme(
+  group_id INTEGER NOT NULL REFERENCES fav_group(id) ON DELETE CASCADE,
+  meme_id INTEGER NOT NULL REFERENCES meme(id) ON DELETE CASCADE,
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (group_id, meme_id)
+);
+CREATE INDEX IF NOT EXISTS fav_group_meme_meme ON fav_group_meme(meme_id);
+
+/* 默认收藏夹，原有的收藏都放入其中 */
+INSERT INTO fav_group(id, name) VALUES (1, 'default');
+INSERT INTO fav_group_meme(group_id, meme_id) SELECT 1, id FROM meme WHERE fav = 1;
+
+/* meme.fav 保留为“在任意收藏夹中”，与收藏夹保持一致 */
+CREATE TRIGGER [FavGroupMemeInsert] AFTER INSERT ON fav_group_meme FOR EACH ROW
+BEGIN
+    UPDATE meme SET fav = 1 WHERE id = NEW.meme_id AND fav = 0;
+END;
+
+CREATE TRIGGER [FavGroupMemeDelete] AFTER DELETE ON fav_group_meme FOR EACH ROW
+BEGIN
+    UPDATE meme SET fav = 0 WHERE id = OLD.meme_id AND fav = 1
+      AND NOT EXISTS (SELECT 1 FROM fav_group_meme WHERE meme_id = OLD.meme_id);
+END;
+
+/* 直接修改 meme.fav 时放入或移出默认收藏夹 */
+CREATE TRIGGER [MemeFavInsert] AFTER INSERT ON meme FOR EACH ROW
+WHEN NEW.fav
+BEGIN
+    INSERT OR IGNORE INTO fav_group_meme(group_id, meme_id) VALUES (1, NEW.id);
+END;
+
+CREATE TRIGGER [MemeFavUpdate] AFTER UPDATE OF fav ON meme FOR EACH ROW
+WHEN OLD.fav != NEW.fav
+BEGIN
+    INSERT OR IGNORE INTO fav_group_meme(group_id, meme_id)
+      SELECT 1, NEW.id WHERE NEW.fav
+        AND NOT EXISTS (SELECT 1 FROM fav_group_meme WHERE meme_id = NEW.id);
+    DELETE FROM fav_group_meme WHERE meme_id = NEW.id AND NOT NEW.fav;
+END;
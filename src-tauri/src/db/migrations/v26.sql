@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS meme_color(
+  meme_id INTEGER NOT NULL REFERENCES meme(id) ON DELETE CASCADE,
+  name TEXT NOT NULL, /* 颜色名称，如 orange、gray */
+  color TEXT NOT NULL, /* 该颜色像素的平均值，#rrggbb */
+  ratio REAL NOT NULL, /* 占图片的比例，0 到 1 */
+  PRIMARY KEY (meme_id, name)
+);
+ALTER TABLE meme ADD COLUMN dominant_hue REAL; /* 主色调的色相，0 到 360，黑白灰图片为空 */
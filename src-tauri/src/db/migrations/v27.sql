@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS meme_embedding(
+  meme_id INTEGER PRIMARY KEY REFERENCES meme(id) ON DELETE CASCADE,
+  model TEXT NOT NULL, /* 生成向量的模型，换模型后需要重新计算 */
+  vector BLOB NOT NULL, /* 归一化后的 f32 向量，小端序 */
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS search_history(
+  stmt TEXT PRIMARY KEY, /* 搜索语句，去掉首尾空白 */
+  pinned BOOLEAN NOT NULL DEFAULT false, /* 固定的搜索不会被清除 */
+  use_count INTEGER NOT NULL DEFAULT 0,
+  last_used DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1 @@
+ALTER TABLE meme ADD COLUMN private BOOLEAN NOT NULL DEFAULT false; /* 私密模因，解锁前不出现在浏览和快速选择中 */
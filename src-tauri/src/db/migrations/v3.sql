@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS meme_text(
+  meme_id INTEGER PRIMARY KEY, /* 每个表情只保存一份识别结果 */
+  text TEXT NOT NULL, /* OCR 识别出的文字 */
+  CONSTRAINT meme_text_meme_id_fk FOREIGN KEY(meme_id) REFERENCES meme(id)
+);
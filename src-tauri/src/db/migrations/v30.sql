@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS meme_file(
+  meme_id INTEGER NOT NULL REFERENCES meme(id) ON DELETE CASCADE,
+  page INTEGER NOT NULL, /* 页码，从 0 开始，第 0 页即 meme.hash，作为封面 */
+  hash TEXT NOT NULL, /* 内容 hash */
+  PRIMARY KEY (meme_id, page)
+);
+CREATE INDEX IF NOT EXISTS meme_file_hash ON meme_file(hash);
+INSERT OR IGNORE INTO meme_file(meme_id, page, hash) SELECT id, 0, hash FROM meme;
+
+/* 第 0 页随 meme.hash 一起写入，插入表情的代码无需关心 meme_file */
+CREATE TRIGGER IF NOT EXISTS [InsertCoverPage] AFTER INSERT ON meme FOR EACH ROW
+BEGIN
+    INSERT OR REPLACE INTO meme_file(meme_id, page, hash) VALUES (NEW.id, 0, NEW.hash);
+END;
+CREATE TRIGGER IF NOT EXISTS [UpdateCoverPage] AFTER UPDATE OF hash ON meme FOR EACH ROW
+BEGIN
+    UPDATE meme_file SET hash = NEW.hash WHERE meme_id = NEW.id AND page = 0;
+END;
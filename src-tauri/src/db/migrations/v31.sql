@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS content_alias(
+  original TEXT PRIMARY KEY, /* 压缩前的内容 hash，导入同样的文件时据此判断重复 */
+  hash TEXT NOT NULL, /* 压缩后的内容 hash */
+  original_size INTEGER NOT NULL, /* 压缩前的字节数 */
+  size INTEGER NOT NULL /* 压缩后的字节数 */
+);
+CREATE INDEX IF NOT EXISTS content_alias_hash ON content_alias(hash);
@@ -0,0 +1,5 @@
+/* 搜索路径上缺少的索引。meme_tag 的主键 (tag_id, meme_id) 已经能按 tag_id 查找，只需补上 meme_id */
+CREATE INDEX IF NOT EXISTS meme_tag_meme_id ON meme_tag(meme_id);
+CREATE INDEX IF NOT EXISTS tag_key_value ON tag(key, value);
+CREATE INDEX IF NOT EXISTS meme_update_time ON meme(update_time);
+CREATE INDEX IF NOT EXISTS meme_trash_fav ON meme(trash, fav);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS maintenance_log(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  size_before INTEGER NOT NULL, /* 整理前数据库的字节数 */
+  size_after INTEGER NOT NULL /* VACUUM 之后的字节数 */
+);
@@ -0,0 +1,18 @@
+ALTER TABLE meme ADD COLUMN version INTEGER NOT NULL DEFAULT 0; /* 用户编辑的次数，保存编辑时用来检测冲突 */
+
+/* 只有编辑页面能改的字段和标签算作编辑，使用、压缩和索引不算 */
+CREATE TRIGGER [MemeVersionUpdate]
+AFTER UPDATE OF name, description, fav, pkg_id, source_url, author, origin ON meme FOR EACH ROW
+BEGIN
+    UPDATE meme SET version = version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER [MemeVersionTagInsert] AFTER INSERT ON meme_tag FOR EACH ROW
+BEGIN
+    UPDATE meme SET version = version + 1 WHERE id = NEW.meme_id;
+END;
+
+CREATE TRIGGER [MemeVersionTagDelete] AFTER DELETE ON meme_tag FOR EACH ROW
+BEGIN
+    UPDATE meme SET version = version + 1 WHERE id = OLD.meme_id;
+END;
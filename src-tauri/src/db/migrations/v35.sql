@@ -0,0 +1,7 @@
+/* 使用表情和压缩内容不算作修改 */
+DROP TRIGGER IF EXISTS [UpdateUpdateTime];
+CREATE TRIGGER [UpdateUpdateTime] AFTER UPDATE ON meme FOR EACH ROW
+WHEN OLD.use_count = NEW.use_count AND OLD.hash = NEW.hash
+BEGIN
+    UPDATE meme SET update_time = CURRENT_TIMESTAMP WHERE id = OLD.id;
+END;
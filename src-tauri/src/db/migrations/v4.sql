@@ -0,0 +1,73 @@
+/* 全文索引，rowid 与 meme.id 对应 */
+CREATE VIRTUAL TABLE IF NOT EXISTS meme_fts USING fts5(name, description, tags, text, tokenize = 'trigram');
+
+INSERT INTO meme_fts(rowid, name, description, tags, text)
+  SELECT id, name, IFNULL(description, ''),
+    IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+    IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+  FROM meme;
+
+CREATE TRIGGER [MemeFtsInsert] AFTER INSERT ON meme FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = NEW.id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER [MemeFtsUpdate] AFTER UPDATE OF name, description ON meme FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = NEW.id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER [MemeFtsDelete] AFTER DELETE ON meme FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = OLD.id;
+END;
+
+CREATE TRIGGER [MemeFtsTagInsert] AFTER INSERT ON meme_tag FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = NEW.meme_id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = NEW.meme_id;
+END;
+
+CREATE TRIGGER [MemeFtsTagDelete] AFTER DELETE ON meme_tag FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = OLD.meme_id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = OLD.meme_id;
+END;
+
+CREATE TRIGGER [MemeFtsTextInsert] AFTER INSERT ON meme_text FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = NEW.meme_id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = NEW.meme_id;
+END;
+
+CREATE TRIGGER [MemeFtsTextUpdate] AFTER UPDATE ON meme_text FOR EACH ROW
+BEGIN
+    DELETE FROM meme_fts WHERE rowid = NEW.meme_id;
+    INSERT INTO meme_fts(rowid, name, description, tags, text)
+      SELECT id, name, IFNULL(description, ''),
+        IFNULL((SELECT group_concat(value, ' ') FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id), ''),
+        IFNULL((SELECT text FROM meme_text WHERE meme_text.meme_id = meme.id), '')
+      FROM meme WHERE id = NEW.meme_id;
+END;
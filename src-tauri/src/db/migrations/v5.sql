@@ -0,0 +1,9 @@
+ALTER TABLE meme ADD COLUMN trashed_at DATETIME; /* 移入回收站的时间 */
+
+UPDATE meme SET trashed_at = CURRENT_TIMESTAMP WHERE trash = 1;
+
+CREATE TRIGGER [UpdateTrashedTime] AFTER UPDATE OF trash ON meme FOR EACH ROW
+WHEN OLD.trash != NEW.trash
+BEGIN
+    UPDATE meme SET trashed_at = CASE WHEN NEW.trash THEN CURRENT_TIMESTAMP ELSE NULL END WHERE id = OLD.id;
+END;
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS album(
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  description TEXT,
+  create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS album_meme(
+  album_id INTEGER NOT NULL,
+  meme_id INTEGER NOT NULL,
+  position INTEGER NOT NULL, /* 在相册中的顺序 */
+  CONSTRAINT album_meme_pk PRIMARY KEY(album_id, meme_id),
+  CONSTRAINT album_meme_album_id_fk FOREIGN KEY(album_id) REFERENCES album(id),
+  CONSTRAINT album_meme_meme_id_fk FOREIGN KEY(meme_id) REFERENCES meme(id)
+);
@@ -0,0 +1 @@
+ALTER TABLE meme ADD COLUMN rating INTEGER NOT NULL DEFAULT 0 CHECK (rating BETWEEN 0 AND 5); /* 评分，0 表示未评分 */
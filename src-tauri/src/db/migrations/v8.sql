@@ -0,0 +1,10 @@
+ALTER TABLE meme ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0; /* 被复制使用的次数 */
+ALTER TABLE meme ADD COLUMN last_used DATETIME; /* 最后一次使用的时间 */
+
+/* 使用表情不算作修改 */
+DROP TRIGGER IF EXISTS [UpdateUpdateTime];
+CREATE TRIGGER [UpdateUpdateTime] AFTER UPDATE ON meme FOR EACH ROW
+WHEN OLD.use_count = NEW.use_count
+BEGIN
+    UPDATE meme SET update_time = CURRENT_TIMESTAMP WHERE id = OLD.id;
+END;
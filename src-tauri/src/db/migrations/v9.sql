@@ -0,0 +1,2 @@
+ALTER TABLE meme ADD COLUMN frame_count INTEGER NOT NULL DEFAULT 1; /* 动图帧数 */
+ALTER TABLE meme ADD COLUMN duration INTEGER; /* 动图时长，单位毫秒 */
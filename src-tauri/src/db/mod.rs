@@ -1,12 +1,43 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use rusqlite::{Connection, OptionalExtension};
+use rusqlite::Connection;
 use tokio::sync::Mutex;
+use tracing::{error, warn};
 
+use crate::{
+    backup::backup_on_open,
+    config::load_settings,
+    encryption::{apply_passphrase, check_unlocked},
+    error::{AppError, AppResult},
+    file::relocate_flat_content,
+    jobs::requeue_interrupted_jobs,
+    maintenance::queue_due_maintenance,
+    staging::settle_staging,
+    storage::{read_config, LocalStorage, Storage},
+    trash::purge_expired_trash,
+};
+
+#[cfg(test)]
+pub mod fixtures;
+pub mod highlight;
+pub mod migration;
+pub mod performance;
 pub mod search;
+
+use migration::{check_compatible, needs_migration, run_migrations};
+
 pub struct MemeDatabaseConnection {
     pub path: PathBuf,
     pub conn: Connection,
+    /// Where content files live, see `Storage`
+    pub storage: Box<dyn Storage>,
+    /// Private memes are listed only after they are unlocked, see `private`
+    pub private_unlocked: bool,
+    /// Key of encrypted database, backups are keyed by it as well
+    pub passphrase: Option<String>,
+    /// `Settings::verify_on_read`, kept here for the protocol handler
+    /// which can not afford loading settings on every request
+    pub verify_on_read: bool,
 }
 pub struct MemeDatabaseState {
     pub state: Mutex<Option<MemeDatabaseConnection>>,
@@ -19,79 +50,174 @@ impl Default for MemeDatabaseState {
         }
     }
 }
+/// Error of commands called while no library is opened
+pub fn library_not_opened() -> AppError {
+    AppError::NotFound("library".to_owned())
+}
+
 impl MemeDatabaseState {
+    /// State with library at `path` already opened,
+    /// no library is opened if it fails so user can pick another one
+    pub fn with_library(path: PathBuf) -> Self {
+        let conn = MemeDatabaseConnection::open(path)
+            .map_err(|e| error!(error = %e, "failed to open library"))
+            .ok();
+        Self {
+            state: Mutex::new(conn),
+        }
+    }
+
     async fn close(&self) {
         *self.state.lock().await = None;
     }
 
-    async fn open(&self, path: PathBuf) {
-        *self.state.lock().await = Some(MemeDatabaseConnection::open(path))
+    pub async fn open(&self, path: PathBuf) -> AppResult<()> {
+        self.open_with_passphrase(path, None).await
+    }
+
+    /// Open library whose database is encrypted by `passphrase`
+    pub async fn open_with_passphrase(
+        &self,
+        path: PathBuf,
+        passphrase: Option<&str>,
+    ) -> AppResult<()> {
+        let conn = MemeDatabaseConnection::open_with_passphrase(path, passphrase)?;
+        *self.state.lock().await = Some(conn);
+        Ok(())
     }
 }
 
 impl MemeDatabaseConnection {
-    const CURRENT_VERSION: u32 = 1;
-    fn init(conn: &mut Connection) {
-        let conn = conn.transaction().unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS table_version (id INTEGER PRIMARY KEY, version INTEGER);",
-            [],
-        )
-        .unwrap();
-
-        let version: Option<u32> = conn
-            .query_row(
-                "SELECT version FROM table_version WHERE id = 0",
-                (),
-                |row| Ok(row.get(0)?),
-            )
-            .optional()
-            .ok()
-            .unwrap_or(None);
-
-        if let Some(_local_version) = version {
-            // upgrade local database
-        } else {
-            // create database
-            conn.execute_batch(&format!(
-                "INSERT OR REPLACE INTO table_version(id, version) VALUES(0, {});",
-                Self::CURRENT_VERSION
-            ))
-            .unwrap();
-
-            conn.execute_batch(include_str!("database_init.sql"))
-                .unwrap();
-        }
-        conn.commit().unwrap();
+    pub const CURRENT_VERSION: u32 = migration::CURRENT_VERSION;
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Statements prepared by `prepare_cached` kept for reuse,
+    /// enough for tag lookups and a few search statements being paged through
+    const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+    /// Create or upgrade database by pending migrations, see `migration::MIGRATIONS`
+    fn init(conn: &mut Connection) -> AppResult<()> {
+        let conn = conn.transaction()?;
+        run_migrations(&conn)?;
+        conn.commit()?;
+        Ok(())
     }
 
-    pub fn open(path: PathBuf) -> Self {
-        let mut conn = Connection::open(path.join("meme.db")).unwrap();
+    /// Tune connection before any other statement runs
+    /// WAL lets readers proceed while an import is writing,
+    /// busy timeout makes a locked database wait instead of failing immediately
+    fn setup_connection(conn: &Connection) -> rusqlite::Result<()> {
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+            row.get::<_, String>(0)
+        })?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        conn.set_prepared_statement_cache_capacity(Self::STATEMENT_CACHE_CAPACITY);
+        Ok(())
+    }
+
+    pub fn open(path: PathBuf) -> AppResult<Self> {
+        Self::open_with_passphrase(path, None)
+    }
+
+    /// Library whose database only lives in memory and is migrated to `CURRENT_VERSION`,
+    /// for tests and tools which must not touch a library on disk.
+    /// `path` is only used for content files, which are kept locally
+    pub fn open_in_memory(path: PathBuf) -> AppResult<Self> {
+        let mut conn = Connection::open_in_memory()?;
+        Self::setup_connection(&conn)?;
+        Self::init(&mut conn)?;
+        Ok(Self {
+            path,
+            conn,
+            storage: Box::new(LocalStorage),
+            private_unlocked: false,
+            passphrase: None,
+            verify_on_read: false,
+        })
+    }
+
+    /// Open library, `passphrase` keys database encrypted by SQLCipher.
+    /// Fail with `AppError::Locked` if database is encrypted and passphrase is missing or wrong
+    pub fn open_with_passphrase(path: PathBuf, passphrase: Option<&str>) -> AppResult<Self> {
+        let mut conn = Connection::open(path.join("meme.db"))?;
+        if let Some(passphrase) = passphrase {
+            apply_passphrase(&conn, passphrase)?;
+        }
+        check_unlocked(&conn)?;
+        // nothing is written to database of a newer build, not even journal mode
+        check_compatible(&conn)?;
 
-        Self::init(&mut conn);
-        Self { path, conn }
+        Self::setup_connection(&conn)?;
+        // a failed backup should not stop user from opening library,
+        // but database is never migrated without one
+        if let Err(e) = backup_on_open(&conn, &path, passphrase) {
+            if needs_migration(&conn)? {
+                return Err(AppError::MigrationFailed {
+                    version: Self::CURRENT_VERSION,
+                    message: format!("failed to backup database before migration: {}", e),
+                });
+            }
+            warn!(error = %e, "failed to backup database");
+        }
+        if let Err(e) = relocate_flat_content(&path) {
+            warn!(error = %e, "failed to relocate content files");
+        }
+        Self::init(&mut conn)?;
+        let storage = read_config(&path)
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "failed to read storage config");
+                Default::default()
+            })
+            .open();
+        // imports interrupted by a crash
+        if let Err(e) = settle_staging(&conn, storage.as_ref(), &path) {
+            warn!(error = %e, "failed to settle staged imports");
+        }
+        if let Err(e) = purge_expired_trash(&mut conn, storage.as_ref(), &path) {
+            warn!(error = %e, "failed to purge trash");
+        }
+        if let Err(e) = requeue_interrupted_jobs(&conn) {
+            warn!(error = %e, "failed to requeue jobs");
+        }
+        if let Err(e) = queue_due_maintenance(&conn) {
+            warn!(error = %e, "failed to queue database maintenance");
+        }
+        let verify_on_read = load_settings(&conn)?.verify_on_read;
+        Ok(Self {
+            path,
+            conn,
+            storage,
+            private_unlocked: false,
+            passphrase: passphrase.map(|x| x.to_owned()),
+            verify_on_read,
+        })
     }
 }
 
 #[tauri::command]
 pub async fn is_storage_available(
     state: tauri::State<'_, MemeDatabaseState>,
-) -> Result<bool, String> {
+) -> AppResult<bool> {
     Ok(state.state.lock().await.is_some())
 }
 
 #[tauri::command]
-pub async fn get_storage(state: tauri::State<'_, MemeDatabaseState>) -> Result<String, String> {
+pub async fn get_storage(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<String> {
     let guard = state.state.lock().await;
-    let path = guard.as_ref().unwrap().path.to_str().unwrap();
-    Ok(path.to_owned())
+    let path = &guard.as_ref().ok_or_else(library_not_opened)?.path;
+    Ok(path.to_string_lossy().to_string())
 }
 
+/// Open library at `path`, `passphrase` is required if it is encrypted
 #[tauri::command]
 pub async fn open_storage(
     state: tauri::State<'_, MemeDatabaseState>,
     path: String,
-) -> Result<(), String> {
-    state.open(PathBuf::from(path)).await;
+    passphrase: Option<String>,
+) -> AppResult<()> {
+    state
+        .open_with_passphrase(PathBuf::from(path), passphrase.as_deref())
+        .await?;
     Ok(())
 }
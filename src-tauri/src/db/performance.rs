@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use rusqlite::{params_from_iter, Connection, Error};
+
+use crate::{
+    config::load_settings,
+    db::{
+        library_not_opened,
+        search::{SortBy, KEYSET_ORDER},
+        MemeDatabaseConnection, MemeDatabaseState,
+    },
+    error::AppResult,
+    meme::build_listing_sql,
+};
+
+/// Indexes on search paths as `(table, index)`, created by migration v32
+const EXPECTED_INDEXES: &[(&str, &str)] = &[
+    ("meme_tag", "meme_tag_meme_id"),
+    ("tag", "tag_key_value"),
+    ("meme", "meme_update_time"),
+    ("meme", "meme_trash_fav"),
+];
+
+#[derive(Debug, serde::Serialize)]
+pub struct PerformanceReport {
+    /// Search SQL as dashboard runs it for the first page
+    sql: String,
+    /// Steps of `EXPLAIN QUERY PLAN`, nested steps are indented
+    plan: Vec<String>,
+    /// Tables read in full and expected indexes missing from library
+    warnings: Vec<String>,
+}
+
+fn query_plan(
+    conn: &Connection,
+    sql: &str,
+    params: &[rusqlite::types::Value],
+) -> Result<Vec<(i64, i64, String)>, Error> {
+    let mut query = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+    let rows = query
+        .query_map(params_from_iter(params), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<(i64, i64, String)>, Error>>()?;
+    Ok(rows)
+}
+
+fn query_names(conn: &Connection, ty: &str) -> Result<Vec<String>, Error> {
+    let mut query = conn.prepare("SELECT name FROM sqlite_master WHERE type = ?1")?;
+    let names = query
+        .query_map([ty], |row| row.get(0))?
+        .collect::<Result<Vec<String>, Error>>()?;
+    Ok(names)
+}
+
+/// Table read row by row without an index, e.g. `SCAN meme_tag`.
+/// Virtual tables such as FTS index are scanned by their own index,
+/// and subquery results are not tables of library
+fn scanned_table<'a>(detail: &'a str, tables: &[String]) -> Option<&'a str> {
+    let table = detail.strip_prefix("SCAN ")?;
+    if table.contains(" USING ") || table.contains(" VIRTUAL TABLE ") {
+        return None;
+    }
+    let table = table.split(' ').next()?;
+    tables.iter().any(|x| x == table).then_some(table)
+}
+
+/// Explain search SQL of `stmt` as `search_memes` generates it for the first page
+pub fn analyze_search(
+    state: &MemeDatabaseConnection,
+    stmt: &str,
+    sort_by: Option<SortBy>,
+) -> AppResult<PerformanceReport> {
+    let settings = load_settings(&state.conn)?;
+    let search = build_listing_sql(state, stmt, false, false)?;
+    let order_by = sort_by
+        .or(settings.default_sort)
+        .and_then(|x| x.order_by())
+        .unwrap_or(search.order_by.as_str());
+    let tie_breaker = if order_by == KEYSET_ORDER {
+        ", meme.id DESC"
+    } else {
+        ""
+    };
+    let sql = format!("{}ORDER BY {}{} LIMIT ?", search.sql, order_by, tie_breaker);
+    let mut params = search.params;
+    params.push(settings.page_size.into());
+
+    let tables = query_names(&state.conn, "table")?;
+    let mut depth = HashMap::new();
+    let mut plan = Vec::new();
+    let mut warnings = Vec::new();
+    for (id, parent, detail) in query_plan(&state.conn, &sql, &params)? {
+        let level = depth.get(&parent).map_or(0, |x| x + 1);
+        depth.insert(id, level);
+        plan.push(format!("{}{}", "  ".repeat(level), detail));
+        if let Some(table) = scanned_table(&detail, &tables) {
+            warnings.push(format!("full scan of table {}", table));
+        }
+    }
+
+    let indexes = query_names(&state.conn, "index")?;
+    for (table, index) in EXPECTED_INDEXES {
+        if !indexes.iter().any(|x| x == index) {
+            warnings.push(format!("missing index {} on table {}", index, table));
+        }
+    }
+    Ok(PerformanceReport {
+        sql,
+        plan,
+        warnings,
+    })
+}
+
+/// Report how search statement `stmt` is executed and which indexes it lacks
+#[tauri::command]
+pub async fn analyze_performance(
+    state: tauri::State<'_, MemeDatabaseState>,
+    stmt: String,
+    sort_by: Option<SortBy>,
+) -> AppResult<PerformanceReport> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    analyze_search(state, &stmt, sort_by)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{fixtures::seeded_library, performance::analyze_search};
+
+    #[test]
+    fn test_analyze_search() {
+        let (state, _) = seeded_library();
+        let report = analyze_search(&state, "character:cat -special:untagged", None).unwrap();
+        assert!(report.sql.starts_with("SELECT"));
+        assert!(report.plan.iter().any(|x| x.contains("meme_tag")));
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        state
+            .conn
+            .execute_batch("DROP INDEX meme_tag_meme_id")
+            .unwrap();
+        let report = analyze_search(&state, "special:untagged", None).unwrap();
+        assert!(report
+            .warnings
+            .contains(&"full scan of table meme_tag".to_owned()));
+        assert!(report
+            .warnings
+            .contains(&"missing index meme_tag_meme_id on table meme_tag".to_owned()));
+    }
+}
@@ -1,9 +1,106 @@
 use std::fmt::Display;
 
-#[derive(Debug)]
-enum SearchStmt<'a> {
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rusqlite::types::Value;
+
+use crate::color::COLOR_NAMES;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl CmpOp {
+    fn sql(&self) -> &'static str {
+        match self {
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Eq => "=",
+        }
+    }
+
+    /// Operator with operands swapped, `a < b` is `b > a`
+    fn reverse(&self) -> CmpOp {
+        match self {
+            CmpOp::Lt => CmpOp::Gt,
+            CmpOp::Le => CmpOp::Ge,
+            CmpOp::Gt => CmpOp::Lt,
+            CmpOp::Ge => CmpOp::Le,
+            CmpOp::Eq => CmpOp::Eq,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Cmp(CmpOp),
+    Colon,
+    Minus,
+    Or,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, PartialEq)]
+enum DateField {
+    Created,
+    Updated,
+}
+
+#[derive(Debug, PartialEq)]
+enum DateValue<'a> {
+    /// `2024-01-01` or `2024-01-01 12:00:00`
+    Absolute(&'a str),
+    /// Amount of time before now, the unit is a modifier of sqlite date function
+    Relative(u32, &'static str),
+}
+
+#[derive(Debug, PartialEq)]
+enum DimensionField {
+    Width,
+    Height,
+}
+
+/// Memes lacking something, used to find memes which still need tagging
+#[derive(Debug, PartialEq)]
+enum Special<'a> {
+    /// `special:untagged`, memes without any tag
+    Untagged,
+    /// `special:nodescription`, memes whose description is missing or empty
+    NoDescription,
+    /// `special:private`, memes marked private, only found while they are unlocked
+    Private,
+    /// `missing:namespace`, memes without any tag in namespace
+    MissingNamespace(&'a str),
+}
+
+#[derive(Debug, PartialEq)]
+enum SearchExpr<'a> {
     Keyowrd(&'a str),
     Tag(&'a str, &'a str),
+    Date(DateField, CmpOp, DateValue<'a>),
+    Rating(CmpOp, u8),
+    Dimension(DimensionField, CmpOp, u32),
+    Format(&'a str),
+    /// `source:twitter.com`, memes whose source url or origin contains the value
+    Source(&'a str),
+    /// `fav:work`, memes in favorite group of the name, `fav:*` for memes in any group
+    FavGroup(&'a str),
+    /// `in:reactions`, memes in album of the name, `in:*` for memes in any album
+    Album(&'a str),
+    /// `color:orange`, memes of which the color covers a noticeable part, see `COLOR_NAMES`
+    Color(&'a str),
+    Special(Special<'a>),
+    Not(Box<SearchExpr<'a>>),
+    And(Vec<SearchExpr<'a>>),
+    Or(Vec<SearchExpr<'a>>),
 }
 
 type Loc = (usize, usize);
@@ -12,6 +109,10 @@ type Loc = (usize, usize);
 enum ErrorKind {
     IncompleteString,
     IncompleteTag,
+    UnmatchedParen,
+    UnexpectedToken,
+    UnknownField,
+    InvalidValue,
 }
 
 #[derive(Debug)]
@@ -33,130 +134,613 @@ impl Display for SearchError {
 
 impl std::error::Error for SearchError {}
 
-fn lexer<'a>(stmt: &'a str) -> Result<Vec<SearchStmt<'a>>, SearchError> {
-    let mut chars = stmt.trim().char_indices();
-    let mut stack = Vec::new();
-    let mut references = Vec::new();
-    let mut begin = chars.next();
-    while let Some((begin_pos, begin_ch)) = begin {
-        match begin_ch {
-            ' ' | '\t' => begin = chars.next(),
-            ':' => {
-                begin = chars.next();
-                stack.push(":");
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, ':' | '"' | '(' | ')' | '|' | '<' | '>' | '=')
+}
+
+fn lexer(stmt: &str) -> Result<Vec<(Token<'_>, Loc)>, SearchError> {
+    let mut chars = stmt.char_indices().peekable();
+    let mut tokens = Vec::new();
+    while let Some((begin, ch)) = chars.next() {
+        match ch {
+            _ if ch.is_whitespace() => {}
+            ':' => tokens.push((Token::Colon, (begin, begin + 1))),
+            '(' => tokens.push((Token::LParen, (begin, begin + 1))),
+            ')' => tokens.push((Token::RParen, (begin, begin + 1))),
+            '|' => tokens.push((Token::Or, (begin, begin + 1))),
+            '<' | '>' | '=' => {
+                let eq = ch != '=' && chars.peek().map_or(false, |(_, next)| *next == '=');
+                let op = match (ch, eq) {
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    ('>', true) => CmpOp::Ge,
+                    _ => CmpOp::Eq,
+                };
+                if eq {
+                    chars.next();
+                    tokens.push((Token::Cmp(op), (begin, begin + 2)));
+                } else {
+                    tokens.push((Token::Cmp(op), (begin, begin + 1)));
+                }
+            }
+            '-' if chars.peek().map_or(false, |(_, next)| !next.is_whitespace()) => {
+                tokens.push((Token::Minus, (begin, begin + 1)))
             }
             '"' => {
-                let mut end = chars.next();
-                while let Some((_, end_ch)) = end {
-                    if end_ch != '"' {
-                        end = chars.next();
-                    } else {
+                let end = loop {
+                    match chars.next() {
+                        Some((end, '"')) => break end,
+                        Some(_) => continue,
+                        None => {
+                            return Err(SearchError {
+                                kind: ErrorKind::IncompleteString,
+                                stmt: stmt.to_owned(),
+                                loc: (begin, stmt.len()),
+                            })
+                        }
+                    }
+                };
+                tokens.push((Token::Word(&stmt[begin + 1..end]), (begin, end + 1)));
+            }
+            _ => {
+                let mut end = stmt.len();
+                while let Some(&(pos, next)) = chars.peek() {
+                    if is_delimiter(next) {
+                        end = pos;
                         break;
                     }
+                    chars.next();
                 }
-                if let Some((end_pos, '"')) = end {
-                    stack.push(stmt.get(begin_pos + 1..end_pos).unwrap());
-                    begin = chars.next();
+                let word = &stmt[begin..end];
+                if word == "OR" {
+                    tokens.push((Token::Or, (begin, end)));
                 } else {
-                    return Err(SearchError {
-                        kind: ErrorKind::IncompleteString,
-                        stmt: stmt.to_owned(),
-                        loc: (begin_pos, references.len()),
-                    });
+                    tokens.push((Token::Word(word), (begin, end)));
                 }
             }
-            _ => {
-                let mut end = chars.next();
-                while let Some((_, end_ch)) = end {
-                    if end_ch == ':' || end_ch == ' ' || end_ch == '\t' || end_ch == '"' {
-                        begin = end;
-                        break;
-                    }
-                    end = chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser of search statement
+///
+/// ```text
+/// or      := and ('OR' and)*
+/// and     := unary unary*
+/// unary   := '-' unary | primary
+/// primary := '(' or ')' | word | word ':' word | word cmp word
+/// cmp     := '<' | '<=' | '>' | '>=' | '='
+/// ```
+/// `namespace:value` only matches tags in that namespace,
+/// value is matched by prefix, except `format:value` which matches file format
+struct Parser<'a, 'b> {
+    stmt: &'a str,
+    tokens: &'b [(Token<'a>, Loc)],
+    pos: usize,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek(&self) -> Option<&'b Token<'a>> {
+        self.tokens.get(self.pos).map(|x| &x.0)
+    }
+
+    fn advance(&mut self) -> Option<&'b Token<'a>> {
+        let tok = self.peek();
+        self.pos += 1;
+        tok
+    }
+
+    fn error(&self, kind: ErrorKind) -> SearchError {
+        let loc = self
+            .tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or((0, 0), |x| x.1);
+        SearchError {
+            kind,
+            stmt: self.stmt.to_owned(),
+            loc,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Option<SearchExpr<'a>>, SearchError> {
+        if self.tokens.is_empty() {
+            return Ok(None);
+        }
+        let expr = self.parse_or()?;
+        match self.peek() {
+            None => Ok(Some(expr)),
+            Some(Token::RParen) => Err(self.error(ErrorKind::UnmatchedParen)),
+            Some(_) => Err(self.error(ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<SearchExpr<'a>, SearchError> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            items.push(self.parse_and()?);
+        }
+        if items.len() == 1 {
+            Ok(items.pop().unwrap())
+        } else {
+            Ok(SearchExpr::Or(items))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<SearchExpr<'a>, SearchError> {
+        let mut items = Vec::new();
+        while let Some(tok) = self.peek() {
+            if matches!(tok, Token::Or | Token::RParen) {
+                break;
+            }
+            items.push(self.parse_unary()?);
+        }
+        match items.len() {
+            0 => Err(self.error(ErrorKind::UnexpectedToken)),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(SearchExpr::And(items)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<SearchExpr<'a>, SearchError> {
+        if self.peek() == Some(&Token::Minus) {
+            self.advance();
+            Ok(SearchExpr::Not(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<SearchExpr<'a>, SearchError> {
+        match self.peek() {
+            Some(Token::LParen) => {
+                self.advance();
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error(ErrorKind::UnmatchedParen));
                 }
-                if end.is_none() {
-                    begin = None;
+                self.advance();
+                Ok(expr)
+            }
+            Some(Token::Word(word)) => {
+                self.advance();
+                if let Some(Token::Cmp(op)) = self.peek() {
+                    self.advance();
+                    return match self.peek() {
+                        Some(Token::Word(value)) => {
+                            let expr = self.parse_compare(word, *op, value)?;
+                            self.advance();
+                            Ok(expr)
+                        }
+                        _ => Err(self.error(ErrorKind::InvalidValue)),
+                    };
+                }
+                if self.peek() != Some(&Token::Colon) {
+                    return Ok(SearchExpr::Keyowrd(word));
+                }
+                self.advance();
+                match self.peek() {
+                    Some(Token::Word(value)) => {
+                        self.advance();
+                        match *word {
+                            "format" => Ok(SearchExpr::Format(value)),
+                            "source" => Ok(SearchExpr::Source(value)),
+                            "fav" => Ok(SearchExpr::FavGroup(value)),
+                            "in" => Ok(SearchExpr::Album(value)),
+                            "color" => COLOR_NAMES
+                                .iter()
+                                .copied()
+                                .find(|name| name.eq_ignore_ascii_case(value))
+                                .map(SearchExpr::Color)
+                                .ok_or_else(|| self.error(ErrorKind::InvalidValue)),
+                            "special" => match *value {
+                                "untagged" => Ok(SearchExpr::Special(Special::Untagged)),
+                                "nodescription" => {
+                                    Ok(SearchExpr::Special(Special::NoDescription))
+                                }
+                                "private" => Ok(SearchExpr::Special(Special::Private)),
+                                _ => Err(self.error(ErrorKind::InvalidValue)),
+                            },
+                            "missing" => {
+                                Ok(SearchExpr::Special(Special::MissingNamespace(value)))
+                            }
+                            _ => Ok(SearchExpr::Tag(word, value)),
+                        }
+                    }
+                    _ => Err(self.error(ErrorKind::IncompleteTag)),
                 }
-                let end_pos = end.map_or(stmt.len(), |x| x.0);
-                stack.push(stmt.get(begin_pos..end_pos).unwrap());
             }
+            Some(Token::Colon) => Err(self.error(ErrorKind::IncompleteTag)),
+            Some(Token::RParen) | None => Err(self.error(ErrorKind::UnmatchedParen)),
+            Some(_) => Err(self.error(ErrorKind::UnexpectedToken)),
         }
-        if stack.len() >= 3 {
-            if stack.get(stack.len() - 2).unwrap() == &":" {
-                let value = stack.pop().unwrap();
-                stack.pop().unwrap();
-                let namespace = stack.pop().unwrap();
-                references.push(SearchStmt::Tag(namespace, value));
+    }
+}
+
+fn is_digits(s: &str, len: usize) -> bool {
+    s.len() == len && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Check `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` or `YYYY-MM-DD HH:MM:SS`
+fn is_datetime(s: &str) -> bool {
+    let (date, time) = match s.split_once(|c| c == ' ' || c == 'T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let date = date.split('-').collect::<Vec<&str>>();
+    if date.len() != 3 || !is_digits(date[0], 4) || !is_digits(date[1], 2) || !is_digits(date[2], 2)
+    {
+        return false;
+    }
+    match time {
+        None => true,
+        Some(time) => {
+            let time = time.split(':').collect::<Vec<&str>>();
+            (time.len() == 2 || time.len() == 3) && time.iter().all(|x| is_digits(x, 2))
+        }
+    }
+}
+
+/// Parse `7d`, `2w`, `3m`, `1y` or `12h`, `None` if amount of days overflows
+fn parse_relative(s: &str) -> Option<DateValue<'static>> {
+    let unit = s.chars().last()?;
+    let amount = s[..s.len() - unit.len_utf8()].parse::<u32>().ok()?;
+    match unit {
+        'h' => Some(DateValue::Relative(amount, "hours")),
+        'd' => Some(DateValue::Relative(amount, "days")),
+        'w' => Some(DateValue::Relative(amount.checked_mul(7)?, "days")),
+        'm' => Some(DateValue::Relative(amount, "months")),
+        'y' => Some(DateValue::Relative(amount, "years")),
+        _ => None,
+    }
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    /// Parse `field op value`, current token should be the value
+    fn parse_compare(
+        &self,
+        field: &'a str,
+        op: CmpOp,
+        value: &'a str,
+    ) -> Result<SearchExpr<'a>, SearchError> {
+        match field {
+            "created" | "updated" => {
+                let field = if field == "created" {
+                    DateField::Created
+                } else {
+                    DateField::Updated
+                };
+                let value = if is_datetime(value) {
+                    DateValue::Absolute(value)
+                } else {
+                    parse_relative(value).ok_or_else(|| self.error(ErrorKind::InvalidValue))?
+                };
+                Ok(SearchExpr::Date(field, op, value))
+            }
+            "rating" => match value.parse::<u8>() {
+                Ok(rating) if rating <= 5 => Ok(SearchExpr::Rating(op, rating)),
+                _ => Err(self.error(ErrorKind::InvalidValue)),
+            },
+            "width" | "height" => {
+                let field = if field == "width" {
+                    DimensionField::Width
+                } else {
+                    DimensionField::Height
+                };
+                let value = value
+                    .parse::<u32>()
+                    .map_err(|_| self.error(ErrorKind::InvalidValue))?;
+                Ok(SearchExpr::Dimension(field, op, value))
             }
+            _ => Err(self.error(ErrorKind::UnknownField)),
         }
     }
-    while !stack.is_empty() {
-        let tok = stack.pop().unwrap().trim();
-        if tok == ":" {
-            return Err(SearchError {
-                kind: ErrorKind::IncompleteTag,
-                stmt: stmt.to_owned(),
-                loc: (0, 0),
-            });
+}
+
+fn parse(stmt: &str) -> Result<Option<SearchExpr<'_>>, SearchError> {
+    let tokens = lexer(stmt)?;
+    Parser {
+        stmt,
+        tokens: &tokens,
+        pos: 0,
+    }
+    .parse()
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum SortBy {
+    /// Best match first if there is any keyword, otherwise recently updated first
+    Relevance,
+    UpdateTime,
+    CreateTime,
+    MostUsed,
+    RecentlyUsed,
+    Rating,
+    /// Along the color wheel from red, memes without a dominant hue last
+    Hue,
+}
+
+impl SortBy {
+    pub fn order_by(&self) -> Option<&'static str> {
+        match self {
+            SortBy::Relevance => None,
+            SortBy::UpdateTime => Some("update_time DESC"),
+            SortBy::CreateTime => Some("create_time DESC"),
+            SortBy::MostUsed => Some("use_count DESC, last_used DESC"),
+            SortBy::RecentlyUsed => Some("last_used IS NULL, last_used DESC"),
+            SortBy::Rating => Some("rating DESC, update_time DESC"),
+            SortBy::Hue => Some("dominant_hue IS NULL, dominant_hue, update_time DESC"),
         }
-        references.push(SearchStmt::Keyowrd(tok));
+    }
+}
+
+/// Generated search statement
+/// `sql` ends with a dangling `WHERE` or `AND`, caller should append more conditions,
+/// and then order the result by `order_by`.
+/// Values from search statement are never put into `sql`, they are bound to its `?`
+/// placeholders in order from `params`, caller should push params of its own conditions
+pub struct SearchSql {
+    pub sql: String,
+    pub params: Vec<Value>,
+    pub order_by: String,
+    /// Whether search has keywords, rows then have `fts_rank`, lower is better
+    pub ranked: bool,
+    /// Terms memes are found by, see `highlight`
+    pub terms: Vec<SearchTerm>,
+}
+
+/// Keyword or tag of search statement which is not negated,
+/// a meme in result may match some of them if they are joined by `OR`
+#[derive(Debug, PartialEq, Clone)]
+pub enum SearchTerm {
+    Keyword(String),
+    /// Namespace and prefix of value, `None` for `*`
+    Tag(Option<String>, Option<String>),
+}
+
+/// Order of search result which is paged by keyset, `meme.id` breaks ties of update time
+pub const KEYSET_ORDER: &str = "update_time DESC";
+
+/// Where the next page of search result starts, clients only pass it back as an opaque string.
+/// Result in `KEYSET_ORDER` is paged by `(update_time, id)` of the last meme, so memes edited
+/// while browsing neither repeat nor go missing. Other orders are paged by offset
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Cursor {
+    After { update_time: String, id: i64 },
+    Offset(i64),
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
     }
 
-    Ok(references)
+    pub fn decode(cursor: &str) -> Option<Cursor> {
+        let data = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
 }
 
-pub fn build_search_sql(search_stmt: &str) -> Result<String, SearchError> {
-    let stmts = lexer(search_stmt)?;
-    let mut tag_select = Vec::new();
-    let mut kwd_where = Vec::new();
+/// Memes joined with their media info,
+/// rows mapped by `meme_from_row` should be selected from it
+pub const MEME_TABLE: &str = "meme LEFT JOIN media_info ON media_info.meme_id = meme.id";
+
+/// Trigram tokenizer can not match keyword shorter than 3 characters
+const FTS_MIN_LEN: usize = 3;
+
+/// Escape wildcards of LIKE pattern, `\\` is used as escape character
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// `namespace:*` matches any tag in namespace, `*:value` matches value in any namespace
+const WILDCARD: &str = "*";
+/// Part of image a color should cover for `color:` to match
+const MIN_COLOR_RATIO: f64 = 0.15;
+
+/// Quote keyword as a FTS5 string so its characters are not taken as FTS5 syntax
+fn fts_phrase(kwd: &str) -> String {
+    format!("\"{}\"", kwd.replace('"', "\"\""))
+}
 
-    for stmt in stmts {
-        match stmt {
-            SearchStmt::Keyowrd(kwd) => kwd_where.push(format!(
-                "name LIKE '%{}%' OR description LIKE '%{}%'",
-                kwd, kwd
-            )),
-            SearchStmt::Tag(namespace, value) => tag_select.push(format!(
-                "SELECT meme_id FROM tagid WHERE key = '{}' AND value LIKE '{}%'",
-                namespace, value
-            )),
+/// Compile expression into a predicate on `meme` table, values are pushed into `params`
+/// keywords and tags which are not negated are collected into `terms`
+fn compile(
+    expr: &SearchExpr,
+    negated: bool,
+    terms: &mut Vec<SearchTerm>,
+    params: &mut Vec<Value>,
+) -> String {
+    match expr {
+        SearchExpr::Keyowrd(kwd) if kwd.chars().count() >= FTS_MIN_LEN => {
+            if !negated {
+                terms.push(SearchTerm::Keyword(kwd.to_string()));
+            }
+            params.push(fts_phrase(kwd).into());
+            "meme.id IN (SELECT rowid FROM meme_fts WHERE meme_fts MATCH ?)".to_owned()
+        }
+        SearchExpr::Keyowrd(kwd) => {
+            if !negated {
+                terms.push(SearchTerm::Keyword(kwd.to_string()));
+            }
+            let pattern = format!("%{}%", escape_like(kwd));
+            for _ in 0..3 {
+                params.push(pattern.clone().into());
+            }
+            "(name LIKE ? ESCAPE '\\' OR description LIKE ? ESCAPE '\\'
+              OR meme.id IN (SELECT meme_id FROM meme_text WHERE text LIKE ? ESCAPE '\\'))"
+                .to_owned()
+        }
+        SearchExpr::Tag(namespace, value) => {
+            if !negated {
+                let some = |x: &str| (x != WILDCARD).then(|| x.to_owned());
+                terms.push(SearchTerm::Tag(some(namespace), some(value)));
+            }
+            let mut cond = String::new();
+            if *namespace != WILDCARD {
+                cond.push_str(" AND key = ?");
+                params.push(namespace.to_string().into());
+            }
+            if *value != WILDCARD {
+                cond.push_str(" AND value LIKE ? ESCAPE '\\'");
+                params.push(format!("{}%", escape_like(value)).into());
+            }
+            format!(
+                "EXISTS (SELECT 1 FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE meme_tag.meme_id = meme.id{})",
+                cond
+            )
+        }
+        SearchExpr::Date(field, op, value) => {
+            let column = match field {
+                DateField::Created => "create_time",
+                DateField::Updated => "update_time",
+            };
+            match value {
+                // compare by day if time is not specified
+                DateValue::Absolute(value) if value.len() == 10 => {
+                    params.push(value.to_string().into());
+                    format!("date({}) {} date(?)", column, op.sql())
+                }
+                DateValue::Absolute(value) => {
+                    params.push(value.replace('T', " ").into());
+                    format!("datetime({}) {} datetime(?)", column, op.sql())
+                }
+                // `updated<7d` means updated less than 7 days ago
+                DateValue::Relative(amount, unit) if *op == CmpOp::Eq => {
+                    params.push(format!("-{} {}", amount, unit).into());
+                    format!("date({}) = date('now', ?)", column)
+                }
+                DateValue::Relative(amount, unit) => {
+                    params.push(format!("-{} {}", amount, unit).into());
+                    format!("{} {} datetime('now', ?)", column, op.reverse().sql())
+                }
+            }
+        }
+        SearchExpr::Rating(op, rating) => {
+            params.push((*rating).into());
+            format!("rating {} ?", op.sql())
+        }
+        SearchExpr::Dimension(field, op, value) => {
+            let column = match field {
+                DimensionField::Width => "media_info.width",
+                DimensionField::Height => "media_info.height",
+            };
+            params.push((*value).into());
+            format!("{} {} ?", column, op.sql())
+        }
+        SearchExpr::Format(format) => {
+            params.push(format.to_lowercase().into());
+            "media_info.format = ?".to_owned()
+        }
+        SearchExpr::Source(source) => {
+            let pattern = format!("%{}%", escape_like(source));
+            params.push(pattern.clone().into());
+            params.push(pattern.into());
+            "(source_url LIKE ? ESCAPE '\\' OR origin LIKE ? ESCAPE '\\')".to_owned()
+        }
+        SearchExpr::FavGroup(name) if *name == WILDCARD => "fav = true".to_owned(),
+        SearchExpr::FavGroup(name) => {
+            params.push(name.to_string().into());
+            "meme.id IN (SELECT meme_id FROM fav_group_meme
+              JOIN fav_group ON fav_group.id = fav_group_meme.group_id
+              WHERE fav_group.name = ?)"
+                .to_owned()
+        }
+        SearchExpr::Album(name) if *name == WILDCARD => {
+            "meme.id IN (SELECT meme_id FROM album_meme)".to_owned()
+        }
+        SearchExpr::Album(name) => {
+            params.push(name.to_string().into());
+            "meme.id IN (SELECT meme_id FROM album_meme
+              JOIN album ON album.id = album_meme.album_id
+              WHERE album.name = ?)"
+                .to_owned()
+        }
+        SearchExpr::Color(name) => {
+            params.push(name.to_string().into());
+            params.push(MIN_COLOR_RATIO.into());
+            "meme.id IN (SELECT meme_id FROM meme_color WHERE name = ? AND ratio >= ?)".to_owned()
         }
+        SearchExpr::Special(Special::Untagged) => {
+            "NOT EXISTS (SELECT 1 FROM meme_tag WHERE meme_tag.meme_id = meme.id)".to_owned()
+        }
+        SearchExpr::Special(Special::NoDescription) => {
+            "IFNULL(TRIM(description), '') = ''".to_owned()
+        }
+        SearchExpr::Special(Special::Private) => "meme.private = true".to_owned(),
+        SearchExpr::Special(Special::MissingNamespace(namespace)) => {
+            params.push(namespace.to_string().into());
+            "NOT EXISTS (SELECT 1 FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id
+              WHERE meme_tag.meme_id = meme.id AND key = ?)"
+                .to_owned()
+        }
+        SearchExpr::Not(inner) => format!("NOT {}", compile(inner, !negated, terms, params)),
+        SearchExpr::And(items) => format!(
+            "({})",
+            items
+                .iter()
+                .map(|x| compile(x, negated, terms, params))
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        ),
+        SearchExpr::Or(items) => format!(
+            "({})",
+            items
+                .iter()
+                .map(|x| compile(x, negated, terms, params))
+                .collect::<Vec<String>>()
+                .join(" OR ")
+        ),
     }
-    let from_table = if tag_select.is_empty() {
-        "meme".to_owned()
-    } else {
-        format!(
-            "(
-                WITH tagid AS (
-                    SELECT * FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id
-                )
-                {}
-            ) LEFT JOIN meme ON meme_id = meme.id",
-            tag_select
-                .into_iter()
-                .reduce(|acc, cur| format!("{}\nINTERSECT\n{}", acc, cur))
-                .unwrap()
-        )
+}
+
+pub fn build_search_sql(search_stmt: &str) -> Result<SearchSql, SearchError> {
+    let expr = parse(search_stmt)?;
+    let mut terms = Vec::new();
+    let mut where_params = Vec::new();
+    let where_stmt = match &expr {
+        Some(expr) => format!("{} AND ", compile(expr, false, &mut terms, &mut where_params)),
+        None => "".to_owned(),
     };
-    let where_stmt = if kwd_where.is_empty() {
-        "".to_owned()
+    // keywords matched by FTS order the result
+    let rank = terms
+        .iter()
+        .filter_map(|term| match term {
+            SearchTerm::Keyword(kwd) if kwd.chars().count() >= FTS_MIN_LEN => Some(fts_phrase(kwd)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+
+    let mut from_table = MEME_TABLE.to_owned();
+    let mut params = Vec::new();
+    let order_by = if rank.is_empty() {
+        "update_time DESC".to_owned()
     } else {
-        let mut wd = String::new();
-        wd.push_str("(");
-        wd.push_str(
-            &kwd_where
-                .into_iter()
-                .reduce(|acc, pre| format!("{} OR {}", acc, pre))
-                .unwrap(),
+        // the join comes before `WHERE`, so is its param
+        from_table.push_str(
+            " LEFT JOIN (
+                SELECT rowid AS fts_id, rank AS fts_rank FROM meme_fts WHERE meme_fts MATCH ?
+            ) ON fts_id = meme.id",
         );
-        wd.push_str(")");
-        wd.push_str(" AND ");
-        wd
+        params.push(rank.join(" OR ").into());
+        "fts_rank IS NULL, fts_rank, update_time DESC".to_owned()
     };
+    params.append(&mut where_params);
 
-    let result = format!("SELECT * FROM {} WHERE {}", from_table, where_stmt);
+    let sql = format!("SELECT * FROM {} WHERE {}", from_table, where_stmt);
 
-    Ok(result)
+    Ok(SearchSql {
+        sql,
+        params,
+        order_by,
+        ranked: !rank.is_empty(),
+        terms,
+    })
 }
 
 mod tests {
@@ -168,4 +752,319 @@ mod tests {
             crate::db::search::lexer("group character:\"aoi sora\"")
         );
     }
+
+    #[test]
+    fn test_parse() {
+        use crate::db::search::{parse, SearchExpr::*};
+
+        assert_eq!(
+            parse("group character:\"aoi sora\"").unwrap(),
+            Some(And(vec![Keyowrd("group"), Tag("character", "aoi sora")]))
+        );
+        assert_eq!(
+            parse("-character:cat (mood:happy OR mood:sad)").unwrap(),
+            Some(And(vec![
+                Not(Box::new(Tag("character", "cat"))),
+                Or(vec![Tag("mood", "happy"), Tag("mood", "sad")])
+            ]))
+        );
+        assert_eq!(parse("  ").unwrap(), None);
+        assert!(parse("(a OR b").is_err());
+        assert!(parse("a)").is_err());
+        assert!(parse("character:").is_err());
+        assert!(parse("a OR").is_err());
+    }
+
+    #[test]
+    fn test_namespace() {
+        use crate::db::search::build_search_sql;
+        use rusqlite::types::Value::Text;
+
+        let search = build_search_sql("character:cat").unwrap();
+        assert!(search.sql.contains("key = ?"));
+        assert!(search.sql.contains("value LIKE ?"));
+        assert_eq!(
+            search.params,
+            vec![Text("character".to_owned()), Text("cat%".to_owned())]
+        );
+
+        let search = build_search_sql("character:*").unwrap();
+        assert!(search.sql.contains("key = ?"));
+        assert!(!search.sql.contains("value LIKE"));
+        assert_eq!(search.params, vec![Text("character".to_owned())]);
+
+        let search = build_search_sql("*:100%").unwrap();
+        assert!(!search.sql.contains("key ="));
+        assert_eq!(search.params, vec![Text("100\\%%".to_owned())]);
+    }
+
+    #[test]
+    fn test_injection() {
+        use crate::db::search::build_search_sql;
+        use rusqlite::types::Value::Text;
+
+        let stmt = "character:\"x' OR 1=1 --\"";
+        let search = build_search_sql(stmt).unwrap();
+        assert!(!search.sql.contains("1=1"));
+        assert_eq!(
+            search.params,
+            vec![Text("character".to_owned()), Text("x' OR 1=1 --%".to_owned())]
+        );
+
+        let search = build_search_sql("\"it's\"").unwrap();
+        assert!(!search.sql.contains("it's"));
+        // ranked by the same phrase it is matched with
+        assert_eq!(
+            search.params,
+            vec![Text("\"it's\"".to_owned()), Text("\"it's\"".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_date() {
+        use crate::db::search::{build_search_sql, parse};
+        use rusqlite::types::Value::Text;
+
+        let search = build_search_sql("created>2024-01-01").unwrap();
+        assert!(search.sql.contains("date(create_time) > date(?)"));
+        assert_eq!(search.params, vec![Text("2024-01-01".to_owned())]);
+        let search = build_search_sql("updated<7d").unwrap();
+        assert!(search.sql.contains("update_time > datetime('now', ?)"));
+        assert_eq!(search.params, vec![Text("-7 days".to_owned())]);
+        let search = build_search_sql("updated>=2w").unwrap();
+        assert!(search.sql.contains("update_time <= datetime('now', ?)"));
+        assert_eq!(search.params, vec![Text("-14 days".to_owned())]);
+
+        assert!(parse("created>yesterday").is_err());
+        assert!(parse("height>7d").is_err());
+        assert!(parse("created>").is_err());
+        assert!(parse("created>99999999999d").is_err());
+        assert!(parse("created>700000000w").is_err());
+    }
+
+    #[test]
+    fn test_rating() {
+        use crate::db::search::{build_search_sql, parse};
+        use rusqlite::types::Value::Integer;
+
+        let search = build_search_sql("rating>=4").unwrap();
+        assert!(search.sql.contains("rating >= ?"));
+        assert_eq!(search.params, vec![Integer(4)]);
+        assert!(parse("rating>6").is_err());
+    }
+
+    #[test]
+    fn test_media_info() {
+        use crate::db::search::{build_search_sql, parse};
+        use rusqlite::types::Value::{Integer, Text};
+
+        let search = build_search_sql("width>1000 format:GIF").unwrap();
+        assert!(search.sql.contains("media_info.width > ?"));
+        assert!(search.sql.contains("media_info.format = ?"));
+        assert!(!search.sql.contains("key = "));
+        assert_eq!(search.params, vec![Integer(1000), Text("gif".to_owned())]);
+        assert!(parse("height<=wide").is_err());
+    }
+
+    #[test]
+    fn test_special() {
+        use crate::db::search::{build_search_sql, parse, SearchExpr::*, Special::*};
+        use rusqlite::types::Value::Text;
+
+        assert_eq!(
+            parse("special:untagged special:nodescription special:private missing:character")
+                .unwrap(),
+            Some(And(vec![
+                Special(Untagged),
+                Special(NoDescription),
+                Special(Private),
+                Special(MissingNamespace("character"))
+            ]))
+        );
+        assert!(parse("special:unknown").is_err());
+
+        let search = build_search_sql("missing:character").unwrap();
+        assert!(search.sql.contains("NOT EXISTS"));
+        assert_eq!(search.params, vec![Text("character".to_owned())]);
+    }
+
+    #[test]
+    fn test_source() {
+        use crate::db::search::{build_search_sql, parse, SearchExpr::*};
+        use rusqlite::types::Value::Text;
+
+        assert_eq!(
+            parse("source:twitter.com -character:cat").unwrap(),
+            Some(And(vec![
+                Source("twitter.com"),
+                Not(Box::new(Tag("character", "cat")))
+            ]))
+        );
+
+        let search = build_search_sql("source:100%").unwrap();
+        assert!(search.sql.contains("source_url LIKE ?"));
+        assert_eq!(
+            search.params,
+            vec![Text("%100\\%%".to_owned()), Text("%100\\%%".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_fav_group() {
+        use crate::db::search::{build_search_sql, parse, SearchExpr::*};
+        use rusqlite::types::Value::Text;
+
+        assert_eq!(
+            parse("fav:work cat").unwrap(),
+            Some(And(vec![FavGroup("work"), Keyowrd("cat")]))
+        );
+
+        let search = build_search_sql("fav:\"work memes\"").unwrap();
+        assert!(search.sql.contains("fav_group_meme"));
+        assert_eq!(search.params, vec![Text("work memes".to_owned())]);
+
+        let search = build_search_sql("fav:*").unwrap();
+        assert!(search.sql.contains("fav = true"));
+        assert!(search.params.is_empty());
+    }
+
+    #[test]
+    fn test_album() {
+        use crate::db::search::{build_search_sql, parse, SearchExpr::*};
+        use rusqlite::types::Value::Text;
+
+        assert_eq!(
+            parse("in:reactions -in:\"old stuff\" cat").unwrap(),
+            Some(And(vec![
+                Album("reactions"),
+                Not(Box::new(Album("old stuff"))),
+                Keyowrd("cat")
+            ]))
+        );
+
+        let search = build_search_sql("in:reactions character:cat").unwrap();
+        assert!(search.sql.contains("album_meme"));
+        assert_eq!(
+            search.params,
+            vec![
+                Text("reactions".to_owned()),
+                Text("character".to_owned()),
+                Text("cat%".to_owned())
+            ]
+        );
+
+        let search = build_search_sql("in:*").unwrap();
+        assert!(search.sql.contains("album_meme"));
+        assert!(search.params.is_empty());
+    }
+
+    #[test]
+    fn test_terms() {
+        use crate::db::search::{build_search_sql, SearchTerm::*};
+
+        let search = build_search_sql("cat -dog (character:* OR *:aoi) -mood:sad").unwrap();
+        assert_eq!(
+            search.terms,
+            vec![
+                Keyword("cat".to_owned()),
+                Tag(Some("character".to_owned()), None),
+                Tag(None, Some("aoi".to_owned())),
+            ]
+        );
+        assert!(search.ranked);
+        // short keywords are matched by LIKE, they still highlight
+        let search = build_search_sql("ab width>100").unwrap();
+        assert_eq!(search.terms, vec![Keyword("ab".to_owned())]);
+        assert!(!search.ranked);
+    }
+
+    #[test]
+    fn test_color() {
+        use crate::db::search::{build_search_sql, parse, SearchExpr::*};
+        use rusqlite::types::Value::{Real, Text};
+
+        assert_eq!(
+            parse("color:Orange -color:white").unwrap(),
+            Some(And(vec![Color("orange"), Not(Box::new(Color("white")))]))
+        );
+        assert!(parse("color:teal").is_err());
+
+        let search = build_search_sql("color:blue").unwrap();
+        assert!(search.sql.contains("meme_color"));
+        assert_eq!(search.params, vec![Text("blue".to_owned()), Real(0.15)]);
+    }
+
+    #[test]
+    fn test_search_library() {
+        use crate::{db::fixtures::seeded_library, meme::search_memes};
+
+        let (state, ids) = seeded_library();
+        let names = |stmt: &str| {
+            let mut names = search_memes(&state, stmt, None, None, false, false, None)
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|meme| meme.name)
+                .collect::<Vec<String>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names("character:cat"), vec!["happy cat", "sad cat"]);
+        assert_eq!(names("mood:happy -character:dog"), vec!["happy cat"]);
+        assert_eq!(names("character:cat OR character:dog").len(), 3);
+        assert_eq!(names("dog"), vec!["happy dog"]);
+        assert_eq!(names("").len(), ids.len());
+
+        state
+            .conn
+            .execute("UPDATE meme SET trash = true WHERE id = ?1", [ids[0]])
+            .unwrap();
+        assert_eq!(names("character:cat"), vec!["sad cat"]);
+    }
+
+    #[test]
+    fn test_cursor() {
+        use crate::{
+            db::{fixtures::seeded_library, search::SortBy},
+            meme::search_memes,
+        };
+
+        let (state, ids) = seeded_library();
+        let sort_by = Some(SortBy::UpdateTime);
+        let first = search_memes(&state, "", None, Some(3), false, false, sort_by).unwrap();
+        assert_eq!(first.items.len(), 3);
+        assert_eq!(first.total, 4);
+
+        // memes edited while browsing move to the front, they are not seen twice
+        state
+            .conn
+            .execute(
+                "UPDATE meme SET update_time = '2999-01-01 00:00:00' WHERE id = ?1",
+                [first.items[0].id],
+            )
+            .unwrap();
+        let cursor = first.next_cursor.as_deref();
+        let second = search_memes(&state, "", cursor, Some(3), false, false, sort_by).unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert!(second.next_cursor.is_none());
+
+        let mut seen = first
+            .items
+            .iter()
+            .chain(second.items.iter())
+            .map(|meme| meme.id)
+            .collect::<Vec<i64>>();
+        seen.sort();
+        assert_eq!(seen, ids);
+
+        // other orders are paged by offset
+        let sort_by = Some(SortBy::Rating);
+        let first = search_memes(&state, "", None, Some(2), false, false, sort_by).unwrap();
+        let cursor = first.next_cursor.as_deref();
+        let second = search_memes(&state, "", cursor, Some(2), false, false, sort_by).unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert!(search_memes(&state, "", cursor, None, false, false, None).is_err());
+        assert!(search_memes(&state, "", Some("garbage"), None, false, false, None).is_err());
+    }
 }
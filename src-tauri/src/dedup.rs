@@ -0,0 +1,269 @@
+use std::{collections::HashMap, path::Path};
+
+use image::imageops::FilterType;
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::compute_path,
+    storage::{fetch_content, Storage},
+};
+
+/// Compute dHash of an image
+/// return None if the file can not be decoded as image
+pub fn compute_phash<P: AsRef<Path>>(path: P) -> Option<u64> {
+    let img = image::open(path).ok()?;
+    let gray = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if gray.get_pixel(x, y)[0] > gray.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+pub fn hamming_distance(l: u64, r: u64) -> u32 {
+    (l ^ r).count_ones()
+}
+
+pub fn save_meme_phash(conn: &Connection, meme_id: i64, phash: Option<u64>) -> Result<(), String> {
+    conn.execute(
+        "UPDATE meme SET phash = ?1 WHERE id = ?2",
+        (phash.map(|x| x as i64), meme_id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Compute and save phash of meme, content must be already in storage
+pub fn update_meme_phash<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    content: &str,
+) -> Result<Option<u64>, String> {
+    let phash = compute_phash(compute_path(base, content));
+    save_meme_phash(conn, meme_id, phash)?;
+    Ok(phash)
+}
+
+/// Compute phash for all images which have not been hashed yet,
+/// content only in remote storage is downloaded first
+pub fn fill_missing_phash(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+) -> Result<(), String> {
+    let mut query = conn
+        .prepare("SELECT id, hash FROM meme WHERE ty = 'image' AND phash IS NULL")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    for (id, hash) in memes {
+        fetch_content(storage, base, &hash)?;
+        update_meme_phash(conn, base, id, &hash)?;
+    }
+    Ok(())
+}
+
+fn query_all_phash(conn: &Connection) -> Result<Vec<(i64, u64)>, String> {
+    let mut query = conn
+        .prepare("SELECT id, phash FROM meme WHERE phash IS NOT NULL AND trash = false")
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, u64)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+fn find(parent: &mut [usize], x: usize) -> usize {
+    if parent[x] != x {
+        let next = parent[x];
+        parent[x] = find(parent, next);
+    }
+    parent[x]
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SimilarMeme {
+    id: i64,
+    distance: u32,
+}
+
+/// Find memes whose phash distance to meme `id` is not greater than `threshold`
+#[tauri::command]
+pub async fn find_similar_memes(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    threshold: u32,
+) -> AppResult<Vec<SimilarMeme>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let phash: Option<i64> = state
+        .conn
+        .query_row("SELECT phash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    let phash = match phash {
+        Some(phash) => phash as u64,
+        None => {
+            let hash: String = state
+                .conn
+                .query_row("SELECT hash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            fetch_content(state.storage.as_ref(), &state.path, &hash)
+                .map_err(AppError::StorageUnavailable)?;
+            match update_meme_phash(&state.conn, &state.path, id, &hash)? {
+                Some(phash) => phash,
+                None => return Ok(Vec::new()),
+            }
+        }
+    };
+
+    let mut result = query_all_phash(&state.conn)?
+        .into_iter()
+        .filter(|(other, _)| *other != id)
+        .map(|(other, other_phash)| SimilarMeme {
+            id: other,
+            distance: hamming_distance(phash, other_phash),
+        })
+        .filter(|x| x.distance <= threshold)
+        .collect::<Vec<SimilarMeme>>();
+    result.sort_by_key(|x| x.distance);
+    Ok(result)
+}
+
+/// Group hashed memes not in trash whose phash distance is not greater than `threshold`,
+/// directly or through other memes. Memes similar to no other meme are left out
+pub fn cluster_by_phash(conn: &Connection, threshold: u32) -> Result<Vec<Vec<i64>>, String> {
+    let memes = query_all_phash(conn)?;
+
+    // union-find over all similar pairs
+    let mut parent = (0..memes.len()).collect::<Vec<usize>>();
+    for i in 0..memes.len() {
+        for j in i + 1..memes.len() {
+            if hamming_distance(memes[i].1, memes[j].1) <= threshold {
+                let (pi, pj) = (find(&mut parent, i), find(&mut parent, j));
+                parent[pi] = pj;
+            }
+        }
+    }
+
+    let mut groups = HashMap::<usize, Vec<i64>>::new();
+    for (i, (id, _)) in memes.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(*id);
+    }
+    Ok(groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect())
+}
+
+/// Scan the whole library and group memes which look similar
+/// Memes without phash will be hashed before scanning
+#[tauri::command]
+pub async fn scan_duplicate_memes(
+    state: tauri::State<'_, MemeDatabaseState>,
+    threshold: u32,
+) -> AppResult<Vec<Vec<i64>>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    fill_missing_phash(&state.conn, state.storage.as_ref(), &state.path)
+        .map_err(AppError::StorageUnavailable)?;
+    Ok(cluster_by_phash(&state.conn, threshold)?)
+}
+
+/// Name compared when looking for duplicates, case, whitespace and punctuation are ignored
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Number of single character edits to turn `l` into `r`
+fn edit_distance(l: &[char], r: &[char]) -> usize {
+    let mut prev = (0..=r.len()).collect::<Vec<usize>>();
+    for (i, lc) in l.iter().enumerate() {
+        let mut cur = vec![i + 1; r.len() + 1];
+        for (j, rc) in r.iter().enumerate() {
+            let replace = prev[j] + usize::from(lc != rc);
+            cur[j + 1] = replace.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[r.len()]
+}
+
+/// Group memes whose names are the same after normalizing,
+/// or differ in at most `max_distance` characters,
+/// e.g. the same meme imported once as PNG and once as JPEG
+#[tauri::command]
+pub async fn scan_duplicate_names(
+    state: tauri::State<'_, MemeDatabaseState>,
+    max_distance: usize,
+) -> AppResult<Vec<Vec<i64>>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let mut query = state
+        .conn
+        .prepare("SELECT id, name FROM meme WHERE trash = false")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut by_name = HashMap::<String, Vec<i64>>::new();
+    for (id, name) in memes {
+        let name = normalize_name(&name);
+        if !name.is_empty() {
+            by_name.entry(name).or_default().push(id);
+        }
+    }
+    let (names, mut groups): (Vec<Vec<char>>, Vec<Vec<i64>>) = by_name
+        .into_iter()
+        .map(|(name, ids)| (name.chars().collect(), ids))
+        .unzip();
+
+    // union-find over names close enough
+    let mut parent = (0..names.len()).collect::<Vec<usize>>();
+    if max_distance > 0 {
+        for i in 0..names.len() {
+            for j in i + 1..names.len() {
+                if names[i].len().abs_diff(names[j].len()) <= max_distance
+                    && edit_distance(&names[i], &names[j]) <= max_distance
+                {
+                    let (pi, pj) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[pi] = pj;
+                }
+            }
+        }
+    }
+
+    let mut clusters = HashMap::<usize, Vec<i64>>::new();
+    for (i, ids) in groups.iter_mut().enumerate() {
+        let root = find(&mut parent, i);
+        clusters.entry(root).or_default().append(ids);
+    }
+    Ok(clusters
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect())
+}
@@ -0,0 +1,156 @@
+use std::{fs, path::Path};
+
+use time::OffsetDateTime;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    error::AppResult,
+    file::media_type,
+    meme::{create_meme_with_tags, AddMemeResult, NewMeme, Tag},
+};
+
+/// Larger files are refused, so a wrong url does not fill up the disk
+pub const MAX_DOWNLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// File downloaded from web, not imported yet
+pub struct Downloaded {
+    pub data: Vec<u8>,
+    /// Extension of file, judged by content type, url and content in order
+    pub ext: String,
+    /// Last segment of url after redirects, without extension
+    pub file_name: Option<String>,
+}
+
+fn mime_extension(mime: &str) -> Option<&'static str> {
+    let mime = mime.split(';').next().unwrap_or(mime).trim();
+    match mime.to_lowercase().as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/bmp" => Some("bmp"),
+        "video/mp4" => Some("mp4"),
+        "video/webm" => Some("webm"),
+        "video/quicktime" => Some("mov"),
+        "video/x-matroska" => Some("mkv"),
+        _ => None,
+    }
+}
+
+/// Extension of file whose content is `data`, by its content type or name if they are known
+pub fn detect_extension(data: &[u8], mime: Option<&str>, name: Option<&str>) -> Option<String> {
+    if let Some(ext) = mime.and_then(mime_extension) {
+        return Some(ext.to_owned());
+    }
+    if let Some(ext) = name.and_then(|name| Path::new(name).extension()) {
+        let ext = ext.to_string_lossy().to_lowercase();
+        if media_type(Path::new("file").with_extension(&ext)).is_some() {
+            return Some(ext);
+        }
+    }
+    image::guess_format(data)
+        .ok()
+        .and_then(|format| format.extensions_str().first())
+        .map(|ext| ext.to_string())
+}
+
+/// Download file at `url`, redirects are followed
+pub async fn download(url: &str) -> Result<Downloaded, String> {
+    let mut response = reqwest::get(url).await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("failed to download {}: {}", url, response.status()));
+    }
+    if response
+        .content_length()
+        .map_or(false, |len| len > MAX_DOWNLOAD_SIZE as u64)
+    {
+        return Err(format!("{} is too large", url));
+    }
+    let name = response
+        .url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_owned());
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .map(|x| x.to_owned());
+    // content length may be absent or wrong, so check the size while reading
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if data.len() + chunk.len() > MAX_DOWNLOAD_SIZE {
+            return Err(format!("{} is too large", url));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let ext = detect_extension(&data, mime.as_deref(), name.as_deref())
+        .ok_or_else(|| format!("{} is not an image or video", url))?;
+    let file_name = name.map(|name| match name.rsplit_once('.') {
+        Some((stem, _)) => stem.to_owned(),
+        None => name,
+    });
+    Ok(Downloaded {
+        data,
+        ext,
+        file_name,
+    })
+}
+
+/// Import file content `data` with extension `ext` as meme,
+/// `meme.content` and `meme.ty` are filled in from the file
+pub fn import_data(
+    state: &mut MemeDatabaseConnection,
+    data: &[u8],
+    ext: &str,
+    mut meme: NewMeme,
+) -> Result<AddMemeResult, String> {
+    // content is imported from a file, which is removed after it is copied into library
+    let path = std::env::temp_dir().join(format!("meme-{}.{}", sha256::digest(data), ext));
+    meme.ty = media_type(&path)
+        .ok_or_else(|| format!("{} file can not be imported", ext))?
+        .to_owned();
+    fs::write(&path, data).map_err(|e| e.to_string())?;
+    meme.content = path.to_string_lossy().to_string();
+    let result =
+        create_meme_with_tags(&mut state.conn, state.storage.as_ref(), &state.path, meme);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Download image or video at `url` and import it as a new meme,
+/// `url` is recorded as its source url. Name of meme is the file name in url if not given
+#[tauri::command]
+pub async fn import_from_url(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    url: String,
+    name: Option<String>,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+) -> AppResult<AddMemeResult> {
+    // library is not locked while downloading
+    let downloaded = download(&url).await?;
+    let name = name.or(downloaded.file_name).unwrap_or_else(|| {
+        format!("web-{}", OffsetDateTime::now_utc().unix_timestamp())
+    });
+    let meme = NewMeme {
+        name,
+        description: None,
+        ty: String::new(),
+        content: String::new(),
+        fav: false,
+        tags,
+        pkg_id,
+        strip_exif: false,
+        source_url: Some(url),
+        author: None,
+        origin: None,
+        derived_from: None,
+    };
+
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    Ok(import_data(state, &downloaded.data, &downloaded.ext, meme)?)
+}
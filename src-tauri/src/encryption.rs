@@ -0,0 +1,120 @@
+use std::{fs, path::Path};
+
+use rusqlite::{Connection, ErrorCode};
+
+use crate::{
+    backup::{create_backup, remove_backups},
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    error::{AppError, AppResult},
+};
+
+const DATABASE_NAME: &str = "meme.db";
+/// Database re-encrypted with new passphrase, it replaces `DATABASE_NAME` once written
+const REKEYED_NAME: &str = "meme.db.rekeyed";
+
+#[derive(Debug, serde::Serialize)]
+pub struct PassphraseResult {
+    /// Backups keyed by the old passphrase, or left unencrypted, that were removed
+    removed_backups: usize,
+    /// Backup keyed by the new passphrase taken in place of them
+    backup: String,
+}
+
+fn not_supported() -> AppError {
+    AppError::InvalidArgument("app is built without encryption support".to_owned())
+}
+
+/// Key connection by passphrase, must be the first statement run on connection.
+/// SQLCipher derives key from passphrase with PBKDF2 and salt stored in database file
+#[cfg(feature = "encryption")]
+pub fn apply_passphrase(conn: &Connection, passphrase: &str) -> AppResult<()> {
+    conn.pragma_update(None, "key", passphrase)?;
+    Ok(())
+}
+
+/// Without SQLCipher `PRAGMA key` is silently ignored, so it is refused instead
+#[cfg(not(feature = "encryption"))]
+pub fn apply_passphrase(_conn: &Connection, _passphrase: &str) -> AppResult<()> {
+    Err(not_supported())
+}
+
+/// Fail with `AppError::Locked` if database can not be read with the key given,
+/// encrypted database looks like a corrupted file without the right key
+pub fn check_unlocked(conn: &Connection) -> AppResult<()> {
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::NotADatabase => {
+            Err(AppError::Locked)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write a copy of database keyed by `passphrase`, an empty one writes a plain database
+pub fn export_rekeyed(conn: &Connection, target: &Path, passphrase: &str) -> AppResult<()> {
+    if target.exists() {
+        fs::remove_file(target)?;
+    }
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        (target.to_string_lossy(), passphrase),
+    )?;
+    let exported = conn.query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()));
+    conn.execute("DETACH DATABASE rekeyed", [])?;
+    exported?;
+    Ok(())
+}
+
+/// Replace database of library at `base` by file `source` as a whole,
+/// library must be closed so its WAL is merged and nothing is written meanwhile
+pub fn replace_database(base: &Path, source: &Path) -> AppResult<()> {
+    fs::copy(source, base.join(DATABASE_NAME))?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(base.join(format!("{}{}", DATABASE_NAME, suffix)));
+    }
+    Ok(())
+}
+
+/// Encrypt opened library with `passphrase`, change its passphrase if it is encrypted already,
+/// or decrypt it if `passphrase` is `None`. Content files are not encrypted.
+/// Library is closed while database file is replaced and opened again with new passphrase.
+/// Backups would still expose the database under the old key, so they are replaced
+/// by a single backup keyed by the new passphrase
+#[tauri::command]
+pub async fn set_library_passphrase(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    passphrase: Option<String>,
+) -> AppResult<PassphraseResult> {
+    if !cfg!(feature = "encryption") {
+        return Err(not_supported());
+    }
+    let passphrase = passphrase.filter(|passphrase| !passphrase.is_empty());
+    let mut guard = db_state.state.lock().await;
+    let path = {
+        let state = guard.as_ref().ok_or_else(library_not_opened)?;
+        let target = state.path.join(REKEYED_NAME);
+        export_rekeyed(&state.conn, &target, passphrase.as_deref().unwrap_or(""))?;
+        state.path.clone()
+    };
+
+    // closing connection merges WAL into the old file, which is replaced as a whole
+    *guard = None;
+    fs::rename(path.join(REKEYED_NAME), path.join(DATABASE_NAME))?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(path.join(format!("{}{}", DATABASE_NAME, suffix)));
+    }
+    let state = MemeDatabaseConnection::open_with_passphrase(path, passphrase.as_deref())?;
+    let result = replace_backups(&state);
+    *guard = Some(state);
+    result
+}
+
+/// Remove backups keyed by the old passphrase and take one keyed by the new one
+fn replace_backups(state: &MemeDatabaseConnection) -> AppResult<PassphraseResult> {
+    let removed_backups = remove_backups(&state.path)?;
+    let backup = create_backup(&state.conn, &state.path, state.passphrase.as_deref())?;
+    Ok(PassphraseResult {
+        removed_backups,
+        backup: backup.to_string_lossy().to_string(),
+    })
+}
@@ -0,0 +1,101 @@
+use serde::ser::SerializeStruct;
+
+use crate::db::search::SearchError;
+
+/// Error returned by commands, serialized as `{ code, message }`
+/// so frontend can branch on `code` and show `message` to user
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0} is not found")]
+    NotFound(String),
+    /// Request conflicts with current state, e.g. a name already in use
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("invalid search statement: {0}")]
+    InvalidQuery(#[from] SearchError),
+    #[error("failed to migrate database to version {version}: {message}")]
+    MigrationFailed { version: u32, message: String },
+    /// Database has been migrated by a newer build, opening it here could corrupt it
+    #[error("database version {version} is newer than version {supported} supported by this app")]
+    DatabaseTooNew { version: u32, supported: u32 },
+    /// Row does not match schema, e.g. a tag link whose tag is missing
+    #[error("library is corrupted: {0}")]
+    Corrupted(String),
+    /// Remote storage can not be reached or refused request
+    #[error("storage is unavailable: {0}")]
+    StorageUnavailable(String),
+    /// Library is encrypted, passphrase is missing or wrong
+    #[error("library is locked")]
+    Locked,
+    /// Request of local api comes without credentials
+    #[error("{0}")]
+    Unauthorized(String),
+    /// Credentials of request are wrong, or the operation is disabled
+    #[error("{0}")]
+    Forbidden(String),
+    #[error(transparent)]
+    Database(rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Errors not classified yet, most helpers still report errors as `String`
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::InvalidArgument(_) => "invalid_argument",
+            AppError::InvalidQuery(_) => "invalid_query",
+            AppError::MigrationFailed { .. } => "migration_failed",
+            AppError::DatabaseTooNew { .. } => "database_too_new",
+            AppError::Corrupted(_) => "corrupted",
+            AppError::StorageUnavailable(_) => "storage_unavailable",
+            AppError::Locked => "locked",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Database(_) => "database",
+            AppError::Io(_) => "io",
+            AppError::Other(_) => "other",
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("record".to_owned()),
+            rusqlite::Error::InvalidColumnType(..)
+            | rusqlite::Error::FromSqlConversionFailure(..)
+            | rusqlite::Error::IntegralValueOutOfRange(..) => AppError::Corrupted(e.to_string()),
+            e => AppError::Database(e),
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other(message.to_owned())
+    }
+}
+
+impl serde::Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("AppError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
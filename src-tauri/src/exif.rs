@@ -0,0 +1,260 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageOutputFormat};
+
+/// JPEG segments carrying EXIF/XMP (APP1) and IPTC (APP13)
+const JPEG_METADATA_MARKERS: &[u8] = &[0xE1, 0xED];
+
+/// PNG chunks carrying EXIF, textual data and modification time
+const PNG_METADATA_CHUNKS: &[&[u8]] = &[b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+const WEBP_METADATA_CHUNKS: &[&[u8]] = &[b"EXIF", b"XMP "];
+
+/// Flags of EXIF and XMP in `VP8X` chunk
+const WEBP_METADATA_FLAGS: u8 = 0x08 | 0x04;
+
+/// EXIF tag telling viewers how to rotate or flip image when displaying it
+const EXIF_ORIENTATION: u16 = 0x0112;
+
+/// Read orientation from payload of JPEG APP1 segment
+/// return None if it is not EXIF or has no orientation in its first IFD
+fn read_orientation(payload: &[u8]) -> Option<u16> {
+    let tiff = payload.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |pos: usize| -> Option<u16> {
+        let bytes = tiff.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let read_u32 = |pos: usize| -> Option<u32> {
+        let bytes = tiff.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+    let ifd = read_u32(4)? as usize;
+    let count = read_u16(ifd)? as usize;
+    // entries of 12 bytes: tag, type, count and value, a SHORT value is in its first 2 bytes
+    let entry = (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| read_u16(entry) == Some(EXIF_ORIENTATION))?;
+    read_u16(entry + 8)
+}
+
+/// JPEG APP1 segment of EXIF with orientation as its only tag
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut payload = b"Exif\0\0MM\0\x2a".to_vec();
+    // offset of the only IFD, right after TIFF header
+    payload.extend_from_slice(&8u32.to_be_bytes());
+    payload.extend_from_slice(&1u16.to_be_bytes());
+    payload.extend_from_slice(&EXIF_ORIENTATION.to_be_bytes());
+    // one SHORT, padded to 4 bytes
+    payload.extend_from_slice(&3u16.to_be_bytes());
+    payload.extend_from_slice(&1u32.to_be_bytes());
+    payload.extend_from_slice(&orientation.to_be_bytes());
+    payload.extend_from_slice(&[0, 0]);
+    // no next IFD
+    payload.extend_from_slice(&0u32.to_be_bytes());
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    segment.extend(payload);
+    segment
+}
+
+/// Drop metadata segments from JPEG without touching compressed image data,
+/// EXIF orientation is kept in a minimal segment so image is not displayed rotated
+/// return None if data is not a well-formed JPEG
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut result = data[..2].to_vec();
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        // padding before marker
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        // markers without payload
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            result.extend_from_slice(&data[pos..pos + 2]);
+            pos += 2;
+            continue;
+        }
+        // entropy-coded data follows start of scan, copy the rest as is
+        if marker == 0xDA || marker == 0xD9 {
+            result.extend_from_slice(&data[pos..]);
+            return Some(result);
+        }
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let end = pos + 2 + len;
+        if len < 2 || end > data.len() {
+            return None;
+        }
+        if !JPEG_METADATA_MARKERS.contains(&marker) {
+            result.extend_from_slice(&data[pos..end]);
+        } else if let Some(orientation) = read_orientation(&data[pos + 4..end]) {
+            // 1 is the default, which needs no tag
+            if orientation != 1 {
+                result.extend(orientation_segment(orientation));
+            }
+        }
+        pos = end;
+    }
+}
+
+/// Drop metadata chunks from PNG
+/// return None if data is not a well-formed PNG
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+    let mut result = SIGNATURE.to_vec();
+    let mut pos = SIGNATURE.len();
+    while pos < data.len() {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let ty = data.get(pos + 4..pos + 8)?;
+        // length, type, data and crc
+        let end = pos + 12 + len;
+        if end > data.len() {
+            return None;
+        }
+        if !PNG_METADATA_CHUNKS.contains(&ty) {
+            result.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+    Some(result)
+}
+
+/// Drop metadata chunks from WebP and clear their flags in `VP8X`
+/// return None if data is not a well-formed WebP
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+    let mut result = data[..12].to_vec();
+    let mut pos = 12;
+    while pos < data.len() {
+        let ty = data.get(pos..pos + 4)?;
+        let len = u32::from_le_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        // chunks are padded to even size
+        let end = pos + 8 + len + (len & 1);
+        if end > data.len() {
+            return None;
+        }
+        if ty == b"VP8X" && len > 0 {
+            let flags = result.len() + 8;
+            result.extend_from_slice(&data[pos..end]);
+            result[flags] &= !WEBP_METADATA_FLAGS;
+        } else if !WEBP_METADATA_CHUNKS.contains(&ty) {
+            result.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+    let riff_size = (result.len() - 8) as u32;
+    result[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(result)
+}
+
+/// Decode and encode image again, metadata is not kept by encoder
+fn reencode(data: &[u8], format: ImageFormat) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory_with_format(data, format).map_err(|e| e.to_string())?;
+    let mut result = Cursor::new(Vec::new());
+    img.write_to(&mut result, ImageOutputFormat::from(format))
+        .map_err(|e| e.to_string())?;
+    Ok(result.into_inner())
+}
+
+/// Remove EXIF (including GPS), XMP and other metadata from image
+/// Metadata is removed losslessly for JPEG, PNG and WebP,
+/// the image is re-encoded only if its structure can not be recognized.
+/// Formats which do not carry such metadata are returned as is
+pub fn strip_metadata(data: &[u8]) -> Result<Vec<u8>, String> {
+    let format = match image::guess_format(data) {
+        Ok(format) => format,
+        Err(_) => return Ok(data.to_vec()),
+    };
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::WebP => strip_webp(data),
+        ImageFormat::Tiff => None,
+        _ => return Ok(data.to_vec()),
+    };
+    match stripped {
+        Some(stripped) => Ok(stripped),
+        None => reencode(data, format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exif::{read_orientation, strip_metadata};
+
+    /// APP1 segment of little-endian EXIF with camera model and orientation
+    fn exif_segment(orientation: u16) -> Vec<u8> {
+        let mut payload = b"Exif\0\0II\x2a\0".to_vec();
+        payload.extend_from_slice(&8u32.to_le_bytes());
+        payload.extend_from_slice(&2u16.to_le_bytes());
+        // model, 4 ASCII bytes stored in the entry
+        payload.extend_from_slice(&0x0110u16.to_le_bytes());
+        payload.extend_from_slice(&2u16.to_le_bytes());
+        payload.extend_from_slice(&4u32.to_le_bytes());
+        payload.extend_from_slice(b"Cam\0");
+        payload.extend_from_slice(&0x0112u16.to_le_bytes());
+        payload.extend_from_slice(&3u16.to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&orientation.to_le_bytes());
+        payload.extend_from_slice(&[0, 0]);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend(payload);
+        segment
+    }
+
+    /// Comment segment, start of scan with some entropy-coded bytes and end of image
+    const BODY: &[u8] = &[
+        0xFF, 0xFE, 0x00, 0x04, b'h', b'i', 0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9,
+    ];
+
+    fn jpeg(orientation: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        data.extend(exif_segment(orientation));
+        data.extend_from_slice(BODY);
+        data
+    }
+
+    #[test]
+    fn test_strip_jpeg_keeps_orientation() {
+        let stripped = strip_metadata(&jpeg(6)).unwrap();
+        assert!(!stripped.windows(3).any(|x| x == b"Cam"));
+        // SOI followed by the minimal EXIF segment
+        assert_eq!(&stripped[2..4], &[0xFF, 0xE1]);
+        let len = u16::from_be_bytes([stripped[4], stripped[5]]) as usize;
+        assert_eq!(read_orientation(&stripped[6..4 + len]), Some(6));
+        assert!(stripped.ends_with(BODY));
+
+        // default orientation needs no EXIF at all
+        let stripped = strip_metadata(&jpeg(1)).unwrap();
+        assert!(!stripped.windows(4).any(|x| x == b"Exif"));
+    }
+}
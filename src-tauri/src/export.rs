@@ -0,0 +1,321 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use image::ImageOutputFormat;
+use rusqlite::Connection;
+use tauri::Window;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    animation::{decode_frames, encode_scaled_gif},
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    meme::query_meme_tags,
+    progress::{TaskProgress, TaskRegistry},
+    sidecar::{write_sidecar, Sidecar, SidecarFormat},
+    storage::{fetch_content, Storage},
+};
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportOptions {
+    /// Write metadata of each meme next to its file, e.g. `cat.png.json`
+    pub sidecar: Option<SidecarFormat>,
+}
+
+/// Characters not allowed in file names on common file systems
+pub fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() {
+        "meme".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Pick `name.ext`, or `name (n).ext` if it is taken by a file exported before or on disk
+fn unique_file_name(dir: &Path, name: &str, ext: &str, used: &mut HashSet<String>) -> PathBuf {
+    let mut file_name = format!("{}.{}", name, ext);
+    let mut n = 2;
+    while used.contains(&file_name.to_lowercase()) || dir.join(&file_name).exists() {
+        file_name = format!("{} ({}).{}", name, n, ext);
+        n += 1;
+    }
+    used.insert(file_name.to_lowercase());
+    dir.join(file_name)
+}
+
+/// Copy content of meme into `dir` named after the meme
+/// return path of the exported file
+pub fn export_meme(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+    id: i64,
+    dir: &Path,
+    options: &ExportOptions,
+    used: &mut HashSet<String>,
+) -> Result<PathBuf, String> {
+    let (ty, hash, mut sidecar): (String, String, Sidecar) = conn
+        .query_row(
+            "SELECT ty, hash, name, description, fav, rating FROM meme WHERE id = ?1",
+            [id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    Sidecar {
+                        name: row.get(2)?,
+                        description: row.get(3)?,
+                        tags: Vec::new(),
+                        fav: row.get(4)?,
+                        rating: row.get(5)?,
+                    },
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let ext = match hash.split_once('.') {
+        Some((_, ext)) => ext,
+        None if ty == "text" => "txt",
+        None => "bin",
+    };
+    let path = unique_file_name(dir, &sanitize_file_name(&sidecar.name), ext, used);
+    fs::copy(fetch_content(storage, base, &hash)?, &path).map_err(|e| e.to_string())?;
+
+    if let Some(format) = options.sidecar {
+        let mut sidecar_path = path.clone().into_os_string();
+        sidecar_path.push(".");
+        sidecar_path.push(format.extension());
+        sidecar.tags = query_meme_tags(conn, id)?;
+        write_sidecar(sidecar_path, &sidecar, format)?;
+    }
+    Ok(path)
+}
+
+/// Copy memes into `dir` with human readable file names, progress of `task` is
+/// reported after each meme. Return paths of exported files in the order of `ids`,
+/// only memes exported before cancelling are returned if it is cancelled
+#[tauri::command]
+pub async fn export_memes(
+    window: Window,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+    ids: Vec<i64>,
+    dir: String,
+    options: ExportOptions,
+) -> AppResult<Vec<String>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let dir = PathBuf::from(dir);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+
+    let progress = TaskProgress::start(window, &registry, task, ids.len())?;
+    let mut used = HashSet::new();
+    let mut result = Vec::new();
+    for id in ids {
+        if progress.is_cancelled() {
+            break;
+        }
+        let path = export_meme(
+            &state.conn,
+            state.storage.as_ref(),
+            &state.path,
+            id,
+            &dir,
+            &options,
+            &mut used,
+        )?;
+        result.push(path.to_string_lossy().to_string());
+        progress.report(result.len(), result.last().unwrap())?;
+    }
+    Ok(result)
+}
+
+/// Discord and Slack both accept custom emoji of 128px under 256KB
+const DEFAULT_EMOJI_SIZE: u32 = 128;
+const DEFAULT_EMOJI_MAX_BYTES: usize = 256 * 1024;
+/// Emoji is not shrunk below this size to fit in the byte limit
+const MIN_EMOJI_SIZE: u32 = 32;
+/// Longest emoji name allowed by Discord
+const MAX_EMOJI_NAME_LEN: usize = 32;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct EmojiPackOptions {
+    /// Emoji is scaled to fit in `size` x `size`
+    size: Option<u32>,
+    /// Emoji larger than this is scaled down further
+    max_bytes: Option<usize>,
+    /// Put all emoji into one zip archive instead of separate files
+    #[serde(default)]
+    zip: bool,
+}
+
+/// Emoji name made of lowercase ascii letters, digits and `_`, e.g. `Happy Cat!` to `happy_cat`.
+/// Return None if nothing is left of the name
+fn emoji_slug(name: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.truncate(MAX_EMOJI_NAME_LEN);
+    let slug = slug.trim_end_matches('_');
+    // platforms require at least 2 characters
+    if slug.len() < 2 {
+        None
+    } else {
+        Some(slug.to_owned())
+    }
+}
+
+/// Pick `slug.ext`, or `slug_n.ext` if it is taken by an emoji exported before
+/// or a file in `dir` if it is given
+fn unique_emoji_name(
+    dir: Option<&Path>,
+    slug: &str,
+    ext: &str,
+    used: &mut HashSet<String>,
+) -> String {
+    let mut file_name = format!("{}.{}", slug, ext);
+    let mut n = 2;
+    while used.contains(&file_name) || dir.map_or(false, |dir| dir.join(&file_name).exists()) {
+        let suffix = format!("_{}", n);
+        let stem = &slug[..slug.len().min(MAX_EMOJI_NAME_LEN - suffix.len())];
+        file_name = format!("{}{}.{}", stem, suffix, ext);
+        n += 1;
+    }
+    used.insert(file_name.clone());
+    file_name
+}
+
+/// Scale image at `path` to fit in `size`, then further until it is no larger than `max_bytes`.
+/// Animation is kept as gif if it fits, otherwise its first frame is used.
+/// Return encoded image and its extension
+fn encode_emoji(
+    path: &Path,
+    size: u32,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, &'static str), String> {
+    let min_size = MIN_EMOJI_SIZE.min(size);
+    if let Some(frames) = decode_frames(path) {
+        let mut size = size;
+        while size >= min_size {
+            let mut data = Vec::new();
+            encode_scaled_gif(frames.clone(), &mut data, size)?;
+            if data.len() <= max_bytes {
+                return Ok((data, "gif"));
+            }
+            size = size * 3 / 4;
+        }
+    }
+
+    let img = image::open(path).map_err(|e| e.to_string())?;
+    let mut size = size;
+    while size >= min_size {
+        let mut data = Cursor::new(Vec::new());
+        img.thumbnail(size, size)
+            .write_to(&mut data, ImageOutputFormat::Png)
+            .map_err(|e| e.to_string())?;
+        if data.get_ref().len() <= max_bytes {
+            return Ok((data.into_inner(), "png"));
+        }
+        size = size * 3 / 4;
+    }
+    Err(format!("{} can not be made smaller than {} bytes", path.display(), max_bytes))
+}
+
+/// Export image memes as custom emoji for chat platforms into `dir`,
+/// scaled to fit in size and byte limits of `options` and named by slug of their names.
+/// Memes which are not images are skipped. Progress of `task` is reported after each meme.
+/// Return paths of exported files, or path of the zip archive if `options.zip` is set
+#[tauri::command]
+pub async fn export_emoji_pack(
+    window: Window,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+    ids: Vec<i64>,
+    dir: String,
+    options: EmojiPackOptions,
+) -> AppResult<Vec<String>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let dir = PathBuf::from(dir);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+    let size = options.size.unwrap_or(DEFAULT_EMOJI_SIZE);
+    let max_bytes = options.max_bytes.unwrap_or(DEFAULT_EMOJI_MAX_BYTES);
+    let mut zip = if options.zip {
+        let path = unique_file_name(&dir, "emoji", "zip", &mut HashSet::new());
+        let file = File::create(&path).map_err(|e| e.to_string())?;
+        Some((path, ZipWriter::new(file)))
+    } else {
+        None
+    };
+    // png and gif are already compressed
+    let zip_options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let progress = TaskProgress::start(window, &registry, task, ids.len())?;
+    let mut used = HashSet::new();
+    let mut result = Vec::new();
+    for (idx, id) in ids.into_iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+        let (ty, hash, name): (String, String, String) = state
+            .conn
+            .query_row(
+                "SELECT ty, hash, name FROM meme WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        if ty == "image" {
+            let path = fetch_content(state.storage.as_ref(), &state.path, &hash)?;
+            let (data, ext) = encode_emoji(&path, size, max_bytes)?;
+            let slug = emoji_slug(&name).unwrap_or_else(|| format!("meme_{}", id));
+            match &mut zip {
+                Some((_, zip)) => {
+                    let file_name = unique_emoji_name(None, &slug, ext, &mut used);
+                    zip.start_file(file_name, zip_options)
+                        .map_err(|e| e.to_string())?;
+                    zip.write_all(&data).map_err(|e| e.to_string())?;
+                }
+                None => {
+                    let path = dir.join(unique_emoji_name(Some(&dir), &slug, ext, &mut used));
+                    fs::write(&path, data).map_err(|e| e.to_string())?;
+                    result.push(path.to_string_lossy().to_string());
+                }
+            }
+        }
+        progress.report(idx + 1, id)?;
+    }
+
+    if let Some((path, mut zip)) = zip {
+        zip.finish().map_err(|e| e.to_string())?;
+        result.push(path.to_string_lossy().to_string());
+    }
+    Ok(result)
+}
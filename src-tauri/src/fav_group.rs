@@ -0,0 +1,168 @@
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+};
+
+/// Group created by migration, `meme.fav` puts memes into it.
+/// `meme.fav` is kept as whether the meme is in any group, see migration v25
+const DEFAULT_GROUP_ID: i64 = 1;
+
+#[derive(Debug, serde::Serialize)]
+pub struct FavGroup {
+    id: i64,
+    name: String,
+    /// Number of memes in group, including those in trash
+    count: i64,
+}
+
+fn check_group_name(conn: &Connection, name: &str) -> AppResult<()> {
+    if name.is_empty() {
+        return Err(AppError::InvalidArgument("name of group is empty".to_owned()));
+    }
+    let existing: Option<i64> = conn
+        .query_row("SELECT id FROM fav_group WHERE name = ?1", [name], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    match existing {
+        Some(_) => Err(AppError::Conflict(format!("group {} already exists", name))),
+        None => Ok(()),
+    }
+}
+
+/// All favorite groups, the default one first
+#[tauri::command]
+pub async fn list_fav_groups(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<FavGroup>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state.conn.prepare(
+        "SELECT id, name, (SELECT COUNT(*) FROM fav_group_meme WHERE group_id = fav_group.id)
+          FROM fav_group ORDER BY id = ?1 DESC, create_time",
+    )?;
+    let result = query
+        .query_map([DEFAULT_GROUP_ID], |row| {
+            Ok(FavGroup {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<FavGroup>, Error>>()?;
+    Ok(result)
+}
+
+/// Return id of the new group
+#[tauri::command]
+pub async fn create_fav_group(
+    state: tauri::State<'_, MemeDatabaseState>,
+    name: String,
+) -> AppResult<i64> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let name = name.trim();
+    check_group_name(&state.conn, name)?;
+    state
+        .conn
+        .execute("INSERT INTO fav_group(name) VALUES (?1)", [name])?;
+    Ok(state.conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub async fn rename_fav_group(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    name: String,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let name = name.trim();
+    check_group_name(&state.conn, name)?;
+    let updated = state
+        .conn
+        .execute("UPDATE fav_group SET name = ?1 WHERE id = ?2", (name, id))?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("favorite group {}", id)));
+    }
+    Ok(())
+}
+
+/// Delete group, its memes stay favorite only if they are in another group.
+/// The default group can not be deleted
+#[tauri::command]
+pub async fn delete_fav_group(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    if id == DEFAULT_GROUP_ID {
+        return Err(AppError::InvalidArgument(
+            "default group can not be deleted".to_owned(),
+        ));
+    }
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    conn.execute("DELETE FROM fav_group WHERE id = ?1", [id])?;
+    conn.commit()?;
+    Ok(())
+}
+
+/// Put memes into group, memes already in it are ignored
+#[tauri::command]
+pub async fn add_to_fav_group(
+    state: tauri::State<'_, MemeDatabaseState>,
+    group_id: i64,
+    meme_ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    for meme_id in meme_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO fav_group_meme(group_id, meme_id) VALUES (?1, ?2)",
+            (group_id, meme_id),
+        )?;
+    }
+    conn.commit()?;
+    Ok(())
+}
+
+/// Take memes out of group, memes in no group are no longer favorite
+#[tauri::command]
+pub async fn remove_from_fav_group(
+    state: tauri::State<'_, MemeDatabaseState>,
+    group_id: i64,
+    meme_ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    for meme_id in meme_ids {
+        conn.execute(
+            "DELETE FROM fav_group_meme WHERE group_id = ?1 AND meme_id = ?2",
+            (group_id, meme_id),
+        )?;
+    }
+    conn.commit()?;
+    Ok(())
+}
+
+/// Ids of groups meme is in
+#[tauri::command]
+pub async fn get_meme_fav_groups(
+    state: tauri::State<'_, MemeDatabaseState>,
+    meme_id: i64,
+) -> AppResult<Vec<i64>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare("SELECT group_id FROM fav_group_meme WHERE meme_id = ?1 ORDER BY group_id")?;
+    let result = query
+        .query_map([meme_id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, Error>>()?;
+    Ok(result)
+}
@@ -3,6 +3,16 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use rusqlite::Connection;
+use tracing::warn;
+
+use crate::{
+    animation::preview_path,
+    normalize::{query_original, CONVERTED_EXTENSIONS},
+    storage::Storage,
+    thumbnail::thumbnail_path,
+};
+
 pub fn compute_path<P: AsRef<Path>>(base: P, hash: &str) -> PathBuf {
     if hash.len() <= 4 {
         return base.as_ref().join(hash);
@@ -17,9 +27,18 @@ pub fn copy_to_storage<P: AsRef<Path>, S: AsRef<Path>>(
     base: P,
     src: S,
 ) -> Result<String, std::io::Error> {
-    let ext = src.as_ref().extension().map(|x| x.to_string_lossy());
     let hash = sha256::try_digest(&src.as_ref())?;
-    let mut path = compute_path(base, &hash);
+    copy_hashed_to_storage(base, src, &hash)
+}
+
+/// Same as `copy_to_storage`, but the sha256 of `src` has been computed by caller
+pub fn copy_hashed_to_storage<P: AsRef<Path>, S: AsRef<Path>>(
+    base: P,
+    src: S,
+    hash: &str,
+) -> Result<String, std::io::Error> {
+    let ext = src.as_ref().extension().map(|x| x.to_string_lossy());
+    let mut path = compute_path(base, hash);
     if let Some(ext) = &ext {
         path.set_extension(ext.as_ref());
     }
@@ -33,31 +52,180 @@ pub fn copy_to_storage<P: AsRef<Path>, S: AsRef<Path>>(
     if let Some(ext) = &ext {
         Ok(format!("{}.{}", hash, ext.as_ref()))
     } else {
-        Ok(hash)
+        Ok(hash.to_owned())
     }
 }
 
-pub fn store_to_storage<P: AsRef<Path>>(
-    base: P,
-    content: &[u8],
-    ext: Option<&str>,
-) -> Result<String, std::io::Error> {
-    let hash = sha256::digest(content);
-    let mut path = compute_path(base, &hash);
-    if let Some(ext) = ext {
-        path.set_extension(ext);
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "webm", "mov", "mkv"];
+
+fn has_extension<P: AsRef<Path>>(path: P, extensions: &[&str]) -> bool {
+    path.as_ref()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .map_or(false, |ext| extensions.contains(&ext.as_str()))
+}
+
+/// Images in `CONVERTED_EXTENSIONS` are accepted as well, they are converted on import
+pub fn is_image_file<P: AsRef<Path>>(path: P) -> bool {
+    has_extension(&path, IMAGE_EXTENSIONS) || has_extension(&path, CONVERTED_EXTENSIONS)
+}
+
+pub fn is_video_file<P: AsRef<Path>>(path: P) -> bool {
+    has_extension(path, VIDEO_EXTENSIONS)
+}
+
+/// Meme type of a file judged by its extension
+/// return None if the file can not be imported as meme
+pub fn media_type<P: AsRef<Path>>(path: P) -> Option<&'static str> {
+    if is_image_file(&path) {
+        Some("image")
+    } else if is_video_file(&path) {
+        Some("video")
+    } else {
+        None
     }
+}
 
-    let path_parent = path.parent().unwrap();
-    if !path_parent.exists() {
-        fs::create_dir_all(path_parent)?;
+/// Collect all image and video files under `dir` recursively
+pub fn list_media<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut result = Vec::new();
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if media_type(&path).is_some() {
+                result.push(path);
+            }
+        }
     }
+    result.sort();
+    Ok(result)
+}
 
-    fs::write(path, content)?;
+fn is_shard_name(name: &str) -> bool {
+    name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
 
-    if let Some(ext) = &ext {
-        Ok(format!("{}.{}", hash, ext))
-    } else {
-        Ok(hash)
+/// Content files are named by sha256 of their content, optionally with an extension
+pub fn is_content_name(name: &str) -> bool {
+    let sha = name.split('.').next().unwrap_or(name);
+    sha.len() == 64 && sha.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Move content files lying directly under `base`, e.g. left by a copy of library in flat
+/// layout, into their sharded directories (see `compute_path`).
+/// A file already in its sharded place is kept and the flat copy is left untouched.
+/// return the number of moved files
+pub fn relocate_flat_content<P: AsRef<Path>>(base: P) -> Result<usize, std::io::Error> {
+    let mut moved = 0;
+    for file in fs::read_dir(&base)? {
+        let file = file?;
+        let name = file.file_name().to_string_lossy().to_string();
+        if !file.file_type()?.is_file() || !is_content_name(&name) {
+            continue;
+        }
+        let path = compute_path(&base, &name);
+        if path.exists() {
+            continue;
+        }
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::rename(file.path(), path)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Collect names of all content files in storage, see `compute_path` for the layout
+/// Thumbnails, database and other files outside the sharded directories are not included
+pub fn list_content_files<P: AsRef<Path>>(base: P) -> Result<Vec<String>, std::io::Error> {
+    let mut result = Vec::new();
+    for f1 in fs::read_dir(base)? {
+        let f1 = f1?;
+        if !f1.file_type()?.is_dir() || !is_shard_name(&f1.file_name().to_string_lossy()) {
+            continue;
+        }
+        for f2 in fs::read_dir(f1.path())? {
+            let f2 = f2?;
+            if !f2.file_type()?.is_dir() || !is_shard_name(&f2.file_name().to_string_lossy()) {
+                continue;
+            }
+            for file in fs::read_dir(f2.path())? {
+                let file = file?;
+                if file.file_type()?.is_file() {
+                    result.push(file.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Number of meme pages referencing the stored content `hash` (file name in storage)
+pub fn count_file_references(conn: &Connection, hash: &str) -> Result<i64, rusqlite::Error> {
+    conn.query_row("SELECT COUNT(*) FROM meme_file WHERE hash = ?1", [hash], |row| {
+        row.get(0)
+    })
+}
+
+/// Number of meme pages referencing the same sha256, no matter which extension it is stored with
+pub fn count_content_references(conn: &Connection, hash: &str) -> Result<i64, rusqlite::Error> {
+    let sha = hash.split('.').next().unwrap_or(hash);
+    conn.query_row(
+        "SELECT COUNT(*) FROM meme_file WHERE hash = ?1 OR hash LIKE ?1 || '.%'",
+        [sha],
+        |row| row.get(0),
+    )
+}
+
+/// Remove content file and files derived from it once they are no longer referenced,
+/// should be called after the meme row has been deleted
+/// return whether the content file is removed, from both local library and `storage`
+pub fn release_content<P: AsRef<Path>>(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: P,
+    hash: &str,
+) -> Result<bool, String> {
+    if count_content_references(conn, hash).map_err(|e| e.to_string())? == 0 {
+        for derived in [thumbnail_path(&base, hash), preview_path(&base, hash)] {
+            if derived.exists() {
+                fs::remove_file(derived).map_err(|e| e.to_string())?;
+            }
+        }
+        if let Some(original) = query_original(&base, hash) {
+            fs::remove_file(original).map_err(|e| e.to_string())?;
+        }
+    }
+    if count_file_references(conn, hash).map_err(|e| e.to_string())? > 0 {
+        return Ok(false);
+    }
+    let path = compute_path(&base, hash);
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    storage.delete(hash)?;
+    conn.execute("DELETE FROM content_alias WHERE hash = ?1", [hash])
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Release content no longer referenced by a committed transaction, see `release_content`.
+/// Files must outlive the transaction, or a failed commit would leave rows without content.
+/// Failures are only logged since rows are gone already, `gc_library` cleans up leftovers
+pub fn release_contents<P: AsRef<Path>>(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: P,
+    hashes: &[String],
+) {
+    for hash in hashes {
+        if let Err(e) = release_content(conn, storage, &base, hash) {
+            warn!(hash = %hash, error = %e, "failed to release content");
+        }
     }
 }
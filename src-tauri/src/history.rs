@@ -0,0 +1,118 @@
+use rusqlite::{Connection, Error};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    journal::{query_meme_state, restore_meme_state, JournalRecorder, MemeState},
+};
+
+#[derive(Debug, serde::Serialize)]
+pub struct MemeHistoryEntry {
+    id: i64,
+    /// Field of `MemeState`
+    field: String,
+    old: serde_json::Value,
+    new: serde_json::Value,
+    time: String,
+}
+
+/// Record every field of meme changed from `before` to `after`
+pub fn record_meme_history(
+    conn: &Connection,
+    meme_id: i64,
+    before: &MemeState,
+    after: &MemeState,
+) -> Result<(), String> {
+    let before = serde_json::to_value(before).map_err(|e| e.to_string())?;
+    let after = serde_json::to_value(after).map_err(|e| e.to_string())?;
+    for (field, new) in after.as_object().unwrap() {
+        let old = &before[field];
+        if old == new {
+            continue;
+        }
+        conn.execute(
+            "INSERT INTO meme_history(meme_id, field, old, new) VALUES (?1, ?2, ?3, ?4)",
+            (meme_id, field, old.to_string(), new.to_string()),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Changes of meme, newest first
+pub fn query_meme_history(conn: &Connection, id: i64) -> Result<Vec<MemeHistoryEntry>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT id, field, old, new, time FROM meme_history
+              WHERE meme_id = ?1 ORDER BY id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map([id], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String, String, String, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(id, field, old, new, time)| {
+            Ok(MemeHistoryEntry {
+                id,
+                field,
+                old: serde_json::from_str(&old).map_err(|e| e.to_string())?,
+                new: serde_json::from_str(&new).map_err(|e| e.to_string())?,
+                time,
+            })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn get_meme_history(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<Vec<MemeHistoryEntry>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_meme_history(&state.conn, id)?)
+}
+
+/// Set field changed by history entry `id` back to its value before the change,
+/// other fields are kept. Reverting is recorded in journal and history as well
+#[tauri::command]
+pub async fn revert_meme_field(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let (meme_id, field, old): (i64, String, String) = conn
+        .query_row(
+            "SELECT meme_id, field, old FROM meme_history WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let current = query_meme_state(&conn, meme_id)?
+        .ok_or_else(|| format!("meme {} does not exist", meme_id))?;
+    let mut reverted = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    reverted[field] = serde_json::from_str(&old).map_err(|e| e.to_string())?;
+    let reverted: MemeState = serde_json::from_value(reverted).map_err(|e| e.to_string())?;
+
+    let mut journal = JournalRecorder::new("Revert field");
+    journal.capture(&conn, meme_id)?;
+    restore_meme_state(&conn, meme_id, &reverted)?;
+    journal.finish(&conn)?;
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
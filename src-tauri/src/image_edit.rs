@@ -0,0 +1,242 @@
+use std::{fs, io::Cursor};
+
+use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use rusttype::{Font, Scale};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    download::import_data,
+    error::{AppError, AppResult},
+    meme::{query_meme_tags, AddMemeResult, NewMeme},
+    relation::{link_meme_relation, RelationKind},
+    storage::fetch_content,
+};
+
+/// Impact font shipped with Windows and macOS, or installed by msttcorefonts on Linux
+const IMPACT_FONT_PATHS: &[&str] = &[
+    "C:\\Windows\\Fonts\\impact.ttf",
+    "/System/Library/Fonts/Supplemental/Impact.ttf",
+    "/Library/Fonts/Impact.ttf",
+    "/usr/share/fonts/truetype/msttcorefonts/Impact.ttf",
+    "/usr/share/fonts/TTF/impact.ttf",
+];
+/// Caption is at most this part of image height per line
+const CAPTION_MAX_HEIGHT_RATIO: f32 = 0.15;
+/// Space between caption and edge of image, in part of image height
+const CAPTION_MARGIN_RATIO: f32 = 0.03;
+
+/// One step of editing, applied in order
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum EditOperation {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Clockwise, only multiples of 90 are supported
+    Rotate { degrees: u32 },
+    /// White text with black outline at top and bottom, lines are separated by `\n`
+    Caption {
+        top: Option<String>,
+        bottom: Option<String>,
+        /// Path of font file, Impact installed in system is used if it is not given
+        font: Option<String>,
+    },
+}
+
+pub fn load_font(path: Option<&str>) -> AppResult<Font<'static>> {
+    let data = match path {
+        Some(path) => fs::read(path)?,
+        None => IMPACT_FONT_PATHS
+            .iter()
+            .find_map(|path| fs::read(path).ok())
+            .ok_or_else(|| {
+                AppError::NotFound("Impact font is not installed, choose a font file".to_owned())
+            })?,
+    };
+    Font::try_from_vec(data)
+        .ok_or_else(|| AppError::InvalidArgument("invalid font file".to_owned()))
+}
+
+/// Largest scale at which every line fits in width of image and the height limit
+fn caption_scale(font: &Font, lines: &[&str], width: u32, height: u32) -> Scale {
+    let max_width = width as f32 * 0.95;
+    let mut size = height as f32 * CAPTION_MAX_HEIGHT_RATIO;
+    while size > 8.0 {
+        let scale = Scale::uniform(size);
+        let fits = lines
+            .iter()
+            .all(|line| text_size(scale, font, line).0 as f32 <= max_width);
+        if fits {
+            break;
+        }
+        size *= 0.9;
+    }
+    Scale::uniform(size.max(8.0))
+}
+
+/// Draw white lines of text with black outline, starting at `y`,
+/// centered horizontally in the column of `width` starting at `x`
+pub fn draw_lines<S: AsRef<str>>(
+    img: &mut RgbaImage,
+    font: &Font,
+    scale: Scale,
+    lines: &[S],
+    (x, width): (i32, i32),
+    y: i32,
+) {
+    let outline = (scale.y / 16.0).ceil().max(1.0) as i32;
+    let line_height = scale.y.ceil() as i32;
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let x = x + (width - text_size(scale, font, line).0) / 2;
+        let y = y + idx as i32 * line_height;
+        for dx in -outline..=outline {
+            for dy in -outline..=outline {
+                if dx != 0 || dy != 0 {
+                    draw_text_mut(img, Rgba([0, 0, 0, 255]), x + dx, y + dy, scale, font, line);
+                }
+            }
+        }
+        draw_text_mut(img, Rgba([255, 255, 255, 255]), x, y, scale, font, line);
+    }
+}
+
+/// Overlay meme style captions, text is upper-cased like classic image macros
+fn draw_caption(
+    img: DynamicImage,
+    top: Option<&str>,
+    bottom: Option<&str>,
+    font: &Font,
+) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+    let margin = (height as f32 * CAPTION_MARGIN_RATIO) as i32;
+    let top = top.map(|x| x.to_uppercase()).unwrap_or_default();
+    let bottom = bottom.map(|x| x.to_uppercase()).unwrap_or_default();
+    let top = top.lines().filter(|x| !x.trim().is_empty()).collect::<Vec<&str>>();
+    let bottom = bottom.lines().filter(|x| !x.trim().is_empty()).collect::<Vec<&str>>();
+
+    if !top.is_empty() {
+        let scale = caption_scale(font, &top, width, height);
+        draw_lines(&mut img, font, scale, &top, (0, width as i32), margin);
+    }
+    if !bottom.is_empty() {
+        let scale = caption_scale(font, &bottom, width, height);
+        let y = height as i32 - margin - bottom.len() as i32 * scale.y.ceil() as i32;
+        draw_lines(&mut img, font, scale, &bottom, (0, width as i32), y);
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+fn apply_operation(img: DynamicImage, operation: &EditOperation) -> AppResult<DynamicImage> {
+    match operation {
+        EditOperation::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            let in_bounds = *width > 0
+                && *height > 0
+                && x.saturating_add(*width) <= img.width()
+                && y.saturating_add(*height) <= img.height();
+            if !in_bounds {
+                return Err(AppError::InvalidArgument(
+                    "crop area is out of image".to_owned(),
+                ));
+            }
+            Ok(img.crop_imm(*x, *y, *width, *height))
+        }
+        EditOperation::Rotate { degrees } => match degrees % 360 {
+            0 => Ok(img),
+            90 => Ok(img.rotate90()),
+            180 => Ok(img.rotate180()),
+            270 => Ok(img.rotate270()),
+            _ => Err(AppError::InvalidArgument(format!(
+                "can not rotate by {} degrees",
+                degrees
+            ))),
+        },
+        EditOperation::Caption { top, bottom, font } => {
+            let font = load_font(font.as_deref())?;
+            Ok(draw_caption(img, top.as_deref(), bottom.as_deref(), &font))
+        }
+    }
+}
+
+/// Decode content of image meme `id`, only the first frame of animation is kept
+pub fn load_meme_image(state: &MemeDatabaseConnection, id: i64) -> AppResult<DynamicImage> {
+    let (ty, hash): (String, String) = state.conn.query_row(
+        "SELECT ty, hash FROM meme WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if ty != "image" {
+        return Err(AppError::InvalidArgument(format!("meme {} is not an image", id)));
+    }
+    let path = fetch_content(state.storage.as_ref(), &state.path, &hash)?;
+    Ok(image::open(path).map_err(|e| e.to_string())?)
+}
+
+/// Save `img` as a new png meme variant of meme `id`, with its description and tags.
+/// It is named `name`, or after the original if not given
+pub fn save_derived_meme(
+    state: &mut MemeDatabaseConnection,
+    id: i64,
+    img: &DynamicImage,
+    name: Option<String>,
+    kind: RelationKind,
+) -> AppResult<AddMemeResult> {
+    let (original_name, description, pkg_id): (String, Option<String>, i64) =
+        state.conn.query_row(
+            "SELECT name, description, pkg_id FROM meme WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+    let mut data = Cursor::new(Vec::new());
+    img.write_to(&mut data, ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+
+    let meme = NewMeme {
+        name: name.unwrap_or(original_name),
+        description,
+        ty: String::new(),
+        content: String::new(),
+        fav: false,
+        tags: query_meme_tags(&state.conn, id)?,
+        pkg_id,
+        strip_exif: false,
+        source_url: None,
+        author: None,
+        origin: None,
+        derived_from: Some(id),
+    };
+    let result = import_data(state, data.get_ref(), "png", meme)?;
+    if let AddMemeResult::Added(new_id) = result {
+        link_meme_relation(&state.conn, id, new_id, kind)?;
+    }
+    Ok(result)
+}
+
+/// Apply `operations` to image meme `id` and save the result as a new png meme
+/// with the same name, description and tags, linked to the original by `derived_from`.
+/// Only the first frame of animated image is edited
+#[tauri::command]
+pub async fn edit_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    operations: Vec<EditOperation>,
+) -> AppResult<AddMemeResult> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let mut img = load_meme_image(state, id)?;
+    for operation in &operations {
+        img = apply_operation(img, operation)?;
+    }
+    save_derived_meme(state, id, &img, None, RelationKind::Edit)
+}
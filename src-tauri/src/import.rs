@@ -0,0 +1,291 @@
+use std::{fs, path::Path};
+
+use rusqlite::Connection;
+use tauri::Window;
+use tracing::warn;
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    exif::strip_metadata,
+    file::{list_media, media_type},
+    meme::{
+        insert_meme, insert_meme_tag, make_tag, process_content, query_meme_by_content_hash,
+        AddMemeResult, Tag,
+    },
+    normalize::{self, convert_image, needs_conversion, CanonicalFormat},
+    progress::{TaskProgress, TaskRegistry},
+    sidecar::{apply_sidecar, find_sidecar, read_sidecar, sidecar_tag_id},
+    staging::{publish_content, settle_staging, stage_bytes, stage_file},
+    storage::{store_content, Storage},
+};
+
+/// Progress item of `import_directory`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImportedFile {
+    file: String,
+    /// Id of existing meme if the file is skipped as a duplicate
+    duplicate: Option<i64>,
+}
+
+/// A file about to be put into storage
+/// If metadata is stripped or format is converted, the resulting content is kept in memory
+/// and `hash` is computed from it, so duplicates are detected after processing
+pub struct PendingFile<'a> {
+    path: &'a Path,
+    processed: Option<Vec<u8>>,
+    /// Extension content is stored with, differs from the file if it is converted
+    ext: Option<String>,
+    pub hash: String,
+}
+
+impl<'a> PendingFile<'a> {
+    /// Images of a format in `CONVERTED_EXTENSIONS` are converted to `format`
+    pub fn open(path: &'a Path, strip_exif: bool, format: CanonicalFormat) -> Result<Self, String> {
+        let ext = path.extension().map(|x| x.to_string_lossy().to_string());
+        if needs_conversion(path) {
+            let data = fs::read(path).map_err(|e| e.to_string())?;
+            // converted image never carries metadata of the original
+            let data = convert_image(path, &data, format)?;
+            Ok(PendingFile {
+                path,
+                hash: sha256::digest(data.as_slice()),
+                processed: Some(data),
+                ext: Some(format.extension().to_owned()),
+            })
+        } else if strip_exif {
+            let data = fs::read(path).map_err(|e| e.to_string())?;
+            let data = strip_metadata(&data)?;
+            Ok(PendingFile {
+                path,
+                hash: sha256::digest(data.as_slice()),
+                processed: Some(data),
+                ext,
+            })
+        } else {
+            Ok(PendingFile {
+                path,
+                hash: sha256::try_digest(path).map_err(|e| e.to_string())?,
+                processed: None,
+                ext,
+            })
+        }
+    }
+
+    /// Name of content in storage, its hash followed by extension
+    fn content_name(&self) -> String {
+        match &self.ext {
+            Some(ext) => format!("{}.{}", self.hash, ext),
+            None => self.hash.clone(),
+        }
+    }
+
+    /// Put file into storage through staging, return the stored content name.
+    /// Caller calls `settle_staging` once the transaction inserting the meme has ended.
+    /// The original of a converted file is kept next to it if `keep_original` is set
+    pub fn store<P: AsRef<Path>>(&self, base: P, keep_original: bool) -> Result<String, String> {
+        let base = base.as_ref();
+        let content = self.content_name();
+        match &self.processed {
+            Some(data) => stage_bytes(base, &content, data),
+            None => stage_file(base, &content, self.path),
+        }
+        .and_then(|_| publish_content(base, &content))
+        .map_err(|e| e.to_string())?;
+        if keep_original && needs_conversion(self.path) {
+            normalize::keep_original(&base, &content, self.path)?;
+        }
+        Ok(content)
+    }
+}
+
+/// Copy an image or video file into storage and insert it as meme named after the file
+/// Nothing is changed if its content is already in library.
+/// EXIF and other metadata of image is removed if `strip_exif` is set
+pub fn import_media_file<P: AsRef<Path>>(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: P,
+    file: &Path,
+    ty: &str,
+    tag_id: &[i64],
+    pkg_id: i64,
+    strip_exif: bool,
+) -> Result<AddMemeResult, String> {
+    let settings = load_settings(conn)?;
+    let pending = PendingFile::open(file, strip_exif && ty == "image", settings.import_format)?;
+    if let Some(id) = query_meme_by_content_hash(conn, &pending.hash)? {
+        return Ok(AddMemeResult::Duplicate(id));
+    }
+
+    let content = pending.store(&base, settings.keep_original_format)?;
+    store_content(storage, base.as_ref(), &content)?;
+    let name = file
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let meme_id = insert_meme(conn, &name, None, ty, &content, false, pkg_id)?;
+    process_content(conn, &base, meme_id, ty, &content)?;
+    for tid in tag_id {
+        insert_meme_tag(conn, meme_id, *tid)?;
+    }
+    Ok(AddMemeResult::Added(meme_id))
+}
+
+/// Import every image and video under `dir` as a meme
+/// If `use_sidecar` is set, name, description and tags are read from
+/// sidecar next to each file (see `find_sidecar`) when it exists.
+/// All records are inserted in a single transaction, progress of `task` is reported
+/// after each file. If it is cancelled, files imported so far are kept.
+/// Files whose content is already in library are skipped,
+/// return the number of memes actually added
+#[tauri::command]
+pub async fn import_directory(
+    window: Window,
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+    dir: String,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+    strip_exif: bool,
+    use_sidecar: bool,
+) -> AppResult<usize> {
+    let files = list_media(&dir).map_err(|e| e.to_string())?;
+    let progress = TaskProgress::start(window, &registry, task, files.len())?;
+
+    let mut guard = db_state.state.lock().await;
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let tag_id = tags
+        .iter()
+        .map(|t| make_tag(&conn, &t.key, &t.value))
+        .collect::<Result<Vec<i64>, String>>()?;
+
+    let mut added = 0;
+    for (idx, file) in files.iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+        let ty = media_type(file).unwrap();
+        let sidecar = match find_sidecar(file).filter(|_| use_sidecar) {
+            Some(path) => Some(read_sidecar(&path)?),
+            None => None,
+        };
+        let file_tag_id = match &sidecar {
+            Some(sidecar) => sidecar_tag_id(&conn, sidecar, &tag_id)?,
+            None => tag_id.clone(),
+        };
+        let duplicate = match import_media_file(
+            &conn,
+            state.storage.as_ref(),
+            &state.path,
+            file,
+            ty,
+            &file_tag_id,
+            pkg_id,
+            strip_exif,
+        )? {
+            AddMemeResult::Added(id) => {
+                if let Some(sidecar) = &sidecar {
+                    apply_sidecar(&conn, id, sidecar)?;
+                }
+                added += 1;
+                None
+            }
+            AddMemeResult::Duplicate(id) => Some(id),
+        };
+
+        progress.report(
+            idx + 1,
+            ImportedFile {
+                file: file.to_string_lossy().to_string(),
+                duplicate,
+            },
+        )?;
+    }
+
+    conn.commit().map_err(|e| e.to_string())?;
+    if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+        warn!(error = %e, "failed to settle staged imports");
+    }
+    Ok(added)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum FileImportStatus {
+    Added { id: i64 },
+    Duplicate { id: i64 },
+    /// Neither an image nor a video file
+    Skipped,
+    Failed { error: String },
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct FileImportResult {
+    path: String,
+    #[serde(flatten)]
+    status: FileImportStatus,
+}
+
+/// Import a list of files (e.g. dropped into window)
+/// Each file is imported in its own transaction, so a failed file does not affect others
+#[tauri::command]
+pub async fn import_files(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    paths: Vec<String>,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+    strip_exif: bool,
+) -> AppResult<Vec<FileImportResult>> {
+    let mut guard = db_state.state.lock().await;
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let mut result = Vec::new();
+    for path in paths {
+        let file = Path::new(&path);
+        let ty = media_type(file).filter(|_| file.is_file());
+        let status = if let Some(ty) = ty {
+            let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+            let imported = tags
+                .iter()
+                .map(|t| make_tag(&conn, &t.key, &t.value))
+                .collect::<Result<Vec<i64>, String>>()
+                .and_then(|tag_id| {
+                    import_media_file(
+                        &conn,
+                        state.storage.as_ref(),
+                        &state.path,
+                        file,
+                        ty,
+                        &tag_id,
+                        pkg_id,
+                        strip_exif,
+                    )
+                });
+            match imported {
+                Ok(AddMemeResult::Added(id)) => {
+                    conn.commit().map_err(|e| e.to_string())?;
+                    FileImportStatus::Added { id }
+                }
+                Ok(AddMemeResult::Duplicate(id)) => FileImportStatus::Duplicate { id },
+                Err(error) => {
+                    warn!(path = %path, %error, "failed to import file");
+                    FileImportStatus::Failed { error }
+                }
+            }
+        } else {
+            FileImportStatus::Skipped
+        };
+        if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+            warn!(error = %e, "failed to settle staged imports");
+        }
+        result.push(FileImportResult { path, status });
+    }
+    Ok(result)
+}
@@ -0,0 +1,498 @@
+use std::{path::Path, time::Duration};
+
+use rusqlite::{Connection, Error, OptionalExtension};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use tracing::{error, warn};
+
+use crate::{
+    autotag::{pick_auto_tags, save_auto_tags},
+    compress::{encode_content, replace_content},
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    dedup::{compute_phash, save_meme_phash},
+    error::AppResult,
+    file::release_contents,
+    library::{digest_mismatch, record_check},
+    maintenance::run_maintenance,
+    ocr::{run_tesseract, save_meme_text, DEFAULT_LANG},
+    storage::{fetch_content, read_config},
+    sync::sync,
+    thumbnail::generate_thumbnail,
+    video::generate_video_thumbnail,
+};
+
+/// A failed job is queued again until it has been run this many times
+const MAX_ATTEMPTS: i64 = 3;
+/// Worker checks queue at least this often, in case it is not woken up
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Emitted whenever a job starts or finishes
+const JOB_EVENT: &str = "job_status";
+
+/// Task run in background by worker, stored in `job` table as JSON
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Job {
+    Ocr { meme_id: i64, lang: Option<String> },
+    Phash { meme_id: i64 },
+    Thumbnail { meme_id: i64 },
+    AutoTag { meme_id: i64 },
+    Sync { folder: String },
+    /// Re-encode large PNG content `hash`, see `encode_content`
+    Compress { hash: String },
+    /// Vacuum and analyze database, see `run_maintenance`
+    Maintain,
+    /// Check sha256 of content `hash` served while `verify_on_read` is enabled
+    Verify { hash: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Failed,
+    Done,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Failed => "failed",
+            JobStatus::Done => "done",
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "failed" => Ok(JobStatus::Failed),
+            "done" => Ok(JobStatus::Done),
+            _ => Err(format!("unknown job status {}", text)),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JobInfo {
+    id: i64,
+    job: Job,
+    status: JobStatus,
+    attempts: i64,
+    error: Option<String>,
+    update_time: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct JobStatusEvent {
+    id: i64,
+    status: JobStatus,
+    error: Option<String>,
+}
+
+/// Wakes worker up when a job is queued
+#[derive(Default)]
+pub struct JobQueue {
+    notify: Notify,
+}
+
+impl JobQueue {
+    /// Let worker know jobs have been queued
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+pub fn enqueue_job(conn: &Connection, job: &Job) -> Result<i64, String> {
+    let job = serde_json::to_string(job).map_err(|e| e.to_string())?;
+    conn.execute("INSERT INTO job(job) VALUES (?1)", [job])
+        .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Jobs left running when app exits are queued again, called on opening library
+pub fn requeue_interrupted_jobs(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "UPDATE job SET status = 'queued' WHERE status = 'running'",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn set_job_status(
+    conn: &Connection,
+    id: i64,
+    status: JobStatus,
+    error: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE job SET status = ?1, error = ?2, update_time = CURRENT_TIMESTAMP WHERE id = ?3",
+        (status.as_str(), error, id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Take the next queued job and mark it running,
+/// jobs retried fewer times go first so a failing job does not hold up the queue
+fn start_next_job(conn: &Connection) -> Result<Option<(i64, Job)>, String> {
+    let next: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, job FROM job WHERE status = 'queued' ORDER BY attempts, id LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (id, job) = match next {
+        Some(next) => next,
+        None => return Ok(None),
+    };
+    conn.execute(
+        "UPDATE job SET status = 'running', attempts = attempts + 1,
+          update_time = CURRENT_TIMESTAMP WHERE id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Some((id, parse_json(&job)?)))
+}
+
+fn query_meme_content(conn: &Connection, meme_id: i64) -> Result<(String, String), String> {
+    conn.query_row(
+        "SELECT ty, hash FROM meme WHERE id = ?1",
+        [meme_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn query_image_content(conn: &Connection, meme_id: i64) -> Result<String, String> {
+    match query_meme_content(conn, meme_id)? {
+        (ty, hash) if ty == "image" => Ok(hash),
+        _ => Err(format!("meme {} is not an image", meme_id)),
+    }
+}
+
+/// Whether meme still has content `hash`,
+/// it may be edited or deleted while its job runs without holding the database
+fn has_content(conn: &Connection, meme_id: i64, hash: &str) -> Result<bool, String> {
+    let current: Option<String> = conn
+        .query_row("SELECT hash FROM meme WHERE id = ?1", [meme_id], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(current.as_deref() == Some(hash))
+}
+
+/// Writes of a job, run once the database is locked again
+type Finish = Box<dyn FnOnce(&mut MemeDatabaseConnection) -> Result<(), String> + Send>;
+/// Slow part of a job, run on a blocking thread without holding the database
+type Work = Box<dyn FnOnce() -> Result<Finish, String> + Send>;
+
+fn work<F>(f: F) -> Work
+where
+    F: FnOnce() -> Result<Finish, String> + Send + 'static,
+{
+    Box::new(f)
+}
+
+fn finish<F>(f: F) -> Result<Finish, String>
+where
+    F: FnOnce(&mut MemeDatabaseConnection) -> Result<(), String> + Send + 'static,
+{
+    Ok(Box::new(f))
+}
+
+/// Read inputs of `job` while the database is locked and return its work.
+/// Storage is opened again from config, since the one of library can not leave the lock
+fn prepare_job(state: &MemeDatabaseConnection, job: &Job) -> Result<Work, String> {
+    let base = state.path.clone();
+    let storage = read_config(&base)?.open();
+    Ok(match job.clone() {
+        Job::Ocr { meme_id, lang } => {
+            let hash = query_image_content(&state.conn, meme_id)?;
+            work(move || {
+                let path = fetch_content(storage.as_ref(), &base, &hash)?;
+                let text = run_tesseract(path, lang.as_deref().unwrap_or(DEFAULT_LANG))?;
+                finish(move |state| {
+                    if has_content(&state.conn, meme_id, &hash)? {
+                        save_meme_text(&state.conn, meme_id, &text)?;
+                    }
+                    Ok(())
+                })
+            })
+        }
+        Job::Phash { meme_id } => {
+            let (_, hash) = query_meme_content(&state.conn, meme_id)?;
+            work(move || {
+                let phash = compute_phash(fetch_content(storage.as_ref(), &base, &hash)?);
+                finish(move |state| {
+                    if has_content(&state.conn, meme_id, &hash)? {
+                        save_meme_phash(&state.conn, meme_id, phash)?;
+                    }
+                    Ok(())
+                })
+            })
+        }
+        Job::Thumbnail { meme_id } => {
+            let (ty, hash) = query_meme_content(&state.conn, meme_id)?;
+            let size = load_settings(&state.conn)?.thumbnail_size;
+            work(move || {
+                fetch_content(storage.as_ref(), &base, &hash)?;
+                match ty.as_str() {
+                    "video" => generate_video_thumbnail(&base, &hash, size)?,
+                    _ => generate_thumbnail(&base, &hash, size)?,
+                };
+                finish(|_| Ok(()))
+            })
+        }
+        Job::AutoTag { meme_id } => {
+            let hash = query_image_content(&state.conn, meme_id)?;
+            let settings = load_settings(&state.conn)?;
+            let tagger = settings
+                .auto_tagger
+                .ok_or_else(|| "auto-tagger is not configured".to_owned())?;
+            let threshold = settings.auto_tag_threshold;
+            work(move || {
+                let path = fetch_content(storage.as_ref(), &base, &hash)?;
+                let names = pick_auto_tags(tagger.open().classify(&path)?, threshold);
+                finish(move |state| {
+                    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+                    if has_content(&conn, meme_id, &hash)? {
+                        save_auto_tags(&conn, meme_id, &names)?;
+                    }
+                    conn.commit().map_err(|e| e.to_string())
+                })
+            })
+        }
+        Job::Sync { folder } => {
+            // sync reads and writes database all along, so it runs as a whole once locked
+            work(move || {
+                finish(move |state| {
+                    let storage = state.storage.as_ref();
+                    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+                    let (_, released) = sync(&conn, storage, &state.path, Path::new(&folder))?;
+                    conn.commit().map_err(|e| e.to_string())?;
+                    release_contents(&state.conn, storage, &state.path, &released);
+                    Ok(())
+                })
+            })
+        }
+        Job::Compress { hash } => {
+            let settings = load_settings(&state.conn)?;
+            let config = settings
+                .compression
+                .ok_or_else(|| "compression is disabled".to_owned())?;
+            let size = settings.thumbnail_size;
+            work(move || {
+                let compressed = encode_content(storage.as_ref(), &base, &hash, &config, size)?;
+                finish(move |state| {
+                    let compressed = match compressed {
+                        Some(compressed) => compressed,
+                        None => return Ok(()),
+                    };
+                    let storage = state.storage.as_ref();
+                    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+                    let replaced = replace_content(&conn, &state.path, &hash, &compressed)?;
+                    conn.commit().map_err(|e| e.to_string())?;
+                    // content is deleted while it is compressed, the result is not used either
+                    let released = if replaced { hash } else { compressed.hash };
+                    release_contents(&state.conn, storage, &state.path, &[released]);
+                    Ok(())
+                })
+            })
+        }
+        Job::Maintain => work(|| finish(|state| run_maintenance(&state.conn))),
+        Job::Verify { hash } => work(move || {
+            // content only in remote storage is checked when it is served next time
+            let corrupted = digest_mismatch(&base, &hash)?;
+            finish(move |state| match corrupted {
+                Some(corrupted) => record_check(&state.conn, &hash, corrupted),
+                None => Ok(()),
+            })
+        }),
+    })
+}
+
+/// Run one queued job of opened library, return false if there is nothing to run.
+/// The database is locked to start the job and to write its result,
+/// but not while the job itself runs
+async fn run_next_job(app: &AppHandle) -> Result<bool, String> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let (path, id, work) = {
+        let mut guard = db_state.state.lock().await;
+        let state = match guard.as_mut() {
+            Some(state) => state,
+            None => return Ok(false),
+        };
+        let (id, job) = match start_next_job(&state.conn)? {
+            Some(next) => next,
+            None => return Ok(false),
+        };
+        (state.path.clone(), id, prepare_job(state, &job))
+    };
+    emit_status(app, id, JobStatus::Running, None);
+
+    let finish = match work {
+        Ok(work) => tauri::async_runtime::spawn_blocking(work)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|x| x),
+        Err(e) => Err(e),
+    };
+
+    let mut guard = db_state.state.lock().await;
+    let state = match guard.as_mut() {
+        // job of a library which is closed meanwhile is left running,
+        // it is queued again when the library is opened next time
+        Some(state) if state.path == path => state,
+        _ => return Ok(true),
+    };
+    let result = finish.and_then(|finish| finish(state));
+    let attempts: i64 = state
+        .conn
+        .query_row("SELECT attempts FROM job WHERE id = ?1", [id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let (status, error) = match result {
+        Ok(()) => (JobStatus::Done, None),
+        Err(e) if attempts < MAX_ATTEMPTS => (JobStatus::Queued, Some(e)),
+        Err(e) => (JobStatus::Failed, Some(e)),
+    };
+    set_job_status(&state.conn, id, status, error.as_deref())?;
+    emit_status(app, id, status, error);
+    Ok(true)
+}
+
+fn emit_status(app: &AppHandle, id: i64, status: JobStatus, error: Option<String>) {
+    if let Err(e) = app.emit_all(JOB_EVENT, JobStatusEvent { id, status, error }) {
+        warn!(error = %e, "failed to emit job status");
+    }
+}
+
+/// Run queued jobs one by one in background until app exits
+pub fn spawn_worker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match run_next_job(&app).await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => error!(error = %e, "job worker failed"),
+            }
+            let queue = app.state::<JobQueue>();
+            let _ = tokio::time::timeout(POLL_INTERVAL, queue.notify.notified()).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn add_job(
+    state: tauri::State<'_, MemeDatabaseState>,
+    queue: tauri::State<'_, JobQueue>,
+    job: Job,
+) -> AppResult<i64> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let id = enqueue_job(&state.conn, &job)?;
+    queue.notify.notify_one();
+    Ok(id)
+}
+
+/// Jobs of opened library newest first, only jobs with `status` if it is given
+#[tauri::command]
+pub async fn list_jobs(
+    state: tauri::State<'_, MemeDatabaseState>,
+    status: Option<JobStatus>,
+) -> AppResult<Vec<JobInfo>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare(
+            "SELECT id, job, status, attempts, error, update_time FROM job
+              WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map([status.map(JobStatus::as_str)], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String, String, i64, Option<String>, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(id, job, status, attempts, error, update_time)| {
+            Ok(JobInfo {
+                id,
+                job: parse_json(&job)?,
+                status: JobStatus::parse(&status)?,
+                attempts,
+                error,
+                update_time,
+            })
+        })
+        .collect()
+}
+
+/// Queue failed job again with its attempts reset
+#[tauri::command]
+pub async fn retry_job(
+    state: tauri::State<'_, MemeDatabaseState>,
+    queue: tauri::State<'_, JobQueue>,
+    id: i64,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "UPDATE job SET status = 'queued', attempts = 0, update_time = CURRENT_TIMESTAMP
+              WHERE id = ?1 AND status = 'failed'",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+    queue.notify.notify_one();
+    Ok(())
+}
+
+/// Remove a job which is not running, queued job is cancelled
+#[tauri::command]
+pub async fn remove_job(state: tauri::State<'_, MemeDatabaseState>, id: i64) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "DELETE FROM job WHERE id = ?1 AND status != 'running'",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn clear_finished_jobs(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute("DELETE FROM job WHERE status = 'done'", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
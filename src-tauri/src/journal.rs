@@ -0,0 +1,331 @@
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    history::record_meme_history,
+    meme::{make_tag, query_meme_tags, Tag},
+};
+
+/// Number of operations kept in journal, older ones can not be undone
+const JOURNAL_LIMIT: i64 = 100;
+
+/// Editable state of a meme, restored as a whole on undo and redo
+/// Its fields are recorded in `meme_history` by name
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MemeState {
+    pub name: String,
+    pub description: Option<String>,
+    pub fav: bool,
+    pub trash: bool,
+    pub rating: u8,
+    pub pkg_id: i64,
+    #[serde(default)]
+    pub source_url: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub origin: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+    /// Including implied tags, so they are restored without applying implications again
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JournalOperation {
+    id: i64,
+    description: String,
+    time: String,
+    undone: bool,
+}
+
+pub fn query_meme_state(conn: &Connection, meme_id: i64) -> Result<Option<MemeState>, String> {
+    let state = conn
+        .query_row(
+            "SELECT name, description, fav, trash, rating, pkg_id, source_url, author, origin,
+              private FROM meme WHERE id = ?1",
+            [meme_id],
+            |row| {
+                Ok(MemeState {
+                    name: row.get(0)?,
+                    description: row.get(1)?,
+                    fav: row.get(2)?,
+                    trash: row.get(3)?,
+                    rating: row.get(4)?,
+                    pkg_id: row.get(5)?,
+                    source_url: row.get(6)?,
+                    author: row.get(7)?,
+                    origin: row.get(8)?,
+                    private: row.get(9)?,
+                    tags: Vec::new(),
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match state {
+        Some(mut state) => {
+            state.tags = query_meme_tags(conn, meme_id)?;
+            state
+                .tags
+                .sort_by(|l, r| (&l.key, &l.value).cmp(&(&r.key, &r.value)));
+            Ok(Some(state))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn restore_meme_state(
+    conn: &Connection,
+    meme_id: i64,
+    state: &MemeState,
+) -> Result<(), String> {
+    let updated = conn
+        .execute(
+            "UPDATE meme SET name = ?1, description = ?2, fav = ?3, trash = ?4, rating = ?5,
+              pkg_id = ?6, source_url = ?7, author = ?8, origin = ?9, private = ?10 WHERE id = ?11",
+            (
+                &state.name,
+                &state.description,
+                state.fav,
+                state.trash,
+                state.rating,
+                state.pkg_id,
+                &state.source_url,
+                &state.author,
+                &state.origin,
+                state.private,
+                meme_id,
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+    // meme has been purged since the operation
+    if updated == 0 {
+        return Ok(());
+    }
+
+    conn.execute("DELETE FROM meme_tag WHERE meme_id = ?1", [meme_id])
+        .map_err(|e| e.to_string())?;
+    for tag in &state.tags {
+        let tag_id = make_tag(conn, &tag.key, &tag.value)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO meme_tag(meme_id, tag_id) VALUES (?1, ?2)",
+            (meme_id, tag_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Delete journal operations matching `condition` with their entries
+fn delete_operations(conn: &Connection, condition: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "DELETE FROM journal_entry WHERE journal_id IN (SELECT id FROM journal WHERE {})",
+            condition
+        ),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(&format!("DELETE FROM journal WHERE {}", condition), [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Records memes changed by an operation so it can be undone.
+/// Memes are captured before they are changed, and the recorder is finished
+/// in the same transaction after all changes are made
+pub struct JournalRecorder {
+    description: String,
+    before: Vec<(i64, MemeState)>,
+}
+
+impl JournalRecorder {
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_owned(),
+            before: Vec::new(),
+        }
+    }
+
+    /// Remember state of meme before it is changed, memes not existing are ignored
+    pub fn capture(&mut self, conn: &Connection, meme_id: i64) -> Result<(), String> {
+        if self.before.iter().any(|(id, _)| *id == meme_id) {
+            return Ok(());
+        }
+        if let Some(state) = query_meme_state(conn, meme_id)? {
+            self.before.push((meme_id, state));
+        }
+        Ok(())
+    }
+
+    /// Write operation into journal and changed fields into history of memes,
+    /// nothing is written if no meme is actually changed
+    pub fn finish(self, conn: &Connection) -> Result<(), String> {
+        let mut entries = Vec::new();
+        for (meme_id, before) in self.before {
+            match query_meme_state(conn, meme_id)? {
+                Some(after) if after != before => {
+                    record_meme_history(conn, meme_id, &before, &after)?;
+                    entries.push((meme_id, before, after));
+                }
+                _ => {}
+            }
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        // undone operations can not be redone after a new operation
+        delete_operations(conn, "undone = true")?;
+        conn.execute(
+            "INSERT INTO journal(description) VALUES (?1)",
+            [&self.description],
+        )
+        .map_err(|e| e.to_string())?;
+        let journal_id = conn.last_insert_rowid();
+        for (meme_id, before, after) in entries {
+            conn.execute(
+                "INSERT INTO journal_entry(journal_id, meme_id, before, after)
+                  VALUES (?1, ?2, ?3, ?4)",
+                (
+                    journal_id,
+                    meme_id,
+                    serde_json::to_string(&before).unwrap(),
+                    serde_json::to_string(&after).unwrap(),
+                ),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        delete_operations(
+            conn,
+            &format!(
+                "id <= (SELECT id FROM journal ORDER BY id DESC LIMIT 1 OFFSET {})",
+                JOURNAL_LIMIT
+            ),
+        )
+    }
+}
+
+/// Restore memes of operation `journal_id` to their state `before` or `after` it
+fn apply_operation(conn: &Connection, journal_id: i64, undo: bool) -> Result<(), String> {
+    let column = if undo { "before" } else { "after" };
+    let mut query = conn
+        .prepare(&format!(
+            "SELECT meme_id, {} FROM journal_entry WHERE journal_id = ?1",
+            column
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = query
+        .query_map([journal_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    for (meme_id, state) in entries {
+        let state: MemeState = serde_json::from_str(&state).map_err(|e| e.to_string())?;
+        if let Some(current) = query_meme_state(conn, meme_id)? {
+            restore_meme_state(conn, meme_id, &state)?;
+            record_meme_history(conn, meme_id, &current, &state)?;
+        }
+    }
+    conn.execute(
+        "UPDATE journal SET undone = ?1 WHERE id = ?2",
+        (undo, journal_id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Last `n` operations which are not undone, newest first
+fn query_undoable_operations(conn: &Connection, n: i64) -> Result<Vec<(i64, String)>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT id, description FROM journal WHERE undone = false
+              ORDER BY id DESC LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([n], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Operations in journal, newest first
+#[tauri::command]
+pub async fn list_journal(
+    state: tauri::State<'_, MemeDatabaseState>,
+    limit: i64,
+) -> AppResult<Vec<JournalOperation>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare("SELECT id, description, time, undone FROM journal ORDER BY id DESC LIMIT ?1")
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([limit], |row| {
+            Ok(JournalOperation {
+                id: row.get(0)?,
+                description: row.get(1)?,
+                time: row.get(2)?,
+                undone: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<JournalOperation>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Undo last `n` operations which are not undone yet, newest first,
+/// return descriptions of operations undone
+#[tauri::command]
+pub async fn undo_last(
+    state: tauri::State<'_, MemeDatabaseState>,
+    n: i64,
+) -> AppResult<Vec<String>> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let operations = query_undoable_operations(&conn, n)?;
+    for (journal_id, _) in &operations {
+        apply_operation(&conn, *journal_id, true)?;
+    }
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(operations
+        .into_iter()
+        .map(|(_, description)| description)
+        .collect())
+}
+
+/// Redo the operation undone last, return its description or `None` if nothing to redo
+#[tauri::command]
+pub async fn redo(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<Option<String>> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let operation: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, description FROM journal WHERE undone = true ORDER BY id LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (journal_id, description) = match operation {
+        Some(operation) => operation,
+        None => return Ok(None),
+    };
+    apply_operation(&conn, journal_id, false)?;
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(Some(description))
+}
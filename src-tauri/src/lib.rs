@@ -0,0 +1,313 @@
+use std::{path::PathBuf, fs};
+
+use app_config::AppConfig;
+use db::MemeDatabaseState;
+use jobs::JobQueue;
+use progress::TaskRegistry;
+use semantic::ClipState;
+use tracing::warn;
+use watch::WatchState;
+
+mod album;
+mod animation;
+pub mod app_config;
+mod archive;
+mod autotag;
+mod backup;
+mod booru;
+#[cfg(feature = "semantic-search")]
+mod clip;
+mod clipboard;
+mod cluster;
+mod color;
+mod comic;
+mod compress;
+mod config;
+pub mod db;
+mod dedup;
+mod download;
+mod encryption;
+pub mod error;
+mod exif;
+pub mod export;
+mod fav_group;
+pub mod file;
+mod history;
+mod image_edit;
+mod import;
+mod jobs;
+mod journal;
+mod library;
+mod logging;
+mod maintenance;
+mod media;
+pub mod meme;
+mod namespace;
+mod normalize;
+mod ocr;
+mod pages;
+mod progress;
+mod picker;
+mod private;
+mod protocol;
+mod quick_pick;
+mod relation;
+mod rpc;
+mod search_history;
+mod semantic;
+#[cfg(feature = "rest-api")]
+mod server;
+mod share;
+mod sidecar;
+mod staging;
+mod storage;
+mod sync;
+mod tag_rule;
+mod tag_stats;
+mod telegram;
+mod template;
+mod thumbnail;
+mod trash;
+mod tray;
+mod video;
+mod watch;
+mod zustand_storage;
+
+pub struct AppDir {
+    storage_dir: PathBuf,
+}
+
+/// App config and other app wide data are stored next to executable,
+/// `meme-cli` finds them there as well
+pub fn app_storage_dir() -> PathBuf {
+    tauri::utils::platform::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// Start the GUI app
+pub fn run() {
+    let storage_dir = app_storage_dir();
+    if !storage_dir.exists(){
+        fs::create_dir_all(&storage_dir).unwrap();
+    }
+    // flushes log files when app exits
+    let _log_guard = logging::init_logging(&storage_dir);
+
+    let config = AppConfig::load(&storage_dir).unwrap_or_else(|e| {
+        warn!(error = %e, "failed to read app config");
+        AppConfig::default()
+    });
+    // reopen library used last time
+    let db_state = match config.current_library() {
+        Some(library) if library.path.exists() => {
+            MemeDatabaseState::with_library(library.path.clone())
+        }
+        _ => MemeDatabaseState::default(),
+    };
+
+    #[cfg(feature = "rest-api")]
+    let api_port = config.api_port;
+    let picker_shortcut = config
+        .picker_shortcut
+        .clone()
+        .unwrap_or_else(|| picker::DEFAULT_SHORTCUT.to_owned());
+    let rpc_enabled = config.rpc_enabled;
+    let watch_folders = config.watch_folders;
+
+    tauri::Builder::default()
+        .manage(AppDir {
+            storage_dir: storage_dir.clone(),
+        })
+        .manage(db_state)
+        .manage(TaskRegistry::default())
+        .manage(JobQueue::default())
+        .manage(ClipState::default())
+        .manage(WatchState::default())
+        .register_uri_scheme_protocol(protocol::SCHEME, protocol::handle_request)
+        .system_tray(tray::build_tray())
+        .on_system_tray_event(tray::handle_tray_event)
+        .setup(move |app| {
+            jobs::spawn_worker(app.handle());
+            let handle = app.handle();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = tray::refresh_tray(&handle).await {
+                    warn!(error = %e, "failed to list recent memes in tray");
+                }
+            });
+            if let Err(e) = picker::register_picker_shortcut(&app.handle(), &picker_shortcut) {
+                warn!(error = %e, "failed to register picker shortcut");
+            }
+            if let Err(e) = watch::start_watching(&app.handle(), watch_folders) {
+                warn!(error = %e, "failed to watch folders");
+            }
+            if rpc_enabled {
+                rpc::spawn_rpc_server(app.handle());
+            }
+            #[cfg(feature = "rest-api")]
+            {
+                if let Some(port) = api_port {
+                    server::spawn_server(app.handle(), port);
+                }
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            zustand_storage::zustand_set,
+            zustand_storage::zustand_get,
+            zustand_storage::zustand_del,
+            app_config::get_app_config,
+            app_config::set_library_root,
+            app_config::add_library,
+            app_config::remove_library,
+            app_config::switch_library,
+            app_config::set_api_port,
+            app_config::set_api_token,
+            app_config::set_rpc_enabled,
+            config::get_settings,
+            config::set_settings,
+            meme::add_meme_record,
+            meme::update_meme_record,
+            meme::bulk_link_tags,
+            meme::bulk_unlink_tags,
+            meme::search_meme,
+            meme::get_meme_by_id,
+            meme::get_tags_by_id,
+            meme::get_tags_by_ids,
+            meme::get_random_meme,
+            meme::get_tag_keys_by_prefix,
+            meme::get_tags_by_prefix,
+            meme::get_tags_fuzzy,
+            meme::get_tags_related,
+            tag_rule::add_tag_alias,
+            tag_rule::remove_tag_alias,
+            tag_rule::list_tag_aliases,
+            tag_rule::add_tag_implication,
+            tag_rule::remove_tag_implication,
+            tag_rule::list_tag_implications,
+            namespace::list_namespaces,
+            namespace::set_namespace,
+            namespace::rename_namespace,
+            journal::list_journal,
+            journal::undo_last,
+            journal::redo,
+            history::get_meme_history,
+            history::revert_meme_field,
+            tag_stats::get_tag_usage_counts,
+            tag_stats::get_most_used_tags,
+            tag_stats::get_recent_tags,
+            tag_stats::get_tag_suggestions,
+            tag_stats::suggest_tags_for_meme,
+            tag_stats::disk_usage_report,
+            meme::delete_meme_by_id,
+            meme::trash_meme_by_id,
+            meme::set_meme_trash,
+            meme::set_meme_fav,
+            meme::set_trash_bulk,
+            meme::set_fav_bulk,
+            meme::delete_bulk,
+            meme::set_meme_rating,
+            meme::mark_meme_used,
+            private::set_meme_private,
+            pages::get_meme_pages,
+            pages::get_meme_page,
+            pages::add_meme_page,
+            pages::remove_meme_page,
+            pages::move_meme_page,
+            comic::split_long_meme,
+            compress::queue_compression,
+            compress::get_compression_savings,
+            maintenance::maintain_database,
+            logging::get_recent_logs,
+            private::unlock_private,
+            private::lock_private,
+            private::is_private_unlocked,
+            private::set_private_passcode,
+            clipboard::copy_meme_to_clipboard,
+            clipboard::import_from_clipboard,
+            download::import_from_url,
+            telegram::import_sticker_set,
+            trash::restore_meme,
+            trash::purge_meme_by_id,
+            trash::purge_trash_older_than,
+            db::open_storage,
+            db::get_storage,
+            db::is_storage_available,
+            db::migration::dry_run_migrations,
+            db::performance::analyze_performance,
+            encryption::set_library_passphrase,
+            import::import_directory,
+            import::import_files,
+            dedup::find_similar_memes,
+            dedup::scan_duplicate_memes,
+            dedup::scan_duplicate_names,
+            thumbnail::regenerate_thumbnails,
+            media::refresh_media_info,
+            library::check_library,
+            library::gc_library,
+            library::library_stats,
+            backup::create_backup_now,
+            backup::list_backups,
+            backup::restore_backup,
+            export::export_memes,
+            export::export_emoji_pack,
+            share::create_share_bundle,
+            image_edit::edit_meme,
+            relation::link_memes,
+            relation::unlink_meme,
+            relation::get_meme_family,
+            template::set_template,
+            template::remove_template,
+            template::list_templates,
+            template::render_template,
+            fav_group::list_fav_groups,
+            fav_group::create_fav_group,
+            fav_group::rename_fav_group,
+            fav_group::delete_fav_group,
+            fav_group::add_to_fav_group,
+            fav_group::remove_from_fav_group,
+            fav_group::get_meme_fav_groups,
+            color::index_colors,
+            semantic::index_embeddings,
+            semantic::semantic_search,
+            cluster::suggest_cluster_tags,
+            autotag::queue_auto_tagging,
+            search_history::list_recent_searches,
+            search_history::pin_search,
+            search_history::remove_search,
+            search_history::clear_search_history,
+            quick_pick::quick_pick,
+            picker::pick_meme,
+            picker::set_picker_shortcut,
+            watch::set_watch_folders,
+            archive::export_library,
+            archive::import_library,
+            sync::sync_library,
+            storage::get_remote_storage,
+            storage::set_remote_storage,
+            storage::push_remote_storage,
+            storage::clear_content_cache,
+            ocr::ocr_meme,
+            ocr::ocr_all_memes,
+            progress::cancel_task,
+            jobs::add_job,
+            jobs::list_jobs,
+            jobs::retry_job,
+            jobs::remove_job,
+            jobs::clear_finished_jobs,
+            ocr::set_meme_text,
+            ocr::get_meme_text,
+            album::create_album,
+            album::update_album,
+            album::delete_album,
+            album::list_albums,
+            album::get_album_memes,
+            album::add_memes_to_album,
+            album::remove_memes_from_album,
+            album::reorder_album
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,430 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use rusqlite::{Connection, Error, OptionalExtension};
+use tracing::error;
+
+use crate::{
+    animation::preview_path,
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    file::{compute_path, list_content_files},
+    jobs::{enqueue_job, Job},
+    staging::recently_published,
+    thumbnail::thumbnail_path,
+};
+
+/// Orphaned files are moved here instead of being deleted if quarantine is requested
+const QUARANTINE_DIR: &str = "quarantine";
+/// Content verified within this period is not hashed again when it is served
+const VERIFY_INTERVAL: &str = "-7 days";
+
+/// Whether content `hash` was found corrupted by a check within `VERIFY_INTERVAL`,
+/// `None` if it has not been checked recently
+fn recent_check(conn: &Connection, hash: &str) -> Result<Option<bool>, String> {
+    conn.query_row(
+        "SELECT corrupted FROM content_check
+          WHERE hash = ?1 AND verified_time > datetime('now', ?2)",
+        (hash, VERIFY_INTERVAL),
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Hash content file `hash` and tell whether it no longer has the sha256 it is named by,
+/// `None` if the file is only in remote storage. Slow for large files, runs without database
+pub fn digest_mismatch<P: AsRef<Path>>(base: P, hash: &str) -> Result<Option<bool>, String> {
+    let path = compute_path(&base, hash);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let sha = hash.split('.').next().unwrap_or(hash);
+    let digest = sha256::try_digest(path.as_path()).map_err(|e| e.to_string())?;
+    Ok(Some(digest != sha))
+}
+
+/// Record result of checking content `hash`, see `digest_mismatch`
+pub fn record_check(conn: &Connection, hash: &str, corrupted: bool) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO content_check(hash, verified_time, corrupted)
+          VALUES (?1, CURRENT_TIMESTAMP, ?2)",
+        (hash, corrupted),
+    )
+    .map_err(|e| e.to_string())?;
+    if corrupted {
+        error!(hash, "content is corrupted");
+    }
+    Ok(())
+}
+
+/// Check that content file `hash` still has the sha256 it is named by, and record the result.
+/// A recent result is reused instead of hashing the file again.
+/// Content only in remote storage is not checked
+pub fn verify_content<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    hash: &str,
+) -> Result<bool, String> {
+    if let Some(corrupted) = recent_check(conn, hash)? {
+        return Ok(!corrupted);
+    }
+    match digest_mismatch(base, hash)? {
+        Some(corrupted) => {
+            record_check(conn, hash, corrupted)?;
+            Ok(!corrupted)
+        }
+        None => Ok(true),
+    }
+}
+
+/// Verify content about to be served if `verify_on_read` is enabled,
+/// corrupted content is still served, it is only recorded for library report
+pub fn verify_on_read<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    hash: &str,
+) -> Result<(), String> {
+    if load_settings(conn)?.verify_on_read {
+        verify_content(conn, base, hash)?;
+    }
+    Ok(())
+}
+
+/// Queue a job checking content `hash` unless it has been checked recently
+/// or is queued already, return whether a job is queued.
+/// Cheap enough for the protocol handler, which must not hash files itself
+pub fn queue_verify(conn: &Connection, hash: &str) -> Result<bool, String> {
+    if recent_check(conn, hash)?.is_some() {
+        return Ok(false);
+    }
+    let job = Job::Verify {
+        hash: hash.to_owned(),
+    };
+    let text = serde_json::to_string(&job).map_err(|e| e.to_string())?;
+    let pending = conn
+        .query_row(
+            "SELECT 1 FROM job WHERE job = ?1 AND status IN ('queued', 'running')",
+            [text],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if pending.is_some() {
+        return Ok(false);
+    }
+    enqueue_job(conn, &job)?;
+    Ok(true)
+}
+
+/// Id and content hash of every page of every meme
+fn query_all_content(conn: &Connection) -> Result<Vec<(i64, String)>, String> {
+    let mut query = conn
+        .prepare("SELECT meme_id, hash FROM meme_file")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(memes)
+}
+
+/// Content files in storage which are not referenced by any of `memes`.
+/// Content published recently is skipped, its meme may not be committed yet
+fn find_orphaned_files<P: AsRef<Path>>(
+    base: P,
+    memes: &[(i64, String)],
+) -> Result<Vec<String>, String> {
+    let base = base.as_ref();
+    let referenced = memes
+        .iter()
+        .map(|(_, hash)| hash.as_str())
+        .collect::<HashSet<&str>>();
+    Ok(list_content_files(base)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|name| !referenced.contains(name.as_str()) && !recently_published(base, name))
+        .collect())
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MissingContent {
+    id: i64,
+    hash: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LibraryReport {
+    /// Messages of `PRAGMA integrity_check`, a single `ok` means no problem
+    integrity: Vec<String>,
+    /// Memes whose content file does not exist
+    missing_content: Vec<MissingContent>,
+    /// Content files not referenced by any meme
+    orphaned_files: Vec<String>,
+    /// Memes whose content did not match its sha256 when it was last verified
+    corrupted_content: Vec<MissingContent>,
+}
+
+/// Check database integrity and consistency between memes and content files
+#[tauri::command]
+pub async fn check_library(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<LibraryReport> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let mut query = state
+        .conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| e.to_string())?;
+    let integrity = query
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let memes = query_all_content(&state.conn)?;
+    let orphaned_files = find_orphaned_files(&state.path, &memes)?;
+
+    let mut missing_content = Vec::new();
+    for (id, hash) in &memes {
+        // content removed from local cache is fine as long as remote storage has it
+        if !compute_path(&state.path, hash).is_file() && !state.storage.exists(hash)? {
+            missing_content.push(MissingContent {
+                id: *id,
+                hash: hash.clone(),
+            });
+        }
+    }
+
+    let mut query = state
+        .conn
+        .prepare(
+            "SELECT DISTINCT meme_file.meme_id, meme_file.hash FROM meme_file
+              JOIN content_check ON content_check.hash = meme_file.hash
+              WHERE content_check.corrupted = true",
+        )
+        .map_err(|e| e.to_string())?;
+    let corrupted_content = query
+        .query_map([], |row| {
+            Ok(MissingContent {
+                id: row.get(0)?,
+                hash: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<MissingContent>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(LibraryReport {
+        integrity,
+        missing_content,
+        orphaned_files,
+        corrupted_content,
+    })
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct GcResult {
+    /// Number of removed content files
+    files: usize,
+    /// Bytes reclaimed, including derived files
+    bytes: u64,
+}
+
+/// Remove content files not referenced by any meme, together with their thumbnails,
+/// e.g. left behind by purged memes or failed imports.
+/// If `quarantine` is set, content files are moved into `quarantine/` under storage instead
+#[tauri::command]
+pub async fn gc_library(
+    state: tauri::State<'_, MemeDatabaseState>,
+    quarantine: bool,
+) -> AppResult<GcResult> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let memes = query_all_content(&state.conn)?;
+    let orphaned_files = find_orphaned_files(&state.path, &memes)?;
+    let referenced_sha = memes
+        .iter()
+        .map(|(_, hash)| hash.split('.').next().unwrap_or(hash))
+        .collect::<HashSet<&str>>();
+
+    let quarantine_dir = state.path.join(QUARANTINE_DIR);
+    if quarantine && !orphaned_files.is_empty() {
+        fs::create_dir_all(&quarantine_dir).map_err(|e| e.to_string())?;
+    }
+
+    let mut result = GcResult { files: 0, bytes: 0 };
+    for name in orphaned_files {
+        let path = compute_path(&state.path, &name);
+        result.bytes += fs::metadata(&path).map_or(0, |x| x.len());
+        if quarantine {
+            fs::rename(&path, quarantine_dir.join(&name)).map_err(|e| e.to_string())?;
+        } else {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        result.files += 1;
+
+        // the same content may still be referenced with another extension
+        if referenced_sha.contains(name.split('.').next().unwrap_or(&name)) {
+            continue;
+        }
+        for derived in [
+            thumbnail_path(&state.path, &name),
+            preview_path(&state.path, &name),
+        ] {
+            if derived.exists() {
+                result.bytes += fs::metadata(&derived).map_or(0, |x| x.len());
+                fs::remove_file(derived).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct GroupCount {
+    key: String,
+    count: i64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LibraryStats {
+    /// Memes not in trash, other counts except `trash` only count these memes too
+    total: i64,
+    fav: i64,
+    trash: i64,
+    untagged: i64,
+    /// Bytes of content files, content shared by several memes is counted once
+    disk_usage: i64,
+    /// Memes with any tag in namespace, most used namespace first
+    namespaces: Vec<GroupCount>,
+    /// Memes added in each month (`YYYY-MM`), oldest first
+    months: Vec<GroupCount>,
+}
+
+fn query_group_counts(conn: &Connection, sql: &str) -> Result<Vec<GroupCount>, String> {
+    let mut query = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([], |row| {
+            Ok(GroupCount {
+                key: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<GroupCount>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+pub fn query_library_stats(conn: &Connection) -> Result<LibraryStats, String> {
+    let (total, fav, trash, untagged) = conn
+        .query_row(
+            "SELECT IFNULL(SUM(trash = false), 0), IFNULL(SUM(trash = false AND fav = true), 0),
+              IFNULL(SUM(trash = true), 0),
+              IFNULL(SUM(trash = false
+                AND NOT EXISTS (SELECT 1 FROM meme_tag WHERE meme_tag.meme_id = meme.id)), 0)
+              FROM meme",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let disk_usage = conn
+        .query_row(
+            "SELECT IFNULL(SUM(size), 0) FROM (
+              SELECT MAX(media_info.size) AS size FROM meme
+              JOIN media_info ON media_info.meme_id = meme.id
+              GROUP BY meme.hash
+            )",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let namespaces = query_group_counts(
+        conn,
+        "SELECT tag.key, COUNT(DISTINCT meme.id) AS count FROM tag
+          JOIN meme_tag ON meme_tag.tag_id = tag.id
+          JOIN meme ON meme.id = meme_tag.meme_id
+          WHERE meme.trash = false
+          GROUP BY tag.key
+          ORDER BY count DESC, tag.key",
+    )?;
+    let months = query_group_counts(
+        conn,
+        "SELECT strftime('%Y-%m', create_time) AS month, COUNT(*) FROM meme
+          WHERE trash = false
+          GROUP BY month
+          ORDER BY month",
+    )?;
+    Ok(LibraryStats {
+        total,
+        fav,
+        trash,
+        untagged,
+        disk_usage,
+        namespaces,
+        months,
+    })
+}
+
+/// Numbers shown on stats page of current library
+#[tauri::command]
+pub async fn library_stats(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<LibraryStats> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_library_stats(&state.conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tauri::Manager;
+    use tokio::sync::Mutex;
+
+    use crate::{
+        db::{MemeDatabaseConnection, MemeDatabaseState},
+        file::compute_path,
+        library::{gc_library, queue_verify, record_check},
+        staging::publish_bytes,
+    };
+
+    #[test]
+    fn test_gc_keeps_published_content() {
+        let dir = std::env::temp_dir().join(format!("mmm-gc-{}", std::process::id()));
+        let state = MemeDatabaseConnection::open_in_memory(dir.clone()).unwrap();
+        // published by a job or another process, its meme is not committed yet
+        let published = publish_bytes(&dir, b"published", Some("png")).unwrap();
+        let orphan = format!("{}.png", sha256::digest("orphan"));
+        let orphan_path = compute_path(&dir, &orphan);
+        fs::create_dir_all(orphan_path.parent().unwrap()).unwrap();
+        fs::write(&orphan_path, b"orphan").unwrap();
+
+        let app = tauri::test::mock_app();
+        app.manage(MemeDatabaseState {
+            state: Mutex::new(Some(state)),
+        });
+        let result = tauri::async_runtime::block_on(gc_library(app.state(), false)).unwrap();
+        assert_eq!(result.files, 1);
+        assert!(compute_path(&dir, &published).exists());
+        assert!(!orphan_path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_queue_verify_once() {
+        let state = MemeDatabaseConnection::open_in_memory(std::env::temp_dir()).unwrap();
+        let hash = format!("{}.png", sha256::digest("served"));
+        assert!(queue_verify(&state.conn, &hash).unwrap());
+        // served again before the job runs
+        assert!(!queue_verify(&state.conn, &hash).unwrap());
+        state.conn.execute("UPDATE job SET status = 'done'", []).unwrap();
+        record_check(&state.conn, &hash, false).unwrap();
+        assert!(!queue_verify(&state.conn, &hash).unwrap());
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use tracing::level_filters::LevelFilter;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{fmt, prelude::*};
+
+use crate::{error::AppResult, AppDir};
+
+/// Directory of log files in app storage
+const LOG_DIR: &str = "logs";
+/// Log files are named `mmm.<date>.log`, a new one is started every day
+const LOG_PREFIX: &str = "mmm";
+const LOG_SUFFIX: &str = "log";
+/// Files older than this many days are deleted on rotation
+const MAX_LOG_FILES: usize = 7;
+/// Lines returned by `get_recent_logs` if not given
+const DEFAULT_RECENT_LINES: usize = 500;
+
+fn log_dir(storage_dir: &Path) -> PathBuf {
+    storage_dir.join(LOG_DIR)
+}
+
+/// Write log to stderr and rotating files in app storage. Events are written to files
+/// by a background thread until returned guard is dropped, so keep it until app exits
+pub fn init_logging(storage_dir: &Path) -> Option<WorkerGuard> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir(storage_dir));
+    let (file_layer, guard, error) = match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard), None)
+        }
+        Err(e) => (None, None, Some(e)),
+    };
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(fmt::layer().with_writer(io::stderr))
+        .with(file_layer)
+        .init();
+    if let Some(e) = error {
+        tracing::warn!(error = %e, "failed to open log files, logging to stderr only");
+    }
+    guard
+}
+
+/// Last `count` lines of log files in `dir`, oldest first
+fn read_recent_logs(dir: &Path, count: usize) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .map_or(false, |name| name.to_string_lossy().starts_with(LOG_PREFIX))
+        })
+        .collect::<Vec<PathBuf>>();
+    // date in file name sorts the newest file last
+    files.sort();
+
+    let mut recent = Vec::new();
+    for file in files.iter().rev() {
+        if recent.len() >= count {
+            break;
+        }
+        let content = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+        let mut lines = content.lines().map(str::to_owned).collect::<Vec<String>>();
+        let skip = lines.len().saturating_sub(count - recent.len());
+        lines.drain(..skip);
+        lines.append(&mut recent);
+        recent = lines;
+    }
+    Ok(recent)
+}
+
+/// Recent lines of app log, for attaching to a report when an import or migration fails
+#[tauri::command]
+pub async fn get_recent_logs(
+    app_dir: tauri::State<'_, AppDir>,
+    lines: Option<usize>,
+) -> AppResult<Vec<String>> {
+    let count = lines.unwrap_or(DEFAULT_RECENT_LINES);
+    Ok(read_recent_logs(&log_dir(&app_dir.storage_dir), count)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::logging::read_recent_logs;
+
+    #[test]
+    fn test_read_recent_logs() {
+        let dir = std::env::temp_dir().join(format!("mmm-logs-{}", std::process::id()));
+        assert!(read_recent_logs(&dir, 10).unwrap().is_empty());
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("mmm.2026-10-14.log"), "a\nb\nc\n").unwrap();
+        fs::write(dir.join("mmm.2026-10-15.log"), "d\ne\n").unwrap();
+        fs::write(dir.join("other.txt"), "x\n").unwrap();
+        assert_eq!(read_recent_logs(&dir, 3).unwrap(), vec!["c", "d", "e"]);
+        assert_eq!(read_recent_logs(&dir, 1).unwrap(), vec!["e"]);
+        assert_eq!(read_recent_logs(&dir, 10).unwrap().len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
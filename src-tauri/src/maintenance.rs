@@ -0,0 +1,161 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    jobs::{enqueue_job, Job},
+};
+
+/// Size of database before and after maintenance in bytes
+#[derive(Debug, serde::Serialize)]
+pub struct MaintenanceReport {
+    size_before: i64,
+    size_after: i64,
+    /// Space freed by trashing and re-importing memes which `VACUUM` gave back
+    reclaimed: i64,
+}
+
+fn database_size(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Rebuild database to drop free pages, refresh statistics used by query planner,
+/// and shrink WAL file. `VACUUM` can not run inside a transaction
+pub fn run_maintenance(conn: &Connection) -> Result<MaintenanceReport, String> {
+    let size_before = database_size(conn)?;
+    conn.execute_batch(
+        "VACUUM;
+         ANALYZE;
+         PRAGMA optimize;",
+    )
+    .map_err(|e| e.to_string())?;
+    // WAL keeps every page VACUUM has written until it is checkpointed
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    let size_after = database_size(conn)?;
+    conn.execute(
+        "INSERT INTO maintenance_log(size_before, size_after) VALUES (?1, ?2)",
+        (size_before, size_after),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(MaintenanceReport {
+        size_before,
+        size_after,
+        reclaimed: (size_before - size_after).max(0),
+    })
+}
+
+/// Queue a maintenance job if the last maintenance is older than
+/// `maintenance_interval_days` in settings, called on opening library.
+/// Return whether a job is queued
+pub fn queue_due_maintenance(conn: &Connection) -> Result<bool, String> {
+    let days = load_settings(conn)?.maintenance_interval_days;
+    if days == 0 {
+        return Ok(false);
+    }
+    let due: bool = conn
+        .query_row(
+            "SELECT IFNULL(MAX(run_time) <= datetime('now', ?1), true) FROM maintenance_log",
+            [format!("-{} days", days)],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let queued = conn
+        .query_row(
+            "SELECT id FROM job WHERE status IN ('queued', 'running')
+              AND json_extract(job, '$.kind') = 'maintain'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if !due || queued.is_some() {
+        return Ok(false);
+    }
+    enqueue_job(conn, &Job::Maintain)?;
+    Ok(true)
+}
+
+/// Run maintenance now, database is locked until it finishes
+#[tauri::command]
+pub async fn maintain_database(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<MaintenanceReport> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(run_maintenance(&state.conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{load_settings, save_settings},
+        db::fixtures::{empty_library, seed_meme},
+        maintenance::{queue_due_maintenance, run_maintenance},
+        trash::delete_meme_rows,
+    };
+
+    #[test]
+    fn test_run_maintenance() {
+        let state = empty_library();
+        let description = "x".repeat(4096);
+        let ids = (0..100)
+            .map(|i| seed_meme(&state.conn, &format!("meme {}", i), &[]))
+            .collect::<Vec<i64>>();
+        for id in &ids {
+            state
+                .conn
+                .execute(
+                    "UPDATE meme SET description = ?1 WHERE id = ?2",
+                    (&description, id),
+                )
+                .unwrap();
+        }
+        // pages of deleted rows stay in database file as free pages
+        for id in ids {
+            delete_meme_rows(&state.conn, id).unwrap();
+        }
+
+        let report = run_maintenance(&state.conn).unwrap();
+        assert!(report.reclaimed > 0);
+        let runs: i64 = state
+            .conn
+            .query_row("SELECT COUNT(*) FROM maintenance_log", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn test_queue_due_maintenance() {
+        let state = empty_library();
+        let mut settings = load_settings(&state.conn).unwrap();
+        settings.maintenance_interval_days = 0;
+        save_settings(&state.conn, &settings).unwrap();
+        assert!(!queue_due_maintenance(&state.conn).unwrap());
+
+        settings.maintenance_interval_days = 7;
+        save_settings(&state.conn, &settings).unwrap();
+        // never maintained
+        assert!(queue_due_maintenance(&state.conn).unwrap());
+        // already queued
+        assert!(!queue_due_maintenance(&state.conn).unwrap());
+
+        state.conn.execute("DELETE FROM job", []).unwrap();
+        run_maintenance(&state.conn).unwrap();
+        assert!(!queue_due_maintenance(&state.conn).unwrap());
+        state
+            .conn
+            .execute(
+                "UPDATE maintenance_log SET run_time = datetime('now', '-8 days')",
+                [],
+            )
+            .unwrap();
+        assert!(queue_due_maintenance(&state.conn).unwrap());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs, path::Path};
+
+use image::io::Reader as ImageReader;
+use rusqlite::{Connection, Error};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    file::compute_path,
+    storage::fetch_content,
+    video::{probe_video, VideoInfo},
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct MediaInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Lowercase format name, e.g. `png`, `gif`, `mp4`
+    pub format: String,
+    /// File size in bytes
+    pub size: i64,
+}
+
+fn file_extension(path: &Path) -> String {
+    path.extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn file_size(path: &Path) -> Result<i64, String> {
+    fs::metadata(path)
+        .map(|x| x.len() as i64)
+        .map_err(|e| e.to_string())
+}
+
+/// Read media info of stored file, the format of image is detected by its content
+/// and others fall back to their extension
+pub fn read_media_info(path: &Path, ty: &str) -> Result<MediaInfo, String> {
+    let size = file_size(path)?;
+    match ty {
+        "image" => {
+            let reader = ImageReader::open(path)
+                .and_then(|x| x.with_guessed_format())
+                .map_err(|e| e.to_string())?;
+            let format = reader
+                .format()
+                .and_then(|x| x.extensions_str().first())
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| file_extension(path));
+            let (width, height) = reader.into_dimensions().map_err(|e| e.to_string())?;
+            Ok(MediaInfo {
+                width: Some(width),
+                height: Some(height),
+                format,
+                size,
+            })
+        }
+        "video" => video_media_info(path, &probe_video(path)?),
+        _ => Ok(MediaInfo {
+            width: None,
+            height: None,
+            format: file_extension(path),
+            size,
+        }),
+    }
+}
+
+/// Media info of video whose stream has been probed
+pub fn video_media_info(path: &Path, info: &VideoInfo) -> Result<MediaInfo, String> {
+    Ok(MediaInfo {
+        width: Some(info.width),
+        height: Some(info.height),
+        format: file_extension(path),
+        size: file_size(path)?,
+    })
+}
+
+pub fn save_media_info(conn: &Connection, meme_id: i64, info: &MediaInfo) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO media_info(meme_id, width, height, format, size)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        (meme_id, info.width, info.height, &info.format, info.size),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Read media info of meme content and save it
+pub fn update_media_info<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    ty: &str,
+    content: &str,
+) -> Result<MediaInfo, String> {
+    let info = read_media_info(&compute_path(base, content), ty)?;
+    save_media_info(conn, meme_id, &info)?;
+    Ok(info)
+}
+
+/// Read media info for all memes that do not have one yet
+/// return the number of updated memes
+#[tauri::command]
+pub async fn refresh_media_info(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let mut query = state
+        .conn
+        .prepare("SELECT id, ty, hash FROM meme WHERE id NOT IN (SELECT meme_id FROM media_info)")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut count = 0;
+    for (id, ty, hash) in memes {
+        // content which can not be read is skipped instead of failing the whole refresh
+        if fetch_content(state.storage.as_ref(), &state.path, &hash).is_err() {
+            continue;
+        }
+        if update_media_info(&state.conn, &state.path, id, &ty, &hash).is_ok() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -1,17 +1,43 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::Path};
 
-use rusqlite::{Connection, Error, OptionalExtension};
+use rusqlite::{params_from_iter, Connection, Error, OptionalExtension, Row};
+use tauri::AppHandle;
+use tracing::{debug, warn};
 
 use crate::{
-    db::{self, search::build_search_sql, MemeDatabaseConnection, MemeDatabaseState},
-    file::{compute_path, copy_to_storage, store_to_storage},
+    animation::{query_preview, update_meme_animation},
+    color::update_meme_colors,
+    config::load_settings,
+    db::{
+        self,
+        highlight::{query_highlights, Highlight},
+        library_not_opened,
+        search::{build_search_sql, Cursor, SearchSql, SortBy, KEYSET_ORDER, MEME_TABLE},
+        MemeDatabaseConnection, MemeDatabaseState,
+    },
+    dedup::update_meme_phash,
+    error::{AppError, AppResult},
+    thumbnail::{generate_thumbnail, query_thumbnail},
+    trash::{delete_meme_rows, purge_meme},
+    file::{compute_path, release_content, release_contents},
+    import::PendingFile,
+    journal::JournalRecorder,
+    library::verify_on_read,
+    search_history::record_search,
+    media::update_media_info,
+    private::private_filter,
+    staging::{publish_bytes, settle_staging},
+    storage::{fetch_content, store_content, Storage},
+    tag_rule::{implied_tags, resolve_alias},
+    tray::refresh_tray,
+    video::process_video_content,
     AppDir,
 };
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub struct Tag {
-    key: String,
-    value: String,
+    pub key: String,
+    pub value: String,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -23,31 +49,124 @@ pub struct MemeToAdd {
     fav: bool,
     tags: Vec<Tag>,
     pkg_id: i64,
+    /// Remove EXIF and other metadata of image before storing
+    #[serde(default)]
+    strip_exif: bool,
+    #[serde(default)]
+    source_url: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    origin: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct MemeQueried {
-    id: i64,
-    name: String,
+    pub id: i64,
+    pub name: String,
     description: Option<String>,
     ty: String,
-    hash: String,
+    pub hash: String,
+    /// Content file in local library, commands download it before returning
+    /// if it is only in remote storage (see `fetch_listed_content`)
     path: String,
+    thumbnail: Option<String>,
+    /// Animated preview, only exists for animated image
+    preview: Option<String>,
+    frame_count: u32,
+    /// Duration of animation or video in milliseconds
+    duration: Option<u32>,
+    /// Media info is None if it has not been read yet, see `refresh_media_info`
+    width: Option<u32>,
+    height: Option<u32>,
+    format: Option<String>,
+    size: Option<i64>,
     fav: bool,
     trash: bool,
+    /// Hidden from listings until private memes are unlocked
+    private: bool,
+    rating: u8,
+    use_count: i64,
+    last_used: Option<String>,
     pkg_id: i64,
+    /// Url the meme is downloaded from
+    source_url: Option<String>,
+    author: Option<String>,
+    /// Where the meme is collected, such as name of a site, group chat or app
+    origin: Option<String>,
+    /// Id of the meme this one is edited from
+    derived_from: Option<i64>,
+    update_time: String,
+    /// Bumped by every edit of fields or tags, passed back to `update_meme_record`
+    /// to detect edits made in the meantime
+    version: i64,
+}
+
+/// Map a row of `MEME_TABLE` into `MemeQueried`, `path` may not exist yet
+/// and must be fetched before the meme is returned
+pub fn meme_from_row<P: AsRef<Path>>(row: &Row, base: P) -> Result<MemeQueried, Error> {
+    let hash: String = row.get("hash")?;
+    Ok(MemeQueried {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        description: row.get("description")?,
+        ty: row.get("ty")?,
+        path: compute_path(&base, &hash).to_string_lossy().to_string(),
+        thumbnail: query_thumbnail(&base, &hash),
+        preview: query_preview(&base, &hash),
+        frame_count: row.get("frame_count")?,
+        duration: row.get("duration")?,
+        width: row.get("width")?,
+        height: row.get("height")?,
+        format: row.get("format")?,
+        size: row.get("size")?,
+        hash,
+        fav: row.get("fav")?,
+        trash: row.get("trash")?,
+        private: row.get("private")?,
+        rating: row.get("rating")?,
+        use_count: row.get("use_count")?,
+        last_used: row.get("last_used")?,
+        pkg_id: row.get("pkg_id")?,
+        source_url: row.get("source_url")?,
+        author: row.get("author")?,
+        origin: row.get("origin")?,
+        derived_from: row.get("derived_from")?,
+        update_time: row.get("update_time")?,
+        version: row.get("version")?,
+    })
+}
+
+/// Namespace of tags written without one
+pub const DEFAULT_NAMESPACE: &str = "general";
+
+/// Parse tag written as `namespace:value`, the first colon separates namespace
+pub fn parse_tag(text: &str) -> Tag {
+    match text.split_once(':') {
+        Some((key, value)) if !key.is_empty() => Tag {
+            key: key.trim().to_owned(),
+            value: value.trim().to_owned(),
+        },
+        _ => Tag {
+            key: DEFAULT_NAMESPACE.to_owned(),
+            value: text.trim_start_matches(':').trim().to_owned(),
+        },
+    }
 }
 
-/// Query tag id
+/// Query tag id, alias is resolved to the tag it points to (see `tag_rule`)
 /// if tag is not exists, it will be inserted into database
-fn make_tag(conn: &Connection, name: &str, value: &str) -> Result<i64, String> {
+pub fn make_tag(conn: &Connection, name: &str, value: &str) -> Result<i64, String> {
+    if let Some(id) = resolve_alias(conn, name, value)? {
+        return Ok(id);
+    }
     let id: Option<i64> = conn
-        .query_row(
-            "SELECT id FROM tag WHERE key = ?1 AND value = ?2",
-            (name, value),
-            |row| Ok(row.get("id").unwrap()),
-        )
-        .optional()
+        .prepare_cached("SELECT id FROM tag WHERE key = ?1 AND value = ?2")
+        .and_then(|mut query| {
+            query
+                .query_row((name, value), |row| row.get("id"))
+                .optional()
+        })
         .map_err(|e| e.to_string())?;
     if let Some(id) = id {
         return Ok(id);
@@ -58,92 +177,296 @@ fn make_tag(conn: &Connection, name: &str, value: &str) -> Result<i64, String> {
     Ok(conn.last_insert_rowid())
 }
 
-fn insert_meme_tag(conn: &Connection, meme_id: i64, tag_id: i64) -> Result<(), String> {
+/// Link tag and all tags implied by it to meme, tags already linked are ignored
+pub fn insert_meme_tag(conn: &Connection, meme_id: i64, tag_id: i64) -> Result<(), String> {
+    for tag_id in std::iter::once(tag_id).chain(implied_tags(conn, tag_id)?) {
+        conn.execute(
+            "INSERT OR IGNORE INTO meme_tag(meme_id, tag_id) VALUES (?1, ?2)",
+            (meme_id, tag_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Insert a meme row whose content has already been put into storage
+/// return the id of new meme
+pub fn insert_meme(
+    conn: &Connection,
+    name: &str,
+    description: Option<&str>,
+    ty: &str,
+    hash: &str,
+    fav: bool,
+    pkg_id: i64,
+) -> Result<i64, String> {
     conn.execute(
-        "INSERT INTO meme_tag(meme_id, tag_id) VALUES (?1, ?2)",
-        (meme_id, tag_id),
+        "INSERT INTO meme(name, description, ty, hash, fav, pkg_id)
+      VALUES(?1, ?2, ?3, ?4, ?5, ?6 ) ",
+        (name, description, ty, hash, fav, pkg_id),
     )
     .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Derive data from image content of a newly inserted meme:
+/// media info, perceptual hash, color palette, thumbnail and animation metadata
+pub fn process_image_content<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    content: &str,
+) -> Result<(), String> {
+    update_media_info(conn, &base, meme_id, "image", content)?;
+    update_meme_phash(conn, &base, meme_id, content)?;
+    update_meme_colors(conn, &base, meme_id, content)?;
+    let size = load_settings(conn)?.thumbnail_size;
+    generate_thumbnail(&base, content, size)?;
+    update_meme_animation(conn, &base, meme_id, content, size)?;
     Ok(())
 }
 
-#[tauri::command]
-pub async fn add_meme_record(
-    db_state: tauri::State<'_, MemeDatabaseState>,
-    mut item: MemeToAdd,
+/// Derive data from content of a newly inserted meme of any type
+pub fn process_content<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    ty: &str,
+    content: &str,
 ) -> Result<(), String> {
-    // let mut connection = db_state.state.as_ref().unwrap().conn.lock().await;
-    let mut guard = db_state.state.lock().await;
-    let state = &mut guard.as_mut().unwrap();
+    match ty {
+        "image" => process_image_content(conn, base, meme_id, content),
+        "video" => process_video_content(conn, base, meme_id, content),
+        _ => update_media_info(conn, base, meme_id, ty, content).map(|_| ()),
+    }
+}
 
-    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+/// Query the meme whose content has the given sha256,
+/// content which has been compressed is found by the sha256 of its original
+pub fn query_meme_by_content_hash(conn: &Connection, hash: &str) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT id FROM meme WHERE hash = ?1 OR hash LIKE ?1 || '.%' OR hash IN
+          (SELECT hash FROM content_alias WHERE original LIKE ?1 || '.%') LIMIT 1",
+        [hash],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
 
-    let tag_id = item
-        .tags
-        .iter()
-        .map(|t| make_tag(&conn, &t.key, &t.value))
-        .collect::<Result<Vec<i64>, String>>()?;
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "status", content = "id", rename_all = "lowercase")]
+pub enum AddMemeResult {
+    Added(i64),
+    /// Content is already stored as another meme
+    Duplicate(i64),
+}
 
-    match item.ty.as_str() {
-        "image" => {
-            let new_content = copy_to_storage(&state.path, PathBuf::from(&item.content))
-                .map_err(|e| e.to_string())?;
-            item.content = new_content
-        }
-        "text" => {
-            let new_content = store_to_storage(&state.path, item.content.as_bytes(), Some("txt"))
-                .map_err(|e| e.to_string())?;
-            item.content = new_content
-        }
-        _ => {
-            unreachable!()
+/// Meme to be created by `create_meme_with_tags`
+#[derive(Debug)]
+pub struct NewMeme {
+    pub name: String,
+    pub description: Option<String>,
+    /// `image`, `video` or `text`
+    pub ty: String,
+    /// Path of image or video file, or text of text meme
+    pub content: String,
+    pub fav: bool,
+    pub tags: Vec<Tag>,
+    pub pkg_id: i64,
+    /// Remove EXIF and other metadata of image before storing
+    pub strip_exif: bool,
+    /// Url the meme is downloaded from
+    pub source_url: Option<String>,
+    pub author: Option<String>,
+    /// Where the meme is collected, such as name of a site, group chat or app
+    pub origin: Option<String>,
+    /// Id of the meme this one is edited from
+    pub derived_from: Option<i64>,
+}
+
+impl From<MemeToAdd> for NewMeme {
+    fn from(item: MemeToAdd) -> Self {
+        Self {
+            name: item.name,
+            description: item.description,
+            ty: item.ty,
+            content: item.content,
+            fav: item.fav,
+            tags: item.tags,
+            pkg_id: item.pkg_id,
+            strip_exif: item.strip_exif,
+            source_url: item.source_url,
+            author: item.author,
+            origin: item.origin,
+            derived_from: None,
         }
     }
+}
+
+/// Insert meme whose content is already stored locally, resolve and link its tags
+/// All rows are written in one transaction, which is rolled back if any step fails.
+/// Content is uploaded before the transaction starts, so database is not locked during it
+fn insert_meme_with_tags<P: AsRef<Path>>(
+    conn: &mut Connection,
+    storage: &dyn Storage,
+    base: P,
+    meme: &NewMeme,
+    content: &str,
+) -> Result<i64, String> {
+    store_content(storage, base.as_ref(), content)?;
+    let conn = conn.transaction().map_err(|e| e.to_string())?;
 
+    let tag_id = meme
+        .tags
+        .iter()
+        .map(|t| make_tag(&conn, &t.key, &t.value))
+        .collect::<Result<Vec<i64>, String>>()?;
+    let meme_id = insert_meme(
+        &conn,
+        &meme.name,
+        meme.description.as_deref(),
+        &meme.ty,
+        content,
+        meme.fav,
+        meme.pkg_id,
+    )?;
     conn.execute(
-        "INSERT INTO meme(name, description, ty, hash, fav, pkg_id)
-      VALUES(?1, ?2, ?3, ?4, ?5, ?6 ) ",
+        "UPDATE meme SET source_url = ?1, author = ?2, origin = ?3, derived_from = ?4
+          WHERE id = ?5",
         (
-            item.name,
-            item.description,
-            item.ty,
-            item.content,
-            item.fav,
-            item.pkg_id,
+            &meme.source_url,
+            &meme.author,
+            &meme.origin,
+            meme.derived_from,
+            meme_id,
         ),
     )
     .map_err(|e| e.to_string())?;
-
-    let meme_id = conn.last_insert_rowid();
+    process_content(&conn, &base, meme_id, &meme.ty, content)?;
     for tid in tag_id {
         insert_meme_tag(&conn, meme_id, tid)?;
     }
 
     conn.commit().map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(meme_id)
+}
+
+/// Store content of meme, insert it and link its tags as a whole.
+/// If any step fails, database is rolled back and stored content is released,
+/// so no meme is left with partial tags
+pub fn create_meme_with_tags<P: AsRef<Path>>(
+    conn: &mut Connection,
+    storage: &dyn Storage,
+    base: P,
+    meme: NewMeme,
+) -> Result<AddMemeResult, String> {
+    let settings = load_settings(conn)?;
+    let pending = match meme.ty.as_str() {
+        "image" | "video" => Some(PendingFile::open(
+            Path::new(&meme.content),
+            meme.strip_exif && meme.ty == "image",
+            settings.import_format,
+        )?),
+        "text" => None,
+        ty => return Err(format!("unknown meme type {}", ty)),
+    };
+    let hash = match &pending {
+        Some(pending) => pending.hash.clone(),
+        None => sha256::digest(meme.content.as_bytes()),
+    };
+    if let Some(id) = query_meme_by_content_hash(conn, &hash)? {
+        return Ok(AddMemeResult::Duplicate(id));
+    }
+
+    let content = match &pending {
+        Some(pending) => pending.store(&base, settings.keep_original_format)?,
+        None => publish_bytes(base.as_ref(), meme.content.as_bytes(), Some("txt"))
+            .map_err(|e| e.to_string())?,
+    };
+    let result = match insert_meme_with_tags(conn, storage, &base, &meme, &content) {
+        Ok(meme_id) => Ok(AddMemeResult::Added(meme_id)),
+        Err(e) => {
+            // content is not referenced by any meme after rolling back,
+            // error of releasing it is less relevant than the original one
+            let _ = release_content(conn, storage, &base, &content);
+            Err(e)
+        }
+    };
+    if let Err(e) = settle_staging(conn, storage, base.as_ref()) {
+        warn!(error = %e, "failed to settle staged content");
+    }
+    result
 }
 
+#[tauri::command]
+pub async fn add_meme_record(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    item: MemeToAdd,
+) -> AppResult<AddMemeResult> {
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    Ok(create_meme_with_tags(
+        &mut state.conn,
+        state.storage.as_ref(),
+        &state.path,
+        item.into(),
+    )?)
+}
+
+/// Overwrite fields and tags of meme. If `expected_version` is given, it fails with
+/// `AppError::Conflict` when meme has been edited since it was loaded with that version.
+/// Using, compressing or indexing meme does not change its version.
+/// Return version of meme after the edit, to be passed with a following edit
 #[tauri::command]
 pub async fn update_meme_record(
     db_state: tauri::State<'_, MemeDatabaseState>,
     meme_id: i64,
     item: MemeToAdd,
-) -> Result<(), String> {
+    expected_version: Option<i64>,
+) -> AppResult<i64> {
     // let mut connection = db_state.state.as_ref().unwrap().conn.lock().await;
     let mut guard = db_state.state.lock().await;
-    let state = &mut guard.as_mut().unwrap();
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
 
     let conn = state.conn.transaction().map_err(|e| e.to_string())?;
 
+    if let Some(expected) = expected_version {
+        let version: i64 = conn
+            .query_row("SELECT version FROM meme WHERE id = ?1", [meme_id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("meme {}", meme_id)))?;
+        if version != expected {
+            return Err(AppError::Conflict(format!(
+                "meme {} has been changed since it was loaded",
+                meme_id
+            )));
+        }
+    }
+
     let tag_id = item
         .tags
         .iter()
         .map(|t| make_tag(&conn, &t.key, &t.value))
         .collect::<Result<Vec<i64>, String>>()?;
 
+    let mut journal = JournalRecorder::new("Edit meme");
+    journal.capture(&conn, meme_id)?;
     conn.execute(
-        "UPDATE meme SET name = ?1, description = ?2, fav = ?3, pkg_id = ?4 WHERE id = ?5",
-        (item.name, item.description, item.fav, item.pkg_id, meme_id),
+        "UPDATE meme SET name = ?1, description = ?2, fav = ?3, pkg_id = ?4, source_url = ?5,
+          author = ?6, origin = ?7 WHERE id = ?8",
+        (
+            item.name,
+            item.description,
+            item.fav,
+            item.pkg_id,
+            item.source_url,
+            item.author,
+            item.origin,
+            meme_id,
+        ),
     )
     .map_err(|e| e.to_string())?;
 
@@ -153,54 +476,262 @@ pub async fn update_meme_record(
     for tid in tag_id {
         insert_meme_tag(&conn, meme_id, tid)?;
     }
+    journal.finish(&conn)?;
+    let version = conn.query_row("SELECT version FROM meme WHERE id = ?1", [meme_id], |row| {
+        row.get(0)
+    })?;
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(version)
+}
+
+/// Apply tags to all given memes in one transaction, tags which are already linked are ignored
+pub fn link_tags(conn: &mut Connection, meme_ids: &[i64], tags: &[Tag]) -> Result<(), String> {
+    let conn = conn.transaction().map_err(|e| e.to_string())?;
+
+    let tag_id = tags
+        .iter()
+        .map(|t| make_tag(&conn, &t.key, &t.value))
+        .collect::<Result<Vec<i64>, String>>()?;
+
+    let mut journal = JournalRecorder::new("Link tags");
+    for meme_id in meme_ids {
+        journal.capture(&conn, *meme_id)?;
+        for tid in &tag_id {
+            insert_meme_tag(&conn, *meme_id, *tid)?;
+        }
+    }
+    journal.finish(&conn)?;
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Apply tags to all given memes, tags which are already linked are ignored
+#[tauri::command]
+pub async fn bulk_link_tags(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    meme_ids: Vec<i64>,
+    tags: Vec<Tag>,
+) -> AppResult<()> {
+    let mut guard = db_state.state.lock().await;
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
+    Ok(link_tags(&mut state.conn, &meme_ids, &tags)?)
+}
+
+/// Remove tags from all given memes in one transaction, tags not linked are ignored
+/// Tags implied by removed tags are kept
+pub fn unlink_tags(conn: &mut Connection, meme_ids: &[i64], tags: &[Tag]) -> Result<(), String> {
+    let conn = conn.transaction().map_err(|e| e.to_string())?;
+
+    let mut journal = JournalRecorder::new("Unlink tags");
+    for meme_id in meme_ids {
+        journal.capture(&conn, *meme_id)?;
+        for tag in tags {
+            conn.execute(
+                "DELETE FROM meme_tag WHERE meme_id = ?1
+                  AND tag_id = (SELECT id FROM tag WHERE key = ?2 AND value = ?3)",
+                (meme_id, &tag.key, &tag.value),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    journal.finish(&conn)?;
 
     conn.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Remove tags from all given memes, tags not linked are ignored
+/// Tags implied by removed tags are kept
+#[tauri::command]
+pub async fn bulk_unlink_tags(
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    meme_ids: Vec<i64>,
+    tags: Vec<Tag>,
+) -> AppResult<()> {
+    let mut guard = db_state.state.lock().await;
+    let state = &mut guard.as_mut().ok_or_else(library_not_opened)?;
+    Ok(unlink_tags(&mut state.conn, &meme_ids, &tags)?)
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SearchPage {
+    pub items: Vec<MemeQueried>,
+    /// Number of all matched memes
+    pub total: i64,
+    page_size: i64,
+    /// Pass it to get the next page, `None` if this is the last page
+    pub next_cursor: Option<String>,
+    /// Where search terms occur in `items`, keyed by meme id
+    pub highlights: HashMap<i64, Vec<Highlight>>,
+}
+
+/// Search SQL of `stmt` restricted to memes in trash or not, and to favorites if `fav`.
+/// Private memes are left out unless they are unlocked
+pub fn build_listing_sql(
+    state: &MemeDatabaseConnection,
+    stmt: &str,
+    fav: bool,
+    trash: bool,
+) -> AppResult<SearchSql> {
+    let mut search = build_search_sql(stmt)?;
+    search.sql.push_str("trash = ? ");
+    search.params.push(trash.into());
+    search.sql.push_str(private_filter(state.private_unlocked));
+    if fav {
+        search.sql.push_str(" AND fav = ? ");
+        search.params.push(fav.into());
+    }
+    Ok(search)
+}
+
+/// Page of memes matching search statement `stmt` starting at `cursor`, the first page if `None`.
+/// page size and order fall back to settings of library if they are not given
+pub fn search_memes(
+    state: &MemeDatabaseConnection,
+    stmt: &str,
+    cursor: Option<&str>,
+    page_size: Option<i64>,
+    fav: bool,
+    trash: bool,
+    sort_by: Option<SortBy>,
+) -> AppResult<SearchPage> {
+    let cursor = cursor
+        .map(|x| {
+            Cursor::decode(x).ok_or_else(|| AppError::InvalidArgument(format!("cursor {}", x)))
+        })
+        .transpose()?;
+    let settings = load_settings(&state.conn)?;
+    let page_size = page_size.unwrap_or(settings.page_size);
+    let search = build_listing_sql(state, stmt, fav, trash)?;
+    let mut sql_stmt = search.sql;
+    let mut params = search.params;
+
+    // paging through result runs the same statements again, only with different cursor
+    let total: i64 = state
+        .conn
+        .prepare_cached(&format!("SELECT COUNT(*) FROM ({})", sql_stmt))
+        .and_then(|mut query| query.query_row(params_from_iter(&params), |row| row.get(0)))
+        .map_err(|e| e.to_string())?;
+
+    let order_by = sort_by
+        .or(settings.default_sort)
+        .and_then(|x| x.order_by())
+        .unwrap_or(search.order_by.as_str());
+    let keyset = order_by == KEYSET_ORDER;
+    let offset = match cursor {
+        Some(Cursor::After { update_time, id }) if keyset => {
+            sql_stmt.push_str(" AND (update_time, meme.id) < (?, ?) ");
+            params.push(update_time.into());
+            params.push(id.into());
+            0
+        }
+        Some(Cursor::Offset(offset)) if !keyset => offset,
+        Some(_) => {
+            return Err(AppError::InvalidArgument(
+                "cursor is from a search in another order".to_owned(),
+            ))
+        }
+        None => 0,
+    };
+    if keyset {
+        sql_stmt.push_str(&format!("ORDER BY {}, meme.id DESC LIMIT ?", order_by));
+    } else {
+        sql_stmt.push_str(&format!("ORDER BY {} LIMIT ? OFFSET ?", order_by));
+    }
+    // one more meme tells whether there is a next page
+    params.push((page_size + 1).into());
+    if !keyset {
+        params.push(offset.into());
+    }
+
+    debug!(sql = %sql_stmt.replace("\n", "").replace("  ", " "), "search memes");
+    let mut query = state.conn.prepare_cached(&sql_stmt)?;
+    let mut result = query
+        .query_map(params_from_iter(&params), |row| {
+            meme_from_row(row, &state.path)
+        })?
+        .collect::<Result<Vec<MemeQueried>, Error>>()?;
+    let next_cursor = if result.len() as i64 > page_size {
+        result.truncate(page_size as usize);
+        let cursor = match result.last() {
+            Some(meme) if keyset => Cursor::After {
+                update_time: meme.update_time.clone(),
+                id: meme.id,
+            },
+            _ => Cursor::Offset(offset + page_size),
+        };
+        Some(cursor.encode())
+    } else {
+        None
+    };
+    fetch_listed_content(state, &result);
+    let ids = result.iter().map(|meme| meme.id).collect::<Vec<i64>>();
+    let highlights = query_highlights(&state.conn, &search.terms, &ids)?;
+
+    Ok(SearchPage {
+        items: result,
+        total,
+        page_size,
+        next_cursor,
+        highlights,
+    })
+}
+
 #[tauri::command]
 pub async fn search_meme(
     state: tauri::State<'_, MemeDatabaseState>,
     stmt: String,
-    page: i64,
+    cursor: Option<String>,
+    page_size: Option<i64>,
     fav: bool,
     trash: bool,
-) -> Result<Vec<MemeQueried>, String> {
+    sort_by: Option<SortBy>,
+) -> AppResult<SearchPage> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
-    let mut sql_stmt = build_search_sql(&stmt).map_err(|e| e.to_string())?;
-    sql_stmt.push_str(&format!("trash == {} ", trash));
-    if fav {
-        sql_stmt.push_str(&format!(" AND fav == {} ", fav));
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let result = search_memes(
+        state,
+        &stmt,
+        cursor.as_deref(),
+        page_size,
+        fav,
+        trash,
+        sort_by,
+    )?;
+    // paging through result is not another use of the search
+    if cursor.is_none() {
+        record_search(&state.conn, &stmt)?;
     }
-    sql_stmt.push_str(&format!(
-        "ORDER BY update_time DESC LIMIT 30 OFFSET {}",
-        30 * page
-    ));
+    Ok(result)
+}
 
-    println!("{}", sql_stmt.replace("\n", "").replace("  ", " "));
-    let mut query = state.conn.prepare(&sql_stmt).map_err(|e| e.to_string())?;
-    let result = query
-        .query_map([], |row| {
-            let hash: String = row.get("hash").unwrap();
-            Ok(MemeQueried {
-                id: row.get("id").unwrap(),
-                name: row.get("name").unwrap(),
-                description: row.get("description").ok(),
-                ty: row.get("ty").unwrap(),
-                path: compute_path(&state.path, &hash)
-                    .to_str()
-                    .unwrap()
-                    .to_owned(),
-                hash: hash,
-                fav: row.get("fav").unwrap(),
-                trash: row.get("trash").unwrap(),
-                pkg_id: row.get("pkg_id").unwrap(),
-            })
-        })
-        .unwrap()
-        .collect::<Result<Vec<MemeQueried>, Error>>()
-        .map_err(|e| e.to_string())?;
+/// Download content of listed memes which is only in remote storage, so their `path` can be read.
+/// Memes whose content can not be fetched are still listed, with only their thumbnail shown
+pub fn fetch_listed_content<'a>(
+    state: &MemeDatabaseConnection,
+    memes: impl IntoIterator<Item = &'a MemeQueried>,
+) {
+    for meme in memes {
+        if let Err(e) = fetch_content(state.storage.as_ref(), &state.path, &meme.hash) {
+            warn!(hash = %meme.hash, error = %e, "failed to fetch content of listed meme");
+        }
+    }
+}
+
+/// Meme `id` with its content ready to be read, downloaded if it is only in remote storage
+pub fn query_meme_by_id(state: &MemeDatabaseConnection, id: i64) -> AppResult<MemeQueried> {
+    let result = state
+        .conn
+        .prepare_cached(&format!("SELECT * FROM {} WHERE id = ?1", MEME_TABLE))?
+        .query_row([id], |row| meme_from_row(row, &state.path))
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("meme {}", id)))?;
+    // content may only be in remote storage
+    fetch_content(state.storage.as_ref(), &state.path, &result.hash)
+        .map_err(AppError::StorageUnavailable)?;
+    verify_on_read(&state.conn, &state.path, &result.hash)?;
 
     Ok(result)
 }
@@ -209,30 +740,40 @@ pub async fn search_meme(
 pub async fn get_meme_by_id(
     state: tauri::State<'_, MemeDatabaseState>,
     id: i64,
-) -> Result<MemeQueried, String> {
+) -> AppResult<MemeQueried> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    query_meme_by_id(state, id)
+}
+
+/// A meme picked uniformly at random from memes not in trash,
+/// only from memes matching search statement `filter` if it is given.
+/// Return `None` if no meme matches
+#[tauri::command]
+pub async fn get_random_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    filter: Option<String>,
+) -> AppResult<Option<MemeQueried>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let search = build_search_sql(filter.as_deref().unwrap_or(""))?;
     let result = state
         .conn
-        .query_row("SELECT * FROM meme WHERE id = ?1", [id], |row| {
-            let hash: String = row.get("hash").unwrap();
-            Ok(MemeQueried {
-                id: row.get("id").unwrap(),
-                name: row.get("name").unwrap(),
-                description: row.get("description").ok(),
-                ty: row.get("ty").unwrap(),
-                path: compute_path(&state.path, &hash)
-                    .to_str()
-                    .unwrap()
-                    .to_owned(),
-                hash: hash,
-                fav: row.get("fav").unwrap(),
-                trash: row.get("trash").unwrap(),
-                pkg_id: row.get("pkg_id").unwrap(),
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
+        .query_row(
+            &format!(
+                "{} trash = false {} ORDER BY RANDOM() LIMIT 1",
+                search.sql,
+                private_filter(state.private_unlocked)
+            ),
+            params_from_iter(&search.params),
+            |row| meme_from_row(row, &state.path),
+        )
+        .optional()?;
+    if let Some(meme) = &result {
+        fetch_content(state.storage.as_ref(), &state.path, &meme.hash)
+            .map_err(AppError::StorageUnavailable)?;
+        verify_on_read(&state.conn, &state.path, &meme.hash)?;
+    }
     Ok(result)
 }
 
@@ -240,14 +781,11 @@ pub async fn get_meme_by_id(
 pub async fn delete_meme_by_id(
     state: tauri::State<'_, MemeDatabaseState>,
     id: i64,
-) -> Result<(), String> {
+) -> AppResult<()> {
     let mut guard = state.state.lock().await;
-    let state = guard.as_mut().unwrap();
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
     let conn = state.conn.transaction().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM meme_tag WHERE meme_id = ?1", [id])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM meme WHERE id = ?1", [id])
-        .map_err(|e| e.to_string())?;
+    delete_meme_rows(&conn, id)?;
     conn.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
@@ -257,56 +795,111 @@ pub async fn trash_meme_by_id(
     state: tauri::State<'_, MemeDatabaseState>,
     id: i64,
     trash: bool,
-) -> Result<(), String> {
+) -> AppResult<()> {
     let mut guard = state.state.lock().await;
-    let state = guard.as_mut().unwrap();
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
     let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let mut journal = JournalRecorder::new(if trash { "Move to trash" } else { "Restore" });
+    journal.capture(&conn, id)?;
     conn.execute("UPDATE meme SET trash = ?1 WHERE id = ?2", (trash, id))
         .map_err(|e| e.to_string())?;
+    journal.finish(&conn)?;
     conn.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-#[tauri::command]
-pub async fn get_tags_by_id(
-    state: tauri::State<'_, MemeDatabaseState>,
-    id: i64,
-) -> Result<Vec<Tag>, String> {
-    let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
-
-    let mut query = state.conn.prepare("SELECT key, value FROM tag LEFT JOIN meme_tag ON tag.id = meme_tag.tag_id WHERE meme_tag.meme_id = ?1").unwrap();
+pub fn query_meme_tags(conn: &Connection, id: i64) -> Result<Vec<Tag>, String> {
+    let mut query = conn
+        .prepare_cached(
+            "SELECT key, value FROM tag LEFT JOIN meme_tag ON tag.id = meme_tag.tag_id
+              WHERE meme_tag.meme_id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
     let result = query
         .query_map([id], |row| {
             Ok(Tag {
-                key: row.get("key").unwrap(),
-                value: row.get("value").unwrap(),
+                key: row.get("key")?,
+                value: row.get("value")?,
             })
         })
-        .unwrap()
+        .map_err(|e| e.to_string())?
         .collect::<Result<Vec<Tag>, Error>>()
         .map_err(|e| e.to_string())?;
 
     Ok(result)
 }
 
+/// Tags of memes `ids` in one query, every meme in `ids` has an entry even if it has no tag
+pub fn query_tags_for_memes(
+    conn: &Connection,
+    ids: &[i64],
+) -> Result<HashMap<i64, Vec<Tag>>, String> {
+    let mut result: HashMap<i64, Vec<Tag>> = ids.iter().map(|id| (*id, Vec::new())).collect();
+    if ids.is_empty() {
+        return Ok(result);
+    }
+    let mut query = conn
+        .prepare(&format!(
+            "SELECT meme_tag.meme_id, key, value FROM tag
+              LEFT JOIN meme_tag ON tag.id = meme_tag.tag_id
+              WHERE meme_tag.meme_id IN ({})",
+            vec!["?"; ids.len()].join(", ")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map(params_from_iter(ids), |row| {
+            Ok((
+                row.get(0)?,
+                Tag {
+                    key: row.get(1)?,
+                    value: row.get(2)?,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, Tag)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    for (meme_id, tag) in rows {
+        result.entry(meme_id).or_default().push(tag);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn get_tags_by_id(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<Vec<Tag>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_meme_tags(&state.conn, id)?)
+}
+
+/// Tags of a page of memes, keyed by meme id
+#[tauri::command]
+pub async fn get_tags_by_ids(
+    state: tauri::State<'_, MemeDatabaseState>,
+    ids: Vec<i64>,
+) -> AppResult<HashMap<i64, Vec<Tag>>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_tags_for_memes(&state.conn, &ids)?)
+}
+
 #[tauri::command]
 pub async fn get_tag_keys_by_prefix(
     state: tauri::State<'_, MemeDatabaseState>,
     prefix: String,
-) -> Result<Vec<String>, String> {
+) -> AppResult<Vec<String>> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
 
     let mut query = state
         .conn
-        .prepare("SELECT DISTINCT(key) FROM tag WHERE key LIKE ?1")
-        .unwrap();
+        .prepare("SELECT DISTINCT(key) FROM tag WHERE key LIKE ?1")?;
     let keys = query
-        .query_map([format!("{}%", prefix)], |r| Ok(r.get(0).unwrap()))
-        .unwrap()
-        .collect::<Result<Vec<String>, Error>>()
-        .map_err(|e| e.to_string())?;
+        .query_map([format!("{}%", prefix)], |r| r.get(0))?
+        .collect::<Result<Vec<String>, Error>>()?;
 
     Ok(keys)
 }
@@ -316,24 +909,21 @@ pub async fn get_tags_by_prefix(
     state: tauri::State<'_, MemeDatabaseState>,
     key: String,
     prefix: String,
-) -> Result<Vec<Tag>, String> {
+) -> AppResult<Vec<Tag>> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
 
     let mut query = state
         .conn
-        .prepare("SELECT key,value FROM tag WHERE key = ?1 AND value LIKE ?2")
-        .unwrap();
+        .prepare("SELECT key,value FROM tag WHERE key = ?1 AND value LIKE ?2")?;
     let tags = query
         .query_map([key, format!("{}%", prefix)], |r| {
             Ok(Tag {
-                key: r.get("key").unwrap(),
-                value: r.get("value").unwrap(),
+                key: r.get("key")?,
+                value: r.get("value")?,
             })
-        })
-        .unwrap()
-        .collect::<Result<Vec<Tag>, Error>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<Tag>, Error>>()?;
 
     Ok(tags)
 }
@@ -342,41 +932,41 @@ pub async fn get_tags_by_prefix(
 pub async fn get_tags_fuzzy(
     state: tauri::State<'_, MemeDatabaseState>,
     keyword: String,
-) -> Result<Vec<Tag>, String> {
+) -> AppResult<Vec<Tag>> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
 
     let mut query = state
         .conn
-        .prepare("SELECT key,value FROM tag WHERE value LIKE ?1")
-        .unwrap();
+        .prepare("SELECT key,value FROM tag WHERE value LIKE ?1")?;
     let tags = query
         .query_map([format!("%{}%", keyword)], |r| {
             Ok(Tag {
-                key: r.get("key").unwrap(),
-                value: r.get("value").unwrap(),
+                key: r.get("key")?,
+                value: r.get("value")?,
             })
-        })
-        .unwrap()
-        .collect::<Result<Vec<Tag>, Error>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<Tag>, Error>>()?;
     Ok(tags)
 }
 
-fn get_relate_tag_single(key: &str, value: &str, conn: &Connection) -> Result<Vec<Tag>, String> {
-    let mut query=  conn.prepare("SELECT key, value FROM meme_tag  LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE (key !=  ?1 OR value != ?2) AND meme_id IN (
-	    SELECT meme_id FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id WHERE key = ?1 AND value = ?2
-    )").unwrap();
+/// Tags linked to memes which have tag `key:value`, a link to a missing tag is `Corrupted`
+fn get_relate_tag_single(key: &str, value: &str, conn: &Connection) -> AppResult<Vec<Tag>> {
+    let mut query = conn.prepare(
+        "SELECT key, value FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id
+          WHERE (key IS NOT ?1 OR value IS NOT ?2) AND meme_id IN (
+            SELECT meme_id FROM meme_tag LEFT JOIN tag ON meme_tag.tag_id = tag.id
+              WHERE key = ?1 AND value = ?2
+          )",
+    )?;
     let tags = query
         .query_map([key, value], |row| {
             Ok(Tag {
-                key: row.get("key").unwrap(),
-                value: row.get("value").unwrap(),
+                key: row.get("key")?,
+                value: row.get("value")?,
             })
-        })
-        .unwrap()
-        .collect::<Result<Vec<Tag>, Error>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<Tag>, Error>>()?;
     Ok(tags)
 }
 
@@ -391,13 +981,13 @@ pub struct TagFreq {
 pub async fn get_tags_related(
     state: tauri::State<'_, MemeDatabaseState>,
     tags: Vec<Tag>,
-) -> Result<Vec<TagFreq>, String> {
+) -> AppResult<Vec<TagFreq>> {
     let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
     let mut freq = tags
         .iter()
         .map(|item| get_relate_tag_single(&item.key, &item.value, &state.conn))
-        .collect::<Result<Vec<Vec<Tag>>, String>>()?
+        .collect::<AppResult<Vec<Vec<Tag>>>>()?
         .into_iter()
         .flatten()
         .filter(|x| !tags.contains(x))
@@ -412,7 +1002,7 @@ pub async fn get_tags_related(
             freq: v.1 as u32,
         })
         .collect::<Vec<TagFreq>>();
-    freq.sort_by(|l, r| u32::partial_cmp(&r.freq, &l.freq).unwrap());
+    freq.sort_by(|l, r| r.freq.cmp(&l.freq));
     Ok(freq)
 }
 
@@ -421,13 +1011,16 @@ pub async fn set_meme_fav(
     state: tauri::State<'_, MemeDatabaseState>,
     id: i32,
     fav: bool,
-) -> Result<(), String> {
-    let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
-    state
-        .conn
-        .execute("UPDATE meme SET fav = ?1 WHERE id = ?2", (fav, id))
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let mut journal = JournalRecorder::new(if fav { "Favorite" } else { "Unfavorite" });
+    journal.capture(&conn, id.into())?;
+    conn.execute("UPDATE meme SET fav = ?1 WHERE id = ?2", (fav, id))
         .map_err(|e| e.to_string())?;
+    journal.finish(&conn)?;
+    conn.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -436,12 +1029,297 @@ pub async fn set_meme_trash(
     state: tauri::State<'_, MemeDatabaseState>,
     id: i32,
     trash: bool,
-) -> Result<(), String> {
-    let guard = state.state.lock().await;
-    let state = guard.as_ref().unwrap();
-    state
-        .conn
-        .execute("UPDATE meme SET trash = ?1 WHERE id = ?2", (trash, id))
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let mut journal = JournalRecorder::new(if trash { "Move to trash" } else { "Restore" });
+    journal.capture(&conn, id.into())?;
+    conn.execute("UPDATE meme SET trash = ?1 WHERE id = ?2", (trash, id))
         .map_err(|e| e.to_string())?;
+    journal.finish(&conn)?;
+    conn.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
+
+/// Set `column` of all given memes to `value` as a single undoable operation,
+/// nothing is changed if any of them does not exist
+fn set_flag_bulk(
+    conn: &mut Connection,
+    ids: &[i64],
+    column: &str,
+    value: bool,
+    description: &str,
+) -> AppResult<()> {
+    let conn = conn.transaction()?;
+    let mut journal = JournalRecorder::new(description);
+    for id in ids {
+        journal.capture(&conn, *id)?;
+        let updated = conn.execute(
+            &format!("UPDATE meme SET {} = ?1 WHERE id = ?2", column),
+            (value, id),
+        )?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!("meme {}", id)));
+        }
+    }
+    journal.finish(&conn)?;
+    conn.commit()?;
+    Ok(())
+}
+
+/// Move all given memes to trash or restore them in one transaction
+#[tauri::command]
+pub async fn set_trash_bulk(
+    state: tauri::State<'_, MemeDatabaseState>,
+    ids: Vec<i64>,
+    value: bool,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let description = if value { "Move to trash" } else { "Restore" };
+    set_flag_bulk(&mut state.conn, &ids, "trash", value, description)
+}
+
+/// Favorite or unfavorite all given memes in one transaction
+#[tauri::command]
+pub async fn set_fav_bulk(
+    state: tauri::State<'_, MemeDatabaseState>,
+    ids: Vec<i64>,
+    value: bool,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let description = if value { "Favorite" } else { "Unfavorite" };
+    set_flag_bulk(&mut state.conn, &ids, "fav", value, description)
+}
+
+/// Remove all given memes permanently in one transaction, content files no longer
+/// referenced are deleted. Nothing is removed from database if any of them fails
+#[tauri::command]
+pub async fn delete_bulk(
+    state: tauri::State<'_, MemeDatabaseState>,
+    ids: Vec<i64>,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    let mut released = Vec::new();
+    for id in ids {
+        released.extend(purge_meme(&conn, id)?);
+    }
+    conn.commit()?;
+    released.sort();
+    released.dedup();
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(())
+}
+
+pub const MAX_RATING: u8 = 5;
+
+pub fn update_meme_rating(conn: &Connection, id: i64, rating: u8) -> Result<(), String> {
+    if rating > MAX_RATING {
+        return Err(format!("rating should be between 0 and {}", MAX_RATING));
+    }
+    conn.execute("UPDATE meme SET rating = ?1 WHERE id = ?2", (rating, id))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_meme_rating(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    rating: u8,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let mut journal = JournalRecorder::new("Rate meme");
+    journal.capture(&conn, id)?;
+    update_meme_rating(&conn, id, rating)?;
+    journal.finish(&conn)?;
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Count one more use of meme and remember it is used just now
+pub fn record_meme_use(conn: &Connection, id: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE meme SET use_count = use_count + 1, last_used = CURRENT_TIMESTAMP WHERE id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record that meme is used (e.g. copied to clipboard)
+#[tauri::command]
+pub async fn mark_meme_used(
+    app: AppHandle,
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    {
+        let guard = state.state.lock().await;
+        let state = guard.as_ref().ok_or_else(library_not_opened)?;
+        record_meme_use(&state.conn, id)?;
+    }
+    refresh_tray(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tauri::Manager;
+    use tokio::sync::Mutex;
+
+    use crate::{
+        db::{fixtures::seeded_library, MemeDatabaseState},
+        error::AppError,
+        meme::{
+            get_meme_by_id, get_relate_tag_single, link_tags, query_meme_tags, record_meme_use,
+            unlink_tags, update_meme_record, MemeToAdd, Tag,
+        },
+    };
+
+    fn tag(key: &str, value: &str) -> Tag {
+        Tag {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    fn tag_names(tags: Vec<Tag>) -> Vec<String> {
+        let mut names = tags
+            .into_iter()
+            .map(|tag| format!("{}:{}", tag.key, tag.value))
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_link_tags() {
+        let (mut state, ids) = seeded_library();
+        let (cat, dog) = (ids[0], ids[2]);
+
+        link_tags(&mut state.conn, &[cat, dog], &[tag("source", "web")]).unwrap();
+        // linking again is ignored
+        link_tags(&mut state.conn, &[cat], &[tag("source", "web")]).unwrap();
+        assert_eq!(
+            tag_names(query_meme_tags(&state.conn, cat).unwrap()),
+            vec!["character:cat", "mood:happy", "source:web"]
+        );
+
+        unlink_tags(&mut state.conn, &[cat, dog], &[tag("mood", "happy")]).unwrap();
+        assert_eq!(
+            tag_names(query_meme_tags(&state.conn, dog).unwrap()),
+            vec!["character:dog", "source:web"]
+        );
+        // tags not linked are ignored
+        unlink_tags(&mut state.conn, &[cat], &[tag("mood", "sad")]).unwrap();
+        assert_eq!(query_meme_tags(&state.conn, cat).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_related_tags() {
+        let (state, ids) = seeded_library();
+        let mut related = get_relate_tag_single("character", "cat", &state.conn)
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.value)
+            .collect::<Vec<String>>();
+        related.sort();
+        assert_eq!(related, vec!["happy", "sad"]);
+
+        // link to a missing tag is reported instead of panicking
+        state
+            .conn
+            .execute_batch("PRAGMA foreign_keys = OFF")
+            .unwrap();
+        state
+            .conn
+            .execute("INSERT INTO meme_tag(meme_id, tag_id) VALUES (?1, -1)", [ids[0]])
+            .unwrap();
+        assert!(matches!(
+            get_relate_tag_single("character", "cat", &state.conn),
+            Err(AppError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn test_command_without_library() {
+        let app = tauri::test::mock_app();
+        app.manage(MemeDatabaseState::default());
+        let result = tauri::async_runtime::block_on(get_meme_by_id(app.state(), 1));
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_version_counts_edits_only() {
+        let (mut state, ids) = seeded_library();
+        let version = |conn: &rusqlite::Connection| -> i64 {
+            conn.query_row("SELECT version FROM meme WHERE id = ?1", [ids[0]], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        let before = version(&state.conn);
+
+        record_meme_use(&state.conn, ids[0]).unwrap();
+        state
+            .conn
+            .execute("UPDATE meme SET phash = 1 WHERE id = ?1", [ids[0]])
+            .unwrap();
+        assert_eq!(version(&state.conn), before);
+
+        state
+            .conn
+            .execute("UPDATE meme SET name = 'renamed' WHERE id = ?1", [ids[0]])
+            .unwrap();
+        assert_eq!(version(&state.conn), before + 1);
+        link_tags(&mut state.conn, &[ids[0]], &[tag("source", "web")]).unwrap();
+        assert_eq!(version(&state.conn), before + 2);
+    }
+
+    #[test]
+    fn test_update_returns_version() {
+        let (state, ids) = seeded_library();
+        let before: i64 = state
+            .conn
+            .query_row("SELECT version FROM meme WHERE id = ?1", [ids[0]], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let app = tauri::test::mock_app();
+        app.manage(MemeDatabaseState {
+            state: Mutex::new(Some(state)),
+        });
+        let item = |name: &str| -> MemeToAdd {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "description": null,
+                "ty": "text",
+                "content": "",
+                "fav": false,
+                "tags": [],
+                "pkg_id": 0,
+            }))
+            .unwrap()
+        };
+        let update = |name, version| {
+            tauri::async_runtime::block_on(update_meme_record(
+                app.state(),
+                ids[0],
+                item(name),
+                Some(version),
+            ))
+        };
+
+        let after = update("first", before).unwrap();
+        assert!(after > before);
+        // a second edit from the same page passes the version of the first one
+        assert!(update("second", after).unwrap() > after);
+        assert!(matches!(update("stale", after), Err(AppError::Conflict(_))));
+    }
+}
@@ -0,0 +1,156 @@
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    tag_rule::{merge_tag, resolve_alias},
+};
+
+/// Display metadata of a namespace (`key` of tags)
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Namespace {
+    pub key: String,
+    /// CSS color value
+    pub color: Option<String>,
+    /// Namespaces with higher priority are listed first
+    #[serde(default)]
+    pub priority: i64,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct NamespaceInfo {
+    #[serde(flatten)]
+    namespace: Namespace,
+    /// Number of tags in namespace
+    count: i64,
+}
+
+/// Move every tag of namespace `old` to `new`,
+/// tags which already exist in `new` are merged into the existing ones
+pub fn rename_tag_namespace(conn: &Connection, old: &str, new: &str) -> Result<(), String> {
+    let mut query = conn
+        .prepare("SELECT id, value FROM tag WHERE key = ?1")
+        .map_err(|e| e.to_string())?;
+    let tags = query
+        .query_map([old], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    for (id, value) in tags {
+        let existing = match resolve_alias(conn, new, &value)? {
+            Some(existing) => Some(existing),
+            None => conn
+                .query_row(
+                    "SELECT id FROM tag WHERE key = ?1 AND value = ?2",
+                    (new, &value),
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?,
+        };
+        match existing {
+            Some(existing) if existing != id => merge_tag(conn, id, existing)?,
+            _ => {
+                conn.execute("UPDATE tag SET key = ?1 WHERE id = ?2", (new, id))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    conn.execute(
+        "UPDATE OR REPLACE tag_alias SET key = ?2 WHERE key = ?1",
+        (old, new),
+    )
+    .map_err(|e| e.to_string())?;
+    // metadata of `new` is kept if both have it
+    conn.execute(
+        "UPDATE OR IGNORE namespace SET key = ?2 WHERE key = ?1",
+        (old, new),
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM namespace WHERE key = ?1", [old])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// All namespaces used by tags or having metadata, ordered by priority
+#[tauri::command]
+pub async fn list_namespaces(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<NamespaceInfo>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare(
+            "WITH used(key) AS (SELECT key FROM tag UNION SELECT key FROM namespace)
+            SELECT used.key, namespace.color, IFNULL(namespace.priority, 0) AS priority,
+              namespace.description, (SELECT COUNT(*) FROM tag WHERE tag.key = used.key) AS count
+            FROM used LEFT JOIN namespace ON namespace.key = used.key
+            ORDER BY priority DESC, used.key",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([], |row| {
+            Ok(NamespaceInfo {
+                namespace: Namespace {
+                    key: row.get(0)?,
+                    color: row.get(1)?,
+                    priority: row.get(2)?,
+                    description: row.get(3)?,
+                },
+                count: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<NamespaceInfo>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn set_namespace(
+    state: tauri::State<'_, MemeDatabaseState>,
+    namespace: Namespace,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "INSERT OR REPLACE INTO namespace(key, color, priority, description)
+              VALUES (?1, ?2, ?3, ?4)",
+            (
+                &namespace.key,
+                &namespace.color,
+                namespace.priority,
+                &namespace.description,
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Rename namespace of tags, aliases and metadata in one transaction
+#[tauri::command]
+pub async fn rename_namespace(
+    state: tauri::State<'_, MemeDatabaseState>,
+    old: String,
+    new: String,
+) -> AppResult<()> {
+    let new = new.trim();
+    if new.is_empty() || new.contains(':') {
+        return Err(AppError::InvalidArgument(format!("invalid namespace: {}", new)));
+    }
+    if old == new {
+        return Ok(());
+    }
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    rename_tag_namespace(&conn, &old, new)?;
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
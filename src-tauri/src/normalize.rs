@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, ImageOutputFormat};
+
+use crate::file::compute_path;
+
+/// Image formats which webview or thumbnailer can not display reliably,
+/// they are converted to `CanonicalFormat` on import
+pub const CONVERTED_EXTENSIONS: &[&str] = &["svg", "bmp", "tif", "tiff", "heic", "heif", "avif"];
+/// Vector images are rasterized so that their longest side is at least this many pixels,
+/// icons are often only a few dozen pixels in size
+const SVG_MIN_SIZE: f32 = 512.0;
+
+/// Format images in `CONVERTED_EXTENSIONS` are stored as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanonicalFormat {
+    #[default]
+    Png,
+    /// Lossless WebP, usually smaller than PNG
+    Webp,
+}
+
+impl CanonicalFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CanonicalFormat::Png => "png",
+            CanonicalFormat::Webp => "webp",
+        }
+    }
+
+    fn output_format(self) -> ImageOutputFormat {
+        match self {
+            CanonicalFormat::Png => ImageOutputFormat::Png,
+            CanonicalFormat::Webp => ImageOutputFormat::WebP,
+        }
+    }
+}
+
+fn extension_of(path: &Path) -> Option<String> {
+    path.extension().map(|x| x.to_string_lossy().to_lowercase())
+}
+
+/// Whether image at `path` is converted on import, judged by its extension
+pub fn needs_conversion<P: AsRef<Path>>(path: P) -> bool {
+    extension_of(path.as_ref())
+        .map_or(false, |ext| CONVERTED_EXTENSIONS.contains(&ext.as_str()))
+}
+
+fn rasterize_svg(data: &[u8]) -> Result<DynamicImage, String> {
+    use resvg::{tiny_skia, usvg, usvg::TreeParsing};
+
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|e| e.to_string())?;
+    let tree = resvg::Tree::from_usvg(&tree);
+    let size = tree.size;
+    let scale = (SVG_MIN_SIZE / size.width().max(size.height())).max(1.0);
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| format!("can not render svg of size {}x{}", width, height))?;
+    tree.render(tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    // pixmap is premultiplied, encoding it as png converts it back to straight alpha
+    let png = pixmap.encode_png().map_err(|e| e.to_string())?;
+    image::load_from_memory(&png).map_err(|e| e.to_string())
+}
+
+/// HEIC and AVIF are both HEIF containers, libheif decodes either
+#[cfg(feature = "heif")]
+fn decode_heif(data: &[u8]) -> Result<DynamicImage, String> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let ctx = HeifContext::read_from_bytes(data).map_err(|e| e.to_string())?;
+    let handle = ctx.primary_image_handle().map_err(|e| e.to_string())?;
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(|e| e.to_string())?;
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or_else(|| "decoded heif image has no interleaved plane".to_owned())?;
+    let (width, height) = (plane.width, plane.height);
+    let row_size = width as usize * 4;
+    // rows are padded to `stride` bytes
+    let mut pixels = Vec::with_capacity(row_size * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_size]);
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "invalid size of decoded heif image".to_owned())
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_data: &[u8]) -> Result<DynamicImage, String> {
+    Err("app is built without HEIC and AVIF support".to_owned())
+}
+
+fn decode(path: &Path, data: &[u8]) -> Result<DynamicImage, String> {
+    match extension_of(path).as_deref() {
+        Some("svg") => rasterize_svg(data),
+        Some("heic" | "heif" | "avif") => decode_heif(data),
+        _ => image::load_from_memory(data).map_err(|e| e.to_string()),
+    }
+}
+
+/// Convert image `data` read from `path` into `format`.
+/// Only the first frame of animated images is kept
+pub fn convert_image(path: &Path, data: &[u8], format: CanonicalFormat) -> Result<Vec<u8>, String> {
+    let img = decode(path, data)
+        .map_err(|e| format!("failed to decode {}: {}", path.display(), e))?;
+    let mut converted = Cursor::new(Vec::new());
+    img.write_to(&mut converted, format.output_format())
+        .map_err(|e| e.to_string())?;
+    Ok(converted.into_inner())
+}
+
+/// Directory of original files kept for converted content, named after the converted content.
+/// They only exist in local library and are never uploaded to remote storage
+fn originals_dir<P: AsRef<Path>>(base: P) -> PathBuf {
+    base.as_ref().join("originals")
+}
+
+/// Keep `original` file from which stored content `hash` is converted
+pub fn keep_original<P: AsRef<Path>>(base: P, hash: &str, original: &Path) -> Result<(), String> {
+    let sha = hash.split('.').next().unwrap_or(hash);
+    let mut path = compute_path(originals_dir(base), sha);
+    if let Some(ext) = extension_of(original) {
+        path.set_extension(ext);
+    }
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+    fs::copy(original, path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Path of the original file kept for content `hash`, if there is one
+pub fn query_original<P: AsRef<Path>>(base: P, hash: &str) -> Option<PathBuf> {
+    let sha = hash.split('.').next().unwrap_or(hash);
+    let dir = compute_path(originals_dir(base), sha).parent()?.to_path_buf();
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.file_stem().map_or(false, |stem| stem == sha))
+}
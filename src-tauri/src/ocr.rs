@@ -0,0 +1,147 @@
+use std::{path::Path, process::Command};
+
+use rusqlite::{Connection, Error, OptionalExtension};
+use tauri::Window;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    progress::{TaskProgress, TaskRegistry},
+    storage::{fetch_content, Storage},
+};
+
+pub const DEFAULT_LANG: &str = "eng";
+
+/// Extract text from image by the external `tesseract` binary
+pub fn run_tesseract<P: AsRef<Path>>(path: P, lang: &str) -> Result<String, String> {
+    let output = Command::new("tesseract")
+        .arg(path.as_ref())
+        .arg("stdout")
+        .args(["-l", lang])
+        .output()
+        .map_err(|e| format!("failed to run tesseract: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+pub fn save_meme_text(conn: &Connection, meme_id: i64, text: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO meme_text(meme_id, text) VALUES (?1, ?2)",
+        (meme_id, text),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Recognize text of image meme `id` and save it for searching
+pub fn recognize_meme_text<P: AsRef<Path>>(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: P,
+    id: i64,
+    lang: Option<&str>,
+) -> Result<String, String> {
+    let hash: String = conn
+        .query_row(
+            "SELECT hash FROM meme WHERE id = ?1 AND ty = 'image'",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let text = run_tesseract(
+        fetch_content(storage, base.as_ref(), &hash)?,
+        lang.unwrap_or(DEFAULT_LANG),
+    )?;
+    save_meme_text(conn, id, &text)?;
+    Ok(text)
+}
+
+/// Recognize text of meme and save it for searching
+#[tauri::command]
+pub async fn ocr_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    lang: Option<String>,
+) -> AppResult<String> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(recognize_meme_text(
+        &state.conn,
+        state.storage.as_ref(),
+        &state.path,
+        id,
+        lang.as_deref(),
+    )?)
+}
+
+/// Recognize text of all images which have not been recognized yet,
+/// progress of `task` is reported with id of each meme.
+/// Return the number of processed memes, which stops growing once it is cancelled
+#[tauri::command]
+pub async fn ocr_all_memes(
+    window: Window,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+    lang: Option<String>,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let lang = lang.as_deref().unwrap_or(DEFAULT_LANG);
+
+    let mut query = state
+        .conn
+        .prepare("SELECT id, hash FROM meme WHERE ty = 'image' AND id NOT IN (SELECT meme_id FROM meme_text)")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let progress = TaskProgress::start(window, &registry, task, memes.len())?;
+    let mut count = 0;
+    for (id, hash) in &memes {
+        if progress.is_cancelled() {
+            break;
+        }
+        let path = fetch_content(state.storage.as_ref(), &state.path, hash)?;
+        let text = run_tesseract(path, lang)?;
+        save_meme_text(&state.conn, *id, &text)?;
+        count += 1;
+        progress.report(count, id)?;
+    }
+    Ok(count)
+}
+
+/// Save text recognized by frontend
+#[tauri::command]
+pub async fn set_meme_text(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    text: String,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(save_meme_text(&state.conn, id, &text)?)
+}
+
+#[tauri::command]
+pub async fn get_meme_text(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<Option<String>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .query_row(
+            "SELECT text FROM meme_text WHERE meme_id = ?1",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(AppError::from)
+}
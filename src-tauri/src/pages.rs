@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use rusqlite::{Connection, Error};
+use tracing::warn;
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::{compute_path, media_type, release_contents},
+    import::PendingFile,
+    meme::process_content,
+    staging::settle_staging,
+    storage::{fetch_content, store_content},
+    thumbnail::{generate_thumbnail, query_thumbnail},
+};
+
+/// A file of meme made of several images, such as a comic or a screenshot thread.
+/// Page 0 is the cover, which is `hash` of meme itself
+#[derive(Debug, serde::Serialize)]
+pub struct MemePage {
+    page: i64,
+    hash: String,
+    path: String,
+    thumbnail: Option<String>,
+}
+
+/// Content hash of every page of meme in order
+pub fn query_page_hashes(conn: &Connection, meme_id: i64) -> Result<Vec<String>, Error> {
+    let mut query =
+        conn.prepare_cached("SELECT hash FROM meme_file WHERE meme_id = ?1 ORDER BY page")?;
+    let hashes = query
+        .query_map([meme_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, Error>>()?;
+    Ok(hashes)
+}
+
+fn to_page(base: &Path, page: usize, hash: String) -> MemePage {
+    MemePage {
+        page: page as i64,
+        path: compute_path(base, &hash).to_string_lossy().to_string(),
+        thumbnail: query_thumbnail(base, &hash),
+        hash,
+    }
+}
+
+/// Pages of an image meme, other types of meme only have the content they are created with
+pub fn query_image_pages(conn: &Connection, meme_id: i64) -> AppResult<Vec<String>> {
+    let ty: String = conn.query_row("SELECT ty FROM meme WHERE id = ?1", [meme_id], |row| {
+        row.get(0)
+    })?;
+    if ty != "image" {
+        return Err(AppError::InvalidArgument(
+            "only image meme can have pages".to_owned(),
+        ));
+    }
+    Ok(query_page_hashes(conn, meme_id)?)
+}
+
+/// Replace pages of meme by `hashes`, derived data is read again from the cover if it changed.
+/// Return content of removed pages, to be passed to `release_contents` after commit
+pub fn write_pages(
+    conn: &Connection,
+    base: &Path,
+    meme_id: i64,
+    old: &[String],
+    hashes: &[String],
+) -> AppResult<Vec<String>> {
+    if hashes.is_empty() {
+        return Err(AppError::InvalidArgument(
+            "can not remove the only page of meme".to_owned(),
+        ));
+    }
+    conn.execute("DELETE FROM meme_file WHERE meme_id = ?1", [meme_id])?;
+    for (page, hash) in hashes.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO meme_file(meme_id, page, hash) VALUES (?1, ?2, ?3)",
+            (meme_id, page as i64, hash),
+        )?;
+    }
+    if old.first() != hashes.first() {
+        let cover = &hashes[0];
+        conn.execute("UPDATE meme SET hash = ?1 WHERE id = ?2", (cover, meme_id))?;
+        process_content(conn, base, meme_id, "image", cover)?;
+    }
+    Ok(old
+        .iter()
+        .filter(|hash| !hashes.contains(hash))
+        .cloned()
+        .collect())
+}
+
+pub fn to_pages(base: &Path, hashes: Vec<String>) -> Vec<MemePage> {
+    hashes
+        .into_iter()
+        .enumerate()
+        .map(|(page, hash)| to_page(base, page, hash))
+        .collect()
+}
+
+fn page_not_found(id: i64, page: usize) -> AppError {
+    AppError::NotFound(format!("page {} of meme {}", page, id))
+}
+
+/// Every page of meme in order, a meme of a single file has only page 0
+#[tauri::command]
+pub async fn get_meme_pages(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<Vec<MemePage>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let hashes = query_page_hashes(&state.conn, id)?;
+    if hashes.is_empty() {
+        return Err(AppError::NotFound(format!("meme {}", id)));
+    }
+    Ok(to_pages(&state.path, hashes))
+}
+
+/// Page `page` of meme with its content ready to be read,
+/// downloaded if it is only in remote storage
+#[tauri::command]
+pub async fn get_meme_page(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    page: usize,
+) -> AppResult<MemePage> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let hash = query_page_hashes(&state.conn, id)?
+        .into_iter()
+        .nth(page)
+        .ok_or_else(|| page_not_found(id, page))?;
+    fetch_content(state.storage.as_ref(), &state.path, &hash)
+        .map_err(AppError::StorageUnavailable)?;
+    Ok(to_page(&state.path, page, hash))
+}
+
+/// Store image `file` as a page of image meme, inserted before `page` or appended if `None`.
+/// Return pages of meme after insertion
+#[tauri::command]
+pub async fn add_meme_page(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    file: String,
+    page: Option<usize>,
+) -> AppResult<Vec<MemePage>> {
+    let path = Path::new(&file);
+    if media_type(path) != Some("image") {
+        return Err(AppError::InvalidArgument(format!("{} is not an image", file)));
+    }
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let settings = load_settings(&state.conn)?;
+    let content = PendingFile::open(path, false, settings.import_format)?
+        .store(&state.path, settings.keep_original_format)?;
+    store_content(state.storage.as_ref(), &state.path, &content)?;
+    generate_thumbnail(&state.path, &content, settings.thumbnail_size)?;
+
+    let conn = state.conn.transaction()?;
+    let old = query_image_pages(&conn, id)?;
+    let mut hashes = old.clone();
+    let page = page.unwrap_or(hashes.len()).min(hashes.len());
+    hashes.insert(page, content);
+    let released = write_pages(&conn, &state.path, id, &old, &hashes)?;
+    conn.commit()?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+        warn!(error = %e, "failed to settle staged page");
+    }
+    Ok(to_pages(&state.path, hashes))
+}
+
+/// Remove page `page` of meme, the next page becomes cover if page 0 is removed.
+/// Return pages of meme after removal
+#[tauri::command]
+pub async fn remove_meme_page(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    page: usize,
+) -> AppResult<Vec<MemePage>> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    let old = query_image_pages(&conn, id)?;
+    if page >= old.len() {
+        return Err(page_not_found(id, page));
+    }
+    let mut hashes = old.clone();
+    hashes.remove(page);
+    let released = write_pages(&conn, &state.path, id, &old, &hashes)?;
+    conn.commit()?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(to_pages(&state.path, hashes))
+}
+
+/// Move page `from` to position `to`, pages between them are shifted.
+/// Return pages of meme in their new order
+#[tauri::command]
+pub async fn move_meme_page(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    from: usize,
+    to: usize,
+) -> AppResult<Vec<MemePage>> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    let old = query_image_pages(&conn, id)?;
+    if from >= old.len() || to >= old.len() {
+        return Err(page_not_found(id, from.max(to)));
+    }
+    let mut hashes = old.clone();
+    let hash = hashes.remove(from);
+    hashes.insert(to, hash);
+    let released = write_pages(&conn, &state.path, id, &old, &hashes)?;
+    conn.commit()?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(to_pages(&state.path, hashes))
+}
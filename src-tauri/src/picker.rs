@@ -0,0 +1,90 @@
+use tauri::{AppHandle, GlobalShortcutManager, Manager, WindowBuilder, WindowUrl};
+use tracing::warn;
+
+use crate::{
+    app_config::AppConfig,
+    clipboard::copy_to_clipboard,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    meme::record_meme_use,
+    tray::refresh_tray,
+    AppDir,
+};
+
+/// Shortcut opening picker if none is set in app config
+pub const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+M";
+const PICKER_LABEL: &str = "picker";
+
+/// Show picker window above every other window, it is created on first use
+/// and only hidden afterwards so it opens fast
+fn show_picker(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_window(PICKER_LABEL) {
+        window.show()?;
+        window.set_focus()?;
+        return Ok(());
+    }
+    WindowBuilder::new(app, PICKER_LABEL, WindowUrl::App("picker".into()))
+        .title("Quick Pick")
+        .inner_size(480.0, 400.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .focused(true)
+        .build()?;
+    Ok(())
+}
+
+/// Open picker by `shortcut` in any application, replacing the shortcut registered before
+pub fn register_picker_shortcut(app: &AppHandle, shortcut: &str) -> Result<(), String> {
+    let mut manager = app.global_shortcut_manager();
+    manager.unregister_all().map_err(|e| e.to_string())?;
+    let handle = app.clone();
+    manager
+        .register(shortcut, move || {
+            // windows can not be created on the thread running event loop
+            let handle = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = show_picker(&handle) {
+                    warn!(error = %e, "failed to open picker");
+                }
+            });
+        })
+        .map_err(|e| format!("failed to register shortcut {}: {}", shortcut, e))
+}
+
+/// Copy meme chosen in picker to clipboard, count it as used and hide picker
+#[tauri::command]
+pub async fn pick_meme(
+    app: AppHandle,
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    {
+        let guard = state.state.lock().await;
+        let state = guard.as_ref().ok_or_else(library_not_opened)?;
+        copy_to_clipboard(state, id)?;
+        record_meme_use(&state.conn, id)?;
+    }
+    if let Some(window) = app.get_window(PICKER_LABEL) {
+        window.hide().map_err(|e| e.to_string())?;
+    }
+    refresh_tray(&app).await
+}
+
+/// Change shortcut opening picker, `None` restores the default one
+#[tauri::command]
+pub async fn set_picker_shortcut(
+    app: AppHandle,
+    app_dir: tauri::State<'_, AppDir>,
+    shortcut: Option<String>,
+) -> AppResult<()> {
+    let shortcut = shortcut.filter(|shortcut| !shortcut.trim().is_empty());
+    // an invalid shortcut is refused before it is saved
+    register_picker_shortcut(&app, shortcut.as_deref().unwrap_or(DEFAULT_SHORTCUT))
+        .map_err(AppError::InvalidArgument)?;
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.picker_shortcut = shortcut;
+    Ok(config.save(&app_dir.storage_dir)?)
+}
@@ -0,0 +1,125 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    journal::JournalRecorder,
+};
+
+/// Key of passcode hash in `settings` table, kept out of `Settings`
+/// so it is never sent to frontend nor overwritten by `set_settings`
+const PASSCODE_KEY: &str = "private_passcode";
+const PASSCODE_SALT: &str = "mmm-private:";
+
+/// Condition appended to statements listing memes, hides private memes while they are locked
+pub fn private_filter(unlocked: bool) -> &'static str {
+    if unlocked {
+        ""
+    } else {
+        " AND private = false "
+    }
+}
+
+/// Private memes are only hidden from listings, passcode does not encrypt anything.
+/// Use an encrypted library to protect them on disk
+fn hash_passcode(passcode: &str) -> String {
+    sha256::digest(format!("{}{}", PASSCODE_SALT, passcode))
+}
+
+fn load_passcode_hash(conn: &Connection) -> Result<Option<String>, rusqlite::Error> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            [PASSCODE_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    // value is stored as JSON like other settings
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Any passcode matches if none is set
+fn check_passcode(conn: &Connection, passcode: &str) -> AppResult<()> {
+    match load_passcode_hash(conn)? {
+        Some(hash) if hash != hash_passcode(passcode) => {
+            Err(AppError::InvalidArgument("wrong passcode".to_owned()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Show private memes in search, random meme, quick pick and tray
+/// until library is closed or `lock_private` is called
+#[tauri::command]
+pub async fn unlock_private(
+    state: tauri::State<'_, MemeDatabaseState>,
+    passcode: String,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    check_passcode(&state.conn, &passcode)?;
+    state.private_unlocked = true;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn lock_private(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    guard.as_mut().ok_or_else(library_not_opened)?.private_unlocked = false;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn is_private_unlocked(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<bool> {
+    let guard = state.state.lock().await;
+    Ok(guard.as_ref().ok_or_else(library_not_opened)?.private_unlocked)
+}
+
+/// Change passcode of private memes, `old` must match the current one if it is set.
+/// `None` removes passcode, so private memes are unlocked by any passcode
+#[tauri::command]
+pub async fn set_private_passcode(
+    state: tauri::State<'_, MemeDatabaseState>,
+    old: Option<String>,
+    passcode: Option<String>,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    check_passcode(&state.conn, old.as_deref().unwrap_or(""))?;
+    match passcode.filter(|passcode| !passcode.is_empty()) {
+        Some(passcode) => {
+            let value = serde_json::to_string(&hash_passcode(&passcode)).unwrap();
+            state.conn.execute(
+                "INSERT OR REPLACE INTO settings(key, value) VALUES (?1, ?2)",
+                (PASSCODE_KEY, value),
+            )?;
+        }
+        None => {
+            state
+                .conn
+                .execute("DELETE FROM settings WHERE key = ?1", [PASSCODE_KEY])?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_meme_private(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    private: bool,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction()?;
+    let mut journal = JournalRecorder::new(if private {
+        "Mark private"
+    } else {
+        "Unmark private"
+    });
+    journal.capture(&conn, id)?;
+    conn.execute("UPDATE meme SET private = ?1 WHERE id = ?2", (private, id))?;
+    journal.finish(&conn)?;
+    conn.commit()?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tauri::Window;
+
+use crate::error::AppResult;
+
+/// Event emitted by every long running task, tasks are told apart by `task`
+const PROGRESS_EVENT: &str = "task_progress";
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct ProgressEvent<T> {
+    task: String,
+    current: usize,
+    total: usize,
+    /// What was just processed, depends on the task
+    item: T,
+}
+
+type CancelFlags = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+/// Cancellation flags of running tasks, by task id chosen by frontend
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: CancelFlags,
+}
+
+/// Progress of a long running task, reported to window after each item.
+/// Task should stop and return what is done so far once it is cancelled.
+/// It is unregistered when dropped
+pub struct TaskProgress {
+    window: Window,
+    task: String,
+    total: usize,
+    cancelled: Arc<AtomicBool>,
+    tasks: CancelFlags,
+}
+
+impl TaskProgress {
+    pub fn start(
+        window: Window,
+        registry: &TaskRegistry,
+        task: String,
+        total: usize,
+    ) -> Result<Self, String> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut tasks = registry.tasks.lock().unwrap();
+        if tasks.contains_key(&task) {
+            return Err(format!("task {} is already running", task));
+        }
+        tasks.insert(task.clone(), cancelled.clone());
+        Ok(Self {
+            window,
+            task,
+            total,
+            cancelled,
+            tasks: registry.tasks.clone(),
+        })
+    }
+
+    pub fn report<T: serde::Serialize + Clone>(
+        &self,
+        current: usize,
+        item: T,
+    ) -> Result<(), String> {
+        self.window
+            .emit(
+                PROGRESS_EVENT,
+                ProgressEvent {
+                    task: self.task.clone(),
+                    current,
+                    total: self.total,
+                    item,
+                },
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TaskProgress {
+    fn drop(&mut self) {
+        self.tasks.lock().unwrap().remove(&self.task);
+    }
+}
+
+/// Ask running task to stop, return false if no such task is running
+#[tauri::command]
+pub async fn cancel_task(
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+) -> AppResult<bool> {
+    match registry.tasks.lock().unwrap().get(&task) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
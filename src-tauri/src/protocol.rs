@@ -0,0 +1,217 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use tauri::{
+    http::{Request, Response, ResponseBuilder},
+    AppHandle, Manager,
+};
+
+use crate::{
+    db::MemeDatabaseState,
+    file::{compute_path, is_content_name},
+    jobs::JobQueue,
+    library::queue_verify,
+    thumbnail::thumbnail_path,
+};
+
+/// Scheme serving files of opened library to webview,
+/// `meme://content/<hash>` is the content file and `meme://thumb/<hash>` its thumbnail
+pub const SCHEME: &str = "meme";
+/// Longest part of file served for one range request,
+/// so scrubbing a large video does not read the whole file into memory
+const MAX_RANGE_LEN: u64 = 1024 * 1024;
+/// Largest file served as a whole to a request without Range header,
+/// only the first part of a larger file is served and the rest has to be requested by range
+const MAX_FULL_LEN: u64 = 16 * 1024 * 1024;
+
+/// MIME type of content file by its extension
+pub fn mime_type(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Split request uri into kind and hash, the uri is
+/// `meme://content/<hash>`, or `https://meme.localhost/content%2F<hash>` made by
+/// `convertFileSrc` on Windows, so only the last two segments are used
+fn parse_uri(uri: &str) -> Option<(&str, String)> {
+    let path = uri.split_once("://")?.1;
+    let path = path.split(['?', '#']).next()?;
+    let path = path.replace("%2F", "/").replace("%2f", "/");
+    let mut segments = path.rsplit('/');
+    let hash = segments.next()?.to_owned();
+    match segments.next()? {
+        "content" => Some(("content", hash)),
+        "thumb" => Some(("thumb", hash)),
+        _ => None,
+    }
+}
+
+/// Parse `bytes=start-end` of Range header into inclusive range of a file of `len` bytes,
+/// capped at `MAX_RANGE_LEN`. Return None if the range can not be satisfied
+fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let range = header.strip_prefix("bytes=")?;
+    // only the first range is served if there are several
+    let range = range.split(',').next()?.trim();
+    let (start, end) = range.split_once('-')?;
+    let last = len.checked_sub(1)?;
+    let (start, end) = if start.is_empty() {
+        // `bytes=-n` is the last n bytes
+        let suffix = end.parse::<u64>().ok()?.min(len);
+        (len - suffix, last)
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = match end {
+            "" => last,
+            end => end.parse::<u64>().ok()?.min(last),
+        };
+        (start, end)
+    };
+    if start > end {
+        return None;
+    }
+    Some((start, end.min(start + MAX_RANGE_LEN - 1)))
+}
+
+/// Read bytes `start..=end` of file
+fn read_range(path: &Path, start: u64, end: u64) -> Result<Vec<u8>, std::io::Error> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::with_capacity((end - start + 1) as usize);
+    file.take(end - start + 1).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn not_found() -> Result<Response, Box<dyn Error>> {
+    ResponseBuilder::new().status(404).body(Vec::new())
+}
+
+/// Service unavailable, webview may load the file again later
+fn busy() -> Result<Response, Box<dyn Error>> {
+    ResponseBuilder::new()
+        .status(503)
+        .header("Retry-After", "1")
+        .body(Vec::new())
+}
+
+/// File of a request, see `resolve_file`
+enum Resolved {
+    File(PathBuf),
+    NotFound,
+    /// Library is locked by a command or job
+    Busy,
+}
+
+/// Resolve file requested in local library. Handler runs on the UI thread, so it neither
+/// waits for library lock nor downloads content only in remote storage,
+/// commands returning memes have fetched their content already.
+/// Content is not hashed here either, a job checks it if `verify_on_read` is enabled
+fn resolve_file(app: &AppHandle, kind: &str, hash: &str) -> Result<Resolved, String> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let guard = match db_state.state.try_lock() {
+        Ok(guard) => guard,
+        Err(_) => return Ok(Resolved::Busy),
+    };
+    let state = match guard.as_ref() {
+        Some(state) => state,
+        None => return Ok(Resolved::NotFound),
+    };
+    let path = match kind {
+        "thumb" => thumbnail_path(&state.path, hash),
+        _ => compute_path(&state.path, hash),
+    };
+    if !path.is_file() {
+        return Ok(Resolved::NotFound);
+    }
+    if kind == "content" && state.verify_on_read && queue_verify(&state.conn, hash)? {
+        app.state::<JobQueue>().wake();
+    }
+    Ok(Resolved::File(path))
+}
+
+/// Handler of `SCHEME`, registered on builder
+pub fn handle_request(app: &AppHandle, request: &Request) -> Result<Response, Box<dyn Error>> {
+    let (kind, hash) = match parse_uri(request.uri()) {
+        Some(parsed) => parsed,
+        None => return not_found(),
+    };
+    // hash is used as file name, anything else could escape the library
+    if !is_content_name(&hash) {
+        return not_found();
+    }
+    let path = match resolve_file(app, kind, &hash)? {
+        Resolved::File(path) => path,
+        Resolved::NotFound => return not_found(),
+        Resolved::Busy => return busy(),
+    };
+    let mime = if kind == "thumb" {
+        "image/webp"
+    } else {
+        mime_type(&hash)
+    };
+    let response = ResponseBuilder::new()
+        .mimetype(mime)
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Accept-Ranges", "bytes");
+
+    let len = fs::metadata(&path)?.len();
+    let range = request.headers().get("range").and_then(|x| x.to_str().ok());
+    let range = match range {
+        Some(range) => range,
+        None if len <= MAX_FULL_LEN => return response.status(200).body(fs::read(path)?),
+        None => "bytes=0-",
+    };
+    match parse_range(range, len) {
+        Some((start, end)) => response
+            .status(206)
+            .header("Content-Range", format!("bytes {}-{}/{}", start, end, len))
+            .body(read_range(&path, start, end)?),
+        None => response
+            .status(416)
+            .header("Content-Range", format!("bytes */{}", len))
+            .body(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{parse_range, MAX_RANGE_LEN};
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some((900, 999)));
+        // suffix
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some((0, 999)));
+        // open-ended, capped at `MAX_RANGE_LEN`
+        assert_eq!(parse_range("bytes=100-", 1000), Some((100, 999)));
+        let len = MAX_RANGE_LEN * 3;
+        assert_eq!(parse_range("bytes=0-", len), Some((0, MAX_RANGE_LEN - 1)));
+        // out of range
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=500-100", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        // only the first of multiple ranges is served
+        assert_eq!(parse_range("bytes=0-9, 20-29", 1000), Some((0, 9)));
+        assert_eq!(parse_range("bytes=-10,0-5", 1000), Some((990, 999)));
+        assert_eq!(parse_range("items=0-9", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+}
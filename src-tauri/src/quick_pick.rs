@@ -0,0 +1,123 @@
+use std::fs;
+
+use rusqlite::{params_from_iter, Error};
+
+use crate::{
+    db::{library_not_opened, search::build_search_sql, MemeDatabaseState},
+    error::AppResult,
+    private::private_filter,
+    share::data_uri,
+    thumbnail::thumbnail_path,
+};
+
+/// Memes ranked by search order before blending, enough to cover
+/// often used memes which match keywords a little worse
+const CANDIDATES: i64 = 200;
+/// Weight of how well meme matches keywords, of how often and of how recently it is used
+const TEXT_WEIGHT: f64 = 0.6;
+const USE_WEIGHT: f64 = 0.25;
+const RECENCY_WEIGHT: f64 = 0.15;
+
+#[derive(Debug, serde::Serialize)]
+pub struct QuickPickItem {
+    id: i64,
+    name: String,
+    score: f64,
+    /// Thumbnail as data URI, None if meme has no thumbnail
+    thumbnail: Option<String>,
+}
+
+struct Candidate {
+    id: i64,
+    name: String,
+    hash: String,
+    use_count: i64,
+    /// Days since last use
+    age: Option<f64>,
+    fts_rank: Option<f64>,
+}
+
+/// Memes not in trash matching search statement, for a launcher-like picker.
+/// Ranked by a blend of keyword match, use count and how recently meme is used,
+/// thumbnails are inlined so the picker needs no more requests
+#[tauri::command]
+pub async fn quick_pick(
+    state: tauri::State<'_, MemeDatabaseState>,
+    query: String,
+    limit: usize,
+) -> AppResult<Vec<QuickPickItem>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let search = build_search_sql(&query)?;
+    let (rank_column, order_by) = if search.ranked {
+        ("fts_rank", search.order_by.as_str())
+    } else {
+        ("NULL", "use_count DESC, last_used DESC")
+    };
+    let sql = format!(
+        "SELECT id, name, hash, use_count, julianday('now') - julianday(last_used), {}
+          FROM ({} trash = false {}) ORDER BY {} LIMIT ?",
+        rank_column,
+        search.sql,
+        private_filter(state.private_unlocked),
+        order_by
+    );
+    let mut params = search.params;
+    params.push(CANDIDATES.into());
+    let candidates = state
+        .conn
+        .prepare_cached(&sql)?
+        .query_map(params_from_iter(&params), |row| {
+            Ok(Candidate {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                hash: row.get(2)?,
+                use_count: row.get(3)?,
+                age: row.get(4)?,
+                fts_rank: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<Candidate>, Error>>()?;
+
+    // bm25 rank is negative, the best match has the lowest
+    let best_rank = candidates
+        .iter()
+        .filter_map(|x| x.fts_rank)
+        .fold(0.0, f64::min);
+    let most_used = candidates.iter().map(|x| x.use_count).max().unwrap_or(0);
+    let mut scored = candidates
+        .into_iter()
+        .map(|x| {
+            let text = match x.fts_rank {
+                Some(rank) if best_rank < 0.0 => rank / best_rank,
+                _ => 0.0,
+            };
+            let usage = if most_used > 0 {
+                (1.0 + x.use_count as f64).ln() / (1.0 + most_used as f64).ln()
+            } else {
+                0.0
+            };
+            let recency = x.age.map_or(0.0, |age| 1.0 / (1.0 + age.max(0.0)));
+            let score = TEXT_WEIGHT * text + USE_WEIGHT * usage + RECENCY_WEIGHT * recency;
+            (score, x)
+        })
+        .collect::<Vec<(f64, Candidate)>>();
+    scored.sort_by(|(l, _), (r, _)| r.total_cmp(l));
+
+    Ok(scored
+        .into_iter()
+        .take(limit)
+        .map(|(score, x)| {
+            let thumbnail = fs::read(thumbnail_path(&state.path, &x.hash))
+                .ok()
+                .map(|data| data_uri("image/webp", &data));
+            QuickPickItem {
+                id: x.id,
+                name: x.name,
+                score,
+                thumbnail,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,162 @@
+use rusqlite::{Connection, Error};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+};
+
+/// How a variant is made from the meme it is based on.
+/// The original of a family is the meme which is not based on any other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationKind {
+    Edit,
+    Translation,
+    TemplateInstance,
+}
+
+impl RelationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            RelationKind::Edit => "edit",
+            RelationKind::Translation => "translation",
+            RelationKind::TemplateInstance => "template_instance",
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "edit" => Ok(RelationKind::Edit),
+            "translation" => Ok(RelationKind::Translation),
+            "template_instance" => Ok(RelationKind::TemplateInstance),
+            _ => Err(format!("unknown relation kind {}", text)),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MemeRelation {
+    parent_id: i64,
+    child_id: i64,
+    kind: RelationKind,
+}
+
+/// Every meme related to one meme, as a tree rooted at their original
+#[derive(Debug, serde::Serialize)]
+pub struct MemeFamily {
+    root: i64,
+    relations: Vec<MemeRelation>,
+}
+
+/// Record `child` as a variant of `parent`, replacing what it was based on before
+pub fn link_meme_relation(
+    conn: &Connection,
+    parent: i64,
+    child: i64,
+    kind: RelationKind,
+) -> AppResult<()> {
+    if parent == child {
+        return Err(AppError::InvalidArgument("meme can not be based on itself".to_owned()));
+    }
+    if is_ancestor(conn, child, parent)? {
+        return Err(AppError::Conflict(format!(
+            "meme {} is already based on meme {}",
+            parent, child
+        )));
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO meme_relation(child_id, parent_id, kind) VALUES (?1, ?2, ?3)",
+        (child, parent, kind.as_str()),
+    )?;
+    Ok(())
+}
+
+/// Whether `ancestor` is `id` itself or any meme it is based on, directly or not
+fn is_ancestor(conn: &Connection, ancestor: i64, id: i64) -> Result<bool, Error> {
+    conn.query_row(
+        "WITH RECURSIVE ancestor(id) AS (
+          SELECT ?1 UNION
+          SELECT parent_id FROM meme_relation JOIN ancestor ON child_id = ancestor.id
+        )
+        SELECT EXISTS (SELECT 1 FROM ancestor WHERE id = ?2)",
+        (id, ancestor),
+        |row| row.get(0),
+    )
+}
+
+/// Original of the family of meme `id`
+fn find_root(conn: &Connection, id: i64) -> Result<i64, Error> {
+    conn.query_row(
+        "WITH RECURSIVE ancestor(id) AS (
+          SELECT ?1 UNION
+          SELECT parent_id FROM meme_relation JOIN ancestor ON child_id = ancestor.id
+        )
+        SELECT IFNULL(
+          (SELECT id FROM ancestor WHERE id NOT IN (SELECT child_id FROM meme_relation)), ?1
+        )",
+        [id],
+        |row| row.get(0),
+    )
+}
+
+pub fn query_meme_family(conn: &Connection, id: i64) -> AppResult<MemeFamily> {
+    let root = find_root(conn, id)?;
+    let mut query = conn.prepare(
+        "WITH RECURSIVE family(id) AS (
+          SELECT ?1 UNION
+          SELECT child_id FROM meme_relation JOIN family ON parent_id = family.id
+        )
+        SELECT parent_id, child_id, kind FROM meme_relation
+        WHERE parent_id IN family ORDER BY create_time",
+    )?;
+    let relations = query
+        .query_map([root], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<Vec<(i64, i64, String)>, Error>>()?
+        .into_iter()
+        .map(|(parent_id, child_id, kind)| {
+            Ok(MemeRelation {
+                parent_id,
+                child_id,
+                kind: RelationKind::parse(&kind)?,
+            })
+        })
+        .collect::<Result<Vec<MemeRelation>, String>>()?;
+    Ok(MemeFamily { root, relations })
+}
+
+/// Record `child_id` as a variant of `parent_id`
+#[tauri::command]
+pub async fn link_memes(
+    state: tauri::State<'_, MemeDatabaseState>,
+    parent_id: i64,
+    child_id: i64,
+    kind: RelationKind,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    link_meme_relation(&state.conn, parent_id, child_id, kind)
+}
+
+/// Detach meme from what it is based on, its own variants stay with it
+#[tauri::command]
+pub async fn unlink_meme(state: tauri::State<'_, MemeDatabaseState>, id: i64) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute("DELETE FROM meme_relation WHERE child_id = ?1", [id])?;
+    Ok(())
+}
+
+/// Family tree of meme `id`: its original and every variant based on it
+#[tauri::command]
+pub async fn get_meme_family(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<MemeFamily> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    query_meme_family(&state.conn, id)
+}
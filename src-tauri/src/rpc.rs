@@ -0,0 +1,333 @@
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{error, warn};
+
+use crate::{
+    clipboard::copy_to_clipboard,
+    db::MemeDatabaseState,
+    error::AppError,
+    file::media_type,
+    meme::{
+        create_meme_with_tags, link_tags, record_meme_use, search_memes, unlink_tags, NewMeme,
+        Tag,
+    },
+    tray::refresh_tray,
+};
+
+/// Directory in app storage holding the socket on Unix, only the owner may enter it
+#[cfg(unix)]
+const SOCKET_DIR: &str = "rpc";
+#[cfg(unix)]
+const SOCKET_FILE: &str = "mmm.sock";
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\mmm-rpc";
+
+/// Error codes defined by JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Errors of app, `data` is `AppError` as it is returned to frontend
+const APP_ERROR: i64 = -32000;
+
+#[derive(Debug, serde::Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Notifications have no id and get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<AppError> for RpcError {
+    fn from(e: AppError) -> Self {
+        Self {
+            code: APP_ERROR,
+            message: e.to_string(),
+            data: serde_json::to_value(&e).ok(),
+        }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        AppError::from(message).into()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct AddParams {
+    /// Path of image or video file
+    path: PathBuf,
+    /// Defaults to file name
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    stmt: String,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    page_size: Option<i64>,
+    #[serde(default)]
+    fav: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TagParams {
+    ids: Vec<i64>,
+    tags: Vec<Tag>,
+    /// Unlink tags instead of linking them
+    #[serde(default)]
+    remove: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CopyParams {
+    id: i64,
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn library_not_opened() -> RpcError {
+    crate::db::library_not_opened().into()
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| e.to_string().into())
+}
+
+/// Import a file as meme, result is the same as `add_meme_record`
+async fn add(app: &AppHandle, params: AddParams) -> Result<Value, RpcError> {
+    let ty = media_type(&params.path).ok_or_else(|| {
+        RpcError::new(INVALID_PARAMS, "path is neither an image nor a video")
+    })?;
+    let name = params.name.unwrap_or_else(|| {
+        params
+            .path
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let meme = NewMeme {
+        name,
+        description: None,
+        ty: ty.to_owned(),
+        content: params.path.to_string_lossy().to_string(),
+        fav: false,
+        tags: params.tags,
+        pkg_id: 0,
+        strip_exif: false,
+        source_url: None,
+        author: None,
+        origin: None,
+        derived_from: None,
+    };
+
+    let db_state = app.state::<MemeDatabaseState>();
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let result =
+        create_meme_with_tags(&mut state.conn, state.storage.as_ref(), &state.path, meme)?;
+    to_value(result)
+}
+
+/// Page of memes matching search statement, memes in trash are never listed
+async fn search(app: &AppHandle, params: SearchParams) -> Result<Value, RpcError> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let guard = db_state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let page = search_memes(
+        state,
+        &params.stmt,
+        params.cursor.as_deref(),
+        params.page_size,
+        params.fav,
+        false,
+        None,
+    )?;
+    to_value(page)
+}
+
+async fn tag(app: &AppHandle, params: TagParams) -> Result<Value, RpcError> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    if params.remove {
+        unlink_tags(&mut state.conn, &params.ids, &params.tags)?;
+    } else {
+        link_tags(&mut state.conn, &params.ids, &params.tags)?;
+    }
+    Ok(Value::Null)
+}
+
+/// Copy meme to clipboard and count it as used, like picking it in quick picker
+async fn copy(app: &AppHandle, params: CopyParams) -> Result<Value, RpcError> {
+    {
+        let db_state = app.state::<MemeDatabaseState>();
+        let guard = db_state.state.lock().await;
+        let state = guard.as_ref().ok_or_else(library_not_opened)?;
+        copy_to_clipboard(state, params.id)?;
+        record_meme_use(&state.conn, params.id)?;
+    }
+    refresh_tray(app).await?;
+    Ok(Value::Null)
+}
+
+async fn dispatch(app: &AppHandle, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "add" => add(app, parse_params(params)?).await,
+        "search" => search(app, parse_params(params)?).await,
+        "tag" => tag(app, parse_params(params)?).await,
+        "copy" => copy(app, parse_params(params)?).await,
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+    }
+}
+
+/// Handle a request line, return response line or `None` for notifications
+async fn handle_line(app: &AppHandle, line: &str) -> Option<String> {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = RpcError::new(PARSE_ERROR, e.to_string());
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(request) => {
+            let error = RpcError::new(INVALID_REQUEST, "only JSON-RPC 2.0 is supported");
+            return Some(response(request.id.unwrap_or_default(), Err(error)));
+        }
+        Err(e) => {
+            let error = RpcError::new(INVALID_REQUEST, e.to_string());
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+    let result = dispatch(app, &request.method, request.params).await;
+    request.id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    let response = match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    };
+    response.to_string()
+}
+
+/// Requests and responses are JSON objects, one per line
+async fn serve_connection<S>(app: AppHandle, stream: S) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_line(&app, &line).await {
+            writer.write_all(response.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    Ok(())
+}
+
+fn spawn_connection<S>(app: &AppHandle, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve_connection(app, stream).await {
+            warn!(error = %e, "rpc connection failed");
+        }
+    });
+}
+
+#[cfg(unix)]
+async fn listen(app: AppHandle) -> std::io::Result<()> {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+    };
+
+    use tokio::net::UnixListener;
+
+    // socket is reachable between bind and chmod, so bind it where others cannot look up
+    let dir = app.state::<crate::AppDir>().storage_dir.join(SOCKET_DIR);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    let path = dir.join(SOCKET_FILE);
+    // socket left by last run makes binding fail
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    // only the user running app may connect
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        spawn_connection(&app, stream);
+    }
+}
+
+#[cfg(windows)]
+async fn listen(app: AppHandle) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(PIPE_NAME)?;
+    loop {
+        server.connect().await?;
+        // next client connects to a new instance of pipe
+        let next = ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(PIPE_NAME)?;
+        spawn_connection(&app, std::mem::replace(&mut server, next));
+    }
+}
+
+/// Serve JSON-RPC 2.0 on a Unix socket in app storage, or a named pipe on Windows,
+/// so launchers and scripts on this machine can add, search, tag and copy memes.
+/// Methods are `add`, `search`, `tag` and `copy`, see their params above
+pub fn spawn_rpc_server(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = listen(app).await {
+            error!(error = %e, "rpc server stopped");
+        }
+    });
+}
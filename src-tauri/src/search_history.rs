@@ -0,0 +1,111 @@
+use rusqlite::{Connection, Error};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+};
+
+/// Searches not pinned beyond this many are forgotten, least recently used first
+const MAX_RECENT_SEARCHES: i64 = 50;
+
+#[derive(Debug, serde::Serialize)]
+pub struct SearchHistoryEntry {
+    stmt: String,
+    pinned: bool,
+    use_count: i64,
+    last_used: String,
+}
+
+/// Remember search statement as just used, empty statements are not recorded
+pub fn record_search(conn: &Connection, stmt: &str) -> Result<(), String> {
+    let stmt = stmt.trim();
+    if stmt.is_empty() {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO search_history(stmt, use_count) VALUES (?1, 1)
+          ON CONFLICT(stmt) DO UPDATE SET use_count = use_count + 1,
+            last_used = CURRENT_TIMESTAMP",
+        [stmt],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM search_history WHERE pinned = false AND stmt NOT IN (
+          SELECT stmt FROM search_history WHERE pinned = false
+          ORDER BY last_used DESC LIMIT ?1
+        )",
+        [MAX_RECENT_SEARCHES],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Pinned searches first, then recently used searches, at most `limit` of them
+#[tauri::command]
+pub async fn list_recent_searches(
+    state: tauri::State<'_, MemeDatabaseState>,
+    limit: i64,
+) -> AppResult<Vec<SearchHistoryEntry>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state.conn.prepare(
+        "SELECT stmt, pinned, use_count, last_used FROM search_history
+          ORDER BY pinned DESC, last_used DESC LIMIT ?1",
+    )?;
+    let result = query
+        .query_map([limit], |row| {
+            Ok(SearchHistoryEntry {
+                stmt: row.get(0)?,
+                pinned: row.get(1)?,
+                use_count: row.get(2)?,
+                last_used: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<SearchHistoryEntry>, Error>>()?;
+    Ok(result)
+}
+
+/// Pin or unpin search statement, it is added to history if it has not been used
+#[tauri::command]
+pub async fn pin_search(
+    state: tauri::State<'_, MemeDatabaseState>,
+    stmt: String,
+    pinned: bool,
+) -> AppResult<()> {
+    if stmt.trim().is_empty() {
+        return Err(AppError::InvalidArgument("search is empty".to_owned()));
+    }
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state.conn.execute(
+        "INSERT INTO search_history(stmt, pinned) VALUES (?1, ?2)
+          ON CONFLICT(stmt) DO UPDATE SET pinned = ?2",
+        (stmt.trim(), pinned),
+    )?;
+    Ok(())
+}
+
+/// Forget one search, even if it is pinned
+#[tauri::command]
+pub async fn remove_search(
+    state: tauri::State<'_, MemeDatabaseState>,
+    stmt: String,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute("DELETE FROM search_history WHERE stmt = ?1", [stmt.trim()])?;
+    Ok(())
+}
+
+/// Forget every search not pinned
+#[tauri::command]
+pub async fn clear_search_history(state: tauri::State<'_, MemeDatabaseState>) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute("DELETE FROM search_history WHERE pinned = false", [])?;
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use rusqlite::{Connection, Error};
+use tauri::Window;
+use tokio::sync::Mutex;
+
+#[cfg(feature = "semantic-search")]
+use crate::clip::ClipModel;
+use crate::{
+    db::{library_not_opened, search::MEME_TABLE, MemeDatabaseState},
+    error::{AppError, AppResult},
+    meme::{fetch_listed_content, meme_from_row, MemeQueried},
+    private::private_filter,
+    progress::{TaskProgress, TaskRegistry},
+    storage::fetch_content,
+    AppDir,
+};
+
+/// Directory of app storage holding `visual.onnx`, `textual.onnx` and `tokenizer.json`
+/// exported from a CLIP model
+const MODEL_DIR: &str = "models/clip";
+/// Stored with each vector, vectors of other models are computed again
+const MODEL_ID: &str = "clip";
+
+/// Stand-in when built without `semantic-search` feature, it never loads
+#[cfg(not(feature = "semantic-search"))]
+pub struct ClipModel;
+
+#[cfg(not(feature = "semantic-search"))]
+impl ClipModel {
+    fn load(_dir: &Path) -> Result<Self, String> {
+        Err("semantic search is not enabled in this build".to_owned())
+    }
+
+    fn embed_image(&self, _img: &image::DynamicImage) -> Result<Vec<f32>, String> {
+        unreachable!()
+    }
+
+    fn embed_text(&self, _text: &str) -> Result<Vec<f32>, String> {
+        unreachable!()
+    }
+}
+
+#[derive(Default)]
+pub struct ClipState {
+    model: Mutex<Option<ClipModel>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SemanticMatch {
+    meme: MemeQueried,
+    /// Cosine similarity between meme and text, higher is closer
+    score: f32,
+}
+
+fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Vectors are normalized, so dot product is the cosine similarity
+fn cosine_similarity(l: &[f32], r: &[f32]) -> f32 {
+    l.iter().zip(r).map(|(l, r)| l * r).sum()
+}
+
+impl ClipState {
+    /// Model loaded from app storage, it is loaded only once
+    async fn model(
+        &self,
+        storage_dir: &Path,
+    ) -> AppResult<tokio::sync::MutexGuard<'_, Option<ClipModel>>> {
+        let mut guard = self.model.lock().await;
+        if guard.is_none() {
+            let dir = storage_dir.join(MODEL_DIR);
+            if !dir.exists() {
+                return Err(AppError::NotFound(format!("CLIP model in {}", dir.display())));
+            }
+            *guard = Some(ClipModel::load(&dir)?);
+        }
+        Ok(guard)
+    }
+}
+
+fn save_embedding(conn: &Connection, meme_id: i64, vector: &[f32]) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO meme_embedding(meme_id, model, vector) VALUES (?1, ?2, ?3)",
+        (meme_id, MODEL_ID, vector_to_blob(vector)),
+    )?;
+    Ok(())
+}
+
+/// Embed image memes which have no vector of current model yet,
+/// progress of `task` is reported after each meme. Return the number of memes embedded
+#[tauri::command]
+pub async fn index_embeddings(
+    window: Window,
+    app_dir: tauri::State<'_, AppDir>,
+    clip: tauri::State<'_, ClipState>,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+) -> AppResult<usize> {
+    let model = clip.model(&app_dir.storage_dir).await?;
+    let model = model.as_ref().unwrap();
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let mut query = state.conn.prepare(
+        "SELECT id, hash FROM meme WHERE ty = 'image' AND id NOT IN
+          (SELECT meme_id FROM meme_embedding WHERE model = ?1)",
+    )?;
+    let memes = query
+        .query_map([MODEL_ID], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, String)>, Error>>()?;
+
+    let progress = TaskProgress::start(window, &registry, task, memes.len())?;
+    let mut count = 0;
+    for (idx, (id, hash)) in memes.iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+        let path = fetch_content(state.storage.as_ref(), &state.path, hash)
+            .map_err(AppError::StorageUnavailable)?;
+        // skip files image crate can not decode instead of failing the whole task
+        if let Ok(img) = image::open(path) {
+            save_embedding(&state.conn, *id, &model.embed_image(&img)?)?;
+            count += 1;
+        }
+        progress.report(idx + 1, id)?;
+    }
+    Ok(count)
+}
+
+/// Memes not in trash most similar to what `text` describes, closest first.
+/// Only memes embedded by `index_embeddings` are searched
+#[tauri::command]
+pub async fn semantic_search(
+    app_dir: tauri::State<'_, AppDir>,
+    clip: tauri::State<'_, ClipState>,
+    state: tauri::State<'_, MemeDatabaseState>,
+    text: String,
+    limit: usize,
+) -> AppResult<Vec<SemanticMatch>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(AppError::InvalidArgument("text is empty".to_owned()));
+    }
+    let target = {
+        let model = clip.model(&app_dir.storage_dir).await?;
+        model.as_ref().unwrap().embed_text(text)?
+    };
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let mut query = state.conn.prepare(&format!(
+        "SELECT meme_id, vector FROM meme_embedding JOIN meme ON meme.id = meme_id
+          WHERE model = ?1 AND trash = false {}",
+        private_filter(state.private_unlocked)
+    ))?;
+    let mut scored = query
+        .query_map([MODEL_ID], |row| {
+            let vector: Vec<u8> = row.get(1)?;
+            Ok((row.get(0)?, cosine_similarity(&target, &blob_to_vector(&vector))))
+        })?
+        .collect::<Result<Vec<(i64, f32)>, Error>>()?;
+    scored.sort_by(|(_, l), (_, r)| r.total_cmp(l));
+    scored.truncate(limit);
+
+    let mut query = state
+        .conn
+        .prepare_cached(&format!("SELECT * FROM {} WHERE id = ?1", MEME_TABLE))?;
+    let matches = scored
+        .into_iter()
+        .map(|(id, score)| -> AppResult<SemanticMatch> {
+            let meme = query.query_row([id], |row| meme_from_row(row, &state.path))?;
+            Ok(SemanticMatch { meme, score })
+        })
+        .collect::<AppResult<Vec<SemanticMatch>>>()?;
+    fetch_listed_content(state, matches.iter().map(|x| &x.meme));
+    Ok(matches)
+}
@@ -0,0 +1,289 @@
+use std::{fs, net::SocketAddr};
+
+use axum::{
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tauri::{AppHandle, Manager};
+use time::OffsetDateTime;
+use tracing::error;
+
+use crate::{
+    app_config::AppConfig,
+    db::{library_not_opened, MemeDatabaseConnection, MemeDatabaseState},
+    download::{detect_extension, download, import_data, MAX_DOWNLOAD_SIZE},
+    error::{AppError, AppResult},
+    library::verify_on_read,
+    meme::{
+        query_meme_by_id, query_meme_tags, search_memes, AddMemeResult, MemeQueried, NewMeme,
+        SearchPage, Tag,
+    },
+    private::private_filter,
+    protocol::mime_type,
+    storage::fetch_content,
+    AppDir,
+};
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::InvalidArgument(_) | AppError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            AppError::StorageUnavailable(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    stmt: String,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    page_size: Option<i64>,
+    #[serde(default)]
+    fav: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MemeDetail {
+    #[serde(flatten)]
+    meme: MemeQueried,
+    tags: Vec<Tag>,
+}
+
+/// Run `f` with private memes hidden, api never serves them even if they are unlocked in app
+fn with_private_hidden<T>(
+    state: &mut MemeDatabaseConnection,
+    f: impl FnOnce(&MemeDatabaseConnection) -> T,
+) -> T {
+    let unlocked = std::mem::replace(&mut state.private_unlocked, false);
+    let result = f(state);
+    state.private_unlocked = unlocked;
+    result
+}
+
+/// Meme `id` with its content fetched, private memes are not found
+fn query_public_meme(state: &MemeDatabaseConnection, id: i64) -> AppResult<MemeQueried> {
+    let public: bool = state.conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM meme WHERE id = ?1 {}", private_filter(false)),
+        [id],
+        |row| row.get(0),
+    )?;
+    if !public {
+        return Err(AppError::NotFound(format!("meme {}", id)));
+    }
+    query_meme_by_id(state, id)
+}
+
+/// `GET /memes?stmt=&cursor=&page_size=&fav=`, memes in trash are never listed
+async fn get_memes(
+    State(app): State<AppHandle>,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<SearchPage>> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let page = with_private_hidden(state, |state| {
+        search_memes(
+            state,
+            &query.stmt,
+            query.cursor.as_deref(),
+            query.page_size,
+            query.fav,
+            false,
+            None,
+        )
+    })?;
+    Ok(Json(page))
+}
+
+/// `GET /memes/:id`, meme with its tags
+async fn get_meme(
+    State(app): State<AppHandle>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<MemeDetail>> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let guard = db_state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let meme = query_public_meme(state, id)?;
+    let tags = query_meme_tags(&state.conn, id)?;
+    Ok(Json(MemeDetail { meme, tags }))
+}
+
+/// `GET /memes/:id/content`, content file of meme
+async fn get_content(
+    State(app): State<AppHandle>,
+    Path(id): Path<i64>,
+) -> AppResult<Response> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let guard = db_state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let meme = query_public_meme(state, id)?;
+    let path = fetch_content(state.storage.as_ref(), &state.path, &meme.hash)
+        .map_err(AppError::StorageUnavailable)?;
+    verify_on_read(&state.conn, &state.path, &meme.hash)?;
+    let body = fs::read(path)?;
+    Ok(([(header::CONTENT_TYPE, mime_type(&meme.hash))], body).into_response())
+}
+
+/// Image sent by browser extension, either its url or its content
+#[derive(Debug, serde::Deserialize)]
+struct ImportRequest {
+    url: Option<String>,
+    /// Base64 encoded content, used if the image can not be downloaded by url
+    data: Option<String>,
+    /// Content type of `data`
+    mime: Option<String>,
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+    /// Page the image is saved from
+    page_url: Option<String>,
+    page_title: Option<String>,
+}
+
+/// Only requests with `Authorization: Bearer <api_token>` may write into library,
+/// writing is disabled if no token is configured
+fn check_token(app: &AppHandle, headers: &HeaderMap) -> AppResult<()> {
+    let config = AppConfig::load(&app.state::<AppDir>().storage_dir)?;
+    verify_token(config.api_token, headers)
+}
+
+/// Compare bearer token in `headers` with the configured one,
+/// a missing token is unauthorized while a wrong one is forbidden
+fn verify_token(expected: Option<String>, headers: &HeaderMap) -> AppResult<()> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "));
+    match (expected.filter(|x| !x.is_empty()), token) {
+        (None, _) => Err(AppError::Forbidden("api token is not configured".to_owned())),
+        (Some(_), None) => Err(AppError::Unauthorized("api token is required".to_owned())),
+        (Some(expected), Some(token)) if expected == token => Ok(()),
+        (Some(_), Some(_)) => Err(AppError::Forbidden("invalid api token".to_owned())),
+    }
+}
+
+/// `POST /import`, import image saved by browser extension from a web page
+async fn post_import(
+    State(app): State<AppHandle>,
+    headers: HeaderMap,
+    Json(request): Json<ImportRequest>,
+) -> AppResult<Json<AddMemeResult>> {
+    check_token(&app, &headers)?;
+    let (data, ext, file_name) = match (&request.url, &request.data) {
+        (_, Some(data)) => {
+            let data = STANDARD
+                .decode(data)
+                .map_err(|e| AppError::InvalidArgument(e.to_string()))?;
+            let name = request.url.as_deref();
+            let ext = detect_extension(&data, request.mime.as_deref(), name)
+                .ok_or_else(|| AppError::InvalidArgument("unknown file type".to_owned()))?;
+            (data, ext, None)
+        }
+        (Some(url), None) => {
+            let downloaded = download(url).await?;
+            (downloaded.data, downloaded.ext, downloaded.file_name)
+        }
+        (None, None) => {
+            return Err(AppError::InvalidArgument("url or data is required".to_owned()));
+        }
+    };
+    let name = request
+        .name
+        .or(request.page_title)
+        .or(file_name)
+        .unwrap_or_else(|| format!("web-{}", OffsetDateTime::now_utc().unix_timestamp()));
+    let meme = NewMeme {
+        name,
+        description: None,
+        ty: String::new(),
+        content: String::new(),
+        fav: false,
+        tags: request.tags,
+        pkg_id: 0,
+        strip_exif: false,
+        source_url: request.page_url.or(request.url),
+        author: None,
+        origin: None,
+        derived_from: None,
+    };
+
+    let db_state = app.state::<MemeDatabaseState>();
+    let mut guard = db_state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    Ok(Json(import_data(state, &data, &ext, meme)?))
+}
+
+/// Serve API of opened library on localhost `port` in background,
+/// for scripts and browser extensions on this machine.
+/// Reading is open to any local process, writing requires api token
+pub fn spawn_server(app: AppHandle, port: u16) {
+    let router = Router::new()
+        .route("/memes", get(get_memes))
+        .route("/memes/:id", get(get_meme))
+        .route("/memes/:id/content", get(get_content))
+        .route("/import", post(post_import))
+        // base64 makes content a third larger
+        .layer(DefaultBodyLimit::max(MAX_DOWNLOAD_SIZE / 3 * 4 + 4096))
+        .with_state(app);
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    tauri::async_runtime::spawn(async move {
+        let server = match axum::Server::try_bind(&addr) {
+            Ok(server) => server,
+            Err(e) => {
+                error!(%addr, error = %e, "failed to start api server");
+                return;
+            }
+        };
+        if let Err(e) = server.serve(router.into_make_service()).await {
+            error!(error = %e, "api server stopped");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use crate::{error::AppError, server::verify_token};
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        headers.insert(header::AUTHORIZATION, value);
+        headers
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let expected = Some("secret".to_owned());
+        assert!(verify_token(expected.clone(), &bearer("secret")).is_ok());
+        assert!(matches!(
+            verify_token(expected.clone(), &HeaderMap::new()),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            verify_token(expected, &bearer("guess")),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            verify_token(None, &bearer("secret")),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            verify_token(Some(String::new()), &bearer("")),
+            Err(AppError::Forbidden(_))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::Connection;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    export::sanitize_file_name,
+    meme::{query_meme_tags, Tag},
+    protocol::mime_type,
+    storage::{fetch_content, Storage},
+    thumbnail::thumbnail_path,
+};
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Memes</title>
+<style>
+body { font-family: sans-serif; margin: 16px; display: flex; flex-wrap: wrap; gap: 16px; }
+figure { margin: 0; width: 240px; }
+img, video { max-width: 240px; max-height: 240px; display: block; }
+pre { white-space: pre-wrap; }
+figcaption p { margin: 4px 0; color: #666; font-size: 0.9em; }
+</style>
+</head>
+<body>
+"#;
+const HTML_TAIL: &str = "</body>\n</html>\n";
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub enum BundleFormat {
+    /// One html file with every file embedded
+    Html,
+    /// `index.html` with files and thumbnails next to it, and metadata in `memes.json`
+    Zip,
+}
+
+/// Metadata of meme in bundle, listed in `memes.json` of zip bundle
+#[derive(Debug, serde::Serialize)]
+struct SharedMeme {
+    name: String,
+    description: Option<String>,
+    ty: String,
+    tags: Vec<Tag>,
+    source_url: Option<String>,
+    /// Path of original file in zip bundle
+    file: String,
+    /// Path of thumbnail in zip bundle
+    thumbnail: Option<String>,
+}
+
+struct BundleItem {
+    meme: SharedMeme,
+    mime: &'static str,
+    content: Vec<u8>,
+    thumbnail: Option<Vec<u8>>,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Relative url of file in zip bundle, `#` and `%` in names would be taken as url syntax
+fn relative_url(path: &str) -> String {
+    path.replace('%', "%25").replace('#', "%23")
+}
+
+pub fn data_uri(mime: &str, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, STANDARD.encode(data))
+}
+
+/// Read content, thumbnail and metadata of meme, files are named by `idx` and name of meme
+fn load_item(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+    idx: usize,
+    id: i64,
+) -> Result<BundleItem, String> {
+    let (name, description, ty, hash, source_url): (String, Option<String>, String, String, _) =
+        conn.query_row(
+            "SELECT name, description, ty, hash, source_url FROM meme WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let ext = match hash.split_once('.') {
+        Some((_, ext)) => ext,
+        None if ty == "text" => "txt",
+        None => "bin",
+    };
+    let content =
+        fs::read(fetch_content(storage, base, &hash)?).map_err(|e| e.to_string())?;
+    let thumbnail = match thumbnail_path(base, &hash) {
+        path if path.is_file() => Some(fs::read(path).map_err(|e| e.to_string())?),
+        _ => None,
+    };
+
+    let stem = format!("{}-{}", idx + 1, sanitize_file_name(&name));
+    Ok(BundleItem {
+        meme: SharedMeme {
+            file: format!("files/{}.{}", stem, ext),
+            thumbnail: thumbnail
+                .as_ref()
+                .map(|_| format!("thumbnails/{}.webp", stem)),
+            tags: query_meme_tags(conn, id)?,
+            name,
+            description,
+            ty,
+            source_url,
+        },
+        mime: mime_type(&hash),
+        content,
+        thumbnail,
+    })
+}
+
+/// Render page listing `items`, files are embedded as data uri if `embed` is set,
+/// otherwise they are linked by their paths in zip bundle
+fn render_html(items: &[BundleItem], embed: bool) -> String {
+    let mut html = HTML_HEAD.to_owned();
+    for item in items {
+        let meme = &item.meme;
+        html.push_str("<figure>\n");
+        if meme.ty == "text" {
+            let text = String::from_utf8_lossy(&item.content);
+            html.push_str(&format!("<pre>{}</pre>\n", escape_html(&text)));
+        } else {
+            let file = if embed {
+                data_uri(item.mime, &item.content)
+            } else {
+                relative_url(&meme.file)
+            };
+            let preview = match (&item.thumbnail, &meme.thumbnail) {
+                (Some(data), _) if embed => Some(data_uri("image/webp", data)),
+                (_, Some(path)) if !embed => Some(relative_url(path)),
+                _ if meme.ty == "image" => Some(file.clone()),
+                _ => None,
+            };
+            let file_name = meme.file.trim_start_matches("files/");
+            html.push_str(&format!(
+                "<a href=\"{}\" download=\"{}\">",
+                escape_html(&file),
+                escape_html(file_name)
+            ));
+            match preview {
+                Some(preview) => html.push_str(&format!(
+                    "<img src=\"{}\" alt=\"{}\">",
+                    escape_html(&preview),
+                    escape_html(&meme.name)
+                )),
+                None => html.push_str(&format!(
+                    "<video src=\"{}\" controls></video>",
+                    escape_html(&file)
+                )),
+            }
+            html.push_str("</a>\n");
+        }
+
+        html.push_str(&format!("<figcaption><b>{}</b>\n", escape_html(&meme.name)));
+        if let Some(description) = meme.description.as_deref().filter(|x| !x.is_empty()) {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(description)));
+        }
+        if !meme.tags.is_empty() {
+            let tags = meme
+                .tags
+                .iter()
+                .map(|tag| format!("{}:{}", tag.key, tag.value))
+                .collect::<Vec<String>>()
+                .join(" ");
+            html.push_str(&format!("<p>{}</p>\n", escape_html(&tags)));
+        }
+        html.push_str("</figcaption>\n</figure>\n");
+    }
+    html.push_str(HTML_TAIL);
+    html
+}
+
+fn write_zip(path: &str, items: &[BundleItem]) -> Result<(), String> {
+    let mut zip = ZipWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let text_options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // media files are already compressed
+    let media_options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file("index.html", text_options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(render_html(items, false).as_bytes())
+        .map_err(|e| e.to_string())?;
+    let memes = items.iter().map(|item| &item.meme).collect::<Vec<&SharedMeme>>();
+    zip.start_file("memes.json", text_options)
+        .map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut zip, &memes).map_err(|e| e.to_string())?;
+
+    for item in items {
+        zip.start_file(&item.meme.file, media_options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(&item.content).map_err(|e| e.to_string())?;
+        if let (Some(path), Some(data)) = (&item.meme.thumbnail, &item.thumbnail) {
+            zip.start_file(path, media_options)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(data).map_err(|e| e.to_string())?;
+        }
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Package memes with their names, descriptions and tags into a self-contained bundle at `path`
+/// to be sent to someone without this app. It can be opened in any browser
+#[tauri::command]
+pub async fn create_share_bundle(
+    state: tauri::State<'_, MemeDatabaseState>,
+    ids: Vec<i64>,
+    path: String,
+    format: BundleFormat,
+) -> AppResult<()> {
+    if ids.is_empty() {
+        return Err(AppError::InvalidArgument("no meme to share".to_owned()));
+    }
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let items = ids
+        .into_iter()
+        .enumerate()
+        .map(|(idx, id)| load_item(&state.conn, state.storage.as_ref(), &state.path, idx, id))
+        .collect::<Result<Vec<BundleItem>, String>>()?;
+    match format {
+        BundleFormat::Html => fs::write(&path, render_html(&items, true))?,
+        BundleFormat::Zip => write_zip(&path, &items)?,
+    }
+    Ok(())
+}
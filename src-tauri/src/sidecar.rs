@@ -0,0 +1,183 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use rusqlite::Connection;
+
+use crate::{
+    booru::{parse_hydrus_tags, BooruTagStrings},
+    meme::{make_tag, parse_tag, Tag, MAX_RATING},
+};
+
+/// Metadata of a meme stored next to its content file
+/// Sidecars written by other tools may use `summary` for name,
+/// tags may be written as `namespace:value` strings or booru tag strings (see `BooruTagStrings`)
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Sidecar {
+    #[serde(default, alias = "summary")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub fav: bool,
+    /// Ratings of booru (e.g. `safe`, `g`) are not the same thing and ignored
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating: u8,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TagEntry {
+    Pair(Tag),
+    Text(String),
+}
+
+fn deserialize_tags<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Tag>, D::Error> {
+    Ok(Vec::<TagEntry>::deserialize(deserializer)?
+        .into_iter()
+        .map(|entry| match entry {
+            TagEntry::Pair(tag) => tag,
+            TagEntry::Text(text) => parse_tag(&text),
+        })
+        .collect())
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RatingEntry {
+    Number(u8),
+    Other(serde::de::IgnoredAny),
+}
+
+fn deserialize_rating<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    match RatingEntry::deserialize(deserializer)? {
+        RatingEntry::Number(rating) => Ok(rating),
+        RatingEntry::Other(_) => Ok(0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum SidecarFormat {
+    Json,
+    Yaml,
+}
+
+impl SidecarFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "json",
+            SidecarFormat::Yaml => "yaml",
+        }
+    }
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "json" => Some(SidecarFormat::Json),
+            "yaml" | "yml" => Some(SidecarFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// `txt` sidecar is a Hydrus tag list
+const SIDECAR_EXTENSIONS: &[&str] = &["json", "yaml", "yml", "txt"];
+
+/// Find sidecar of `file`, either `cat.png.json` or `cat.json`
+pub fn find_sidecar(file: &Path) -> Option<PathBuf> {
+    SIDECAR_EXTENSIONS
+        .iter()
+        .flat_map(|ext| {
+            let mut appended = file.as_os_str().to_owned();
+            appended.push(".");
+            appended.push(ext);
+            [PathBuf::from(appended), file.with_extension(ext)]
+        })
+        .find(|path| path.is_file())
+}
+
+pub fn read_sidecar(path: &Path) -> Result<Sidecar, String> {
+    let ext = path
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if ext == "txt" {
+        return Ok(Sidecar {
+            name: String::new(),
+            description: None,
+            tags: parse_hydrus_tags(&content),
+            fav: false,
+            rating: 0,
+        });
+    }
+
+    let format = SidecarFormat::from_extension(&ext)
+        .ok_or_else(|| format!("unknown sidecar format: {}", path.to_string_lossy()))?;
+    let (mut sidecar, booru): (Sidecar, BooruTagStrings) = match format {
+        SidecarFormat::Json => serde_json::from_str(&content)
+            .and_then(|sidecar| Ok((sidecar, serde_json::from_str(&content)?)))
+            .map_err(|e| e.to_string())?,
+        SidecarFormat::Yaml => serde_yaml::from_str(&content)
+            .and_then(|sidecar| Ok((sidecar, serde_yaml::from_str(&content)?)))
+            .map_err(|e| e.to_string())?,
+    };
+    sidecar.tags.extend(booru.into_tags());
+    Ok(sidecar)
+}
+
+pub fn write_sidecar<P: AsRef<Path>>(
+    path: P,
+    sidecar: &Sidecar,
+    format: SidecarFormat,
+) -> Result<(), String> {
+    let content = match format {
+        SidecarFormat::Json => serde_json::to_string_pretty(sidecar).map_err(|e| e.to_string())?,
+        SidecarFormat::Yaml => serde_yaml::to_string(sidecar).map_err(|e| e.to_string())?,
+    };
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Ids of tags in sidecar appended to `common`, tags already in `common` are skipped
+pub fn sidecar_tag_id(
+    conn: &Connection,
+    sidecar: &Sidecar,
+    common: &[i64],
+) -> Result<Vec<i64>, String> {
+    let mut tag_id = common.to_vec();
+    for tag in &sidecar.tags {
+        let id = make_tag(conn, &tag.key, &tag.value)?;
+        if !tag_id.contains(&id) {
+            tag_id.push(id);
+        }
+    }
+    Ok(tag_id)
+}
+
+/// Overwrite metadata of imported meme by sidecar, tags are not touched
+pub fn apply_sidecar(conn: &Connection, meme_id: i64, sidecar: &Sidecar) -> Result<(), String> {
+    if !sidecar.name.is_empty() {
+        conn.execute(
+            "UPDATE meme SET name = ?1 WHERE id = ?2",
+            (&sidecar.name, meme_id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    conn.execute(
+        "UPDATE meme SET description = ?1, fav = ?2, rating = ?3 WHERE id = ?4",
+        (
+            &sidecar.description,
+            sidecar.fav,
+            sidecar.rating.min(MAX_RATING),
+            meme_id,
+        ),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use rusqlite::Connection;
+
+use crate::{
+    file::{compute_path, release_content},
+    storage::Storage,
+};
+
+/// Directory in library root where incoming content is written before it enters storage
+pub const STAGING_DIR: &str = "staging";
+/// Marker left in staging for content moved into storage, until the transaction
+/// inserting its meme has ended
+const PUBLISHED_SUFFIX: &str = ".published";
+/// Entries younger than this are left alone by `settle_staging`, they may belong to an import
+/// of meme-cli in another process, or to a download or job running without the library lock
+const SETTLE_GRACE: Duration = Duration::from_secs(60 * 60);
+
+fn staging_dir(base: &Path) -> PathBuf {
+    base.join(STAGING_DIR)
+}
+
+fn marker_path(base: &Path, name: &str) -> PathBuf {
+    staging_dir(base).join(format!("{}{}", name, PUBLISHED_SUFFIX))
+}
+
+/// Make entries created, renamed or removed in `dir` durable.
+/// Directories can not be opened as files on Windows, where it is skipped
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Path of content `name` in staging, staging directory is created if missing
+pub fn staged_path(base: &Path, name: &str) -> io::Result<PathBuf> {
+    let dir = staging_dir(base);
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+/// Write `data` into staging as content `name` and flush it to disk
+pub fn stage_bytes(base: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(staged_path(base, name)?)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Copy file `src` into staging as content `name` and flush it to disk
+pub fn stage_file(base: &Path, name: &str, src: &Path) -> io::Result<()> {
+    let path = staged_path(base, name)?;
+    fs::copy(src, &path)?;
+    OpenOptions::new().write(true).open(path)?.sync_all()
+}
+
+/// Stage `data` and publish it as content named by its hash and `ext`, return the name.
+/// Caller calls `settle_staging` once the transaction inserting its meme has ended
+pub fn publish_bytes(base: &Path, data: &[u8], ext: Option<&str>) -> io::Result<String> {
+    let hash = sha256::digest(data);
+    let name = match ext {
+        Some(ext) => format!("{}.{}", hash, ext),
+        None => hash,
+    };
+    stage_bytes(base, &name, data)?;
+    publish_content(base, &name)?;
+    Ok(name)
+}
+
+/// Whether content `name` has been published within `SETTLE_GRACE` and may not be
+/// referenced by a meme yet, e.g. by an import of another process or a running job.
+/// Garbage collection must not take such content as orphaned
+pub fn recently_published(base: &Path, name: &str) -> bool {
+    fs::metadata(marker_path(base, name))
+        .and_then(|x| x.modified())
+        .map_or(false, |modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .map_or(true, |age| age < SETTLE_GRACE)
+        })
+}
+
+/// Move staged content `name` into storage by a rename, so storage never has a partial file.
+/// A marker is written first and kept until `settle_staging` runs,
+/// which removes the content again if its meme has not been committed
+pub fn publish_content(base: &Path, name: &str) -> io::Result<()> {
+    let dir = staging_dir(base);
+    File::create(marker_path(base, name))?.sync_all()?;
+    sync_dir(&dir)?;
+
+    let target = compute_path(base, name);
+    let parent = target.parent().unwrap();
+    fs::create_dir_all(parent)?;
+    fs::rename(dir.join(name), &target)?;
+    sync_dir(parent)?;
+    sync_dir(&dir)
+}
+
+/// Finish imports whose transaction has ended, called after commit or rollback and
+/// on opening library to recover from a crash in the middle of an import.
+/// Content published for a meme which is committed is kept, content of a rolled back
+/// meme is released, and files which have never left staging are deleted.
+/// It must not run while an import transaction is open on `conn`.
+/// Entries younger than `SETTLE_GRACE` are kept for a later run, so imports of other
+/// processes sharing library are not disturbed.
+/// Return the number of content files removed
+pub fn settle_staging(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+) -> Result<usize, String> {
+    settle_older_than(conn, storage, base, SETTLE_GRACE)
+}
+
+fn settle_older_than(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+    grace: Duration,
+) -> Result<usize, String> {
+    let now = SystemTime::now();
+    let entries = match fs::read_dir(staging_dir(base)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let modified = entry
+            .metadata()
+            .and_then(|x| x.modified())
+            .map_err(|e| e.to_string())?;
+        // clock going backwards counts as young
+        if now.duration_since(modified).map_or(true, |age| age < grace) {
+            continue;
+        }
+        let path = entry.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        match file_name.strip_suffix(PUBLISHED_SUFFIX) {
+            Some(name) => {
+                if release_content(conn, storage, base, name)? {
+                    removed += 1;
+                }
+            }
+            None => removed += 1,
+        }
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use crate::{
+        db::{fixtures::seed_meme, MemeDatabaseConnection},
+        file::compute_path,
+        staging::{
+            publish_bytes, publish_content, settle_older_than, settle_staging, stage_bytes,
+            staging_dir,
+        },
+        storage::LocalStorage,
+    };
+
+    #[test]
+    fn test_settle_staging() {
+        // staging of library in the shared temp dir of `empty_library` is not touched
+        let dir = std::env::temp_dir().join(format!("mmm-staging-{}", std::process::id()));
+        let state = MemeDatabaseConnection::open_in_memory(dir.clone()).unwrap();
+        let base = state.path.as_path();
+        let id = seed_meme(&state.conn, "committed", &[]);
+        let committed: String = state
+            .conn
+            .query_row("SELECT hash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+
+        stage_bytes(base, &committed, b"committed").unwrap();
+        publish_content(base, &committed).unwrap();
+        // meme row of this content is never inserted
+        stage_bytes(base, "rolledback.png", b"rolled back").unwrap();
+        publish_content(base, "rolledback.png").unwrap();
+        // crashed before it is published
+        stage_bytes(base, "partial.png", b"partial").unwrap();
+        let orphan = publish_bytes(base, b"orphan", Some("png")).unwrap();
+        assert_eq!(orphan, format!("{}.png", sha256::digest("orphan")));
+
+        // all of them may still be in use by another process
+        assert_eq!(settle_staging(&state.conn, &LocalStorage, base).unwrap(), 0);
+        assert!(compute_path(base, &orphan).exists());
+
+        let settled = settle_older_than(&state.conn, &LocalStorage, base, Duration::ZERO);
+        assert_eq!(settled.unwrap(), 3);
+        assert!(compute_path(base, &committed).exists());
+        assert!(!compute_path(base, "rolledback.png").exists());
+        assert_eq!(fs::read_dir(staging_dir(base)).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    StatusCode,
+};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::{compute_path, list_content_files},
+    staging::staged_path,
+};
+
+use self::{
+    s3::{S3Config, S3Storage},
+    webdav::{WebDavConfig, WebDavStorage},
+};
+
+pub mod s3;
+pub mod webdav;
+
+/// Backend config is stored in library root, next to `meme.db`
+pub const CONFIG_FILE: &str = "storage.json";
+
+/// Place where content files live
+/// The local library always keeps a copy of content it is working with,
+/// remote backends act as the source of truth and local library as a cache of them
+pub trait Storage: Send {
+    /// Upload content file `name`, which is at `local` in local library
+    fn put(&self, name: &str, local: &Path) -> Result<(), String>;
+    /// Download content file `name` into `local`
+    fn get(&self, name: &str, local: &Path) -> Result<(), String>;
+    fn delete(&self, name: &str) -> Result<(), String>;
+    fn exists(&self, name: &str) -> Result<bool, String>;
+    /// Whether content file can be removed from local library and downloaded later
+    fn is_remote(&self) -> bool {
+        true
+    }
+}
+
+/// Content files only live in local library
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn put(&self, _name: &str, _local: &Path) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get(&self, name: &str, _local: &Path) -> Result<(), String> {
+        Err(format!("content file {} is missing", name))
+    }
+
+    fn delete(&self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn exists(&self, _name: &str) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    #[default]
+    Local,
+    WebDav(WebDavConfig),
+    S3(S3Config),
+}
+
+impl StorageConfig {
+    pub fn open(self) -> Box<dyn Storage> {
+        match self {
+            StorageConfig::Local => Box::new(LocalStorage),
+            StorageConfig::WebDav(config) => Box::new(WebDavStorage::new(config)),
+            StorageConfig::S3(config) => Box::new(S3Storage::new(config)),
+        }
+    }
+}
+
+pub fn read_config(base: &Path) -> Result<StorageConfig, String> {
+    let path = base.join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(StorageConfig::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+fn write_config(base: &Path, config: &StorageConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(base.join(CONFIG_FILE), content).map_err(|e| e.to_string())
+}
+
+/// Key of content file in remote storage, sharded the same as local library
+/// (see `compute_path`), e.g. `ab/cd/abcd...png`
+pub fn object_key(name: &str) -> String {
+    if name.len() <= 4 {
+        return name.to_owned();
+    }
+    format!("{}/{}/{}", &name[..2], &name[2..4], name)
+}
+
+/// Send request built by `build` and read the whole response
+/// `reqwest::blocking` panics inside the async runtime running tauri commands,
+/// so requests are sent from a separate thread
+pub fn send_request<F>(build: F) -> Result<(StatusCode, Vec<u8>), String>
+where
+    F: FnOnce(&Client) -> RequestBuilder + Send,
+{
+    thread::scope(|s| {
+        s.spawn(move || {
+            let response = build(&Client::new()).send().map_err(|e| e.to_string())?;
+            let status = response.status();
+            let data = response.bytes().map_err(|e| e.to_string())?;
+            Ok((status, data.to_vec()))
+        })
+        .join()
+        .map_err(|_| "request panicked".to_owned())?
+    })
+}
+
+/// Upload content file which has just been put into local library
+pub fn store_content(storage: &dyn Storage, base: &Path, name: &str) -> Result<(), String> {
+    storage.put(name, &compute_path(base, name))
+}
+
+/// Path of content file in local library,
+/// it is downloaded from storage first if it is not cached.
+/// Download goes to staging and is renamed into place once complete,
+/// so an interrupted download is never taken for content
+pub fn fetch_content(storage: &dyn Storage, base: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = compute_path(base, name);
+    if !path.exists() {
+        let partial = staged_path(base, &format!("{}.download", name))
+            .map_err(|e| e.to_string())?;
+        if let Err(e) = storage.get(name, &partial) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+        fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn get_remote_storage(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<StorageConfig> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(read_config(&state.path)?)
+}
+
+/// Switch storage backend of current library
+/// Content already in library is not moved, call `push_remote_storage` to upload it
+#[tauri::command]
+pub async fn set_remote_storage(
+    state: tauri::State<'_, MemeDatabaseState>,
+    config: StorageConfig,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    write_config(&state.path, &config)?;
+    state.storage = config.open();
+    Ok(())
+}
+
+/// Upload content files in local library which are not in remote storage yet
+/// return the number of uploaded files
+#[tauri::command]
+pub async fn push_remote_storage(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    if !state.storage.is_remote() {
+        return Ok(0);
+    }
+
+    let mut pushed = 0;
+    for name in list_content_files(&state.path).map_err(|e| e.to_string())? {
+        if !state.storage.exists(&name).map_err(AppError::StorageUnavailable)? {
+            store_content(state.storage.as_ref(), &state.path, &name)
+                .map_err(AppError::StorageUnavailable)?;
+            pushed += 1;
+        }
+    }
+    Ok(pushed)
+}
+
+/// Remove local copies of content files which are in remote storage,
+/// they will be downloaded again when needed
+/// return the number of removed files
+#[tauri::command]
+pub async fn clear_content_cache(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    if !state.storage.is_remote() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for name in list_content_files(&state.path).map_err(|e| e.to_string())? {
+        if state.storage.exists(&name).map_err(AppError::StorageUnavailable)? {
+            fs::remove_file(compute_path(&state.path, &name))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
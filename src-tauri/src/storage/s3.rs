@@ -0,0 +1,239 @@
+use std::{fs, path::Path};
+
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode};
+use sha2::Sha256;
+use time::{macros::format_description, OffsetDateTime};
+
+use super::{object_key, send_request, Storage};
+
+fn default_region() -> String {
+    "us-east-1".to_owned()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct S3Config {
+    /// e.g. `https://s3.us-east-1.amazonaws.com`, or `http://localhost:9000` for MinIO
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Put bucket name in path instead of host name, MinIO usually requires it
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+/// Content files in S3 compatible object storage
+pub struct S3Storage {
+    config: S3Config,
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Names of `headers` as listed in `SignedHeaders`
+fn signed_headers(headers: &[(&str, &str)]) -> String {
+    headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Canonical request of AWS Signature Version 4,
+/// `headers` must be lowercase and sorted by name
+fn canonical_request(
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let canonical_headers = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect::<String>();
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        path,
+        query,
+        canonical_headers,
+        signed_headers(headers),
+        payload_hash
+    )
+}
+
+/// Hex signature of the canonical request, `amz_date` is like `20130524T000000Z`
+fn signature(secret_key: &str, region: &str, amz_date: &str, canonical_request: &str) -> String {
+    let date = &amz_date[..8];
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}/{}/s3/aws4_request\n{}",
+        amz_date,
+        date,
+        region,
+        sha256::digest(canonical_request.as_bytes())
+    );
+    let signing_key = [date, region, "s3", "aws4_request"]
+        .into_iter()
+        .fold(format!("AWS4{}", secret_key).into_bytes(), |key, data| {
+            hmac_sha256(&key, data)
+        });
+    hmac_sha256(&signing_key, &string_to_sign)
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> Self {
+        Self { config }
+    }
+
+    fn url(&self, key: &str) -> String {
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        if self.config.path_style {
+            return format!("{}/{}/{}", endpoint, self.config.bucket, key);
+        }
+        match endpoint.split_once("://") {
+            Some((scheme, host)) => format!("{}://{}.{}/{}", scheme, self.config.bucket, host, key),
+            None => format!("https://{}.{}/{}", self.config.bucket, endpoint, key),
+        }
+    }
+
+    /// Sign request by AWS Signature Version 4 and send it
+    fn request(
+        &self,
+        method: Method,
+        key: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<(StatusCode, Vec<u8>), String> {
+        let url = reqwest::Url::parse(&self.url(key)).map_err(|e| e.to_string())?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let amz_date = OffsetDateTime::now_utc()
+            .format(format_description!(
+                "[year][month][day]T[hour][minute][second]Z"
+            ))
+            .map_err(|e| e.to_string())?;
+        let date = &amz_date[..8];
+        let payload_hash = sha256::digest(body.as_deref().unwrap_or_default());
+
+        let headers = [
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", payload_hash.as_str()),
+            ("x-amz-date", amz_date.as_str()),
+        ];
+        let canonical_request =
+            canonical_request(method.as_str(), url.path(), "", &headers, &payload_hash);
+        let signature = signature(
+            &self.config.secret_key,
+            &self.config.region,
+            &amz_date,
+            &canonical_request,
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders={}, Signature={}",
+            self.config.access_key,
+            date,
+            self.config.region,
+            signed_headers(&headers),
+            signature
+        );
+
+        send_request(move |client| {
+            let mut request = client
+                .request(method, url)
+                .header("x-amz-date", amz_date)
+                .header("x-amz-content-sha256", payload_hash)
+                .header("authorization", authorization);
+            if let Some(body) = body {
+                request = request.body(body);
+            }
+            request
+        })
+    }
+}
+
+impl Storage for S3Storage {
+    fn put(&self, name: &str, local: &Path) -> Result<(), String> {
+        let data = fs::read(local).map_err(|e| e.to_string())?;
+        let (status, _) = self.request(Method::PUT, &object_key(name), Some(data))?;
+        if !status.is_success() {
+            return Err(format!("s3: failed to upload {}: {}", name, status));
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str, local: &Path) -> Result<(), String> {
+        let (status, data) = self.request(Method::GET, &object_key(name), None)?;
+        if !status.is_success() {
+            return Err(format!("s3: failed to download {}: {}", name, status));
+        }
+        fs::write(local, data).map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let (status, _) = self.request(Method::DELETE, &object_key(name), None)?;
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            return Err(format!("s3: failed to delete {}: {}", name, status));
+        }
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, String> {
+        let (status, _) = self.request(Method::HEAD, &object_key(name), None)?;
+        match status {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(format!("s3: failed to query {}: {}", name, status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::s3::{canonical_request, signature};
+
+    const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+    const AMZ_DATE: &str = "20130524T000000Z";
+    const HOST: &str = "examplebucket.s3.amazonaws.com";
+    const EMPTY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    // Examples from the AWS documentation of Signature Version 4 for S3
+    #[test]
+    fn test_signature_get_object() {
+        let headers = [
+            ("host", HOST),
+            ("range", "bytes=0-9"),
+            ("x-amz-content-sha256", EMPTY_HASH),
+            ("x-amz-date", AMZ_DATE),
+        ];
+        let request = canonical_request("GET", "/test.txt", "", &headers, EMPTY_HASH);
+        assert_eq!(
+            signature(SECRET_KEY, "us-east-1", AMZ_DATE, &request),
+            "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41"
+        );
+    }
+
+    #[test]
+    fn test_signature_get_bucket_lifecycle() {
+        let headers = [
+            ("host", HOST),
+            ("x-amz-content-sha256", EMPTY_HASH),
+            ("x-amz-date", AMZ_DATE),
+        ];
+        let request = canonical_request("GET", "/", "lifecycle=", &headers, EMPTY_HASH);
+        assert_eq!(
+            signature(SECRET_KEY, "us-east-1", AMZ_DATE, &request),
+            "fea454ca298b7da1c68078a5d1bdbfbbe0d65c699e0f91ac7a200a0136783543"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{fs, path::Path};
+
+use reqwest::{Method, StatusCode};
+
+use super::{object_key, send_request, Storage};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebDavConfig {
+    /// Collection content files are put in,
+    /// e.g. `https://cloud.example.com/remote.php/dav/files/me/memes`
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Content files on a WebDAV server (NAS, Nextcloud, ...)
+pub struct WebDavStorage {
+    config: WebDavConfig,
+}
+
+impl WebDavStorage {
+    pub fn new(config: WebDavConfig) -> Self {
+        Self { config }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.url.trim_end_matches('/'), path)
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<(StatusCode, Vec<u8>), String> {
+        let url = self.url(path);
+        send_request(move |client| {
+            let mut request = client.request(method, url);
+            if let Some(username) = &self.config.username {
+                request = request.basic_auth(username, self.config.password.as_ref());
+            }
+            if let Some(body) = body {
+                request = request.body(body);
+            }
+            request
+        })
+    }
+
+    /// Create parent collections of `key`, WebDAV does not create them on `PUT`
+    fn make_collections(&self, key: &str) -> Result<(), String> {
+        let mut dirs = key.split('/').collect::<Vec<&str>>();
+        dirs.pop();
+        for depth in 1..=dirs.len() {
+            let mkcol = Method::from_bytes(b"MKCOL").unwrap();
+            let (status, _) = self.request(mkcol, &dirs[..depth].join("/"), None)?;
+            // 405 Method Not Allowed if collection exists
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(format!("webdav: failed to create collection: {}", status));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for WebDavStorage {
+    fn put(&self, name: &str, local: &Path) -> Result<(), String> {
+        let key = object_key(name);
+        self.make_collections(&key)?;
+        let data = fs::read(local).map_err(|e| e.to_string())?;
+        let (status, _) = self.request(Method::PUT, &key, Some(data))?;
+        if !status.is_success() {
+            return Err(format!("webdav: failed to upload {}: {}", name, status));
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str, local: &Path) -> Result<(), String> {
+        let (status, data) = self.request(Method::GET, &object_key(name), None)?;
+        if !status.is_success() {
+            return Err(format!("webdav: failed to download {}: {}", name, status));
+        }
+        fs::write(local, data).map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let (status, _) = self.request(Method::DELETE, &object_key(name), None)?;
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            return Err(format!("webdav: failed to delete {}: {}", name, status));
+        }
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, String> {
+        let (status, _) = self.request(Method::HEAD, &object_key(name), None)?;
+        match status {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(format!("webdav: failed to query {}: {}", name, status)),
+        }
+    }
+}
@@ -0,0 +1,707 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use time::OffsetDateTime;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    file::{compute_path, release_contents},
+    meme::{insert_meme, insert_meme_tag, make_tag, process_content, query_meme_tags, Tag},
+    pages::query_page_hashes,
+    storage::{fetch_content, store_content, Storage},
+    trash::purge_meme,
+};
+
+/// Every device appends its changes to `log/<device id>.jsonl` in sync folder,
+/// and reads logs of other devices. A log is only written by its own device,
+/// so file sync tools (Dropbox, Syncthing, ...) never have to merge a file
+const LOG_DIR: &str = "log";
+const LOG_EXT: &str = "jsonl";
+/// Content files shared in sync folder, named by their hash
+const CONTENT_DIR: &str = "content";
+
+/// Content of a compressed meme, it is shared in place of the original content
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct SharedContent {
+    hash: String,
+    original_size: i64,
+}
+
+/// Metadata of a meme shared between devices, memes are identified by content hash
+/// since row ids differ on every device. A compressed meme is still identified by
+/// the hash of its original content, so compressing does not look like a new meme
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct MemeSnapshot {
+    hash: String,
+    ty: String,
+    name: String,
+    description: Option<String>,
+    fav: bool,
+    trash: bool,
+    rating: u8,
+    tags: Vec<Tag>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compressed: Option<SharedContent>,
+    /// Content hash of every page after the cover in order, see `MemePage`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pages: Vec<String>,
+}
+
+impl MemeSnapshot {
+    /// Digest of metadata to tell whether meme is changed since last sync,
+    /// compressing content is not a change
+    fn digest(&self) -> String {
+        let metadata = MemeSnapshot {
+            compressed: None,
+            ..self.clone()
+        };
+        sha256::digest(serde_json::to_string(&metadata).unwrap().as_bytes())
+    }
+
+    /// Hash of content file shared in sync folder
+    fn content(&self) -> &str {
+        match &self.compressed {
+            Some(compressed) => &compressed.hash,
+            None => &self.hash,
+        }
+    }
+
+    /// Hash of every content file of meme shared in sync folder, the cover first
+    fn contents(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.content()).chain(self.pages.iter().map(|x| x.as_str()))
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum ChangeOp {
+    Put { meme: MemeSnapshot },
+    Delete { hash: String },
+}
+
+/// A line of change log
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Change {
+    /// Unix timestamp, the newer change wins when both devices changed a meme
+    time: i64,
+    #[serde(flatten)]
+    op: ChangeOp,
+}
+
+impl Change {
+    fn hash(&self) -> &str {
+        match &self.op {
+            ChangeOp::Put { meme } => &meme.hash,
+            ChangeOp::Delete { hash } => hash,
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct SyncResult {
+    /// Local changes written to sync folder
+    sent: usize,
+    /// Remote changes applied to local library
+    received: usize,
+    /// Changes of both sides on the same meme, the older one is dropped
+    conflicts: usize,
+}
+
+struct LocalMeme {
+    /// Unix timestamp of `update_time`
+    time: i64,
+    snapshot: MemeSnapshot,
+}
+
+/// Outcome of comparing a change from another device with local changes
+#[derive(Debug, PartialEq, Eq)]
+enum Merge {
+    /// Meme is not changed locally since last sync
+    Apply,
+    /// Both sides changed meme, the remote change is newer
+    Override,
+    /// Both sides changed meme, the local change is newer or as new
+    Drop,
+}
+
+/// Local changes since last sync, hash -> time of change.
+/// A meme which is synced but no longer in `local` has been deleted at `now`
+fn collect_pending(
+    local: &HashMap<String, LocalMeme>,
+    synced: &HashMap<String, String>,
+    now: i64,
+) -> HashMap<String, i64> {
+    let mut pending = HashMap::new();
+    for (hash, meme) in local {
+        if synced.get(hash) != Some(&meme.snapshot.digest()) {
+            pending.insert(hash.clone(), meme.time);
+        }
+    }
+    for hash in synced.keys() {
+        if !local.contains_key(hash) {
+            pending.insert(hash.clone(), now);
+        }
+    }
+    pending
+}
+
+fn merge_change(pending: &HashMap<String, i64>, change: &Change) -> Merge {
+    match pending.get(change.hash()) {
+        None => Merge::Apply,
+        Some(time) if *time < change.time => Merge::Override,
+        Some(_) => Merge::Drop,
+    }
+}
+
+/// Identifier of this library in sync folder, generated on first sync
+fn device_id(conn: &Connection, base: &Path) -> Result<String, String> {
+    let id: Option<String> = conn
+        .query_row("SELECT device_id FROM sync_device WHERE id = 0", [], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = id {
+        return Ok(id);
+    }
+
+    let seed = format!(
+        "{}-{}-{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos(),
+        std::process::id(),
+        base.to_string_lossy()
+    );
+    let id = sha256::digest(seed.as_bytes())[..16].to_owned();
+    conn.execute("INSERT INTO sync_device(id, device_id) VALUES (0, ?1)", [&id])
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+fn query_local_memes(conn: &Connection) -> Result<HashMap<String, LocalMeme>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT meme.id, meme.hash, ty, name, description, fav, trash, rating,
+              CAST(strftime('%s', update_time) AS INTEGER), a.original, a.original_size
+              FROM meme LEFT JOIN content_alias a ON a.original =
+                (SELECT MIN(original) FROM content_alias WHERE hash = meme.hash)",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            let content: String = row.get(1)?;
+            let (hash, compressed) = match row.get::<_, Option<String>>(9)? {
+                Some(original) => (
+                    original,
+                    Some(SharedContent {
+                        hash: content,
+                        original_size: row.get(10)?,
+                    }),
+                ),
+                None => (content, None),
+            };
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(8)?,
+                MemeSnapshot {
+                    hash,
+                    ty: row.get(2)?,
+                    name: row.get(3)?,
+                    description: row.get(4)?,
+                    fav: row.get(5)?,
+                    trash: row.get(6)?,
+                    rating: row.get(7)?,
+                    tags: Vec::new(),
+                    compressed,
+                    pages: Vec::new(),
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut result = HashMap::new();
+    for (id, time, mut snapshot) in rows {
+        snapshot.tags = query_meme_tags(conn, id)?;
+        // keep digest independent of insertion order of tags
+        snapshot
+            .tags
+            .sort_by(|a, b| (&a.key, &a.value).cmp(&(&b.key, &b.value)));
+        let pages = query_page_hashes(conn, id).map_err(|e| e.to_string())?;
+        snapshot.pages = pages.into_iter().skip(1).collect();
+        result.insert(snapshot.hash.clone(), LocalMeme { time, snapshot });
+    }
+    Ok(result)
+}
+
+fn query_synced_digests(conn: &Connection) -> Result<HashMap<String, String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT hash, digest FROM sync_snapshot")
+        .map_err(|e| e.to_string())?;
+    let result = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<String, String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Record state of meme known by both sides, `None` if it is deleted
+fn set_synced(conn: &Connection, hash: &str, digest: Option<&str>) -> Result<(), String> {
+    match digest {
+        Some(digest) => conn.execute(
+            "INSERT OR REPLACE INTO sync_snapshot(hash, digest) VALUES (?1, ?2)",
+            (hash, digest),
+        ),
+        None => conn.execute("DELETE FROM sync_snapshot WHERE hash = ?1", [hash]),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Replace pages after the cover of meme by `pages`,
+/// content of removed pages is added to `released`, to be released after commit
+fn set_pages(
+    conn: &Connection,
+    meme_id: i64,
+    pages: &[String],
+    released: &mut Vec<String>,
+) -> Result<(), String> {
+    let old = query_page_hashes(conn, meme_id).map_err(|e| e.to_string())?;
+    let old = old.get(1..).unwrap_or_default();
+    if old == pages {
+        return Ok(());
+    }
+    conn.execute("DELETE FROM meme_file WHERE meme_id = ?1 AND page > 0", [meme_id])
+        .map_err(|e| e.to_string())?;
+    for (page, hash) in pages.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO meme_file(meme_id, page, hash) VALUES (?1, ?2, ?3)",
+            (meme_id, page as i64 + 1, hash),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    released.extend(old.iter().filter(|x| !pages.contains(x)).cloned());
+    Ok(())
+}
+
+/// Copy content file `hash` from sync folder into storage unless it is there already
+fn receive_content(
+    storage: &dyn Storage,
+    base: &Path,
+    folder: &Path,
+    hash: &str,
+) -> Result<(), String> {
+    let path = compute_path(base, hash);
+    if !path.exists() {
+        fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+        let shared = folder.join(CONTENT_DIR).join(hash);
+        fs::copy(shared, &path).map_err(|e| format!("{}: {}", hash, e))?;
+        store_content(storage, base, hash)?;
+    }
+    Ok(())
+}
+
+fn set_tags(conn: &Connection, meme_id: i64, tags: &[Tag]) -> Result<(), String> {
+    conn.execute("DELETE FROM meme_tag WHERE meme_id = ?1", [meme_id])
+        .map_err(|e| e.to_string())?;
+    for tag in tags {
+        let tag_id = make_tag(conn, &tag.key, &tag.value)?;
+        insert_meme_tag(conn, meme_id, tag_id)?;
+    }
+    Ok(())
+}
+
+/// Apply change from another device to local library,
+/// content of new meme and of its pages is copied from sync folder into storage.
+/// Content of a deleted meme or page is added to `released`, to be released after commit.
+/// Return false without changing anything if content of a meme has not been
+/// copied into sync folder by file sync tool yet
+fn apply_change(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+    folder: &Path,
+    change: &Change,
+    released: &mut Vec<String>,
+) -> Result<bool, String> {
+    let id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM meme WHERE hash = ?1 OR hash IN
+              (SELECT hash FROM content_alias WHERE original = ?1) LIMIT 1",
+            [change.hash()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    match &change.op {
+        ChangeOp::Put { meme } => {
+            let available = |hash: &str| {
+                compute_path(base, hash).exists() || folder.join(CONTENT_DIR).join(hash).exists()
+            };
+            // cover of a known meme is not replaced, only its pages may be new
+            let needed = meme
+                .contents()
+                .skip(if id.is_some() { 1 } else { 0 })
+                .collect::<Vec<&str>>();
+            if !needed.iter().all(|hash| available(hash)) {
+                return Ok(false);
+            }
+            for hash in needed {
+                receive_content(storage, base, folder, hash)?;
+            }
+            let id = match id {
+                Some(id) => {
+                    conn.execute(
+                        "UPDATE meme SET name = ?1, description = ?2, fav = ?3 WHERE id = ?4",
+                        (&meme.name, &meme.description, meme.fav, id),
+                    )
+                    .map_err(|e| e.to_string())?;
+                    id
+                }
+                None => {
+                    let content = meme.content();
+                    let path = compute_path(base, content);
+                    if let Some(compressed) = &meme.compressed {
+                        let size = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+                        conn.execute(
+                            "INSERT OR REPLACE INTO content_alias
+                              (original, hash, original_size, size) VALUES (?1, ?2, ?3, ?4)",
+                            (&meme.hash, content, compressed.original_size, size as i64),
+                        )
+                        .map_err(|e| e.to_string())?;
+                    }
+                    let id = insert_meme(
+                        conn,
+                        &meme.name,
+                        meme.description.as_deref(),
+                        &meme.ty,
+                        content,
+                        meme.fav,
+                        0,
+                    )?;
+                    process_content(conn, base, id, &meme.ty, content)?;
+                    id
+                }
+            };
+            conn.execute(
+                "UPDATE meme SET trash = ?1, rating = ?2 WHERE id = ?3",
+                (meme.trash, meme.rating, id),
+            )
+            .map_err(|e| e.to_string())?;
+            set_tags(conn, id, &meme.tags)?;
+            set_pages(conn, id, &meme.pages, released)?;
+            set_synced(conn, &meme.hash, Some(&meme.digest()))?;
+        }
+        ChangeOp::Delete { hash } => {
+            if let Some(id) = id {
+                released.extend(purge_meme(conn, id)?);
+            }
+            set_synced(conn, hash, None)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Two-way sync with sync folder:
+/// 1. collect local changes since last sync by comparing with `sync_snapshot`
+/// 2. apply unread lines of logs of other devices, when a meme is changed on both sides
+///    the change with newer timestamp is kept
+/// 3. append remaining local changes to log of this device
+///
+/// Content of memes deleted by other devices is returned, and should be passed to
+/// `release_contents` once the transaction has committed
+pub fn sync(
+    conn: &Connection,
+    storage: &dyn Storage,
+    base: &Path,
+    folder: &Path,
+) -> Result<(SyncResult, Vec<String>), String> {
+    let device = device_id(conn, base)?;
+    let log_dir = folder.join(LOG_DIR);
+    let content_dir = folder.join(CONTENT_DIR);
+    fs::create_dir_all(&log_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&content_dir).map_err(|e| e.to_string())?;
+
+    let local = query_local_memes(conn)?;
+    let synced = query_synced_digests(conn)?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    let mut pending = collect_pending(&local, &synced, now);
+
+    let mut result = SyncResult::default();
+    let mut released = Vec::new();
+    let mut peer_logs = fs::read_dir(&log_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|path| path.extension().map_or(false, |x| x == LOG_EXT))
+        .collect::<Vec<PathBuf>>();
+    peer_logs.sort();
+    for path in peer_logs {
+        let peer = path.file_stem().unwrap().to_string_lossy().to_string();
+        if peer == device {
+            continue;
+        }
+        let applied: usize = conn
+            .query_row(
+                "SELECT applied FROM sync_peer WHERE device_id = ?1",
+                [&peer],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or(0);
+
+        let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+        let mut count = applied;
+        for line in BufReader::new(file).lines().skip(applied) {
+            let line = line.map_err(|e| e.to_string())?;
+            // a line being written by file sync tool, read it next time
+            let change: Change = match serde_json::from_str(&line) {
+                Ok(change) => change,
+                Err(_) => break,
+            };
+            let merge = merge_change(&pending, &change);
+            if merge != Merge::Drop
+                && !apply_change(conn, storage, base, folder, &change, &mut released)?
+            {
+                // content is still on its way, read this line again next time
+                break;
+            }
+            count += 1;
+            match merge {
+                Merge::Apply => result.received += 1,
+                Merge::Override => {
+                    pending.remove(change.hash());
+                    result.conflicts += 1;
+                    result.received += 1;
+                }
+                Merge::Drop => result.conflicts += 1,
+            }
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_peer(device_id, applied) VALUES (?1, ?2)",
+            (&peer, count),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let mut pending = pending.into_iter().collect::<Vec<(String, i64)>>();
+    pending.sort_by_key(|(_, time)| *time);
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_dir.join(format!("{}.{}", device, LOG_EXT)))
+        .map_err(|e| e.to_string())?;
+    for (hash, time) in pending {
+        let change = match local.get(&hash) {
+            Some(meme) => {
+                for content in meme.snapshot.contents() {
+                    let shared = content_dir.join(content);
+                    if !shared.exists() {
+                        let path = fetch_content(storage, base, content)?;
+                        fs::copy(path, &shared).map_err(|e| e.to_string())?;
+                    }
+                }
+                set_synced(conn, &hash, Some(&meme.snapshot.digest()))?;
+                Change {
+                    time,
+                    op: ChangeOp::Put {
+                        meme: meme.snapshot.clone(),
+                    },
+                }
+            }
+            None => {
+                set_synced(conn, &hash, None)?;
+                Change {
+                    time,
+                    op: ChangeOp::Delete { hash },
+                }
+            }
+        };
+        let line = serde_json::to_string(&change).map_err(|e| e.to_string())?;
+        writeln!(log, "{}", line).map_err(|e| e.to_string())?;
+        result.sent += 1;
+    }
+    Ok((result, released))
+}
+
+/// Sync library with other devices through `folder`, which is shared by
+/// file sync tools such as Dropbox or Syncthing
+#[tauri::command]
+pub async fn sync_library(
+    state: tauri::State<'_, MemeDatabaseState>,
+    folder: String,
+) -> AppResult<SyncResult> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let (result, released) =
+        sync(&conn, state.storage.as_ref(), &state.path, Path::new(&folder))?;
+    conn.commit().map_err(|e| e.to_string())?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        db::fixtures::{empty_library, seed_meme},
+        sync::{
+            collect_pending, merge_change, query_local_memes, Change, ChangeOp, LocalMeme, Merge,
+            MemeSnapshot, SharedContent,
+        },
+    };
+
+    fn snapshot(hash: &str, name: &str) -> MemeSnapshot {
+        MemeSnapshot {
+            hash: hash.to_owned(),
+            ty: "image".to_owned(),
+            name: name.to_owned(),
+            description: None,
+            fav: false,
+            trash: false,
+            rating: 0,
+            tags: Vec::new(),
+            compressed: None,
+            pages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_collect_pending() {
+        let unchanged = snapshot("a", "a");
+        let synced = HashMap::from([
+            ("a".to_owned(), unchanged.digest()),
+            ("b".to_owned(), snapshot("b", "old").digest()),
+            ("deleted".to_owned(), snapshot("deleted", "deleted").digest()),
+        ]);
+        let meme = |time, snapshot| LocalMeme { time, snapshot };
+        let local = HashMap::from([
+            ("a".to_owned(), meme(1, unchanged)),
+            ("b".to_owned(), meme(2, snapshot("b", "new"))),
+            ("new".to_owned(), meme(3, snapshot("new", "new"))),
+        ]);
+        assert_eq!(
+            collect_pending(&local, &synced, 10),
+            HashMap::from([
+                ("b".to_owned(), 2),
+                ("new".to_owned(), 3),
+                ("deleted".to_owned(), 10),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compressed_digest() {
+        let original = snapshot("a.png", "a");
+        let compressed = MemeSnapshot {
+            compressed: Some(SharedContent {
+                hash: "b.webp".to_owned(),
+                original_size: 100,
+            }),
+            ..original.clone()
+        };
+        assert_eq!(original.digest(), compressed.digest());
+        assert_eq!(original.content(), "a.png");
+        assert_eq!(compressed.content(), "b.webp");
+    }
+
+    #[test]
+    fn test_compressed_identity() {
+        let state = empty_library();
+        let id = seed_meme(&state.conn, "cat", &[]);
+        let original: String = state
+            .conn
+            .query_row("SELECT hash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        state
+            .conn
+            .execute(
+                "UPDATE meme SET update_time = '2020-01-01 00:00:00' WHERE id = ?1",
+                [id],
+            )
+            .unwrap();
+        let before = query_local_memes(&state.conn).unwrap();
+
+        // what `replace_content` does to a meme
+        state
+            .conn
+            .execute_batch(&format!(
+                "UPDATE meme SET hash = 'b.webp' WHERE hash = '{0}';
+                 INSERT INTO content_alias(original, hash, original_size, size)
+                   VALUES ('{0}', 'b.webp', 100, 10);",
+                original
+            ))
+            .unwrap();
+        let after = query_local_memes(&state.conn).unwrap();
+        let meme = &after[&original];
+        assert_eq!(meme.snapshot.content(), "b.webp");
+        assert_eq!(meme.snapshot.digest(), before[&original].snapshot.digest());
+        assert_eq!(meme.time, before[&original].time);
+    }
+
+    #[test]
+    fn test_pages_are_shared() {
+        let state = empty_library();
+        let id = seed_meme(&state.conn, "comic", &[]);
+        let original: String = state
+            .conn
+            .query_row("SELECT hash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        let before = query_local_memes(&state.conn).unwrap();
+        assert!(before[&original].snapshot.pages.is_empty());
+
+        state
+            .conn
+            .execute(
+                "INSERT INTO meme_file(meme_id, page, hash)
+                  VALUES (?1, 1, 'p1.png'), (?1, 2, 'p2.png')",
+                [id],
+            )
+            .unwrap();
+        let after = query_local_memes(&state.conn).unwrap();
+        let meme = &after[&original].snapshot;
+        assert_eq!(meme.pages, ["p1.png", "p2.png"]);
+        assert_eq!(
+            meme.contents().collect::<Vec<_>>(),
+            [original.as_str(), "p1.png", "p2.png"]
+        );
+        // adding a page is a change
+        assert_ne!(meme.digest(), before[&original].snapshot.digest());
+    }
+
+    #[test]
+    fn test_merge_change() {
+        let pending = HashMap::from([("a".to_owned(), 5)]);
+        let put = |hash: &str, time| Change {
+            time,
+            op: ChangeOp::Put {
+                meme: snapshot(hash, "remote"),
+            },
+        };
+        let delete = |hash: &str, time| Change {
+            time,
+            op: ChangeOp::Delete {
+                hash: hash.to_owned(),
+            },
+        };
+        assert_eq!(merge_change(&pending, &put("b", 1)), Merge::Apply);
+        assert_eq!(merge_change(&pending, &delete("b", 1)), Merge::Apply);
+        assert_eq!(merge_change(&pending, &put("a", 6)), Merge::Override);
+        assert_eq!(merge_change(&pending, &delete("a", 6)), Merge::Override);
+        // local change wins a tie
+        assert_eq!(merge_change(&pending, &put("a", 5)), Merge::Drop);
+        assert_eq!(merge_change(&pending, &delete("a", 4)), Merge::Drop);
+    }
+}
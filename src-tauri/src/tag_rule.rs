@@ -0,0 +1,262 @@
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    meme::{insert_meme_tag, make_tag, Tag},
+};
+
+#[derive(Debug, serde::Serialize)]
+pub struct TagAlias {
+    alias: Tag,
+    tag: Tag,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TagImplication {
+    tag: Tag,
+    implied: Tag,
+}
+
+/// Id of tag which `key:value` is an alias of
+pub fn resolve_alias(conn: &Connection, key: &str, value: &str) -> Result<Option<i64>, String> {
+    conn.prepare_cached("SELECT tag_id FROM tag_alias WHERE key = ?1 AND value = ?2")
+        .and_then(|mut query| query.query_row((key, value), |row| row.get(0)).optional())
+        .map_err(|e| e.to_string())
+}
+
+/// Ids of all tags implied by `tag_id`, directly or through other implications
+pub fn implied_tags(conn: &Connection, tag_id: i64) -> Result<Vec<i64>, String> {
+    let mut query = conn
+        .prepare(
+            "WITH RECURSIVE implied(id) AS (
+              SELECT implied_id FROM tag_implication WHERE tag_id = ?1
+              UNION
+              SELECT tag_implication.implied_id FROM tag_implication
+                JOIN implied ON tag_implication.tag_id = implied.id
+            )
+            SELECT id FROM implied",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([tag_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<i64>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+fn query_tag(conn: &Connection, id: i64) -> Result<Tag, Error> {
+    conn.query_row("SELECT key, value FROM tag WHERE id = ?1", [id], |row| {
+        Ok(Tag {
+            key: row.get(0)?,
+            value: row.get(1)?,
+        })
+    })
+}
+
+/// Memes linked to tag `tag_id`
+fn query_tagged_memes(conn: &Connection, tag_id: i64) -> Result<Vec<i64>, String> {
+    let mut query = conn
+        .prepare("SELECT meme_id FROM meme_tag WHERE tag_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([tag_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<i64>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Replace tag `old` by `new` everywhere and delete it
+pub fn merge_tag(conn: &Connection, old: i64, new: i64) -> Result<(), String> {
+    for meme_id in query_tagged_memes(conn, old)? {
+        insert_meme_tag(conn, meme_id, new)?;
+    }
+    for sql in [
+        "UPDATE OR IGNORE tag_implication SET tag_id = ?2 WHERE tag_id = ?1",
+        "UPDATE OR IGNORE tag_implication SET implied_id = ?2 WHERE implied_id = ?1",
+        "UPDATE tag_alias SET tag_id = ?2 WHERE tag_id = ?1",
+    ] {
+        conn.execute(sql, (old, new)).map_err(|e| e.to_string())?;
+    }
+    // rows left are duplicated after merging
+    for sql in [
+        "DELETE FROM meme_tag WHERE tag_id = ?1",
+        "DELETE FROM tag_implication WHERE tag_id = ?1 OR implied_id = ?1 OR tag_id = implied_id",
+        "DELETE FROM tag WHERE id = ?1",
+    ] {
+        conn.execute(sql, [old]).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Make `alias` resolve to `tag` whenever a tag is written
+/// If `alias` is already used as a tag, memes tagged with it are moved to `tag`
+#[tauri::command]
+pub async fn add_tag_alias(
+    state: tauri::State<'_, MemeDatabaseState>,
+    alias: Tag,
+    tag: Tag,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let tag_id = make_tag(&conn, &tag.key, &tag.value)?;
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM tag WHERE key = ?1 AND value = ?2",
+            (&alias.key, &alias.value),
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if existing == Some(tag_id) {
+        return Err(AppError::InvalidArgument(
+            "tag can not be an alias of itself".to_owned(),
+        ));
+    }
+    if let Some(existing) = existing {
+        merge_tag(&conn, existing, tag_id)?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO tag_alias(key, value, tag_id) VALUES (?1, ?2, ?3)",
+        (&alias.key, &alias.value, tag_id),
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_tag_alias(
+    state: tauri::State<'_, MemeDatabaseState>,
+    alias: Tag,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "DELETE FROM tag_alias WHERE key = ?1 AND value = ?2",
+            (&alias.key, &alias.value),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_tag_aliases(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<TagAlias>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare(
+            "SELECT tag_alias.key, tag_alias.value, tag.key, tag.value
+              FROM tag_alias JOIN tag ON tag_alias.tag_id = tag.id
+              ORDER BY tag_alias.key, tag_alias.value",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([], |row| {
+            Ok(TagAlias {
+                alias: Tag {
+                    key: row.get(0)?,
+                    value: row.get(1)?,
+                },
+                tag: Tag {
+                    key: row.get(2)?,
+                    value: row.get(3)?,
+                },
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<TagAlias>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Make memes tagged with `tag` also tagged with `implied`,
+/// memes already tagged with `tag` are updated as well
+#[tauri::command]
+pub async fn add_tag_implication(
+    state: tauri::State<'_, MemeDatabaseState>,
+    tag: Tag,
+    implied: Tag,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+
+    let tag_id = make_tag(&conn, &tag.key, &tag.value)?;
+    let implied_id = make_tag(&conn, &implied.key, &implied.value)?;
+    if tag_id == implied_id || implied_tags(&conn, implied_id)?.contains(&tag_id) {
+        return Err(AppError::InvalidArgument(
+            "tag implication can not be circular".to_owned(),
+        ));
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO tag_implication(tag_id, implied_id) VALUES (?1, ?2)",
+        (tag_id, implied_id),
+    )
+    .map_err(|e| e.to_string())?;
+    for meme_id in query_tagged_memes(&conn, tag_id)? {
+        insert_meme_tag(&conn, meme_id, implied_id)?;
+    }
+
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Remove implication, tags added by it are kept
+#[tauri::command]
+pub async fn remove_tag_implication(
+    state: tauri::State<'_, MemeDatabaseState>,
+    tag: Tag,
+    implied: Tag,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute(
+            "DELETE FROM tag_implication
+              WHERE tag_id = (SELECT id FROM tag WHERE key = ?1 AND value = ?2)
+              AND implied_id = (SELECT id FROM tag WHERE key = ?3 AND value = ?4)",
+            (&tag.key, &tag.value, &implied.key, &implied.value),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_tag_implications(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<TagImplication>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare("SELECT tag_id, implied_id FROM tag_implication")
+        .map_err(|e| e.to_string())?;
+    let pairs = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, i64)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    pairs
+        .into_iter()
+        .map(|(tag_id, implied_id)| {
+            Ok(TagImplication {
+                tag: query_tag(&state.conn, tag_id)?,
+                implied: query_tag(&state.conn, implied_id)?,
+            })
+        })
+        .collect::<Result<Vec<TagImplication>, Error>>()
+        .map_err(AppError::from)
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use rusqlite::{Connection, Error, OptionalExtension};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    file::compute_path,
+    meme::Tag,
+};
+
+#[derive(Debug, serde::Serialize)]
+pub struct TagCount {
+    key: String,
+    value: String,
+    count: i64,
+}
+
+/// Every tag with the number of memes (not in trash) it is linked to, most used first
+/// Only tags in `namespace` are counted if it is given, `limit` of None returns all tags
+pub fn query_tag_usage_counts(
+    conn: &Connection,
+    namespace: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<TagCount>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT tag.key, tag.value, COUNT(meme.id) AS count FROM tag
+              JOIN meme_tag ON meme_tag.tag_id = tag.id
+              JOIN meme ON meme.id = meme_tag.meme_id
+              WHERE meme.trash = false AND (?1 IS NULL OR tag.key = ?1)
+              GROUP BY tag.id
+              ORDER BY count DESC, tag.key, tag.value
+              LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    // negative limit means no limit in sqlite
+    let result = query
+        .query_map((namespace, limit.unwrap_or(-1)), |row| {
+            Ok(TagCount {
+                key: row.get(0)?,
+                value: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<TagCount>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Tags of memes used most often (see `mark_meme_used`),
+/// `count` is the total use count of memes with the tag
+pub fn query_most_used_tags(conn: &Connection, limit: i64) -> Result<Vec<TagCount>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT tag.key, tag.value, SUM(meme.use_count) AS count FROM tag
+              JOIN meme_tag ON meme_tag.tag_id = tag.id
+              JOIN meme ON meme.id = meme_tag.meme_id
+              WHERE meme.trash = false
+              GROUP BY tag.id
+              HAVING count > 0
+              ORDER BY count DESC, tag.key, tag.value
+              LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([limit], |row| {
+            Ok(TagCount {
+                key: row.get(0)?,
+                value: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<TagCount>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Tags most recently linked to a meme
+pub fn query_recent_tags(conn: &Connection, limit: i64) -> Result<Vec<Tag>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT key, value FROM tag WHERE last_used IS NOT NULL
+              ORDER BY last_used DESC, id DESC LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let result = query
+        .query_map([limit], |row| {
+            Ok(Tag {
+                key: row.get(0)?,
+                value: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Tag>, Error>>()
+        .map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn get_tag_usage_counts(
+    state: tauri::State<'_, MemeDatabaseState>,
+    namespace: Option<String>,
+    limit: Option<i64>,
+) -> AppResult<Vec<TagCount>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_tag_usage_counts(&state.conn, namespace.as_deref(), limit)?)
+}
+
+#[tauri::command]
+pub async fn get_most_used_tags(
+    state: tauri::State<'_, MemeDatabaseState>,
+    limit: i64,
+) -> AppResult<Vec<TagCount>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_most_used_tags(&state.conn, limit)?)
+}
+
+#[tauri::command]
+pub async fn get_recent_tags(
+    state: tauri::State<'_, MemeDatabaseState>,
+    limit: i64,
+) -> AppResult<Vec<Tag>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(query_recent_tags(&state.conn, limit)?)
+}
+
+/// Score a tag gains if it was just used, halved after a day, a third after two days...
+const RECENCY_WEIGHT: f64 = 5.0;
+
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TagSuggestion {
+    Namespace { key: String, count: i64 },
+    Tag { key: String, value: String, count: i64 },
+}
+
+/// Escape `text` for `LIKE ... ESCAPE '\'` and match it as prefix
+fn like_prefix(text: &str) -> String {
+    let mut pattern = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    pattern.push('%');
+    pattern
+}
+
+/// Complete tag being typed, ranked by number of memes linked to it and how recently it is used
+/// `namespace:value` completes value in the namespace, otherwise both namespaces and values are
+/// completed. Aliases are completed as the tag they point to
+pub fn suggest_tags(
+    conn: &Connection,
+    input: &str,
+    limit: i64,
+) -> Result<Vec<TagSuggestion>, String> {
+    let (namespace, value) = match input.split_once(':') {
+        Some((namespace, value)) => (Some(namespace.trim()), value.trim_start()),
+        None => (None, input.trim_start()),
+    };
+
+    let mut scored = Vec::new();
+    if namespace.is_none() {
+        let mut query = conn
+            .prepare(
+                "SELECT key, COUNT(meme_tag.meme_id) AS count FROM tag
+                  LEFT JOIN meme_tag ON meme_tag.tag_id = tag.id
+                  WHERE key LIKE ?1 ESCAPE '\\'
+                  GROUP BY key
+                  ORDER BY count DESC
+                  LIMIT ?2",
+            )
+            .map_err(|e| e.to_string())?;
+        let namespaces = query
+            .query_map((like_prefix(value), limit), |row| {
+                let count: i64 = row.get(1)?;
+                Ok((
+                    count as f64,
+                    TagSuggestion::Namespace {
+                        key: row.get(0)?,
+                        count,
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<(f64, TagSuggestion)>, Error>>()
+            .map_err(|e| e.to_string())?;
+        scored.extend(namespaces);
+    }
+
+    let mut query = conn
+        .prepare(
+            "WITH matched(id) AS (
+              SELECT id FROM tag WHERE (?1 IS NULL OR key = ?1) AND value LIKE ?2 ESCAPE '\\'
+              UNION
+              SELECT tag_id FROM tag_alias
+                WHERE (?1 IS NULL OR key = ?1) AND value LIKE ?2 ESCAPE '\\'
+            )
+            SELECT tag.key, tag.value, COUNT(meme_tag.meme_id) AS count,
+              COUNT(meme_tag.meme_id) + CASE WHEN tag.last_used IS NULL THEN 0
+                ELSE ?3 / (1 + julianday('now') - julianday(tag.last_used)) END AS score
+            FROM matched JOIN tag ON tag.id = matched.id
+            LEFT JOIN meme_tag ON meme_tag.tag_id = tag.id
+            GROUP BY tag.id
+            ORDER BY score DESC, tag.value
+            LIMIT ?4",
+        )
+        .map_err(|e| e.to_string())?;
+    let tags = query
+        .query_map(
+            (namespace, like_prefix(value), RECENCY_WEIGHT, limit),
+            |row| {
+                Ok((
+                    row.get(3)?,
+                    TagSuggestion::Tag {
+                        key: row.get(0)?,
+                        value: row.get(1)?,
+                        count: row.get(2)?,
+                    },
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(f64, TagSuggestion)>, Error>>()
+        .map_err(|e| e.to_string())?;
+    scored.extend(tags);
+
+    // stable sort keeps namespaces before tags of the same score
+    scored.sort_by(|(l, _), (r, _)| r.total_cmp(l));
+    Ok(scored
+        .into_iter()
+        .take(limit.max(0) as usize)
+        .map(|(_, suggestion)| suggestion)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_tag_suggestions(
+    state: tauri::State<'_, MemeDatabaseState>,
+    input: String,
+    limit: i64,
+) -> AppResult<Vec<TagSuggestion>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(suggest_tags(&state.conn, &input, limit)?)
+}
+
+/// How much a match counts in name, description and recognized text of meme
+const TEXT_WEIGHTS: [f64; 3] = [3.0, 2.0, 1.0];
+/// Tag values shorter than this are too likely to match by accident
+const MIN_MATCH_CHARS: usize = 2;
+
+/// Lowercase words of `text` separated and surrounded by single spaces,
+/// so a phrase can be matched as whole words by `contains(" phrase ")`
+fn normalize_words(text: &str) -> String {
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>();
+    format!(" {} ", words.join(" "))
+}
+
+/// Times `value` appears in text, as whole words if it is made of ASCII,
+/// otherwise anywhere since text like Chinese has no spaces between words
+fn count_matches(text: &str, words: &str, value: &str) -> usize {
+    if value.chars().count() < MIN_MATCH_CHARS {
+        return 0;
+    }
+    if value.is_ascii() {
+        let value = normalize_words(value);
+        if value.trim().is_empty() {
+            return 0;
+        }
+        words.matches(value.as_str()).count()
+    } else {
+        text.matches(value).count()
+    }
+}
+
+/// Tags whose value or alias appears in name, description or recognized text of meme,
+/// ranked by where and how often they appear, then by number of memes linked to them.
+/// Tags meme already has are left out
+pub fn suggest_tags_from_text(
+    conn: &Connection,
+    id: i64,
+    limit: usize,
+) -> Result<Vec<TagSuggestion>, String> {
+    let (name, description): (String, Option<String>) = conn
+        .query_row(
+            "SELECT name, description FROM meme WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let ocr: Option<String> = conn
+        .query_row("SELECT text FROM meme_text WHERE meme_id = ?1", [id], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let texts = [Some(name), description, ocr]
+        .into_iter()
+        .zip(TEXT_WEIGHTS)
+        .filter_map(|(text, weight)| {
+            let text = text?.to_lowercase();
+            let words = normalize_words(&text);
+            Some((text, words, weight))
+        })
+        .collect::<Vec<(String, String, f64)>>();
+
+    let mut query = conn
+        .prepare(
+            "WITH candidate(tag_id, matched) AS (
+              SELECT id, value FROM tag UNION ALL SELECT tag_id, value FROM tag_alias
+            )
+            SELECT tag.id, tag.key, tag.value, candidate.matched,
+              (SELECT COUNT(*) FROM meme_tag WHERE meme_tag.tag_id = tag.id)
+            FROM candidate JOIN tag ON tag.id = candidate.tag_id
+            WHERE tag.id NOT IN (SELECT tag_id FROM meme_tag WHERE meme_id = ?1)",
+        )
+        .map_err(|e| e.to_string())?;
+    let candidates = query
+        .query_map([id], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(i64, String, String, String, i64)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    // a tag may match by its value and several aliases, the best match is kept
+    let mut scored = HashMap::<i64, (f64, TagSuggestion)>::new();
+    for (tag_id, key, value, matched, count) in candidates {
+        let matched = matched.to_lowercase();
+        let score = texts
+            .iter()
+            .map(|(text, words, weight)| weight * count_matches(text, words, &matched) as f64)
+            .sum::<f64>();
+        if score == 0.0 {
+            continue;
+        }
+        // linked memes only break ties between matches of the same text
+        let score = score + (1.0 + count as f64).ln() / 100.0;
+        let best = scored.get(&tag_id).map_or(0.0, |(best, _)| *best);
+        if score > best {
+            scored.insert(tag_id, (score, TagSuggestion::Tag { key, value, count }));
+        }
+    }
+    let mut scored = scored.into_values().collect::<Vec<(f64, TagSuggestion)>>();
+    scored.sort_by(|(l, _), (r, _)| r.total_cmp(l));
+    Ok(scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, suggestion)| suggestion)
+        .collect())
+}
+
+/// Tags mentioned by text of meme `id`, to speed up tagging it by hand
+#[tauri::command]
+pub async fn suggest_tags_for_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+    limit: usize,
+) -> AppResult<Vec<TagSuggestion>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    Ok(suggest_tags_from_text(&state.conn, id, limit)?)
+}
+
+/// What `disk_usage_report` groups memes by
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroup {
+    Tag,
+    Namespace,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct DiskUsage {
+    key: String,
+    /// `None` if grouped by namespace
+    value: Option<String>,
+    /// Number of memes in group
+    memes: usize,
+    /// Bytes of content files of every page of memes in group,
+    /// a file shared by several memes in group is counted once
+    size: u64,
+}
+
+/// Size of stored content, content only in remote storage falls back to size in media info
+fn content_size(base: &Path, hash: &str, recorded: Option<i64>) -> u64 {
+    fs::metadata(compute_path(base, hash))
+        .map(|x| x.len())
+        .unwrap_or_else(|_| recorded.unwrap_or(0).max(0) as u64)
+}
+
+/// Content size of memes grouped by tag or namespace, biggest first.
+/// Memes in trash are counted as their content is still on disk
+pub fn query_disk_usage(
+    conn: &Connection,
+    base: &Path,
+    group_by: UsageGroup,
+) -> Result<Vec<DiskUsage>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT tag.key, tag.value, meme_file.meme_id, meme_file.hash, media_info.size
+              FROM tag
+              JOIN meme_tag ON meme_tag.tag_id = tag.id
+              JOIN meme_file ON meme_file.meme_id = meme_tag.meme_id
+              LEFT JOIN media_info
+                ON media_info.meme_id = meme_file.meme_id AND meme_file.page = 0",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(String, String, i64, String, Option<i64>)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    let mut groups: HashMap<(String, Option<String>), (HashSet<i64>, HashSet<String>)> =
+        HashMap::new();
+    for (key, value, meme_id, hash, recorded) in rows {
+        sizes
+            .entry(hash.clone())
+            .or_insert_with(|| content_size(base, &hash, recorded));
+        let value = match group_by {
+            UsageGroup::Tag => Some(value),
+            UsageGroup::Namespace => None,
+        };
+        let (memes, contents) = groups.entry((key, value)).or_default();
+        memes.insert(meme_id);
+        contents.insert(hash);
+    }
+
+    let mut result = groups
+        .into_iter()
+        .map(|((key, value), (memes, contents))| DiskUsage {
+            key,
+            value,
+            memes: memes.len(),
+            size: contents.iter().map(|hash| sizes[hash]).sum(),
+        })
+        .collect::<Vec<DiskUsage>>();
+    result.sort_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then_with(|| a.key.cmp(&b.key))
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    Ok(result)
+}
+
+/// Content size of memes by tag or namespace, biggest first,
+/// so it is easy to tell what to prune when library grows large
+#[tauri::command]
+pub async fn disk_usage_report(
+    state: tauri::State<'_, MemeDatabaseState>,
+    group_by: UsageGroup,
+    limit: Option<usize>,
+) -> AppResult<Vec<DiskUsage>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut report = query_disk_usage(&state.conn, &state.path, group_by)?;
+    if let Some(limit) = limit {
+        report.truncate(limit);
+    }
+    Ok(report)
+}
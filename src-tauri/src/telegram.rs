@@ -0,0 +1,206 @@
+use serde::de::DeserializeOwned;
+use tauri::Window;
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    download::{detect_extension, import_data, MAX_DOWNLOAD_SIZE},
+    error::{AppError, AppResult},
+    import::FileImportStatus,
+    meme::{AddMemeResult, NewMeme, Tag},
+    progress::{TaskProgress, TaskRegistry},
+};
+
+const API_URL: &str = "https://api.telegram.org";
+/// Namespace of the tag holding title of sticker pack
+const PACK_NAMESPACE: &str = "pack";
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StickerSet {
+    name: String,
+    title: String,
+    stickers: Vec<Sticker>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PhotoSize {
+    file_id: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Sticker {
+    file_id: String,
+    emoji: Option<String>,
+    /// Animated sticker in `.tgs` (gzipped Lottie) format
+    #[serde(default)]
+    is_animated: bool,
+    /// Named `thumb` by older versions of Bot API
+    #[serde(alias = "thumb")]
+    thumbnail: Option<PhotoSize>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TelegramFile {
+    file_path: Option<String>,
+}
+
+/// Progress item of `import_sticker_set`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImportedSticker {
+    emoji: Option<String>,
+    #[serde(flatten)]
+    status: FileImportStatus,
+}
+
+/// Bot token is part of every url, so it is removed from errors before they are shown
+fn request_error(e: reqwest::Error) -> String {
+    e.without_url().to_string()
+}
+
+async fn call_api<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    token: &str,
+    method: &str,
+    query: &[(&str, &str)],
+) -> Result<T, String> {
+    let text = client
+        .get(format!("{}/bot{}/{}", API_URL, token, method))
+        .query(query)
+        .send()
+        .await
+        .map_err(request_error)?
+        .text()
+        .await
+        .map_err(request_error)?;
+    let response: ApiResponse<T> = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    match response.result {
+        Some(result) if response.ok => Ok(result),
+        _ => Err(response
+            .description
+            .unwrap_or_else(|| format!("{} failed", method))),
+    }
+}
+
+/// Download file by its id, return its content and extension
+async fn download_file(
+    client: &reqwest::Client,
+    token: &str,
+    file_id: &str,
+) -> Result<(Vec<u8>, String), String> {
+    let file: TelegramFile = call_api(client, token, "getFile", &[("file_id", file_id)]).await?;
+    let path = file.file_path.ok_or("file is not available")?;
+    let response = client
+        .get(format!("{}/file/bot{}/{}", API_URL, token, path))
+        .send()
+        .await
+        .map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(format!("failed to download {}: {}", path, response.status()));
+    }
+    let data = response.bytes().await.map_err(request_error)?.to_vec();
+    if data.len() > MAX_DOWNLOAD_SIZE {
+        return Err(format!("{} is too large", path));
+    }
+    let ext = detect_extension(&data, None, Some(&path))
+        .ok_or_else(|| format!("{} is not an image or video", path))?;
+    Ok((data, ext))
+}
+
+/// Import every sticker of Telegram sticker set `name` as a meme, using bot `token`.
+/// Static stickers are imported as webp and video stickers as webm.
+/// Animated `.tgs` stickers can not be rendered, their static thumbnail is imported instead,
+/// and they are skipped if they have none.
+/// Memes are tagged with `tags` and title of the set, emoji of sticker is the description.
+/// Progress of `task` is reported after each sticker, return the number of memes added
+#[tauri::command]
+pub async fn import_sticker_set(
+    window: Window,
+    db_state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+    token: String,
+    name: String,
+    tags: Vec<Tag>,
+    pkg_id: i64,
+) -> AppResult<usize> {
+    // share link of the set is accepted as well
+    let name = name.trim();
+    let name = name.strip_prefix("https://t.me/addstickers/").unwrap_or(name);
+    if token.is_empty() || name.is_empty() {
+        return Err(AppError::InvalidArgument(
+            "bot token and sticker set name are required".to_owned(),
+        ));
+    }
+    let client = reqwest::Client::new();
+    let set: StickerSet = call_api(&client, &token, "getStickerSet", &[("name", name)]).await?;
+    let progress = TaskProgress::start(window, &registry, task, set.stickers.len())?;
+
+    let mut tags = tags;
+    tags.push(Tag {
+        key: PACK_NAMESPACE.to_owned(),
+        value: set.title.clone(),
+    });
+    let source_url = format!("https://t.me/addstickers/{}", set.name);
+
+    let mut added = 0;
+    for (idx, sticker) in set.stickers.iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+        let file_id = if sticker.is_animated {
+            sticker.thumbnail.as_ref().map(|thumb| &thumb.file_id)
+        } else {
+            Some(&sticker.file_id)
+        };
+        let status = match file_id {
+            Some(file_id) => {
+                // library is not locked while downloading
+                let imported = match download_file(&client, &token, file_id).await {
+                    Ok((data, ext)) => {
+                        let meme = NewMeme {
+                            name: format!("{} {}", set.title, idx + 1),
+                            description: sticker.emoji.clone(),
+                            ty: String::new(),
+                            content: String::new(),
+                            fav: false,
+                            tags: tags.clone(),
+                            pkg_id,
+                            strip_exif: false,
+                            source_url: Some(source_url.clone()),
+                            author: None,
+                            origin: Some("Telegram".to_owned()),
+                            derived_from: None,
+                        };
+                        let mut guard = db_state.state.lock().await;
+                        let state = guard.as_mut().ok_or_else(library_not_opened)?;
+                        import_data(state, &data, &ext, meme)
+                    }
+                    Err(e) => Err(e),
+                };
+                match imported {
+                    Ok(AddMemeResult::Added(id)) => {
+                        added += 1;
+                        FileImportStatus::Added { id }
+                    }
+                    Ok(AddMemeResult::Duplicate(id)) => FileImportStatus::Duplicate { id },
+                    Err(error) => FileImportStatus::Failed { error },
+                }
+            }
+            None => FileImportStatus::Skipped,
+        };
+        progress.report(
+            idx + 1,
+            ImportedSticker {
+                emoji: sticker.emoji.clone(),
+                status,
+            },
+        )?;
+    }
+    Ok(added)
+}
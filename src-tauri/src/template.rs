@@ -0,0 +1,204 @@
+use image::DynamicImage;
+use imageproc::drawing::text_size;
+use rusqlite::{Connection, Error};
+use rusttype::{Font, Scale};
+
+use crate::{
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    image_edit::{draw_lines, load_font, load_meme_image, save_derived_meme},
+    meme::AddMemeResult,
+    relation::RelationKind,
+};
+
+/// Text is not shrunk below this size to fit in its region
+const MIN_FONT_SIZE: f32 = 8.0;
+
+/// Rectangle of template image to be filled with text, in pixels
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TextRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Template {
+    meme_id: i64,
+    regions: Vec<TextRegion>,
+    /// Path of font file, Impact installed in system is used if it is None
+    font: Option<String>,
+}
+
+fn query_template(conn: &Connection, meme_id: i64) -> AppResult<Template> {
+    let (regions, font): (String, Option<String>) = conn.query_row(
+        "SELECT regions, font FROM template WHERE meme_id = ?1",
+        [meme_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(Template {
+        meme_id,
+        regions: serde_json::from_str(&regions).map_err(|e| e.to_string())?,
+        font,
+    })
+}
+
+/// Break text into lines no wider than `width`, at spaces if it has any,
+/// otherwise between any characters as in Chinese
+fn wrap_text(font: &Font, scale: Scale, text: &str, width: i32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let (units, separator) = if paragraph.contains(' ') {
+            (paragraph.split_whitespace().map(|x| x.to_owned()).collect(), " ")
+        } else {
+            (paragraph.chars().map(|x| x.to_string()).collect::<Vec<String>>(), "")
+        };
+        let mut line = String::new();
+        for unit in units {
+            if line.is_empty() {
+                line = unit;
+                continue;
+            }
+            let candidate = format!("{}{}{}", line, separator, unit);
+            if text_size(scale, font, &candidate).0 > width {
+                lines.push(std::mem::replace(&mut line, unit));
+            } else {
+                line = candidate;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Largest font size at which wrapped text fits in `region`, and the wrapped lines
+fn fit_text(font: &Font, text: &str, region: &TextRegion) -> (Scale, Vec<String>) {
+    let mut size = region.height as f32;
+    loop {
+        let scale = Scale::uniform(size);
+        let lines = wrap_text(font, scale, text, region.width as i32);
+        let fits = lines.len() as f32 * size.ceil() <= region.height as f32
+            && lines
+                .iter()
+                .all(|line| text_size(scale, font, line).0 <= region.width as i32);
+        if fits || size <= MIN_FONT_SIZE {
+            return (scale, lines);
+        }
+        size = (size * 0.9).max(MIN_FONT_SIZE);
+    }
+}
+
+/// Mark image meme as a template with text regions, replacing regions defined before
+#[tauri::command]
+pub async fn set_template(
+    state: tauri::State<'_, MemeDatabaseState>,
+    meme_id: i64,
+    regions: Vec<TextRegion>,
+    font: Option<String>,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+
+    let img = load_meme_image(state, meme_id)?;
+    let in_bounds = regions.iter().all(|region| {
+        region.width > 0
+            && region.height > 0
+            && region.x.saturating_add(region.width) <= img.width()
+            && region.y.saturating_add(region.height) <= img.height()
+    });
+    if regions.is_empty() || !in_bounds {
+        return Err(AppError::InvalidArgument("invalid text regions".to_owned()));
+    }
+    // fail early if the font can not be used
+    load_font(font.as_deref())?;
+
+    let regions = serde_json::to_string(&regions).map_err(|e| e.to_string())?;
+    state.conn.execute(
+        "INSERT OR REPLACE INTO template(meme_id, regions, font) VALUES (?1, ?2, ?3)",
+        (meme_id, regions, font),
+    )?;
+    Ok(())
+}
+
+/// Meme is no longer a template, memes rendered from it are kept
+#[tauri::command]
+pub async fn remove_template(
+    state: tauri::State<'_, MemeDatabaseState>,
+    meme_id: i64,
+) -> AppResult<()> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    state
+        .conn
+        .execute("DELETE FROM template WHERE meme_id = ?1", [meme_id])?;
+    Ok(())
+}
+
+/// All templates, recently added first
+#[tauri::command]
+pub async fn list_templates(
+    state: tauri::State<'_, MemeDatabaseState>,
+) -> AppResult<Vec<Template>> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let mut query = state
+        .conn
+        .prepare("SELECT meme_id FROM template ORDER BY create_time DESC")?;
+    let ids = query
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, Error>>()?;
+    ids.into_iter()
+        .map(|id| query_template(&state.conn, id))
+        .collect()
+}
+
+/// Fill `texts` into regions of template in order and save the result as a new meme,
+/// named by the texts and linked to the template as its instance.
+/// Text is wrapped and shrunk to fit its region, empty texts leave their regions blank
+#[tauri::command]
+pub async fn render_template(
+    state: tauri::State<'_, MemeDatabaseState>,
+    template_id: i64,
+    texts: Vec<String>,
+) -> AppResult<AddMemeResult> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+
+    let template = query_template(&state.conn, template_id)?;
+    if texts.len() > template.regions.len() {
+        return Err(AppError::InvalidArgument(format!(
+            "template has only {} text regions",
+            template.regions.len()
+        )));
+    }
+    let font = load_font(template.font.as_deref())?;
+    let mut img = load_meme_image(state, template_id)?.to_rgba8();
+    for (region, text) in template.regions.iter().zip(&texts) {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let (scale, lines) = fit_text(&font, text, region);
+        let text_height = lines.len() as i32 * scale.y.ceil() as i32;
+        let y = region.y as i32 + (region.height as i32 - text_height) / 2;
+        let column = (region.x as i32, region.width as i32);
+        draw_lines(&mut img, &font, scale, &lines, column, y);
+    }
+
+    let name = texts
+        .iter()
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let name = Some(name).filter(|name| !name.is_empty());
+    save_derived_meme(
+        state,
+        template_id,
+        &DynamicImage::ImageRgba8(img),
+        name,
+        RelationKind::TemplateInstance,
+    )
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, ImageFormat};
+use rusqlite::Error;
+use tauri::Window;
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    file::compute_path,
+    progress::{TaskProgress, TaskRegistry},
+    storage::fetch_content,
+    video::generate_video_thumbnail,
+};
+
+/// Thumbnails are stored in `thumbnails/` under storage directory,
+/// keyed by the content hash without extension
+pub fn thumbnail_path<P: AsRef<Path>>(base: P, hash: &str) -> PathBuf {
+    let hash = hash.split('.').next().unwrap_or(hash);
+    let mut path = compute_path(base.as_ref().join("thumbnails"), hash);
+    path.set_extension("webp");
+    path
+}
+
+/// Generate thumbnail for content which is already in storage
+/// return None if the content can not be decoded as image
+pub fn generate_thumbnail<P: AsRef<Path>>(
+    base: P,
+    hash: &str,
+    size: u32,
+) -> Result<Option<PathBuf>, String> {
+    let img = match image::open(compute_path(&base, hash)) {
+        Ok(img) => img,
+        Err(_) => return Ok(None),
+    };
+    save_thumbnail(base, hash, &img, size).map(Some)
+}
+
+/// Down-scale `img` to fit in `size` and save it as thumbnail of content `hash`
+pub fn save_thumbnail<P: AsRef<Path>>(
+    base: P,
+    hash: &str,
+    img: &DynamicImage,
+    size: u32,
+) -> Result<PathBuf, String> {
+    let path = thumbnail_path(&base, hash);
+    let path_parent = path.parent().unwrap();
+    if !path_parent.exists() {
+        fs::create_dir_all(path_parent).map_err(|e| e.to_string())?;
+    }
+
+    img.thumbnail(size, size)
+        .to_rgba8()
+        .save_with_format(&path, ImageFormat::WebP)
+        .map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Return path of the thumbnail if it exists
+pub fn query_thumbnail<P: AsRef<Path>>(base: P, hash: &str) -> Option<String> {
+    let path = thumbnail_path(base, hash);
+    if path.exists() {
+        path.to_str().map(|x| x.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Generate thumbnails for all image and video memes whose thumbnail is missing,
+/// progress of `task` is reported with hash of each meme checked.
+/// Return the number of generated thumbnails, it stops early if cancelled
+#[tauri::command]
+pub async fn regenerate_thumbnails(
+    window: Window,
+    state: tauri::State<'_, MemeDatabaseState>,
+    registry: tauri::State<'_, TaskRegistry>,
+    task: String,
+) -> AppResult<usize> {
+    let guard = state.state.lock().await;
+    let state = guard.as_ref().ok_or_else(library_not_opened)?;
+    let size = load_settings(&state.conn)?.thumbnail_size;
+
+    let mut query = state
+        .conn
+        .prepare("SELECT ty, hash FROM meme WHERE ty IN ('image', 'video')")
+        .map_err(|e| e.to_string())?;
+    let memes = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(String, String)>, Error>>()
+        .map_err(|e| e.to_string())?;
+
+    let progress = TaskProgress::start(window, &registry, task, memes.len())?;
+    let mut count = 0;
+    for (idx, (ty, hash)) in memes.iter().enumerate() {
+        if progress.is_cancelled() {
+            break;
+        }
+        if !thumbnail_path(&state.path, hash).exists() {
+            fetch_content(state.storage.as_ref(), &state.path, hash)
+                .map_err(AppError::StorageUnavailable)?;
+            let generated = match ty.as_str() {
+                "video" => generate_video_thumbnail(&state.path, hash, size)?,
+                _ => generate_thumbnail(&state.path, hash, size)?,
+            };
+            if generated.is_some() {
+                count += 1;
+            }
+        }
+        progress.report(idx + 1, hash)?;
+    }
+    Ok(count)
+}
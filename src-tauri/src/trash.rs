@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use rusqlite::{Connection, Error};
+
+use crate::{
+    config::load_settings,
+    db::{library_not_opened, MemeDatabaseState},
+    error::AppResult,
+    file::release_contents,
+    journal::JournalRecorder,
+    pages::query_page_hashes,
+    storage::Storage,
+};
+
+/// Delete meme row and every row referencing it,
+/// children must go first since foreign keys are enforced
+pub fn delete_meme_rows(conn: &Connection, id: i64) -> Result<(), String> {
+    for table in [
+        "meme_tag",
+        "meme_text",
+        "media_info",
+        "album_meme",
+        "meme_history",
+        "meme_file",
+    ] {
+        conn.execute(&format!("DELETE FROM {} WHERE meme_id = ?1", table), [id])
+            .map_err(|e| e.to_string())?;
+    }
+    conn.execute("DELETE FROM meme WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Remove meme from database permanently, return content of its pages which should be
+/// passed to `release_contents` once the transaction has committed
+pub fn purge_meme(conn: &Connection, id: i64) -> Result<Vec<String>, String> {
+    let mut hashes = query_page_hashes(conn, id).map_err(|e| e.to_string())?;
+    if hashes.is_empty() {
+        // meme must exist even if it somehow has no page
+        let hash: String = conn
+            .query_row("SELECT hash FROM meme WHERE id = ?1", [id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        hashes.push(hash);
+    }
+    hashes.sort();
+    hashes.dedup();
+
+    delete_meme_rows(conn, id)?;
+    Ok(hashes)
+}
+
+#[tauri::command]
+pub async fn restore_meme(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let mut journal = JournalRecorder::new("Restore");
+    journal.capture(&conn, id)?;
+    conn.execute("UPDATE meme SET trash = false WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    journal.finish(&conn)?;
+    conn.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn purge_meme_by_id(
+    state: tauri::State<'_, MemeDatabaseState>,
+    id: i64,
+) -> AppResult<()> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let released = purge_meme(&conn, id)?;
+    conn.commit().map_err(|e| e.to_string())?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(())
+}
+
+/// Purge memes which have been in trash for more than `days` days
+/// return the number of purged memes and their content to release after commit
+pub fn purge_trash(conn: &Connection, days: u32) -> Result<(usize, Vec<String>), String> {
+    let ids = {
+        let mut query = conn
+            .prepare(
+                "SELECT id FROM meme WHERE trash = true AND trashed_at <= datetime('now', ?1)",
+            )
+            .map_err(|e| e.to_string())?;
+        let ids = query
+            .query_map([format!("-{} days", days)], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<i64>, Error>>()
+            .map_err(|e| e.to_string())?;
+        ids
+    };
+
+    let mut released = Vec::new();
+    for id in &ids {
+        released.extend(purge_meme(conn, *id)?);
+    }
+    released.sort();
+    released.dedup();
+    Ok((ids.len(), released))
+}
+
+/// Purge memes in trash older than `trash_retention_days` in settings
+pub fn purge_expired_trash(
+    conn: &mut Connection,
+    storage: &dyn Storage,
+    base: &Path,
+) -> Result<usize, String> {
+    let days = load_settings(conn)?.trash_retention_days;
+    if days == 0 {
+        return Ok(0);
+    }
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let (purged, released) = purge_trash(&tx, days)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    release_contents(conn, storage, base, &released);
+    Ok(purged)
+}
+
+#[tauri::command]
+pub async fn purge_trash_older_than(
+    state: tauri::State<'_, MemeDatabaseState>,
+    days: u32,
+) -> AppResult<usize> {
+    let mut guard = state.state.lock().await;
+    let state = guard.as_mut().ok_or_else(library_not_opened)?;
+    let conn = state.conn.transaction().map_err(|e| e.to_string())?;
+    let (purged, released) = purge_trash(&conn, days)?;
+    conn.commit().map_err(|e| e.to_string())?;
+    release_contents(&state.conn, state.storage.as_ref(), &state.path, &released);
+    Ok(purged)
+}
@@ -0,0 +1,126 @@
+use rusqlite::{Connection, Error};
+use tauri::{
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem,
+};
+use tracing::warn;
+
+use crate::{
+    clipboard::{copy_to_clipboard, import_clipboard_image},
+    db::{library_not_opened, MemeDatabaseState},
+    error::{AppError, AppResult},
+    meme::record_meme_use,
+    private::private_filter,
+};
+
+/// Number of recently used memes listed in tray menu
+const RECENT_COUNT: i64 = 10;
+/// Menu item of a meme has id of this prefix followed by id of meme
+const MEME_ITEM_PREFIX: &str = "meme:";
+const IMPORT_ITEM: &str = "import_clipboard";
+const SHOW_ITEM: &str = "show";
+const QUIT_ITEM: &str = "quit";
+const MAIN_WINDOW: &str = "main";
+
+/// Memes not in trash used most recently, id and name
+fn query_recent_memes(
+    conn: &Connection,
+    private_unlocked: bool,
+) -> Result<Vec<(i64, String)>, Error> {
+    let mut query = conn.prepare(&format!(
+        "SELECT id, name FROM meme WHERE last_used IS NOT NULL AND trash = false {}
+          ORDER BY last_used DESC LIMIT ?1",
+        private_filter(private_unlocked)
+    ))?;
+    let memes = query
+        .query_map([RECENT_COUNT], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, String)>, Error>>()?;
+    Ok(memes)
+}
+
+fn build_menu(recent: Vec<(i64, String)>) -> SystemTrayMenu {
+    let mut menu = SystemTrayMenu::new();
+    let has_recent = !recent.is_empty();
+    for (id, name) in recent {
+        let item_id = format!("{}{}", MEME_ITEM_PREFIX, id);
+        menu = menu.add_item(CustomMenuItem::new(item_id, name));
+    }
+    if has_recent {
+        menu = menu.add_native_item(SystemTrayMenuItem::Separator);
+    }
+    menu.add_item(CustomMenuItem::new(IMPORT_ITEM, "Import from Clipboard"))
+        .add_item(CustomMenuItem::new(SHOW_ITEM, "Open Meme Management"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(QUIT_ITEM, "Quit"))
+}
+
+/// Tray without recent memes, they are filled in by `refresh_tray` once app is set up
+pub fn build_tray() -> SystemTray {
+    SystemTray::new().with_menu(build_menu(Vec::new()))
+}
+
+/// List recently used memes of opened library in tray menu again
+pub async fn refresh_tray(app: &AppHandle) -> AppResult<()> {
+    let db_state = app.state::<MemeDatabaseState>();
+    let recent = match db_state.state.lock().await.as_ref() {
+        Some(state) => query_recent_memes(&state.conn, state.private_unlocked)?,
+        None => Vec::new(),
+    };
+    app.tray_handle()
+        .set_menu(build_menu(recent))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn show_main_window(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_window(MAIN_WINDOW) {
+        window.show()?;
+        window.unminimize()?;
+        window.set_focus()?;
+    }
+    Ok(())
+}
+
+async fn copy_recent_meme(app: &AppHandle, id: i64) -> AppResult<()> {
+    {
+        let db_state = app.state::<MemeDatabaseState>();
+        let guard = db_state.state.lock().await;
+        let state = guard.as_ref().ok_or_else(library_not_opened)?;
+        copy_to_clipboard(state, id)?;
+        record_meme_use(&state.conn, id)?;
+    }
+    refresh_tray(app).await
+}
+
+async fn import_from_tray(app: &AppHandle) -> AppResult<()> {
+    let db_state = app.state::<MemeDatabaseState>();
+    import_clipboard_image(&db_state, None, Vec::new(), 0).await?;
+    Ok(())
+}
+
+pub fn handle_tray_event(app: &AppHandle, event: SystemTrayEvent) {
+    let id = match event {
+        SystemTrayEvent::LeftClick { .. } => SHOW_ITEM.to_owned(),
+        SystemTrayEvent::MenuItemClick { id, .. } => id,
+        _ => return,
+    };
+    let app = app.clone();
+    // commands lock database asynchronously, handler runs on the event loop thread
+    tauri::async_runtime::spawn(async move {
+        let result = match id.as_str() {
+            SHOW_ITEM => show_main_window(&app).map_err(|e| AppError::Other(e.to_string())),
+            QUIT_ITEM => {
+                app.exit(0);
+                Ok(())
+            }
+            IMPORT_ITEM => import_from_tray(&app).await,
+            _ => match id.strip_prefix(MEME_ITEM_PREFIX).map(str::parse::<i64>) {
+                Some(Ok(meme_id)) => copy_recent_meme(&app, meme_id).await,
+                _ => Ok(()),
+            },
+        };
+        if let Err(e) = result {
+            warn!(action = %id, error = %e, "tray action failed");
+        }
+    });
+}
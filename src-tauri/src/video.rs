@@ -0,0 +1,125 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use rusqlite::Connection;
+use tracing::warn;
+
+use crate::{
+    config::load_settings,
+    file::compute_path,
+    media::{save_media_info, video_media_info},
+    thumbnail::save_thumbnail,
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Duration in milliseconds, unknown for some streaming containers
+    pub duration: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeStream {
+    width: u32,
+    height: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeOutput {
+    streams: Vec<ProbeStream>,
+    format: ProbeFormat,
+}
+
+/// Read dimensions and duration of the first video stream by the external `ffprobe` binary
+pub fn probe_video<P: AsRef<Path>>(path: P) -> Result<VideoInfo, String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height:format=duration"])
+        .args(["-of", "json"])
+        .arg(path.as_ref())
+        .output()
+        .map_err(|e| format!("failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let stream = probe
+        .streams
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no video stream found".to_owned())?;
+    let duration = probe
+        .format
+        .duration
+        .and_then(|x| x.parse::<f64>().ok())
+        .map(|x| (x * 1000.0) as u32);
+    Ok(VideoInfo {
+        width: stream.width,
+        height: stream.height,
+        duration,
+    })
+}
+
+/// Pick a representative frame of video by the external `ffmpeg` binary
+pub fn extract_poster_frame<P: AsRef<Path>>(path: P) -> Result<image::DynamicImage, String> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path.as_ref())
+        .args(["-vf", "thumbnail", "-frames:v", "1"])
+        .args(["-f", "image2pipe", "-vcodec", "png", "-"])
+        .output()
+        .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    image::load_from_memory(&output.stdout).map_err(|e| e.to_string())
+}
+
+/// Generate thumbnail of video content from its poster frame
+/// return None if no frame can be extracted
+pub fn generate_video_thumbnail<P: AsRef<Path>>(
+    base: P,
+    hash: &str,
+    size: u32,
+) -> Result<Option<PathBuf>, String> {
+    let frame = match extract_poster_frame(compute_path(&base, hash)) {
+        Ok(frame) => frame,
+        Err(_) => return Ok(None),
+    };
+    save_thumbnail(base, hash, &frame, size).map(Some)
+}
+
+/// Derive data from video content of a newly inserted meme:
+/// media info, duration and poster thumbnail.
+/// Video which `ffprobe` can not read, or imported without `ffprobe` installed, is kept
+/// without duration and media info, `refresh_media_info` reads it again later
+pub fn process_video_content<P: AsRef<Path>>(
+    conn: &Connection,
+    base: P,
+    meme_id: i64,
+    content: &str,
+) -> Result<(), String> {
+    let path = compute_path(&base, content);
+    match probe_video(&path) {
+        Ok(info) => {
+            conn.execute(
+                "UPDATE meme SET duration = ?1 WHERE id = ?2",
+                (info.duration, meme_id),
+            )
+            .map_err(|e| e.to_string())?;
+            save_media_info(conn, meme_id, &video_media_info(&path, &info)?)?;
+        }
+        Err(e) => warn!(meme_id, error = %e, "failed to probe video"),
+    }
+    let size = load_settings(conn)?.thumbnail_size;
+    generate_video_thumbnail(&base, content, size)?;
+    Ok(())
+}
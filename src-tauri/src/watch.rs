@@ -0,0 +1,257 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::{error, warn};
+
+use crate::{
+    app_config::AppConfig,
+    db::MemeDatabaseState,
+    error::{AppError, AppResult},
+    file::media_type,
+    import::import_media_file,
+    meme::{make_tag, AddMemeResult, Tag},
+    staging::settle_staging,
+    AppDir,
+};
+
+/// A new file is imported once it has not been written to for this long,
+/// so files still being saved are not imported half written
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+/// Emitted after a file in watched folder is imported
+const WATCH_EVENT: &str = "watch_imported";
+
+/// What is done to a file in watched folder once it is imported
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum AfterImport {
+    #[default]
+    Keep,
+    Delete,
+    /// Move file into directory `to`
+    Move { to: PathBuf },
+}
+
+/// Folder whose new images and videos are imported into opened library
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WatchFolder {
+    pub path: PathBuf,
+    /// Tags added to every meme imported from this folder
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub after_import: AfterImport,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct WatchImportEvent {
+    path: String,
+    id: i64,
+    duplicate: bool,
+}
+
+/// Watcher of folders in app config, dropping it stops importing
+#[derive(Default)]
+pub struct WatchState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+/// Path of a new file, or of a file written to
+struct FileEvent {
+    path: PathBuf,
+    created: bool,
+}
+
+/// Start watching `folders`, folders watched before are no longer watched
+pub fn start_watching(app: &AppHandle, folders: Vec<WatchFolder>) -> Result<(), String> {
+    let state = app.state::<WatchState>();
+    let mut current = state.watcher.lock().unwrap();
+    // old importer stops once its watcher is dropped
+    *current = None;
+    if folders.is_empty() {
+        return Ok(());
+    }
+
+    let (sender, receiver) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                error!(error = %e, "folder watcher failed");
+                return;
+            }
+        };
+        let created = match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) => true,
+            EventKind::Modify(_) => false,
+            _ => return,
+        };
+        for path in event.paths {
+            // receiver is gone only while watcher is being replaced
+            let _ = sender.send(FileEvent { path, created });
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    for folder in &folders {
+        watcher
+            .watch(&folder.path, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("failed to watch {}: {}", folder.path.display(), e))?;
+    }
+    *current = Some(watcher);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(run_importer(app, folders, receiver));
+    Ok(())
+}
+
+/// Import files once they settle, until watcher is dropped.
+/// Files only written to are ignored unless they are new,
+/// so editing an existing file in folder does not import it
+async fn run_importer(
+    app: AppHandle,
+    folders: Vec<WatchFolder>,
+    mut receiver: UnboundedReceiver<FileEvent>,
+) {
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match tokio::time::timeout(SETTLE_DELAY, receiver.recv()).await {
+            Ok(Some(event)) => {
+                if event.created || pending.contains_key(&event.path) {
+                    pending.insert(event.path, Instant::now());
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {}
+        }
+
+        let settled = pending
+            .iter()
+            .filter(|(_, time)| time.elapsed() >= SETTLE_DELAY)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+        for path in settled {
+            pending.remove(&path);
+            let folder = folders
+                .iter()
+                .find(|folder| path.parent() == Some(folder.path.as_path()));
+            if let Some(folder) = folder {
+                if let Err(e) = import_watched_file(&app, folder, &path).await {
+                    warn!(path = %path.display(), error = %e, "failed to import watched file");
+                }
+            }
+        }
+    }
+}
+
+/// Import a file of watched folder into opened library, skipped if no library is opened.
+/// The file is moved or deleted as configured even if its content is already in library
+async fn import_watched_file(
+    app: &AppHandle,
+    folder: &WatchFolder,
+    path: &Path,
+) -> AppResult<()> {
+    let ty = match media_type(path).filter(|_| path.is_file()) {
+        Some(ty) => ty,
+        None => return Ok(()),
+    };
+
+    let result = {
+        let db_state = app.state::<MemeDatabaseState>();
+        let mut guard = db_state.state.lock().await;
+        let state = match guard.as_mut() {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        let conn = state.conn.transaction()?;
+        let tag_id = folder
+            .tags
+            .iter()
+            .map(|t| make_tag(&conn, &t.key, &t.value))
+            .collect::<Result<Vec<i64>, String>>()?;
+        let result = import_media_file(
+            &conn,
+            state.storage.as_ref(),
+            &state.path,
+            path,
+            ty,
+            &tag_id,
+            0,
+            false,
+        )?;
+        conn.commit()?;
+        if let Err(e) = settle_staging(&state.conn, state.storage.as_ref(), &state.path) {
+            warn!(error = %e, "failed to settle staged imports");
+        }
+        result
+    };
+
+    let (id, duplicate) = match result {
+        AddMemeResult::Added(id) => (id, false),
+        AddMemeResult::Duplicate(id) => (id, true),
+    };
+    handle_original(path, &folder.after_import)?;
+    let event = WatchImportEvent {
+        path: path.to_string_lossy().to_string(),
+        id,
+        duplicate,
+    };
+    if let Err(e) = app.emit_all(WATCH_EVENT, event) {
+        warn!(error = %e, "failed to emit watch import");
+    }
+    Ok(())
+}
+
+fn handle_original(path: &Path, after_import: &AfterImport) -> Result<(), String> {
+    match after_import {
+        AfterImport::Keep => Ok(()),
+        AfterImport::Delete => fs::remove_file(path).map_err(|e| e.to_string()),
+        AfterImport::Move { to } => {
+            let target = to.join(path.file_name().unwrap_or_default());
+            if target.exists() {
+                return Err(format!("{} already exists", target.display()));
+            }
+            // rename fails when target is on another device
+            if fs::rename(path, &target).is_err() {
+                fs::copy(path, &target).map_err(|e| e.to_string())?;
+                fs::remove_file(path).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Replace watched folders, new files in them are imported into opened library from now on
+#[tauri::command]
+pub async fn set_watch_folders(
+    app: AppHandle,
+    app_dir: tauri::State<'_, AppDir>,
+    folders: Vec<WatchFolder>,
+) -> AppResult<()> {
+    for folder in &folders {
+        if !folder.path.is_dir() {
+            return Err(AppError::InvalidArgument(format!(
+                "{} is not a directory",
+                folder.path.display()
+            )));
+        }
+        if let AfterImport::Move { to } = &folder.after_import {
+            if !to.is_dir() || to == &folder.path {
+                return Err(AppError::InvalidArgument(format!(
+                    "can not move imported files to {}",
+                    to.display()
+                )));
+            }
+        }
+    }
+    start_watching(&app, folders.clone())?;
+    let mut config = AppConfig::load(&app_dir.storage_dir)?;
+    config.watch_folders = folders;
+    Ok(config.save(&app_dir.storage_dir)?)
+}
@@ -1,9 +1,9 @@
 use tokio::fs;
 
-use crate::AppDir;
+use crate::{error::AppResult, AppDir};
 
 #[tauri::command]
-pub async fn zustand_set(state: tauri::State<'_, AppDir>, name: String, value: String) -> Result<(), String> {
+pub async fn zustand_set(state: tauri::State<'_, AppDir>, name: String, value: String) -> AppResult<()> {
   let dir = state.storage_dir.join("storage");
   if !dir.exists(){
     fs::create_dir_all(&dir).await.map_err(|e|e.to_string())?;
@@ -16,7 +16,7 @@ pub async fn zustand_set(state: tauri::State<'_, AppDir>, name: String, value: S
 }
 
 #[tauri::command]
-pub async fn zustand_get(state: tauri::State<'_, AppDir>, name: String) -> Result<Option<String>, String> {
+pub async fn zustand_get(state: tauri::State<'_, AppDir>, name: String) -> AppResult<Option<String>> {
   let mut file = state.storage_dir.join("storage").join(name);
   file.set_extension("json");
   if file.exists() {
@@ -27,7 +27,7 @@ pub async fn zustand_get(state: tauri::State<'_, AppDir>, name: String) -> Resul
 }
 
 #[tauri::command]
-pub async fn zustand_del(state: tauri::State<'_, AppDir>, name: String) -> Result<(), String> {
+pub async fn zustand_del(state: tauri::State<'_, AppDir>, name: String) -> AppResult<()> {
   let mut file = state.storage_dir.join("storage").join(name);
   file.set_extension("json");
   if file.exists() {
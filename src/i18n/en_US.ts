@@ -35,4 +35,15 @@ export default {
 
   'Light Theme': 'Light',
   'Dark Theme': 'Dark',
-}
\ No newline at end of file
+
+  'Quick Pick': 'Type to find a meme, Enter to copy',
+  'No Match': 'No matching meme',
+
+  'Library Locked': 'This library is encrypted',
+  'Passphrase': 'Passphrase',
+  'Wrong Passphrase': 'Passphrase is wrong',
+  'Unlock': 'Unlock',
+  'Library Too New': 'This library needs a newer version',
+  'Library Too New Detail': 'It was opened by a newer version of the app. Update the app to open it, so its data is not damaged.',
+  'Close': 'Close',
+}
@@ -35,4 +35,15 @@ export default {
 
   'Light Theme': '明亮',
   'Dark Theme': '夜间',
-}
\ No newline at end of file
+
+  'Quick Pick': '输入以查找模因，回车复制',
+  'No Match': '没有匹配的模因',
+
+  'Library Locked': '此库已加密',
+  'Passphrase': '密码',
+  'Wrong Passphrase': '密码错误',
+  'Unlock': '解锁',
+  'Library Too New': '此库需要更新版本的应用',
+  'Library Too New Detail': '此库已被更新版本的应用打开过，请更新应用后再打开，以免损坏其中的数据。',
+  'Close': '关闭',
+}
@@ -0,0 +1,41 @@
+import { invoke } from '@tauri-apps/api'
+import { MemeQueried } from './db'
+
+export interface Album {
+  id: number,
+  name: string,
+  description?: string,
+  count: number
+}
+
+export async function createAlbum(name: string, description?: string): Promise<number> {
+  return invoke<number>('create_album', { name, description })
+}
+
+export async function updateAlbum(id: number, name: string, description?: string): Promise<void> {
+  return invoke('update_album', { id, name, description })
+}
+
+export async function deleteAlbum(id: number): Promise<void> {
+  return invoke('delete_album', { id })
+}
+
+export async function listAlbums(): Promise<Album[]> {
+  return invoke<Album[]>('list_albums')
+}
+
+export async function getAlbumMemes(id: number): Promise<MemeQueried[]> {
+  return invoke<MemeQueried[]>('get_album_memes', { id })
+}
+
+export async function addMemesToAlbum(id: number, memeIds: number[]): Promise<void> {
+  return invoke('add_memes_to_album', { id, memeIds })
+}
+
+export async function removeMemesFromAlbum(id: number, memeIds: number[]): Promise<void> {
+  return invoke('remove_memes_from_album', { id, memeIds })
+}
+
+export async function reorderAlbum(id: number, memeIds: number[]): Promise<void> {
+  return invoke('reorder_album', { id, memeIds })
+}
@@ -1,7 +1,17 @@
 import * as R from 'ramda'
-import { invoke } from '@tauri-apps/api'
+import { invoke, tauri } from '@tauri-apps/api'
 import { MemePkg, Tag } from '../../model/meme'
 
+/**
+ * Error rejected by every command
+ */
+export interface AppError {
+  code: 'not_found' | 'conflict' | 'invalid_argument' | 'invalid_query' | 'migration_failed'
+    | 'database_too_new' | 'corrupted' | 'storage_unavailable' | 'locked' | 'unauthorized'
+    | 'forbidden' | 'database' | 'io' | 'other'
+  message: string
+}
+
 export interface MemeToAdd {
   name: string,
   description?: string,
@@ -9,29 +19,197 @@ export interface MemeToAdd {
   content: string,
   fav: boolean,
   tags: Tag[],
-  pkg_id: number
+  pkg_id: number,
+  strip_exif?: boolean,
+  source_url?: string,
+  author?: string,
+  origin?: string
 }
 
-export async function openStorage(path: string) {
+/**
+ * Open library at `path`, rejected with `locked` if it is encrypted and passphrase is missing or wrong
+ */
+export async function openStorage(path: string, passphrase?: string) {
   return await invoke('open_storage', {
-    path
+    path,
+    passphrase
   })
 }
 
+/**
+ * What opening library at `path` would migrate, schema objects are named like `table meme_file`
+ */
+export interface MigrationReport {
+  from_version: number | null,
+  to_version: number,
+  pending: number[],
+  /** Opening library fails if script of any applied migration has changed */
+  changed_scripts: number[],
+  created: string[],
+  dropped: string[],
+  altered: string[]
+}
+
+/**
+ * Check migrations of library at `path` without opening it, nothing is written
+ */
+export async function dryRunMigrations(path: string, passphrase?: string): Promise<MigrationReport> {
+  return invoke<MigrationReport>('dry_run_migrations', { path, passphrase })
+}
+
+export interface PerformanceReport {
+  sql: string
+  /** Steps of `EXPLAIN QUERY PLAN`, nested steps are indented */
+  plan: string[]
+  /** Tables read in full and indexes missing from library */
+  warnings: string[]
+}
+
+/**
+ * Explain how search statement `stmt` is executed for the first page
+ */
+export async function analyzePerformance(stmt: string, sortBy?: SortBy): Promise<PerformanceReport> {
+  return invoke<PerformanceReport>('analyze_performance', { stmt, sortBy })
+}
+
+/**
+export interface PassphraseResult {
+  /** Backups under the old passphrase that were removed */
+  removed_backups: number
+  /** Path of backup taken with the new passphrase */
+  backup: string
+}
+
+/**
+ * Encrypt opened library, change its passphrase, or decrypt it by `null`.
+ * Only database is encrypted, content files are kept as they are.
+ * Old backups are replaced by one under the new passphrase
+ */
+export async function setLibraryPassphrase(passphrase: string | null): Promise<PassphraseResult> {
+  return invoke<PassphraseResult>('set_library_passphrase', { passphrase })
+}
+
+export interface NamedLibrary {
+  name: string
+  path: string
+}
+
+export interface AppConfig {
+  library_root: string | null
+  libraries: NamedLibrary[]
+  current: string | null
+  api_port: number | null
+  api_token: string | null
+  picker_shortcut: string | null
+  rpc_enabled: boolean
+  watch_folders: WatchFolder[]
+}
+
+export async function getAppConfig(): Promise<AppConfig> {
+  return invoke<AppConfig>('get_app_config')
+}
+
+export async function setLibraryRoot(path: string | null): Promise<void> {
+  return invoke('set_library_root', { path })
+}
+
+export async function addLibrary(name: string, path?: string): Promise<NamedLibrary> {
+  return invoke<NamedLibrary>('add_library', { name, path })
+}
+
+export async function removeLibrary(name: string): Promise<void> {
+  return invoke('remove_library', { name })
+}
+
+export async function switchLibrary(name: string, passphrase?: string): Promise<NamedLibrary> {
+  return invoke<NamedLibrary>('switch_library', { name, passphrase })
+}
+
+/**
+ * Port of local read-only REST API, `null` disables it, takes effect after restart
+ */
+export async function setApiPort(port: number | null): Promise<void> {
+  return invoke('set_api_port', { port })
+}
+
+/**
+ * Token browser extension sends to import memes through REST API, `null` disables importing
+ */
+export async function setApiToken(token: string | null): Promise<void> {
+  return invoke('set_api_token', { token })
+}
+
+/**
+ * Serve JSON-RPC on a local socket for launchers and scripts, takes effect after restart
+ */
+export async function setRpcEnabled(enabled: boolean): Promise<void> {
+  return invoke('set_rpc_enabled', { enabled })
+}
+
+/**
+ * Global shortcut opening quick picker, e.g. `CmdOrCtrl+Shift+M`, `null` restores the default
+ */
+export async function setPickerShortcut(shortcut: string | null): Promise<void> {
+  return invoke('set_picker_shortcut', { shortcut })
+}
+
+export type AfterImport =
+  | { action: 'keep' }
+  | { action: 'delete' }
+  | { action: 'move', to: string }
+
+export interface WatchFolder {
+  path: string
+  tags: Tag[]
+  after_import: AfterImport
+}
+
+/**
+ * Import new files in these folders into opened library automatically,
+ * `watch_imported` event is emitted after each file
+ */
+export async function setWatchFolders(folders: WatchFolder[]): Promise<void> {
+  return invoke('set_watch_folders', { folders })
+}
+
 export async function getStorage(): Promise<string> {
   return invoke('get_storage')
 }
 
-export async function addMemeRecord(meme: MemeToAdd) {
+export type AddMemeResult =
+  | { status: 'added', id: number }
+  | { status: 'duplicate', id: number }
+
+export async function addMemeRecord(meme: MemeToAdd): Promise<AddMemeResult> {
   return invoke('add_meme_record', {
     item: meme
   })
 }
 
-export async function updateMemeRecord(id: number, meme: MemeToAdd){
-  return invoke('update_meme_record', {
+/**
+ * Rejected with `conflict` if meme is no longer at `expectedVersion`,
+ * i.e. it has been edited elsewhere since it was loaded.
+ * Resolve to version of meme after the edit
+ */
+export async function updateMemeRecord(id: number, meme: MemeToAdd, expectedVersion?: number){
+  return invoke<number>('update_meme_record', {
     memeId: id,
-    item: meme
+    item: meme,
+    expectedVersion
+  })
+}
+
+export async function bulkLinkTags(memeIds: number[], tags: Tag[]) {
+  return invoke('bulk_link_tags', {
+    memeIds,
+    tags
+  })
+}
+
+export async function bulkUnlinkTags(memeIds: number[], tags: Tag[]) {
+  return invoke('bulk_unlink_tags', {
+    memeIds,
+    tags
   })
 }
 
@@ -48,22 +226,165 @@ export async function setTrashMemeRecord(id: number, trash: boolean){
   })
 }
 
+export async function restoreMeme(id: number): Promise<void> {
+  return invoke('restore_meme', { id })
+}
+
+export async function purgeMeme(id: number): Promise<void> {
+  return invoke('purge_meme_by_id', { id })
+}
+
+export async function purgeTrashOlderThan(days: number): Promise<number> {
+  return invoke<number>('purge_trash_older_than', { days })
+}
+
 export interface MemeQueried extends MemePkg {
-  path: string
+  path: string,
+  rating: number,
+  use_count: number,
+  last_used?: string,
+  thumbnail?: string,
+  preview?: string,
+  frame_count: number,
+  duration?: number,
+  width?: number,
+  height?: number,
+  format?: string,
+  size?: number,
+  source_url?: string,
+  author?: string,
+  origin?: string,
+  derived_from?: number,
+  update_time: string
+  /** Bumped by every edit, passed back to `updateMemeRecord` */
+  version: number
+}
+
+/**
+ * Part of a field matching a search term, `start` and `end` are byte offsets into UTF-8 value of the field
+ */
+export interface Highlight {
+  field: 'name' | 'description' | 'tag' | 'text'
+  /** Tag whose value is matched, only set for `tag` */
+  tag: Tag | null
+  start: number
+  end: number
+}
+
+export interface SearchPage {
+  items: MemeQueried[],
+  total: number,
+  page_size: number,
+  /** Opaque position of the next page, `null` on the last page */
+  next_cursor: string | null,
+  /** Keyed by meme id, memes matching no keyword or tag have no entry */
+  highlights: Record<number, Highlight[]>
+}
+
+export type SortBy = 'Relevance' | 'UpdateTime' | 'CreateTime' | 'MostUsed' | 'RecentlyUsed' | 'Rating' | 'Hue'
+
+/**
+ * Page of search result, the first page if `cursor` is `null`
+ */
+export async function searchMeme(stmt: string, cursor: string | null, fav: boolean, trash: boolean, pageSize?: number, sortBy?: SortBy): Promise<SearchPage> {
+  return invoke<SearchPage>('search_meme', { stmt, cursor, pageSize, fav, trash, sortBy })
+}
+
+export interface Settings {
+  page_size: number
+  thumbnail_size: number
+  trash_retention_days: number
+  default_sort: SortBy | null
+  verify_on_read: boolean
+  auto_tagger: AutoTaggerConfig | null
+  auto_tag_threshold: number
+  /** Format SVG, BMP, TIFF, HEIC and AVIF images are converted to on import */
+  import_format: 'png' | 'webp'
+  keep_original_format: boolean
+  compression: CompressionConfig | null
+  /** Days between background maintenance of database, 0 only maintains it on request */
+  maintenance_interval_days: number
+}
+
+/**
+ * Lossy re-encoding of PNG content larger than `min_size` bytes, `quality` is from 0 to 100
+ */
+export interface CompressionConfig {
+  format: 'webp' | 'avif'
+  quality: number
+  min_size: number
+}
+
+/**
+ * Classifier answering a JSON object of tag names to confidences from 0 to 1
+ */
+export type AutoTaggerConfig =
+  | { type: 'command', program: string, args: string[] }
+  | { type: 'http', url: string }
+
+export async function getSettings(): Promise<Settings> {
+  return invoke<Settings>('get_settings')
+}
+
+export async function setSettings(settings: Settings): Promise<void> {
+  return invoke('set_settings', { settings })
+}
+
+export async function markMemeUsed(id: number): Promise<void> {
+  return invoke('mark_meme_used', { id })
+}
+
+export async function copyMemeToClipboard(id: number): Promise<void> {
+  return invoke('copy_meme_to_clipboard', { id })
+}
+
+export async function importFromClipboard(tags: Tag[], pkgId: number, name?: string): Promise<AddMemeResult> {
+  return invoke<AddMemeResult>('import_from_clipboard', { name, tags, pkgId })
 }
 
-export async function searchMeme(stmt: string, page: number, fav: boolean, trash: boolean): Promise<MemeQueried[]> {
-  return invoke<MemeQueried[]>('search_meme', { stmt, page, fav, trash })
+/**
+ * Download image or video at `url` and import it, `url` is kept as source url of the meme
+ */
+export async function importFromUrl(url: string, tags: Tag[], pkgId: number, name?: string): Promise<AddMemeResult> {
+  return invoke<AddMemeResult>('import_from_url', { url, name, tags, pkgId })
 }
 
 export async function getMemeById(id: number): Promise<MemeQueried> {
   return invoke<MemeQueried>('get_meme_by_id', { id })
 }
 
+/**
+ * Random meme not in trash, only from memes matching search statement `filter` if given
+ */
+export async function getRandomMeme(filter?: string): Promise<MemeQueried | null> {
+  return invoke<MemeQueried | null>('get_random_meme', { filter })
+}
+
+/**
+ * Url of content file served by `meme` protocol, downloaded first if it is only in remote storage
+ */
+export function contentUrl(hash: string): string {
+  return tauri.convertFileSrc(`content/${hash}`, 'meme')
+}
+
+/**
+ * Url of thumbnail served by `meme` protocol
+ */
+export function thumbnailUrl(hash: string): string {
+  return tauri.convertFileSrc(`thumb/${hash}`, 'meme')
+}
+
 export async function getTagsById(id: number): Promise<Tag[]> {
   return invoke<Tag[]>('get_tags_by_id', { id })
 }
 
+/**
+ * Tags of many memes in one call, keyed by meme id
+ */
+export async function getTagsByIds(ids: number[]): Promise<Record<number, Tag[]>> {
+  return invoke<Record<number, Tag[]>>('get_tags_by_ids', { ids })
+}
+
 export async function getTagKeysByPrefix(prefix: string): Promise<string[]> {
   return invoke<string[]>('get_tag_keys_by_prefix', { prefix: prefix })
 }
@@ -85,9 +406,771 @@ export async function getTagsRelated(tags: Tag[]): Promise<TagFreq[]>{
   return invoke<TagFreq[]>('get_tags_related', {tags: tags})
 }
 
+export interface TagAlias {
+  alias: Tag
+  tag: Tag
+}
+
+export interface TagImplication {
+  tag: Tag
+  implied: Tag
+}
+
+export async function addTagAlias(alias: Tag, tag: Tag): Promise<void> {
+  return invoke('add_tag_alias', { alias, tag })
+}
+
+export async function removeTagAlias(alias: Tag): Promise<void> {
+  return invoke('remove_tag_alias', { alias })
+}
+
+export async function listTagAliases(): Promise<TagAlias[]> {
+  return invoke<TagAlias[]>('list_tag_aliases')
+}
+
+export async function addTagImplication(tag: Tag, implied: Tag): Promise<void> {
+  return invoke('add_tag_implication', { tag, implied })
+}
+
+export async function removeTagImplication(tag: Tag, implied: Tag): Promise<void> {
+  return invoke('remove_tag_implication', { tag, implied })
+}
+
+export async function listTagImplications(): Promise<TagImplication[]> {
+  return invoke<TagImplication[]>('list_tag_implications')
+}
+
+export interface Namespace {
+  key: string
+  color?: string
+  priority: number
+  description?: string
+}
+
+export interface NamespaceInfo extends Namespace {
+  count: number
+}
+
+export async function listNamespaces(): Promise<NamespaceInfo[]> {
+  return invoke<NamespaceInfo[]>('list_namespaces')
+}
+
+export async function setNamespace(namespace: Namespace): Promise<void> {
+  return invoke('set_namespace', { namespace })
+}
+
+export async function renameNamespace(old: string, newName: string): Promise<void> {
+  return invoke('rename_namespace', { old, new: newName })
+}
+
+export interface JournalOperation {
+  id: number
+  description: string
+  time: string
+  undone: boolean
+}
+
+export async function listJournal(limit: number): Promise<JournalOperation[]> {
+  return invoke<JournalOperation[]>('list_journal', { limit })
+}
+
+export async function undoLast(n: number): Promise<string[]> {
+  return invoke<string[]>('undo_last', { n })
+}
+
+export async function redo(): Promise<string | null> {
+  return invoke<string | null>('redo')
+}
+
+export interface MemeHistoryEntry {
+  id: number
+  field: string
+  old: unknown
+  new: unknown
+  time: string
+}
+
+export async function getMemeHistory(id: number): Promise<MemeHistoryEntry[]> {
+  return invoke<MemeHistoryEntry[]>('get_meme_history', { id })
+}
+
+export async function revertMemeField(id: number): Promise<void> {
+  return invoke('revert_meme_field', { id })
+}
+
+export interface TagCount {
+  key: string
+  value: string
+  count: number
+}
+
+export async function getTagUsageCounts(namespace?: string, limit?: number): Promise<TagCount[]> {
+  return invoke<TagCount[]>('get_tag_usage_counts', { namespace, limit })
+}
+
+export async function getMostUsedTags(limit: number): Promise<TagCount[]> {
+  return invoke<TagCount[]>('get_most_used_tags', { limit })
+}
+
+export async function getRecentTags(limit: number): Promise<Tag[]> {
+  return invoke<Tag[]>('get_recent_tags', { limit })
+}
+
+export type TagSuggestion =
+  | { kind: 'namespace', key: string, count: number }
+  | { kind: 'tag', key: string, value: string, count: number }
+
+export async function getTagSuggestions(input: string, limit: number): Promise<TagSuggestion[]> {
+  return invoke<TagSuggestion[]>('get_tag_suggestions', { input, limit })
+}
+
+/**
+ * Tags mentioned by name, description or recognized text of meme, only of kind `tag`
+ */
+export async function suggestTagsForMeme(id: number, limit: number): Promise<TagSuggestion[]> {
+  return invoke<TagSuggestion[]>('suggest_tags_for_meme', { id, limit })
+}
+
+export interface DiskUsage {
+  key: string,
+  /** `null` if grouped by namespace */
+  value: string | null,
+  memes: number,
+  /** Bytes of content files, a file shared by several memes is counted once */
+  size: number
+}
+
+/**
+ * Content size of memes grouped by tag or namespace, biggest first
+ */
+export async function diskUsageReport(groupBy: 'tag' | 'namespace', limit?: number): Promise<DiskUsage[]> {
+  return invoke<DiskUsage[]>('disk_usage_report', { groupBy, limit })
+}
+
 export async function setMemeFav(id: number, fav:boolean): Promise<void>{
   return invoke('set_meme_fav', {id, fav})
 }
+export async function setMemeRating(id: number, rating: number): Promise<void> {
+  return invoke('set_meme_rating', { id, rating })
+}
 export async function setMemeTrash(id:number, trash: boolean): Promise<void>{
   return invoke('set_meme_trash', {id, trash})
-}
\ No newline at end of file
+}
+/**
+ * Move memes to trash, or restore them by `false`, all or none of them are changed
+ */
+export async function setTrashBulk(ids: number[], value: boolean): Promise<void> {
+  return invoke('set_trash_bulk', { ids, value })
+}
+/**
+ * Favorite memes, or unfavorite them by `false`, all or none of them are changed
+ */
+export async function setFavBulk(ids: number[], value: boolean): Promise<void> {
+  return invoke('set_fav_bulk', { ids, value })
+}
+/**
+ * Remove memes permanently, all or none of them are removed
+ */
+export async function deleteBulk(ids: number[]): Promise<void> {
+  return invoke('delete_bulk', { ids })
+}
+/**
+ * Private memes are hidden from search, random meme, quick pick and tray until `unlockPrivate`
+ */
+export async function setMemePrivate(id: number, isPrivate: boolean): Promise<void> {
+  return invoke('set_meme_private', { id, private: isPrivate })
+}
+/**
+ * Show private memes until library is closed or `lockPrivate` is called,
+ * any passcode is accepted if none is set
+ */
+export async function unlockPrivate(passcode: string): Promise<void> {
+  return invoke('unlock_private', { passcode })
+}
+export async function lockPrivate(): Promise<void> {
+  return invoke('lock_private')
+}
+export async function isPrivateUnlocked(): Promise<boolean> {
+  return invoke<boolean>('is_private_unlocked')
+}
+/**
+ * `old` must match the current passcode if one is set, `null` removes passcode
+ */
+export async function setPrivatePasscode(old: string | null, passcode: string | null): Promise<void> {
+  return invoke('set_private_passcode', { old, passcode })
+}
+/**
+ * Page 0 is the cover, whose content is the meme itself
+ */
+export interface MemePage {
+  page: number,
+  hash: string,
+  path: string,
+  thumbnail: string | null
+}
+export async function getMemePages(id: number): Promise<MemePage[]> {
+  return invoke<MemePage[]>('get_meme_pages', { id })
+}
+/**
+ * Page with its content downloaded if it is only in remote storage
+ */
+export async function getMemePage(id: number, page: number): Promise<MemePage> {
+  return invoke<MemePage>('get_meme_page', { id, page })
+}
+/**
+ * Add image `file` as a page of image meme, before `page` or at the end if it is omitted
+ */
+export async function addMemePage(id: number, file: string, page?: number): Promise<MemePage[]> {
+  return invoke<MemePage[]>('add_meme_page', { id, file, page })
+}
+export async function removeMemePage(id: number, page: number): Promise<MemePage[]> {
+  return invoke<MemePage[]>('remove_meme_page', { id, page })
+}
+export async function moveMemePage(id: number, from: number, to: number): Promise<MemePage[]> {
+  return invoke<MemePage[]>('move_meme_page', { id, from, to })
+}
+/**
+ * Split a tall stitched image meme into one page per panel, cut at runs of at least `minGap` blank rows.
+ * Rejected with `invalid_argument` if no boundary is found
+ */
+export async function splitLongMeme(id: number, minGap?: number): Promise<MemePage[]> {
+  return invoke<MemePage[]>('split_long_meme', { id, minGap })
+}
+/**
+ * Payload of `task_progress` event emitted by long running tasks,
+ * `item` is what was just processed, e.g. `ImportedFile` for `importDirectory`
+ */
+export interface TaskProgress<T> {
+  task: string,
+  current: number,
+  total: number,
+  item: T
+}
+
+export interface ImportedFile {
+  file: string,
+  duplicate?: number
+}
+
+export async function cancelTask(task: string): Promise<boolean> {
+  return invoke<boolean>('cancel_task', { task })
+}
+
+export type Job =
+  | { kind: 'ocr', meme_id: number, lang?: string }
+  | { kind: 'phash', meme_id: number }
+  | { kind: 'thumbnail', meme_id: number }
+  | { kind: 'autotag', meme_id: number }
+  | { kind: 'sync', folder: string }
+  | { kind: 'compress', hash: string }
+  | { kind: 'maintain' }
+  | { kind: 'verify', hash: string }
+
+export type JobStatus = 'queued' | 'running' | 'failed' | 'done'
+
+export interface JobInfo {
+  id: number,
+  job: Job,
+  status: JobStatus,
+  attempts: number,
+  error?: string,
+  update_time: string
+}
+
+/** Payload of `job_status` event */
+export interface JobStatusEvent {
+  id: number,
+  status: JobStatus,
+  error?: string
+}
+
+export async function addJob(job: Job): Promise<number> {
+  return invoke<number>('add_job', { job })
+}
+
+export async function listJobs(status?: JobStatus): Promise<JobInfo[]> {
+  return invoke<JobInfo[]>('list_jobs', { status })
+}
+
+export async function retryJob(id: number): Promise<void> {
+  return invoke('retry_job', { id })
+}
+
+export async function removeJob(id: number): Promise<void> {
+  return invoke('remove_job', { id })
+}
+
+export async function clearFinishedJobs(): Promise<void> {
+  return invoke('clear_finished_jobs')
+}
+
+/**
+ * Queue a compression job for every large PNG content, return the number of queued jobs.
+ * Rejected with `invalid_argument` if `compression` is not set in settings
+ */
+export async function queueCompression(): Promise<number> {
+  return invoke<number>('queue_compression')
+}
+
+/** Sizes in bytes of every compressed content before and after compression */
+export interface CompressionSavings {
+  count: number,
+  original_size: number,
+  size: number
+}
+
+export async function getCompressionSavings(): Promise<CompressionSavings> {
+  return invoke<CompressionSavings>('get_compression_savings')
+}
+
+/** Sizes of database in bytes */
+export interface MaintenanceReport {
+  size_before: number,
+  size_after: number,
+  reclaimed: number
+}
+
+/**
+ * Vacuum and analyze database now, other commands wait until it finishes
+ */
+export async function maintainDatabase(): Promise<MaintenanceReport> {
+  return invoke<MaintenanceReport>('maintain_database')
+}
+
+/**
+ * Last `lines` lines of app log, oldest first, to attach when an import or migration fails
+ */
+export async function getRecentLogs(lines?: number): Promise<string[]> {
+  return invoke<string[]>('get_recent_logs', { lines })
+}
+
+export interface FileImportResult {
+  path: string,
+  status: 'added' | 'duplicate' | 'skipped' | 'failed',
+  id?: number,
+  error?: string
+}
+
+export async function importFiles(paths: string[], tags: Tag[], pkgId: number, stripExif = false): Promise<FileImportResult[]> {
+  return invoke<FileImportResult[]>('import_files', { paths, tags, pkgId, stripExif })
+}
+
+export async function importDirectory(dir: string, tags: Tag[], pkgId: number, stripExif = false, useSidecar = false, task = 'import_directory'): Promise<number> {
+  return invoke<number>('import_directory', { task, dir, tags, pkgId, stripExif, useSidecar })
+}
+
+/**
+ * Progress item of `importStickerSet`
+ */
+export interface ImportedSticker {
+  emoji?: string,
+  status: 'added' | 'duplicate' | 'skipped' | 'failed',
+  id?: number,
+  error?: string
+}
+
+/**
+ * Import every sticker of Telegram sticker set `name` (or its share link) using bot `token`
+ */
+export async function importStickerSet(token: string, name: string, tags: Tag[], pkgId: number, task = 'import_sticker_set'): Promise<number> {
+  return invoke<number>('import_sticker_set', { task, token, name, tags, pkgId })
+}
+
+export interface SimilarMeme {
+  id: number,
+  distance: number
+}
+
+export async function findSimilarMemes(id: number, threshold: number): Promise<SimilarMeme[]> {
+  return invoke<SimilarMeme[]>('find_similar_memes', { id, threshold })
+}
+
+export async function scanDuplicateMemes(threshold: number): Promise<number[][]> {
+  return invoke<number[][]>('scan_duplicate_memes', { threshold })
+}
+
+/**
+ * Group memes with the same name, ignoring case and punctuation,
+ * names differing in at most `maxDistance` characters are grouped too
+ */
+export async function scanDuplicateNames(maxDistance: number): Promise<number[][]> {
+  return invoke<number[][]>('scan_duplicate_names', { maxDistance })
+}
+
+export async function regenerateThumbnails(task = 'regenerate_thumbnails'): Promise<number> {
+  return invoke<number>('regenerate_thumbnails', { task })
+}
+
+export async function refreshMediaInfo(): Promise<number> {
+  return invoke<number>('refresh_media_info')
+}
+
+export interface LibraryReport {
+  integrity: string[],
+  missing_content: { id: number, hash: string }[],
+  orphaned_files: string[],
+  corrupted_content: { id: number, hash: string }[]
+}
+
+export async function checkLibrary(): Promise<LibraryReport> {
+  return invoke<LibraryReport>('check_library')
+}
+
+export interface GcResult {
+  files: number,
+  bytes: number
+}
+
+export async function gcLibrary(quarantine: boolean): Promise<GcResult> {
+  return invoke<GcResult>('gc_library', { quarantine })
+}
+
+export interface GroupCount {
+  key: string,
+  count: number
+}
+
+export interface LibraryStats {
+  total: number,
+  fav: number,
+  trash: number,
+  untagged: number,
+  disk_usage: number,
+  namespaces: GroupCount[],
+  months: GroupCount[]
+}
+
+export async function libraryStats(): Promise<LibraryStats> {
+  return invoke<LibraryStats>('library_stats')
+}
+
+export interface BackupFile {
+  path: string,
+  size: number
+}
+
+export async function createBackup(): Promise<string> {
+  return invoke<string>('create_backup_now')
+}
+
+export async function listBackups(): Promise<BackupFile[]> {
+  return invoke<BackupFile[]>('list_backups')
+}
+
+export async function restoreBackup(path: string): Promise<void> {
+  return invoke('restore_backup', { path })
+}
+
+export type SidecarFormat = 'Json' | 'Yaml'
+
+export interface ExportOptions {
+  sidecar?: SidecarFormat
+}
+
+export async function exportMemes(ids: number[], dir: string, options: ExportOptions, task = 'export_memes'): Promise<string[]> {
+  return invoke<string[]>('export_memes', { task, ids, dir, options })
+}
+
+export type EditOperation =
+  | { op: 'crop', x: number, y: number, width: number, height: number }
+  | { op: 'rotate', degrees: 90 | 180 | 270 }
+  | { op: 'caption', top?: string, bottom?: string, font?: string }
+
+/**
+ * Apply `operations` to image meme `id` in order, the result is added as a new meme derived from it
+ */
+export async function editMeme(id: number, operations: EditOperation[]): Promise<AddMemeResult> {
+  return invoke<AddMemeResult>('edit_meme', { id, operations })
+}
+
+export type RelationKind = 'edit' | 'translation' | 'template_instance'
+
+export interface MemeRelation {
+  parent_id: number,
+  child_id: number,
+  kind: RelationKind
+}
+
+/**
+ * Memes related to one meme as a tree, `root` is the original they are all based on
+ */
+export interface MemeFamily {
+  root: number,
+  relations: MemeRelation[]
+}
+
+/**
+ * Record `childId` as a variant of `parentId`, replacing what it was based on before
+ */
+export async function linkMemes(parentId: number, childId: number, kind: RelationKind): Promise<void> {
+  return invoke('link_memes', { parentId, childId, kind })
+}
+
+export async function unlinkMeme(id: number): Promise<void> {
+  return invoke('unlink_meme', { id })
+}
+
+export async function getMemeFamily(id: number): Promise<MemeFamily> {
+  return invoke<MemeFamily>('get_meme_family', { id })
+}
+
+/**
+ * Rectangle of template image to be filled with text, in pixels
+ */
+export interface TextRegion {
+  x: number,
+  y: number,
+  width: number,
+  height: number
+}
+
+export interface Template {
+  meme_id: number,
+  regions: TextRegion[],
+  font?: string
+}
+
+/**
+ * Mark image meme as a template, Impact installed in system is used if `font` is not given
+ */
+export async function setTemplate(memeId: number, regions: TextRegion[], font?: string): Promise<void> {
+  return invoke('set_template', { memeId, regions, font })
+}
+
+export async function removeTemplate(memeId: number): Promise<void> {
+  return invoke('remove_template', { memeId })
+}
+
+export async function listTemplates(): Promise<Template[]> {
+  return invoke<Template[]>('list_templates')
+}
+
+/**
+ * Fill `texts` into regions of template in order, the result is added as a new meme
+ */
+export async function renderTemplate(templateId: number, texts: string[]): Promise<AddMemeResult> {
+  return invoke<AddMemeResult>('render_template', { templateId, texts })
+}
+
+export interface FavGroup {
+  id: number,
+  name: string,
+  count: number
+}
+
+/**
+ * All favorite groups, the default one first. Memes in any group are `fav`
+ */
+export async function listFavGroups(): Promise<FavGroup[]> {
+  return invoke<FavGroup[]>('list_fav_groups')
+}
+
+export async function createFavGroup(name: string): Promise<number> {
+  return invoke<number>('create_fav_group', { name })
+}
+
+export async function renameFavGroup(id: number, name: string): Promise<void> {
+  return invoke('rename_fav_group', { id, name })
+}
+
+export async function deleteFavGroup(id: number): Promise<void> {
+  return invoke('delete_fav_group', { id })
+}
+
+export async function addToFavGroup(groupId: number, memeIds: number[]): Promise<void> {
+  return invoke('add_to_fav_group', { groupId, memeIds })
+}
+
+export async function removeFromFavGroup(groupId: number, memeIds: number[]): Promise<void> {
+  return invoke('remove_from_fav_group', { groupId, memeIds })
+}
+
+export async function getMemeFavGroups(memeId: number): Promise<number[]> {
+  return invoke<number[]>('get_meme_fav_groups', { memeId })
+}
+
+export async function indexColors(task = 'index_colors'): Promise<number> {
+  return invoke<number>('index_colors', { task })
+}
+
+export interface SemanticMatch {
+  meme: MemeQueried,
+  score: number,
+}
+
+/**
+ * Only available if the app is built with `semantic-search` feature
+ */
+export async function indexEmbeddings(task = 'index_embeddings'): Promise<number> {
+  return invoke<number>('index_embeddings', { task })
+}
+
+export async function semanticSearch(text: string, limit: number): Promise<SemanticMatch[]> {
+  return invoke<SemanticMatch[]>('semantic_search', { text, limit })
+}
+
+export interface SuggestedTag {
+  tag: Tag,
+  support: number,
+}
+
+export interface ClusterSuggestion {
+  members: number[],
+  untagged: number[],
+  tags: SuggestedTag[],
+}
+
+/**
+ * Tags of `namespace` (`character` by default) found on similar memes, to apply to untagged ones
+ */
+export async function suggestClusterTags(threshold: number, namespace?: string): Promise<ClusterSuggestion[]> {
+  return invoke<ClusterSuggestion[]>('suggest_cluster_tags', { threshold, namespace })
+}
+
+/**
+ * Queue jobs writing `auto:` tags, for every image without them if `ids` is not given
+ */
+export async function queueAutoTagging(ids?: number[]): Promise<number> {
+  return invoke<number>('queue_auto_tagging', { ids })
+}
+
+export interface SearchHistoryEntry {
+  stmt: string,
+  pinned: boolean,
+  use_count: number,
+  last_used: string,
+}
+
+/**
+ * Pinned searches first, then recently used ones
+ */
+export async function listRecentSearches(limit: number): Promise<SearchHistoryEntry[]> {
+  return invoke<SearchHistoryEntry[]>('list_recent_searches', { limit })
+}
+
+export async function pinSearch(stmt: string, pinned: boolean): Promise<void> {
+  return invoke('pin_search', { stmt, pinned })
+}
+
+export async function removeSearch(stmt: string): Promise<void> {
+  return invoke('remove_search', { stmt })
+}
+
+/**
+ * Forget every search not pinned
+ */
+export async function clearSearchHistory(): Promise<void> {
+  return invoke('clear_search_history')
+}
+
+export interface QuickPickItem {
+  id: number,
+  name: string,
+  score: number,
+  /**
+   * Data URI of thumbnail
+   */
+  thumbnail: string | null,
+}
+
+/**
+ * Best memes for a launcher-like picker, by keyword match, use count and recency
+ */
+export async function quickPick(query: string, limit: number): Promise<QuickPickItem[]> {
+  return invoke<QuickPickItem[]>('quick_pick', { query, limit })
+}
+
+/**
+ * Copy meme chosen in quick picker to clipboard and hide the picker
+ */
+export async function pickMeme(id: number): Promise<void> {
+  return invoke('pick_meme', { id })
+}
+
+export type BundleFormat = 'Html' | 'Zip'
+
+/**
+ * Package memes into one html file or zip archive at `path`, which can be opened in any browser
+ */
+export async function createShareBundle(ids: number[], path: string, format: BundleFormat): Promise<void> {
+  return invoke('create_share_bundle', { ids, path, format })
+}
+
+export interface EmojiPackOptions {
+  /** Defaults to 128 */
+  size?: number,
+  /** Defaults to 256KB */
+  max_bytes?: number,
+  zip?: boolean
+}
+
+/**
+ * Export image memes as custom emoji for Discord or Slack,
+ * return paths of exported files, or path of the zip archive if `options.zip` is set
+ */
+export async function exportEmojiPack(ids: number[], dir: string, options: EmojiPackOptions, task = 'export_emoji_pack'): Promise<string[]> {
+  return invoke<string[]>('export_emoji_pack', { task, ids, dir, options })
+}
+
+export async function exportLibrary(path: string): Promise<void> {
+  return invoke('export_library', { path })
+}
+
+export async function importLibrary(path: string, target: string): Promise<void> {
+  return invoke('import_library', { path, target })
+}
+
+export interface SyncResult {
+  sent: number
+  received: number
+  conflicts: number
+}
+
+export async function syncLibrary(folder: string): Promise<SyncResult> {
+  return invoke<SyncResult>('sync_library', { folder })
+}
+
+export type StorageConfig =
+  | { type: 'local' }
+  | { type: 'webdav', url: string, username?: string, password?: string }
+  | {
+    type: 's3'
+    endpoint: string
+    bucket: string
+    region?: string
+    access_key: string
+    secret_key: string
+    path_style?: boolean
+  }
+
+export async function getRemoteStorage(): Promise<StorageConfig> {
+  return invoke<StorageConfig>('get_remote_storage')
+}
+
+export async function setRemoteStorage(config: StorageConfig): Promise<void> {
+  return invoke('set_remote_storage', { config })
+}
+
+export async function pushRemoteStorage(): Promise<number> {
+  return invoke<number>('push_remote_storage')
+}
+
+export async function clearContentCache(): Promise<number> {
+  return invoke<number>('clear_content_cache')
+}
+
+export async function ocrMeme(id: number, lang?: string): Promise<string> {
+  return invoke<string>('ocr_meme', { id, lang })
+}
+
+export async function ocrAllMemes(lang?: string, task = 'ocr_all_memes'): Promise<number> {
+  return invoke<number>('ocr_all_memes', { task, lang })
+}
+
+export async function setMemeText(id: number, text: string): Promise<void> {
+  return invoke('set_meme_text', { id, text })
+}
+
+export async function getMemeText(id: number): Promise<string | null> {
+  return invoke<string | null>('get_meme_text', { id })
+}
@@ -1,4 +1,4 @@
-import { MemeQueried, searchMeme } from './native/db'
+import { Highlight, MemeQueried, searchMeme } from './native/db'
 
 interface SearchOption{
   statement: string
@@ -25,13 +25,19 @@ export class SearchRequestBuilder implements SearchOption{
 
 export class SearchResponse{
   chunk: MemeQueried[]
+  highlights: Record<number, Highlight[]>
   index: number
+  cursor: string | null
+  total: number
   endOfSearch: boolean
   options: SearchOption
 
   constructor(options: SearchOption){
     this.chunk = []
+    this.highlights = {}
     this.index = 0
+    this.cursor = null
+    this.total = 0
     this.endOfSearch = false
     this.options = options
   }
@@ -46,10 +52,13 @@ export class SearchResponse{
 
   async next(): Promise<SearchResponse> {
     const nextGeneration = new SearchResponse(this.options)
-    const addition = await searchMeme(this.options.statement, this.index, this.options.filterFav, this.options.filterTrash)
-    nextGeneration.chunk = [...this.chunk, ...addition]
+    const addition = await searchMeme(this.options.statement, this.cursor, this.options.filterFav, this.options.filterTrash)
+    nextGeneration.chunk = [...this.chunk, ...addition.items]
+    nextGeneration.highlights = { ...this.highlights, ...addition.highlights }
     nextGeneration.index = this.index + 1
-    nextGeneration.endOfSearch = addition.length == 0
+    nextGeneration.cursor = addition.next_cursor
+    nextGeneration.total = addition.total
+    nextGeneration.endOfSearch = addition.next_cursor === null
     return nextGeneration
   }
 
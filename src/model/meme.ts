@@ -9,7 +9,7 @@ export interface TagM {
 }
 export interface Meme {
   id: string | null,
-  ty: 'image' | 'text',
+  ty: 'image' | 'text' | 'video',
   name: string,
   description?: string,
   content: string,
@@ -20,6 +20,7 @@ export interface Meme {
 export interface MemePkg extends Meme {
   content: string,
   trash: string,
+  private?: boolean,
 
   pkg_id: number,
 }
@@ -7,7 +7,7 @@ export default async function loader() {
     recursive: true,
     filters: [{
       name: 'Pictures',
-      extensions: ['png', 'jpg', 'jpeg', 'webp', 'gif']
+      extensions: ['png', 'jpg', 'jpeg', 'webp', 'gif', 'svg', 'bmp', 'tif', 'tiff', 'heic', 'heif', 'avif']
     }]
   })
 
@@ -7,6 +7,7 @@ import { Meme } from '../../../model/meme'
 import { MemeLoadValue } from '../loader'
 import { updateMemeRecord } from '../../../libs/native/db'
 import { useTranslation } from 'react-i18next'
+import { useState } from 'react'
 
 export default function EditPage() {
 
@@ -21,13 +22,15 @@ export default function EditPage() {
   }
 
   const navigate = useNavigate()
+  // edits after a saved one are checked against the version it produced
+  const [version, setVersion] = useState(value.meme.version)
 
   // const database = useDatabase()
 
   async function handleMemeAdd(meme: Meme) {
     let noErr = true
     try {
-      await updateMemeRecord(value.id, { ...meme, pkg_id: 0, content: '' })
+      setVersion(await updateMemeRecord(value.id, { ...meme, pkg_id: 0, content: '' }, version))
     } catch(e) {
       noErr = false
       console.error(e)
@@ -0,0 +1,93 @@
+import { useEffect, useState, KeyboardEvent } from 'react'
+import { Avatar, CssBaseline, List, ListItemAvatar, ListItemButton, ListItemText, Paper, TextField, Typography } from '@mui/material'
+import { window } from '@tauri-apps/api'
+import { useTranslation } from 'react-i18next'
+import { pickMeme, quickPick, QuickPickItem } from '../../libs/native/db'
+
+const PICK_LIMIT = 8
+
+export default function PickerPage() {
+  const { t } = useTranslation()
+  const [query, setQuery] = useState('')
+  const [items, setItems] = useState<QuickPickItem[]>([])
+  const [selected, setSelected] = useState(0)
+
+  useEffect(() => {
+    let cancelled = false
+    quickPick(query, PICK_LIMIT)
+      .then((result) => {
+        if (!cancelled) {
+          setItems(result)
+          setSelected(0)
+        }
+      })
+      // statement being typed may not be complete yet
+      .catch(() => {})
+    return () => {
+      cancelled = true
+    }
+  }, [query])
+
+  // picker is hidden instead of closed, so it opens again instantly
+  useEffect(() => {
+    const unlisten = window.getCurrent().onFocusChanged(({ payload: focused }) => {
+      if (!focused) {
+        window.getCurrent().hide()
+      }
+    })
+    return () => {
+      unlisten.then((f) => f())
+    }
+  }, [])
+
+  async function pick(item: QuickPickItem | undefined) {
+    if (item) {
+      await pickMeme(item.id)
+      setQuery('')
+    }
+  }
+
+  function onKeyDown(e: KeyboardEvent) {
+    if (e.key === 'ArrowDown') {
+      setSelected((selected + 1) % Math.max(items.length, 1))
+    } else if (e.key === 'ArrowUp') {
+      setSelected((selected - 1 + items.length) % Math.max(items.length, 1))
+    } else if (e.key === 'Enter') {
+      pick(items[selected]).catch((err) => console.error(err))
+    } else if (e.key === 'Escape') {
+      window.getCurrent().hide()
+    } else {
+      return
+    }
+    e.preventDefault()
+  }
+
+  return (
+    <Paper sx={{ height: '100vh', overflow: 'hidden' }}>
+      <CssBaseline />
+      <TextField
+        autoFocus
+        fullWidth
+        variant='filled'
+        label={t('Quick Pick')}
+        value={query}
+        onChange={(e) => setQuery(e.target.value)}
+        onKeyDown={onKeyDown} />
+      {items.length === 0
+        ? <Typography sx={{ p: 2 }} color='text.secondary'>{t('No Match')}</Typography>
+        : <List dense>
+          {items.map((item, idx) => (
+            <ListItemButton
+              key={item.id}
+              selected={idx === selected}
+              onClick={() => pick(item).catch((err) => console.error(err))}>
+              <ListItemAvatar>
+                <Avatar variant='rounded' src={item.thumbnail ?? undefined}>{item.name[0]}</Avatar>
+              </ListItemAvatar>
+              <ListItemText primary={item.name} />
+            </ListItemButton>
+          ))}
+        </List>}
+    </Paper>
+  )
+}
@@ -1,17 +1,45 @@
+import { useState } from 'react'
 import { path, dialog } from '@tauri-apps/api'
-import { Fab, List, ListItem, ListItemButton, ListItemText, capitalize } from '@mui/material'
+import {
+  Button, Dialog, DialogActions, DialogContent, DialogContentText, DialogTitle, Fab, List, ListItem,
+  ListItemButton, ListItemText, TextField, capitalize
+} from '@mui/material'
+import { useTranslation } from 'react-i18next'
 import { useStorageHistory } from '../../store/storageHistory'
 import { Add as AddIcon } from '@mui/icons-material'
-import { openStorage } from '../../libs/native/db'
+import { AppError, openStorage } from '../../libs/native/db'
 import { useNavigate } from 'react-router-dom'
 
 export default function WelcomePage() {
+  const { t } = useTranslation()
   const storageHistory = useStorageHistory()
   const navigate = useNavigate()
+  // encrypted library waiting for passphrase
+  const [lockedPath, setLockedPath] = useState<string | null>(null)
+  const [passphrase, setPassphrase] = useState('')
+  const [wrongPassphrase, setWrongPassphrase] = useState(false)
+  // library migrated by a newer version of app
+  const [tooNewError, setTooNewError] = useState<string | null>(null)
 
-  function handleOpenStorage(path: string) {
+  async function handleOpenStorage(path: string, passphrase?: string) {
+    try {
+      await openStorage(path, passphrase)
+    } catch (e) {
+      if ((e as AppError).code === 'locked') {
+        setWrongPassphrase(passphrase !== undefined)
+        setLockedPath(path)
+        return
+      }
+      if ((e as AppError).code === 'database_too_new') {
+        setLockedPath(null)
+        setTooNewError((e as AppError).message)
+        return
+      }
+      throw e
+    }
     storageHistory.add(path)
-    openStorage(path)
+    setLockedPath(null)
+    setPassphrase('')
     navigate('/dashboard')
   }
   async function handleAddStorage() {
@@ -20,7 +48,13 @@ export default function WelcomePage() {
       directory: true
     })
     if (path) {
-      handleOpenStorage(path as string)
+      await handleOpenStorage(path as string)
+    }
+  }
+
+  function handleUnlock() {
+    if (lockedPath) {
+      handleOpenStorage(lockedPath, passphrase).catch((e) => console.error(e))
     }
   }
 
@@ -31,7 +65,7 @@ export default function WelcomePage() {
           storageHistory.items.map((item) => (
             <>
               <ListItem key={item}>
-                <ListItemButton onClick={() => handleOpenStorage(item)}>
+                <ListItemButton onClick={() => handleOpenStorage(item).catch((e) => console.error(e))}>
                   <ListItemText
                     primary={capitalize(item.substring(item.lastIndexOf(path.sep) + 1))}
                     secondary={item} />
@@ -48,6 +82,34 @@ export default function WelcomePage() {
         onClick={handleAddStorage}>
         <AddIcon />
       </Fab>
+      <Dialog open={lockedPath !== null} onClose={() => setLockedPath(null)}>
+        <DialogTitle>{t('Library Locked')}</DialogTitle>
+        <DialogContent>
+          <TextField
+            autoFocus
+            fullWidth
+            type='password'
+            label={t('Passphrase')}
+            value={passphrase}
+            error={wrongPassphrase}
+            helperText={wrongPassphrase ? t('Wrong Passphrase') : undefined}
+            onChange={(e) => setPassphrase(e.target.value)}
+            onKeyDown={(e) => e.key === 'Enter' && handleUnlock()} />
+        </DialogContent>
+        <DialogActions>
+          <Button onClick={handleUnlock}>{t('Unlock')}</Button>
+        </DialogActions>
+      </Dialog>
+      <Dialog open={tooNewError !== null} onClose={() => setTooNewError(null)}>
+        <DialogTitle>{t('Library Too New')}</DialogTitle>
+        <DialogContent>
+          <DialogContentText>{t('Library Too New Detail')}</DialogContentText>
+          <DialogContentText variant='caption'>{tooNewError}</DialogContentText>
+        </DialogContent>
+        <DialogActions>
+          <Button onClick={() => setTooNewError(null)}>{t('Close')}</Button>
+        </DialogActions>
+      </Dialog>
     </>
   )
 
@@ -19,6 +19,8 @@ const EditTextPage = lazy(() => import('./pages/edit/image/page'))
 const MemePreviewPage = lazy(() => import('./pages/meme/page'))
 import MemePreivewPageLoader from './pages/meme/loader'
 
+const PickerPage = lazy(() => import('./pages/picker/page'))
+
 const SettingsLayout = lazy(() => import('./pages/settings/layout'))
 const SettingsPage = lazy(() => import('./pages/settings/page'))
 
@@ -89,6 +91,10 @@ const routes = createBrowserRouter([
       }
     ]
   },
+  {
+    path: '/picker',
+    element: <PickerPage />
+  },
   {
     path: '/settings',
     element: <SettingsLayout />,